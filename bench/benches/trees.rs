@@ -0,0 +1,152 @@
+//! Criterion benchmarks of construction and query times, grouped by structure, lane count `L` and
+//! cloud size.
+//!
+//! Run them with `cargo bench -p bench`.
+//! Criterion reports the change from the previous run of each benchmark, and `--save-baseline` and
+//! `--baseline` compare against named runs, which makes this suite the place to check for
+//! regressions.
+//! To export raw timings as CSV for plotting, run the `perf_plots` binary instead.
+
+use std::hint::black_box;
+
+use bench::{simd_trace_new, Trace};
+use captree::{Capt, PkdTree, Radius};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use kiddo::{ImmutableKdTree, SquaredEuclidean};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+/// The sizes of the point clouds to benchmark.
+const SIZES: [usize; 3] = [1 << 10, 1 << 13, 1 << 16];
+/// The number of queries in each trace.
+const N_QUERIES: usize = 1 << 12;
+/// The largest radius of a query.
+const QUERY_RADIUS: f32 = 0.05;
+/// The radius range to build each CAPT for.
+const R_RANGE: (f32, f32) = (0.01, 0.08);
+
+/// Generate `n` points uniformly in the unit cube.
+fn cloud(n: usize) -> Vec<[f32; 3]> {
+    let mut rng = ChaCha20Rng::seed_from_u64(2707);
+    (0..n).map(|_| [rng.gen(), rng.gen(), rng.gen()]).collect()
+}
+
+/// Generate a trace of queries with centers uniformly in the unit cube.
+fn trace() -> Box<Trace> {
+    let mut rng = ChaCha20Rng::seed_from_u64(2708);
+    (0..N_QUERIES)
+        .map(|_| {
+            (
+                [rng.gen(), rng.gen(), rng.gen()],
+                rng.gen_range(0.0..=QUERY_RADIUS),
+            )
+        })
+        .collect()
+}
+
+fn construct(c: &mut Criterion) {
+    let mut group = c.benchmark_group("construct");
+    for n in SIZES {
+        let points = cloud(n);
+        group.throughput(Throughput::Elements(n as u64));
+        group.bench_with_input(BenchmarkId::new("kiddo", n), &points, |b, p| {
+            b.iter(|| ImmutableKdTree::<f32, 3>::new_from_slice(p));
+        });
+        group.bench_with_input(BenchmarkId::new("pkdt", n), &points, |b, p| {
+            b.iter(|| PkdTree::new(p));
+        });
+        group.bench_with_input(BenchmarkId::new("capt/L=4", n), &points, |b, p| {
+            b.iter(|| Capt::<3, 4, f32, u32>::new(p, R_RANGE));
+        });
+        group.bench_with_input(BenchmarkId::new("capt/L=8", n), &points, |b, p| {
+            b.iter(|| Capt::<3, 8, f32, u32>::new(p, R_RANGE));
+        });
+        group.bench_with_input(BenchmarkId::new("capt/L=16", n), &points, |b, p| {
+            b.iter(|| Capt::<3, 16, f32, u32>::new(p, R_RANGE));
+        });
+    }
+    group.finish();
+}
+
+fn collides(c: &mut Criterion) {
+    let trace = trace();
+    let mut group = c.benchmark_group("collides");
+    group.throughput(Throughput::Elements(N_QUERIES as u64));
+    for n in SIZES {
+        let points = cloud(n);
+
+        let kdt = ImmutableKdTree::<f32, 3>::new_from_slice(&points);
+        group.bench_function(BenchmarkId::new("kiddo", n), |b| {
+            b.iter(|| {
+                for (center, radius) in &trace {
+                    black_box(
+                        kdt.nearest_one::<SquaredEuclidean>(center).distance <= radius.powi(2),
+                    );
+                }
+            });
+        });
+
+        let pkdt = PkdTree::new(&points);
+        group.bench_function(BenchmarkId::new("pkdt", n), |b| {
+            b.iter(|| {
+                for (center, radius) in &trace {
+                    black_box(pkdt.might_collide_r(*center, Radius(*radius)));
+                }
+            });
+        });
+
+        let capt4 = Capt::<3, 4, f32, u32>::new(&points, R_RANGE);
+        let capt8 = Capt::<3, 8, f32, u32>::new(&points, R_RANGE);
+        let capt16 = Capt::<3, 16, f32, u32>::new(&points, R_RANGE);
+        group.bench_function(BenchmarkId::new("capt/L=4", n), |b| {
+            b.iter(|| {
+                for (center, radius) in &trace {
+                    black_box(capt4.collides(center, *radius));
+                }
+            });
+        });
+        group.bench_function(BenchmarkId::new("capt/L=8", n), |b| {
+            b.iter(|| {
+                for (center, radius) in &trace {
+                    black_box(capt8.collides(center, *radius));
+                }
+            });
+        });
+        group.bench_function(BenchmarkId::new("capt/L=16", n), |b| {
+            b.iter(|| {
+                for (center, radius) in &trace {
+                    black_box(capt16.collides(center, *radius));
+                }
+            });
+        });
+
+        let batches4 = simd_trace_new::<4>(&trace);
+        let batches8 = simd_trace_new::<8>(&trace);
+        let batches16 = simd_trace_new::<16>(&trace);
+        group.bench_function(BenchmarkId::new("capt_simd/L=4", n), |b| {
+            b.iter(|| {
+                for batch in &batches4 {
+                    black_box(capt4.collides_batch(batch));
+                }
+            });
+        });
+        group.bench_function(BenchmarkId::new("capt_simd/L=8", n), |b| {
+            b.iter(|| {
+                for batch in &batches8 {
+                    black_box(capt8.collides_batch(batch));
+                }
+            });
+        });
+        group.bench_function(BenchmarkId::new("capt_simd/L=16", n), |b| {
+            b.iter(|| {
+                for batch in &batches16 {
+                    black_box(capt16.collides_batch(batch));
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, construct, collides);
+criterion_main!(benches);
@@ -0,0 +1,139 @@
+//! Traces of queries from a simulated robot arm.
+//!
+//! Motion planners do not query uniformly random spheres: they check every sphere of a robot's
+//! collision model at each configuration along a densely interpolated edge, so successive queries
+//! are close in space and their radii vary with the size of each link.
+//! [`arm_trace`] mimics that workload with a 7-jointed arm made of spheres.
+
+use std::f32::consts::PI;
+
+use rand::Rng;
+
+use crate::Trace;
+
+/// The axis about which a joint rotates, in the frame of the link before it.
+#[derive(Clone, Copy, Debug)]
+enum Joint {
+    /// A joint which twists about the link.
+    Roll,
+    /// A joint which bends the link.
+    Pitch,
+}
+
+/// A joint and the link following it.
+struct Link {
+    joint: Joint,
+    /// The length of the link.
+    length: f32,
+    /// The radius of each sphere covering the link.
+    radius: f32,
+}
+
+/// The links of the arm, from the base to the end effector.
+///
+/// The arm reaches about 0.8 from its base, and its radii span the default radius range of the
+/// benchmarks, so that it is about the size of a manipulator in a unit-sized workspace.
+const LINKS: [Link; 7] = [
+    Link {
+        joint: Joint::Roll,
+        length: 0.15,
+        radius: 0.07,
+    },
+    Link {
+        joint: Joint::Pitch,
+        length: 0.2,
+        radius: 0.06,
+    },
+    Link {
+        joint: Joint::Roll,
+        length: 0.1,
+        radius: 0.05,
+    },
+    Link {
+        joint: Joint::Pitch,
+        length: 0.15,
+        radius: 0.045,
+    },
+    Link {
+        joint: Joint::Roll,
+        length: 0.1,
+        radius: 0.035,
+    },
+    Link {
+        joint: Joint::Pitch,
+        length: 0.06,
+        radius: 0.03,
+    },
+    Link {
+        joint: Joint::Roll,
+        length: 0.04,
+        radius: 0.02,
+    },
+];
+
+/// The largest change in any joint angle, in radians, between successive configurations along an
+/// edge.
+const RESOLUTION: f32 = 0.02;
+
+/// A rotation matrix, stored by rows.
+type Rotation = [[f32; 3]; 3];
+
+/// Compose `r` with a rotation of `angle` about `joint`.
+fn rotate(r: &Rotation, joint: Joint, angle: f32) -> Rotation {
+    let (s, c) = angle.sin_cos();
+    let local = match joint {
+        Joint::Roll => [[c, -s, 0.0], [s, c, 0.0], [0.0, 0.0, 1.0]],
+        Joint::Pitch => [[c, 0.0, s], [0.0, 1.0, 0.0], [-s, 0.0, c]],
+    };
+    std::array::from_fn(|i| std::array::from_fn(|j| (0..3).map(|k| r[i][k] * local[k][j]).sum()))
+}
+
+/// Append the spheres covering the arm at configuration `q`, with its base at `base`, to `trace`.
+///
+/// Spheres are placed from the base to the end effector, so that consecutive spheres overlap.
+fn push_spheres(q: &[f32; 7], base: [f32; 3], trace: &mut Vec<([f32; 3], f32)>) {
+    let mut r: Rotation = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    let mut p = base;
+    for (link, &angle) in LINKS.iter().zip(q) {
+        r = rotate(&r, link.joint, angle);
+        // each link extends along the local z-axis
+        let dir = [r[0][2], r[1][2], r[2][2]];
+        let n = (link.length / link.radius).ceil() as usize;
+        for i in 1..=n {
+            let t = link.length * i as f32 / n as f32;
+            trace.push((std::array::from_fn(|k| p[k] + t * dir[k]), link.radius));
+        }
+        p = std::array::from_fn(|k| p[k] + link.length * dir[k]);
+    }
+}
+
+/// Generate a trace of `n_queries` spheres from an arm with its base at `base` moving between
+/// random configurations.
+///
+/// Like a motion planner checking edges, the arm moves in a straight line through configuration
+/// space from its current configuration to a random goal, checking every sphere of its body at
+/// each step, then picks a new goal.
+/// Successive queries are therefore strongly correlated in space, and their radii cycle through
+/// the radii of the links.
+pub fn arm_trace(rng: &mut impl Rng, n_queries: usize, base: [f32; 3]) -> Box<Trace> {
+    let mut random_config = || std::array::from_fn(|_| rng.gen_range(-PI..PI));
+    let mut q: [f32; 7] = random_config();
+    let mut trace = Vec::with_capacity(n_queries);
+    while trace.len() < n_queries {
+        let goal = random_config();
+        let max_delta = q
+            .iter()
+            .zip(&goal)
+            .map(|(a, b)| (b - a).abs())
+            .fold(0.0, f32::max);
+        let n_steps = (max_delta / RESOLUTION).ceil().max(1.0) as usize;
+        for step in 1..=n_steps {
+            let t = step as f32 / n_steps as f32;
+            let config = std::array::from_fn(|j| q[j] + t * (goal[j] - q[j]));
+            push_spheres(&config, base, &mut trace);
+        }
+        q = goal;
+    }
+    trace.truncate(n_queries);
+    trace.into_boxed_slice()
+}
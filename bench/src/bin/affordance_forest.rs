@@ -0,0 +1,64 @@
+use bench::{forest::AffordanceForest, fuzz_pointcloud, get_points};
+use captree::Capt;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+const N: usize = 1 << 16;
+const N_TRIALS: usize = 100_000;
+const R_RANGE: (f32, f32) = (0.01, 0.08);
+
+fn main() {
+    let mut rng = ChaCha20Rng::seed_from_u64(2707);
+    let mut points = get_points(N);
+    fuzz_pointcloud(&mut points, 0.001, &mut rng);
+
+    let exact = Capt::<3>::new(&points, R_RANGE);
+    let queries: Vec<([f32; 3], f32)> = (0..N_TRIALS)
+        .map(|_| {
+            (
+                [rng.gen(), rng.gen(), rng.gen()],
+                rng.gen_range(R_RANGE.0..=R_RANGE.1),
+            )
+        })
+        .collect();
+    let expected: Vec<bool> = queries.iter().map(|(c, r)| exact.collides(c, *r)).collect();
+
+    eprintln!("exact tree: {} bytes", exact.memory_used());
+    println!("T\tspacing\tinflation\tpoints\tbytes\tfalse negatives\tfalse positives");
+    for spacing in [0.005, 0.01, 0.02] {
+        let conservative = spacing * 3.0f32.sqrt();
+        for inflation in [0.0, conservative / 2.0, conservative] {
+            err_forest::<1>(&points, spacing, inflation, &queries, &expected);
+            err_forest::<2>(&points, spacing, inflation, &queries, &expected);
+            err_forest::<4>(&points, spacing, inflation, &queries, &expected);
+        }
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn err_forest<const T: usize>(
+    points: &[[f32; 3]],
+    spacing: f32,
+    inflation: f32,
+    queries: &[([f32; 3], f32)],
+    expected: &[bool],
+) {
+    let forest = AffordanceForest::<3, T>::new(points, R_RANGE, spacing, inflation);
+
+    let (mut false_negatives, mut false_positives) = (0, 0);
+    for ((center, r), &exact) in queries.iter().zip(expected) {
+        match (forest.collides(center, *r), exact) {
+            (false, true) => false_negatives += 1,
+            (true, false) => false_positives += 1,
+            _ => (),
+        }
+    }
+
+    println!(
+        "{T}\t{spacing}\t{inflation}\t{}\t{}\t{}\t{}",
+        forest.n_points(),
+        forest.memory_used(),
+        false_negatives as f64 / queries.len() as f64,
+        false_positives as f64 / queries.len() as f64,
+    );
+}
@@ -0,0 +1,103 @@
+use std::{error::Error, hint::black_box, path::PathBuf};
+
+#[cfg(feature = "rstar")]
+use bench::competitors::RStar;
+use bench::{
+    competitors::{BruteForce, Kiddo},
+    parse_pointcloud_csv, parse_trace_csv, stopwatch, Trace,
+};
+use captree::{Capt, Collider};
+use clap::Parser;
+use rand::Rng;
+use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+
+#[derive(Parser)]
+/// Compare the construction and query time of a CAPT against other spatial structures on the same
+/// point cloud and queries.
+///
+/// Every structure answers queries through `captree::Collider`, so each is timed by the same code.
+/// Build with `--features rstar` to include an R*-tree.
+struct Args {
+    /// A CSV point cloud to benchmark against. If not given, points are sampled uniformly in the
+    /// unit cube.
+    cloud: Option<PathBuf>,
+    /// A CSV trace of queries. If not given, queries are sampled uniformly in the unit cube.
+    trace: Option<PathBuf>,
+    /// The number of points to sample when no point cloud is given.
+    #[arg(long, default_value_t = 1 << 16)]
+    n_points: usize,
+    /// The number of queries to sample when no trace is given.
+    #[arg(long, default_value_t = 100_000)]
+    n_trials: usize,
+    /// The smallest radius of a query.
+    #[arg(long, default_value_t = 0.01)]
+    r_min: f32,
+    /// The largest radius of a query.
+    #[arg(long, default_value_t = 0.08)]
+    r_max: f32,
+    /// Also benchmark a brute-force scan, whose queries take time linear in the number of points.
+    #[arg(long)]
+    brute_force: bool,
+    /// The seed for sampling points and queries.
+    #[arg(long, default_value_t = 2707)]
+    seed: u64,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let mut rng = ChaCha20Rng::seed_from_u64(args.seed);
+    let r_range = (args.r_min, args.r_max);
+    let points: Box<[[f32; 3]]> = match &args.cloud {
+        None => (0..args.n_points)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect(),
+        Some(path) => parse_pointcloud_csv(path)?,
+    };
+    let trace: Box<Trace> = match &args.trace {
+        None => (0..args.n_trials)
+            .map(|_| {
+                (
+                    [rng.gen(), rng.gen(), rng.gen()],
+                    rng.gen_range(r_range.0..=r_range.1),
+                )
+            })
+            .collect(),
+        Some(path) => parse_trace_csv(path)?,
+    };
+
+    println!("number of points: {}", points.len());
+    println!("number of tests: {}", trace.len());
+
+    let mut structures: Vec<(&str, Box<dyn Collider<3, f32>>)> = Vec::new();
+    let mut build = |name, f: &dyn Fn() -> Box<dyn Collider<3, f32>>| {
+        let (structure, time) = stopwatch(f);
+        println!("{name}: built in {time:?}");
+        structures.push((name, structure));
+    };
+    build("captree", &|| Box::new(Capt::<3>::new(&points, r_range)));
+    build("kiddo", &|| Box::new(Kiddo::new(&points)));
+    #[cfg(feature = "rstar")]
+    build("rstar", &|| Box::new(RStar::new(&points)));
+    if args.brute_force {
+        build("brute force", &|| Box::new(BruteForce::<8>::new(&points)));
+    }
+
+    let mut expected = None;
+    for (name, structure) in &structures {
+        let (n_hits, time) = stopwatch(|| {
+            trace
+                .iter()
+                .filter(|(center, r)| structure.collides(black_box(center), *r))
+                .count()
+        });
+        println!(
+            "{name}: {:?} per query, {n_hits} collisions",
+            time / u32::try_from(trace.len())?
+        );
+        if *expected.get_or_insert(n_hits) != n_hits {
+            eprintln!("{name} disagrees with captree on the number of collisions");
+        }
+    }
+
+    Ok(())
+}
@@ -2,9 +2,8 @@
 
 use std::simd::Simd;
 
-use bench::{dist, kdt::PkdTree, parse_pointcloud_csv, parse_trace_csv, trace_r_range};
-use captree::Capt;
-use kiddo::SquaredEuclidean;
+use bench::{dist, parse_pointcloud_csv, parse_trace_csv, trace_r_range};
+use captree::{testing, Capt, PkdTree};
 use rand::{seq::SliceRandom, Rng, SeedableRng};
 
 const N: usize = 1 << 12;
@@ -50,35 +49,30 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let r_range = trace_r_range(&trace);
 
     let kdt = PkdTree::new(&points);
-    let mut kiddo_kdt = kiddo::KdTree::new();
-    for pt in points.iter() {
-        kiddo_kdt.add(pt, 0);
-    }
-
     let aff_tree = Capt::<3>::new(&points, r_range);
+    aff_tree.validate()?;
+    testing::check_collider(&aff_tree, &points, &trace)?;
 
     for (i, (center, r)) in trace.iter().enumerate() {
-        let exact_kiddo_dist = kiddo_kdt
-            .nearest_one::<SquaredEuclidean>(center)
-            .distance
-            .sqrt();
+        let (_, exact_distsq) = testing::brute_force_nearest(&points, center).unwrap();
         let exact_dist = dist(kdt.get_point(kdt.query1_exact(*center)), *center);
-        assert_eq!(exact_dist, exact_kiddo_dist);
+        assert_eq!(exact_dist, exact_distsq.sqrt());
 
         let simd_center: [Simd<f32, 8>; 3] = [
             Simd::splat(center[0]),
             Simd::splat(center[1]),
             Simd::splat(center[2]),
         ];
-        if exact_dist <= *r {
-            println!("iter {i}: {:?} (collides)", (center, r));
-            assert!(aff_tree.collides(center, *r));
-            assert!(aff_tree.collides_simd(&simd_center, Simd::splat(*r)))
-        } else {
-            println!("iter {i}: {:?} (no collides)", (center, r));
-            assert!(!aff_tree.collides(center, *r));
-            assert!(!aff_tree.collides_simd(&simd_center, Simd::splat(*r)))
-        }
+        let collides = exact_dist <= *r;
+        println!(
+            "iter {i}: {:?} ({})",
+            (center, r),
+            if collides { "collides" } else { "no collides" }
+        );
+        assert_eq!(
+            aff_tree.collides_simd(&simd_center, Simd::splat(*r)),
+            collides
+        );
     }
 
     Ok(())
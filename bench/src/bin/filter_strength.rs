@@ -1,16 +1,7 @@
 use std::{fs::File, io::Write};
 
 use bench::parse_pointcloud_csv;
-use morton_filter::filter_permutation;
-
-const PERMUTATIONS_3D: [[u8; 3]; 6] = [
-    [0, 1, 2],
-    [0, 2, 1],
-    [1, 0, 2],
-    [1, 2, 0],
-    [2, 0, 1],
-    [2, 1, 0],
-];
+use captree::filter::{filter_permutation, PERMUTATIONS_3D};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut f_results = File::create("filter_strength.csv")?;
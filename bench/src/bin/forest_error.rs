@@ -4,26 +4,53 @@ use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha20Rng;
 
 const N: usize = 1 << 12;
+const R: f32 = 0.02;
 
 fn main() {
     let mut rng = ChaCha20Rng::seed_from_u64(2707);
     let mut starting_points = get_points(N);
     fuzz_pointcloud(&mut starting_points, 0.001, &mut rng);
 
-    err_forest::<1>(&starting_points, &mut rng);
-    err_forest::<2>(&starting_points, &mut rng);
-    err_forest::<3>(&starting_points, &mut rng);
-    err_forest::<4>(&starting_points, &mut rng);
-    err_forest::<5>(&starting_points, &mut rng);
-    err_forest::<6>(&starting_points, &mut rng);
-    err_forest::<7>(&starting_points, &mut rng);
-    err_forest::<8>(&starting_points, &mut rng);
-    err_forest::<9>(&starting_points, &mut rng);
-    err_forest::<10>(&starting_points, &mut rng);
+    for rotated in [false, true] {
+        err_forest::<1>(&starting_points, rotated, &mut rng);
+        err_forest::<2>(&starting_points, rotated, &mut rng);
+        err_forest::<3>(&starting_points, rotated, &mut rng);
+        err_forest::<4>(&starting_points, rotated, &mut rng);
+        err_forest::<5>(&starting_points, rotated, &mut rng);
+        err_forest::<6>(&starting_points, rotated, &mut rng);
+        err_forest::<7>(&starting_points, rotated, &mut rng);
+        err_forest::<8>(&starting_points, rotated, &mut rng);
+        err_forest::<9>(&starting_points, rotated, &mut rng);
+        err_forest::<10>(&starting_points, rotated, &mut rng);
+    }
+
+    // one measurement of a large forest predicts the miss rate of every smaller one
+    let (needles, _) = make_needles::<3, 1>(&mut rng, 10_000);
+    for (name, forest) in [
+        ("axis-aligned", PkdForest::<3, 10>::new(&starting_points)),
+        ("rotated", PkdForest::<3, 10>::new_rotated(&starting_points)),
+    ] {
+        let stats = forest.stats(&needles, R * R);
+        eprintln!("{name}, r={R}: tree sizes {:?}", stats.tree_sizes);
+        for t in 1..=10 {
+            eprintln!(
+                "{name}, r={R}, T={t}: predicted miss probability {}",
+                stats.predicted_miss_probability(t)
+            );
+        }
+        eprintln!(
+            "{name}, r={R}, T=10: measured miss probability {}",
+            stats.miss_probability
+        );
+    }
 }
 
-fn err_forest<const T: usize>(points: &[[f32; 3]], rng: &mut impl Rng) {
-    let forest = PkdForest::<3, T>::new(points);
+fn err_forest<const T: usize>(points: &[[f32; 3]], rotated: bool, rng: &mut impl Rng) {
+    let forest = if rotated {
+        PkdForest::<3, T>::new_rotated(points)
+    } else {
+        PkdForest::<3, T>::new(points)
+    };
 
     let mut kiddo_kdt = kiddo::KdTree::new();
     for pt in points {
@@ -41,8 +68,12 @@ fn err_forest<const T: usize>(points: &[[f32; 3]], rng: &mut impl Rng) {
         let err = forest_distsq.sqrt() - exact_dist;
         total_err += err;
         let rel_err = err / exact_distsq.sqrt();
-        println!("{T}\t{err}\t{rel_err}\t{exact_dist}");
+        println!("{T}\t{rotated}\t{err}\t{rel_err}\t{exact_dist}");
     }
 
-    eprintln!("T={T}: mean error {}", total_err / seq_needles.len() as f32);
+    eprintln!(
+        "T={T}, rotated={rotated}: mean error {}, {} bytes",
+        total_err / seq_needles.len() as f32,
+        forest.memory_used()
+    );
 }
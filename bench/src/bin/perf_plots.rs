@@ -1,41 +1,86 @@
-#![feature(portable_simd)]
-
 use std::{
-    cmp::min, env::args, error::Error, fs::File, hint::black_box, io::Write, simd::f32x8,
+    cmp::min,
+    error::Error,
+    fs::File,
+    hint::black_box,
+    io::Write,
+    path::{Path, PathBuf},
     time::Duration,
 };
 
+#[cfg(feature = "plots")]
+use bench::plot::{line_plot, PlotFormat, Series};
 use bench::{
-    forest::PkdForest, fuzz_pointcloud, kdt::PkdTree, parse_pointcloud_csv, parse_trace_csv,
-    simd_trace_new, stopwatch, SimdTrace, Trace,
+    arm::arm_trace, forest::PkdForest, fuzz_pointcloud, latency_histogram, latency_percentiles,
+    parse_pointcloud_csv, parse_trace_csv, simd_trace_new, stopwatch, trace_r_range, SimdTrace,
+    Trace,
 };
-use captree::Capt;
+use captree::{filter::morton_filter, Capt, PkdTree, Radius};
+use clap::Parser;
+use elain::{Align, Alignment};
 #[allow(unused_imports)]
 use kiddo::SquaredEuclidean;
-use morton_filter::morton_filter;
 use rand::{seq::SliceRandom, Rng};
 use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
 
-const N_TRIALS: usize = 100_000;
-const L: usize = 8;
-
-const QUERY_RADIUS: f32 = 0.05;
+#[derive(Parser)]
+/// Measure the construction time, memory use and query time of each structure over a sweep of
+/// progressively filtered point clouds, writing the results as CSV.
+struct Args {
+    /// A CSV point cloud to benchmark against. If not given, points are sampled uniformly in the
+    /// unit cube.
+    cloud: Option<PathBuf>,
+    /// A CSV trace of queries. If not given, queries are sampled uniformly in the unit cube.
+    trace: Option<PathBuf>,
+    /// The number of points to sample, or to keep from the point cloud.
+    #[arg(long, default_value_t = 1 << 16)]
+    n_points: usize,
+    /// The number of queries to sample when no trace is given.
+    #[arg(long, default_value_t = 100_000)]
+    n_trials: usize,
+    /// The number of SIMD lanes in each tree and query batch: 4, 8 or 16.
+    #[arg(long, default_value_t = 8)]
+    lanes: usize,
+    /// Sample queries from a simulated robot arm moving between random configurations instead of
+    /// uniformly in the unit cube.
+    #[arg(long)]
+    arm: bool,
+    /// The largest radius of a query sampled uniformly.
+    #[arg(long, default_value_t = 0.05)]
+    query_radius: f32,
+    /// The smallest query radius which each tree is built to support.
+    #[arg(long, default_value_t = 0.01)]
+    r_min: f32,
+    /// The largest query radius which each tree is built to support.
+    #[arg(long, default_value_t = 0.08)]
+    r_max: f32,
+    /// Fit the radius range to the queries instead of using `--r-min` and `--r-max`.
+    #[arg(long)]
+    fit_r_range: bool,
+    /// The seed for sampling points and queries and for perturbing the point cloud.
+    #[arg(long, default_value_t = 2707)]
+    seed: u64,
+    /// The directory to write CSV files to.
+    #[arg(long, short, default_value = ".")]
+    output_dir: PathBuf,
+    /// Also plot construction time, memory use and query time against cloud size in this format,
+    /// next to the CSV files.
+    #[cfg(feature = "plots")]
+    #[arg(long)]
+    plot: Option<PlotFormat>,
+}
 
-struct Benchmark<'a> {
+struct Benchmark<'a, const L: usize> {
     seq: &'a Trace,
     simd: &'a SimdTrace<L>,
     f_query: File,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let mut f_construct = File::create("construct_time.csv")?;
-    let mut f_mem = File::create("mem.csv")?;
-
-    let args: Vec<String> = args().collect();
-
-    let mut rng = ChaCha20Rng::seed_from_u64(2707);
-    let points: Box<[[f32; 3]]> = if args.len() < 2 {
-        (0..1 << 16)
+    let args = Args::parse();
+    let mut rng = ChaCha20Rng::seed_from_u64(args.seed);
+    let points: Box<[[f32; 3]]> = match &args.cloud {
+        None => (0..args.n_points)
             .map(|_| {
                 [
                     rng.gen_range::<f32, _>(0.0..1.0),
@@ -43,17 +88,19 @@ fn main() -> Result<(), Box<dyn Error>> {
                     rng.gen_range::<f32, _>(0.0..1.0),
                 ]
             })
-            .collect()
-    } else {
-        let mut p = parse_pointcloud_csv(&args[1])?.to_vec();
-        fuzz_pointcloud(&mut p, 0.001, &mut rng);
-        p.shuffle(&mut rng);
-        p.truncate(1 << 16);
-        p.into_boxed_slice()
+            .collect(),
+        Some(path) => {
+            let mut p = parse_pointcloud_csv(path)?.to_vec();
+            fuzz_pointcloud(&mut p, 0.001, &mut rng);
+            p.shuffle(&mut rng);
+            p.truncate(args.n_points);
+            p.into_boxed_slice()
+        }
     };
 
-    let all_trace: Box<[([f32; 3], f32)]> = if args.len() < 3 {
-        (0..N_TRIALS)
+    let all_trace: Box<Trace> = match &args.trace {
+        None if args.arm => arm_trace(&mut rng, args.n_trials, [0.5, 0.5, 0.0]),
+        None => (0..args.n_trials)
             .map(|_| {
                 (
                     [
@@ -61,35 +108,118 @@ fn main() -> Result<(), Box<dyn Error>> {
                         rng.gen_range(0.0..1.0),
                         rng.gen_range(0.0..1.0),
                     ],
-                    rng.gen_range(0.0..=QUERY_RADIUS),
+                    rng.gen_range(0.0..=args.query_radius),
                 )
             })
-            .collect()
-    } else {
-        parse_trace_csv(&args[2])?
+            .collect(),
+        Some(path) => parse_trace_csv(path)?,
     };
 
-    // let rsq_range = (
-    //     all_trace
-    //         .iter()
-    //         .map(|x| x.1)
-    //         .min_by(|a, b| a.partial_cmp(b).unwrap())
-    //         .ok_or("no points")?
-    //         .powi(2),
-    //     all_trace
-    //         .iter()
-    //         .map(|x| x.1)
-    //         .max_by(|a, b| a.partial_cmp(b).unwrap())
-    //         .ok_or("no points")?
-    //         .powi(2),
-    // );
-    let r_range = (0.01, 0.08);
+    let r_range = if args.fit_r_range {
+        trace_r_range(&all_trace)
+    } else {
+        (args.r_min, args.r_max)
+    };
 
     println!("number of points: {}", points.len());
     println!("number of tests: {}", all_trace.len());
     println!("radius range: {r_range:?}");
+    println!("lanes: {}", args.lanes);
+
+    std::fs::create_dir_all(&args.output_dir)?;
+    match args.lanes {
+        4 => run::<4>(&points, &all_trace, r_range, &args.output_dir)?,
+        8 => run::<8>(&points, &all_trace, r_range, &args.output_dir)?,
+        16 => run::<16>(&points, &all_trace, r_range, &args.output_dir)?,
+        l => return Err(format!("unsupported lane count {l}; use 4, 8 or 16").into()),
+    }
+
+    #[cfg(feature = "plots")]
+    if let Some(format) = args.plot {
+        plot_results(&args.output_dir, format)?;
+    }
+
+    Ok(())
+}
 
-    let captree = Capt::<3>::new(&points, r_range);
+#[cfg(feature = "plots")]
+/// Read back the CSV files in `out_dir` and plot them next to the CSV files.
+fn plot_results(out_dir: &Path, format: PlotFormat) -> Result<(), Box<dyn Error>> {
+    // read the given columns of a CSV file as series against its first column
+    let read_series =
+        |name: &str, columns: &[(usize, &str)]| -> Result<Vec<Series>, Box<dyn Error>> {
+            let rows = std::fs::read_to_string(out_dir.join(name))?
+                .lines()
+                .map(|l| l.split(',').map(str::parse).collect())
+                .collect::<Result<Vec<Vec<f64>>, _>>()?;
+            Ok(columns
+                .iter()
+                .map(|&(col, label)| Series {
+                    name: label.into(),
+                    points: rows.iter().map(|row| (row[0], row[col])).collect(),
+                })
+                .collect())
+        };
+
+    line_plot(
+        &out_dir.join("construct_time"),
+        format,
+        "Construction time",
+        "number of points",
+        "time (s)",
+        &read_series(
+            "construct_time.csv",
+            &[(1, "kiddo"), (2, "forward tree"), (3, "CAPT")],
+        )?,
+    )?;
+    line_plot(
+        &out_dir.join("mem"),
+        format,
+        "Memory use",
+        "number of points",
+        "memory (bytes)",
+        &read_series("mem.csv", &[(1, "forward tree"), (2, "CAPT")])?,
+    )?;
+    for (name, title) in [
+        ("mixed", "Query time (all queries)"),
+        ("collides", "Query time (colliding queries)"),
+        ("no_collides", "Query time (non-colliding queries)"),
+    ] {
+        line_plot(
+            &out_dir.join(name),
+            format,
+            title,
+            "number of points",
+            "time per query (s)",
+            &read_series(
+                &format!("{name}.csv"),
+                &[
+                    (2, "kiddo"),
+                    (3, "forward tree"),
+                    (4, "forward tree (SIMD)"),
+                    (5, "CAPT"),
+                    (6, "CAPT (SIMD)"),
+                ],
+            )?,
+        )?;
+    }
+    Ok(())
+}
+
+/// Run the benchmarks with trees and query batches of `L` lanes, writing CSV files to `out_dir`.
+fn run<const L: usize>(
+    points: &[[f32; 3]],
+    all_trace: &Trace,
+    r_range: (f32, f32),
+    out_dir: &Path,
+) -> Result<(), Box<dyn Error>>
+where
+    Align<L>: Alignment,
+{
+    let mut f_construct = File::create(out_dir.join("construct_time.csv"))?;
+    let mut f_mem = File::create(out_dir.join("mem.csv"))?;
+
+    let captree = Capt::<3, L>::new(points, r_range);
 
     let collide_trace: Box<Trace> = all_trace
         .iter()
@@ -103,25 +233,25 @@ fn main() -> Result<(), Box<dyn Error>> {
         .copied()
         .collect();
 
-    let all_simd_trace = simd_trace_new(&all_trace);
+    let all_simd_trace = simd_trace_new(all_trace);
     let collide_simd_trace = simd_trace_new(&collide_trace);
     let no_collide_simd_trace = simd_trace_new(&no_collide_trace);
 
     let mut benchmarks = [
         Benchmark {
-            seq: &all_trace,
+            seq: all_trace,
             simd: &all_simd_trace,
-            f_query: File::create("mixed.csv")?,
+            f_query: File::create(out_dir.join("mixed.csv"))?,
         },
         Benchmark {
             seq: &collide_trace,
             simd: &collide_simd_trace,
-            f_query: File::create("collides.csv")?,
+            f_query: File::create(out_dir.join("collides.csv"))?,
         },
         Benchmark {
             seq: &no_collide_trace,
             simd: &no_collide_simd_trace,
-            f_query: File::create("no_collides.csv")?,
+            f_query: File::create(out_dir.join("no_collides.csv"))?,
         },
     ];
 
@@ -145,13 +275,16 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn do_row(
+fn do_row<const L: usize>(
     points: &[[f32; 3]],
-    benchmarks: &mut [Benchmark],
+    benchmarks: &mut [Benchmark<L>],
     r_range: (f32, f32),
     f_construct: &mut File,
     f_mem: &mut File,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<(), Box<dyn Error>>
+where
+    Align<L>: Alignment,
+{
     let (kdt, kdt_time) = stopwatch(|| kiddo::ImmutableKdTree::new_from_slice(points));
 
     let (pkdt, pkdt_time) = stopwatch(|| PkdTree::new(points));
@@ -219,22 +352,22 @@ fn do_row(
 
         let (_, pkdt_total_seq_q_time) = stopwatch(|| {
             for (center, radius) in trace.iter() {
-                black_box(pkdt.might_collide(*center, radius.powi(2)));
+                black_box(pkdt.might_collide_r(*center, Radius(*radius)));
             }
         });
         let (_, pkdt_total_simd_q_time) = stopwatch(|| {
-            for (centers, radii) in simd_trace.iter() {
-                black_box(pkdt.might_collide_simd(centers, radii * radii));
+            for batch in simd_trace.iter() {
+                black_box(pkdt.might_collide_batch(batch));
             }
         });
         let (_, captree_total_seq_q_time) = stopwatch(|| {
             for (center, radius) in trace.iter() {
-                black_box(captree.collides(center, radius.powi(2)));
+                black_box(captree.collides_r(center, Radius(*radius)));
             }
         });
         let (_, captree_total_simd_q_time) = stopwatch(|| {
-            for (centers, radii) in simd_trace.iter() {
-                black_box(captree.collides_simd(centers, radii * radii));
+            for batch in simd_trace.iter() {
+                black_box(captree.collides_batch(batch));
             }
         });
 
@@ -268,19 +401,39 @@ fn do_row(
             write!(f_query, ",{}", query_time.as_secs_f64() / trace_len)?;
         }
 
+        // tail latencies, appended after the means so that existing columns keep their indices:
+        // the latency at each of `LATENCY_QUANTILES` for sequential forward tree queries, then
+        // sequential CAPT queries, then SIMD CAPT batches
+        let latencies = [
+            latency_histogram(trace, |(center, radius)| {
+                black_box(pkdt.might_collide_r(*center, Radius(*radius)));
+            }),
+            latency_histogram(trace, |(center, radius)| {
+                black_box(captree.collides_r(center, Radius(*radius)));
+            }),
+            latency_histogram(simd_trace, |batch| {
+                black_box(captree.collides_batch(batch));
+            }),
+        ];
+        for histogram in &latencies {
+            for latency in latency_percentiles(histogram) {
+                write!(f_query, ",{latency}")?;
+            }
+        }
+
         writeln!(f_query)?;
     }
 
     Ok(())
 }
 
-fn bench_forest<const T: usize>(
+fn bench_forest<const T: usize, const L: usize>(
     forest: &PkdForest<3, T>,
-    simd_trace: &[([f32x8; 3], f32x8)],
+    simd_trace: &SimdTrace<L>,
 ) -> Duration {
     stopwatch(|| {
-        for (centers, radii) in simd_trace {
-            black_box(forest.might_collide_simd(centers, radii * radii));
+        for batch in simd_trace {
+            black_box(forest.might_collide_batch(batch));
         }
     })
     .1
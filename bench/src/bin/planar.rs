@@ -0,0 +1,110 @@
+use std::hint::black_box;
+
+use bench::{latency_histogram, latency_percentiles, make_needles, stopwatch, LATENCY_QUANTILES};
+use captree::{Capt, Capt2d};
+use clap::Parser;
+use elain::{Align, Alignment};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+#[derive(Parser)]
+/// Compare the query time of a 2-dimensional tree against a 3-dimensional tree of the same points
+/// with zero z.
+struct Args {
+    /// The number of points in each tree.
+    #[arg(long, default_value_t = 1 << 16)]
+    n_points: usize,
+    /// The number of queries to run.
+    #[arg(long, default_value_t = 1 << 20)]
+    n_trials: usize,
+    /// The number of SIMD lanes in each tree: 4, 8 or 16.
+    #[arg(long, default_value_t = 8)]
+    lanes: usize,
+    /// The smallest radius of a query.
+    #[arg(long, default_value_t = 0.001)]
+    r_min: f32,
+    /// The largest radius of a query.
+    #[arg(long, default_value_t = 0.01)]
+    r_max: f32,
+    /// The seed for sampling points and queries.
+    #[arg(long, default_value_t = 2707)]
+    seed: u64,
+}
+
+fn main() -> Result<(), String> {
+    let args = Args::parse();
+    match args.lanes {
+        4 => run::<4>(&args),
+        8 => run::<8>(&args),
+        16 => run::<16>(&args),
+        l => return Err(format!("unsupported lane count {l}; use 4, 8 or 16")),
+    }
+    Ok(())
+}
+
+/// Run the comparison with trees of `L` lanes.
+fn run<const L: usize>(args: &Args)
+where
+    Align<L>: Alignment,
+{
+    let n_trials = args.n_trials;
+    let r_range = (args.r_min, args.r_max);
+    let mut rng = ChaCha20Rng::seed_from_u64(args.seed);
+    let points: Box<[[f32; 2]]> = (0..args.n_points).map(|_| [rng.gen(), rng.gen()]).collect();
+    let lifted: Box<[[f32; 3]]> = points.iter().map(|&[x, y]| [x, y, 0.0]).collect();
+    let (needles, _) = make_needles::<2, L>(&mut rng, n_trials);
+    let lifted_needles: Vec<[f32; 3]> = needles.iter().map(|&[x, y]| [x, y, 0.0]).collect();
+    let radii: Vec<f32> = (0..n_trials)
+        .map(|_| rng.gen_range(r_range.0..=r_range.1))
+        .collect();
+
+    let planar = Capt2d::<L>::new(&points, r_range);
+    let spatial = Capt::<3, L>::new(&lifted, r_range);
+
+    let (n_planar, planar_time) = stopwatch(|| {
+        needles
+            .iter()
+            .zip(&radii)
+            .filter(|(c, &r)| planar.collides(black_box(c), r))
+            .count()
+    });
+    let (n_spatial, spatial_time) = stopwatch(|| {
+        lifted_needles
+            .iter()
+            .zip(&radii)
+            .filter(|(c, &r)| spatial.collides(black_box(c), r))
+            .count()
+    });
+    assert_eq!(n_planar, n_spatial);
+
+    println!("collisions: {n_planar} of {n_trials}");
+    println!(
+        "2D: {:?} per query",
+        planar_time / u32::try_from(n_trials).unwrap()
+    );
+    println!(
+        "3D with zero z: {:?} per query",
+        spatial_time / u32::try_from(n_trials).unwrap()
+    );
+
+    let planar_queries: Vec<_> = needles.iter().zip(&radii).collect();
+    let spatial_queries: Vec<_> = lifted_needles.iter().zip(&radii).collect();
+    let planar_latencies = latency_histogram(&planar_queries, |&(c, &r)| {
+        black_box(planar.collides(black_box(c), r));
+    });
+    let spatial_latencies = latency_histogram(&spatial_queries, |&(c, &r)| {
+        black_box(spatial.collides(black_box(c), r));
+    });
+    for (name, histogram) in [
+        ("2D", planar_latencies),
+        ("3D with zero z", spatial_latencies),
+    ] {
+        let percentiles = latency_percentiles(&histogram);
+        let summary: Vec<String> = LATENCY_QUANTILES
+            .iter()
+            .zip(percentiles)
+            .map(|(q, t)| format!("p{}: {:.0} ns", q * 100.0, t * 1e9))
+            .collect();
+        println!("{name} latency: {}", summary.join(", "));
+    }
+}
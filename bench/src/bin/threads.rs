@@ -0,0 +1,122 @@
+use std::{hint::black_box, thread, time::Instant};
+
+use bench::{latency_histogram, latency_percentiles, LATENCY_QUANTILES};
+use captree::Capt;
+use clap::Parser;
+use rand::Rng;
+use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+
+#[derive(Parser)]
+/// Query one shared tree from an increasing number of threads, reporting aggregate throughput and
+/// per-thread latency.
+///
+/// Queries only read the tree, so throughput should scale with the number of threads up to the
+/// number of cores.
+struct Args {
+    /// The number of points in the tree.
+    #[arg(long, default_value_t = 1 << 16)]
+    n_points: usize,
+    /// The number of queries run by each thread.
+    #[arg(long, default_value_t = 1 << 18)]
+    n_trials: usize,
+    /// The largest number of threads to run. Defaults to the available parallelism.
+    #[arg(long)]
+    max_threads: Option<usize>,
+    /// The smallest radius of a query.
+    #[arg(long, default_value_t = 0.01)]
+    r_min: f32,
+    /// The largest radius of a query.
+    #[arg(long, default_value_t = 0.08)]
+    r_max: f32,
+    /// The seed for sampling points and queries.
+    #[arg(long, default_value_t = 2707)]
+    seed: u64,
+}
+
+fn main() {
+    let args = Args::parse();
+    let r_range = (args.r_min, args.r_max);
+    let max_threads = args
+        .max_threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, usize::from));
+
+    let mut rng = ChaCha20Rng::seed_from_u64(args.seed);
+    let points: Box<[[f32; 3]]> = (0..args.n_points)
+        .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+        .collect();
+    // each thread gets its own queries, so that threads do not share cache lines of the trace
+    let traces: Vec<Vec<([f32; 3], f32)>> = (0..max_threads)
+        .map(|_| {
+            (0..args.n_trials)
+                .map(|_| {
+                    (
+                        [rng.gen(), rng.gen(), rng.gen()],
+                        rng.gen_range(r_range.0..=r_range.1),
+                    )
+                })
+                .collect()
+        })
+        .collect();
+    let tree = Capt::<3>::new(&points, r_range);
+
+    println!("number of points: {}", points.len());
+    println!("queries per thread: {}", args.n_trials);
+    println!(
+        "threads,queries per second,mean latency (ns),{}",
+        LATENCY_QUANTILES
+            .map(|q| format!("p{} latency (ns)", q * 100.0))
+            .join(",")
+    );
+    for n_threads in 1..=max_threads {
+        let traces = &traces[..n_threads];
+        let tree = &tree;
+
+        // time the whole run first, then time each query separately, since reading the clock
+        // around every query slows down the run
+        let tic = Instant::now();
+        let means: Vec<f64> = thread::scope(|s| {
+            let handles: Vec<_> = traces
+                .iter()
+                .map(|trace| {
+                    s.spawn(move || {
+                        let tic = Instant::now();
+                        for (center, r) in trace {
+                            black_box(tree.collides(black_box(center), *r));
+                        }
+                        tic.elapsed().as_secs_f64() / trace.len() as f64
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        let elapsed = tic.elapsed().as_secs_f64();
+
+        let histograms: Vec<_> = thread::scope(|s| {
+            let handles: Vec<_> = traces
+                .iter()
+                .map(|trace| {
+                    s.spawn(move || {
+                        latency_histogram(trace, |(center, r)| {
+                            black_box(tree.collides(black_box(center), *r));
+                        })
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        let mut histogram = histograms[0].clone();
+        for h in &histograms[1..] {
+            histogram.add(h).unwrap();
+        }
+
+        let throughput = (n_threads * args.n_trials) as f64 / elapsed;
+        let mean = means.iter().sum::<f64>() / n_threads as f64;
+        println!(
+            "{n_threads},{throughput:.0},{:.1},{}",
+            mean * 1e9,
+            latency_percentiles(&histogram)
+                .map(|t| format!("{:.0}", t * 1e9))
+                .join(",")
+        );
+    }
+}
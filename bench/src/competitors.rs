@@ -0,0 +1,89 @@
+//! Other spatial structures, wrapped to implement [`Collider`] so that they can be benchmarked
+//! against CAPTs with the same code.
+//!
+//! `nabo` is not among them: its k-d tree answers a collision check with a nearest-neighbor
+//! query, just as `kiddo` does, so it is left out rather than adding another dependency to the
+//! workspace.
+
+use std::simd::{cmp::SimdPartialOrd, Simd};
+
+use captree::Collider;
+use kiddo::{ImmutableKdTree, SquaredEuclidean};
+
+/// A `kiddo` k-d tree, which checks for collisions with a nearest-neighbor query.
+pub struct Kiddo(ImmutableKdTree<f32, 3>);
+
+impl Kiddo {
+    #[must_use]
+    pub fn new(points: &[[f32; 3]]) -> Self {
+        Self(ImmutableKdTree::new_from_slice(points))
+    }
+}
+
+impl Collider<3, f32> for Kiddo {
+    fn collides(&self, center: &[f32; 3], radius: f32) -> bool {
+        self.0.nearest_one::<SquaredEuclidean>(center).distance <= radius * radius
+    }
+}
+
+#[cfg(feature = "rstar")]
+/// An `rstar` R*-tree, which checks for collisions by looking for any point within the radius.
+pub struct RStar(rstar::RTree<[f32; 3]>);
+
+#[cfg(feature = "rstar")]
+impl RStar {
+    #[must_use]
+    pub fn new(points: &[[f32; 3]]) -> Self {
+        Self(rstar::RTree::bulk_load(points.to_vec()))
+    }
+}
+
+#[cfg(feature = "rstar")]
+impl Collider<3, f32> for RStar {
+    fn collides(&self, center: &[f32; 3], radius: f32) -> bool {
+        self.0
+            .locate_within_distance(*center, radius * radius)
+            .next()
+            .is_some()
+    }
+}
+
+/// A brute-force scan over every point, `L` points at a time.
+///
+/// Queries take time linear in the number of points, so this is only a baseline for small clouds.
+pub struct BruteForce<const L: usize> {
+    /// The points, transposed into lanes of `L` values along each axis.
+    /// The last lanes are padded with infinity, which never collides.
+    lanes: Box<[[Simd<f32, L>; 3]]>,
+}
+
+impl<const L: usize> BruteForce<L> {
+    #[must_use]
+    pub fn new(points: &[[f32; 3]]) -> Self {
+        Self {
+            lanes: points
+                .chunks(L)
+                .map(|chunk| {
+                    std::array::from_fn(|k| {
+                        Simd::from_array(std::array::from_fn(|l| {
+                            chunk.get(l).map_or(f32::INFINITY, |p| p[k])
+                        }))
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<const L: usize> Collider<3, f32> for BruteForce<L> {
+    fn collides(&self, center: &[f32; 3], radius: f32) -> bool {
+        let rsq = Simd::splat(radius * radius);
+        let centers = center.map(Simd::splat);
+        self.lanes.iter().any(|lane| {
+            let distsq = (0..3)
+                .map(|k| lane[k] - centers[k])
+                .fold(Simd::splat(0.0), |acc, d| acc + d * d);
+            distsq.simd_le(rsq).any()
+        })
+    }
+}
@@ -1,15 +1,23 @@
-//! Power-of-two k-d forests.
+//! Power-of-two k-d forests, and forests of affordance trees over subsampled clouds.
 
-use std::simd::{
-    cmp::SimdPartialOrd, ptr::SimdConstPtr, LaneCount, Mask, Simd, SupportedLaneCount,
+use std::{
+    collections::HashMap,
+    simd::{cmp::SimdPartialOrd, ptr::SimdConstPtr, Mask, Simd},
 };
 
+use captree::{Capt, Collider, SimdQueryBatch};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use rand_distr::{Distribution, StandardNormal};
+
 use crate::{distsq, median_partition};
 
 #[derive(Clone, Debug)]
 struct RandomizedTree<const K: usize> {
     tests: Box<[f32]>,
     seed: u32,
+    /// The rows of the rotation applied to points before they are stored in this tree, if any.
+    rotation: Option<[[f32; K]; K]>,
     points: Box<[[f32; K]]>,
 }
 
@@ -28,30 +36,58 @@ impl<const K: usize, const T: usize> PkdForest<K, T> {
         trees
             .iter_mut()
             .enumerate()
-            .for_each(|(t, opt)| *opt = Some(RandomizedTree::new(points, t as u32)));
+            // xorshift maps zero to itself, so seed from one to keep every tree randomized
+            .for_each(|(t, opt)| *opt = Some(RandomizedTree::new(points, t as u32 + 1)));
+        Self {
+            test_seqs: trees.map(Option::unwrap),
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    #[must_use]
+    /// Construct a new forest whose trees are each built over a random rotation of `points`.
+    ///
+    /// Every tree in a forest built by [`PkdForest::new`] splits along the coordinate axes, so
+    /// the trees tend to put their split planes in the same places and miss the same neighbors.
+    /// Rotating each tree decorrelates their cells, which lowers the miss probability for a given
+    /// `T` at the cost of a matrix-vector product per tree on every query.
+    ///
+    /// Each rotation is seeded from the index of its tree, so construction is deterministic.
+    pub fn new_rotated(points: &[[f32; K]]) -> Self {
+        let mut trees = [Self::T_NONE; T];
+        trees
+            .iter_mut()
+            .enumerate()
+            .for_each(|(t, opt)| *opt = Some(RandomizedTree::new_rotated(points, t as u32 + 1)));
         Self {
             test_seqs: trees.map(Option::unwrap),
         }
     }
 
     #[must_use]
+    /// Get the nearest of the points stored in the leaf cells which `needle` falls into in each
+    /// tree, along with its squared distance to `needle`.
+    ///
+    /// This is the sequential counterpart to the forward passes of
+    /// [`PkdForest::might_collide_simd`], for code paths which do not batch queries.
+    /// In a forest built by [`PkdForest::new_rotated`], the returned point is rotated back from
+    /// the frame of its tree, so it may differ from the original point by rounding error.
+    ///
     /// # Panics
     ///
     /// This function will panic if `T` is 0.
     pub fn approx_nearest(&self, needle: [f32; K]) -> ([f32; K], f32) {
-        self.test_seqs
-            .iter()
-            .map(|t| t.points[t.forward_pass(&needle)])
-            .map(|point| (point, distsq(needle, point)))
-            .min_by(|(_, d1), (_, d2)| d1.partial_cmp(d2).unwrap())
-            .unwrap()
+        approx_nearest(&self.test_seqs, needle)
     }
 
     #[must_use]
+    /// Determine whether a ball centered at `needle` with squared radius `r_squared` could collide
+    /// with a point in this forest.
+    ///
+    /// As with [`PkdForest::might_collide_simd`], this only checks the leaf cell containing
+    /// `needle` in each tree, so it may miss collisions but never reports a false one.
     pub fn might_collide(&self, needle: [f32; K], r_squared: f32) -> bool {
-        self.test_seqs
-            .iter()
-            .any(|t| distsq(t.points[t.forward_pass(&needle)], needle) < r_squared)
+        might_collide(&self.test_seqs, needle, r_squared)
     }
 
     #[must_use]
@@ -59,33 +95,322 @@ impl<const K: usize, const T: usize> PkdForest<K, T> {
         &self,
         needles: &[Simd<f32, L>; K],
         radii_squared: Simd<f32, L>,
-    ) -> bool
-    where
-        LaneCount<L>: SupportedLaneCount,
-    {
-        let mut not_yet_collided = Mask::splat(true);
-
-        for tree in &self.test_seqs {
-            let indices = tree.mask_query(needles, not_yet_collided);
-            let mut dists_sq = Simd::splat(0.0);
-            let mut ptrs = Simd::splat(tree.points.as_ptr().cast()).wrapping_offset(indices);
-            for needle_set in needles {
-                let diffs =
-                    unsafe { Simd::gather_select_ptr(ptrs, not_yet_collided, Simd::splat(0.0)) }
-                        - needle_set;
-                dists_sq += diffs * diffs;
-                ptrs = ptrs.wrapping_add(Simd::splat(1));
+    ) -> bool {
+        might_collide_simd(&self.test_seqs, needles, radii_squared)
+    }
+
+    #[must_use]
+    /// Determine whether any ball in `batch` could collide with a point in this forest.
+    pub fn might_collide_batch<const L: usize>(&self, batch: &SimdQueryBatch<K, L>) -> bool {
+        self.might_collide_simd(&batch.centers, batch.radii * batch.radii)
+    }
+
+    #[must_use]
+    /// Get the total memory used by this forest, in bytes.
+    pub fn memory_used(&self) -> usize {
+        size_of::<Self>()
+            + self
+                .test_seqs
+                .iter()
+                .map(RandomizedTree::heap_used)
+                .sum::<usize>()
+    }
+
+    #[must_use]
+    /// Get the number of points in each tree, not counting the padding which fills each tree out
+    /// to a power of two.
+    pub fn tree_sizes(&self) -> [usize; T] {
+        std::array::from_fn(|t| self.test_seqs[t].n_points())
+    }
+
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    /// Measure how often this forest misses collisions between the balls centered at `needles`
+    /// with squared radius `r_squared` and its points.
+    ///
+    /// Each needle is checked against every point, so this takes time proportional to the number
+    /// of needles times the number of points.
+    pub fn stats(&self, needles: &[[f32; K]], r_squared: f32) -> ForestStats<T> {
+        let first = &self.test_seqs[0];
+        let points = &first.points[..first.n_points()];
+        let (mut n_colliding, mut tree_misses, mut forest_misses) = (0, [0; T], 0);
+        for &needle in needles {
+            // distances are the same in every tree's frame, so search the first one
+            let first_local = first.localize(needle);
+            if !points.iter().any(|&p| distsq(p, first_local) <= r_squared) {
+                continue;
+            }
+            n_colliding += 1;
+            let mut missed = true;
+            for (t, tree) in self.test_seqs.iter().enumerate() {
+                let local = tree.localize(needle);
+                if distsq(tree.points[tree.forward_pass(&local)], local) <= r_squared {
+                    missed = false;
+                } else {
+                    tree_misses[t] += 1;
+                }
+            }
+            forest_misses += usize::from(missed);
+        }
+
+        let rate = |misses: usize| {
+            if n_colliding == 0 {
+                0.0
+            } else {
+                misses as f64 / f64::from(n_colliding)
             }
+        };
+        ForestStats {
+            tree_sizes: self.tree_sizes(),
+            memory_used: self.memory_used(),
+            tree_miss_probability: tree_misses.map(rate),
+            miss_probability: rate(forest_misses),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// A report on the size and accuracy of a [`PkdForest`], as returned by [`PkdForest::stats`].
+pub struct ForestStats<const T: usize> {
+    /// The number of points in each tree.
+    pub tree_sizes: [usize; T],
+    /// The total memory used by the forest, in bytes.
+    pub memory_used: usize,
+    /// For each tree on its own, the fraction of colliding needles whose collision it misses.
+    pub tree_miss_probability: [f64; T],
+    /// The fraction of colliding needles whose collision every tree misses.
+    pub miss_probability: f64,
+}
+
+impl<const T: usize> ForestStats<T> {
+    #[must_use]
+    /// Predict the miss probability of a forest made of only the first `n_trees` trees, assuming
+    /// that the trees miss independently.
+    ///
+    /// Every tree holds the same points, so their misses are positively correlated and this
+    /// prediction is optimistic.
+    /// Comparing it against [`ForestStats::miss_probability`] for the whole forest shows how far
+    /// off it is, so that `T` can be chosen from a single measurement of a large forest.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `n_trees` is greater than `T`.
+    pub fn predicted_miss_probability(&self, n_trees: usize) -> f64 {
+        self.tree_miss_probability[..n_trees].iter().product()
+    }
+}
+
+#[derive(Clone, Debug)]
+/// A [`PkdForest`] whose number of trees is chosen at runtime rather than baked into its type.
+///
+/// This behaves exactly like a `PkdForest` with the same number of trees, so that the tradeoff
+/// between accuracy and latency can be tuned from configuration without recompiling.
+/// Each query pays for an extra indirection to reach the trees.
+pub struct DynPkdForest<const K: usize> {
+    test_seqs: Box<[RandomizedTree<K>]>,
+}
+
+impl<const K: usize> DynPkdForest<K> {
+    #[allow(clippy::cast_possible_truncation)]
+    #[must_use]
+    /// Construct a new forest of `n_trees` trees over `points`, as in [`PkdForest::new`].
+    pub fn new(points: &[[f32; K]], n_trees: usize) -> Self {
+        Self {
+            test_seqs: (0..n_trees)
+                .map(|t| RandomizedTree::new(points, t as u32 + 1))
+                .collect(),
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    #[must_use]
+    /// Construct a new forest of `n_trees` randomly rotated trees over `points`, as in
+    /// [`PkdForest::new_rotated`].
+    pub fn new_rotated(points: &[[f32; K]], n_trees: usize) -> Self {
+        Self {
+            test_seqs: (0..n_trees)
+                .map(|t| RandomizedTree::new_rotated(points, t as u32 + 1))
+                .collect(),
+        }
+    }
+
+    #[must_use]
+    /// Get the number of trees in this forest.
+    pub fn n_trees(&self) -> usize {
+        self.test_seqs.len()
+    }
+
+    #[must_use]
+    /// Get the approximate nearest neighbor of `needle` and its squared distance, as in
+    /// [`PkdForest::approx_nearest`].
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if this forest has no trees.
+    pub fn approx_nearest(&self, needle: [f32; K]) -> ([f32; K], f32) {
+        approx_nearest(&self.test_seqs, needle)
+    }
+
+    #[must_use]
+    /// Determine whether a ball centered at `needle` with squared radius `r_squared` could collide
+    /// with a point in this forest, as in [`PkdForest::might_collide`].
+    pub fn might_collide(&self, needle: [f32; K], r_squared: f32) -> bool {
+        might_collide(&self.test_seqs, needle, r_squared)
+    }
+
+    #[must_use]
+    /// Determine whether any of a set of balls could collide with a point in this forest, as in
+    /// [`PkdForest::might_collide_simd`].
+    pub fn might_collide_simd<const L: usize>(
+        &self,
+        needles: &[Simd<f32, L>; K],
+        radii_squared: Simd<f32, L>,
+    ) -> bool {
+        might_collide_simd(&self.test_seqs, needles, radii_squared)
+    }
+
+    #[must_use]
+    /// Determine whether any ball in `batch` could collide with a point in this forest.
+    pub fn might_collide_batch<const L: usize>(&self, batch: &SimdQueryBatch<K, L>) -> bool {
+        self.might_collide_simd(&batch.centers, batch.radii * batch.radii)
+    }
+
+    #[must_use]
+    /// Get the total memory used by this forest, in bytes.
+    pub fn memory_used(&self) -> usize {
+        size_of::<Self>()
+            + self
+                .test_seqs
+                .iter()
+                .map(|t| size_of::<RandomizedTree<K>>() + t.heap_used())
+                .sum::<usize>()
+    }
 
-            not_yet_collided &= radii_squared.simd_lt(dists_sq).cast();
+    #[must_use]
+    /// Get the number of points in each tree, not counting padding.
+    pub fn tree_sizes(&self) -> Vec<usize> {
+        self.test_seqs
+            .iter()
+            .map(RandomizedTree::n_points)
+            .collect()
+    }
+}
 
-            if !not_yet_collided.all() {
-                // at least one has collided - can return quickly
-                return true;
+impl<const K: usize, const T: usize> From<PkdForest<K, T>> for DynPkdForest<K> {
+    fn from(forest: PkdForest<K, T>) -> Self {
+        Self {
+            test_seqs: Box::new(forest.test_seqs),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+/// A forest of `T` CAPTs, each built over a stratified subsample of a point cloud.
+///
+/// Space is divided into cubic voxels, and the `t`-th point to fall in each voxel goes to the
+/// `t`-th tree; any further points in a voxel are dropped.
+/// On dense clouds, this stores far fewer points than a single tree over the whole cloud.
+///
+/// Every dropped point is within `spacing * sqrt(K)` of the point kept by the first tree in its
+/// voxel, so queries are inflated by a fixed `inflation` to make up for the dropped points.
+/// With `inflation` equal to `spacing * sqrt(K)`, the forest never misses a collision but may
+/// report collisions up to `inflation` away from any point; smaller inflations trade a bounded
+/// rate of missed collisions for fewer spurious ones.
+pub struct AffordanceForest<const K: usize, const T: usize> {
+    trees: [Capt<K>; T],
+    inflation: f32,
+}
+
+impl<const K: usize, const T: usize> AffordanceForest<K, T> {
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    /// Construct a new forest over `points`, supporting query radii in `r_range`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `spacing` is not positive or if any point is not finite.
+    pub fn new(points: &[[f32; K]], r_range: (f32, f32), spacing: f32, inflation: f32) -> Self {
+        assert!(spacing > 0.0);
+        let mut counts: HashMap<[i64; K], usize> = HashMap::new();
+        let mut strata: [Vec<[f32; K]>; T] = std::array::from_fn(|_| Vec::new());
+        for p in points {
+            let count = counts
+                .entry(p.map(|x| (x / spacing).floor() as i64))
+                .or_default();
+            if let Some(stratum) = strata.get_mut(*count) {
+                stratum.push(*p);
             }
+            *count += 1;
         }
 
-        false
+        let r_range = (r_range.0 + inflation, r_range.1 + inflation);
+        Self {
+            trees: strata.map(|stratum| Capt::new(&stratum, r_range)),
+            inflation,
+        }
+    }
+
+    #[must_use]
+    /// Determine whether a ball centered at `center` with radius `radius` might collide with a
+    /// point in the original cloud, by checking each tree with an inflated radius.
+    pub fn collides(&self, center: &[f32; K], radius: f32) -> bool {
+        self.trees
+            .iter()
+            .any(|t| t.collides(center, radius + self.inflation))
+    }
+
+    #[must_use]
+    /// Determine whether any ball in the list of provided balls might collide with a point in the
+    /// original cloud, as in [`AffordanceForest::collides`].
+    pub fn collides_simd(&self, centers: &[Simd<f32, 8>; K], radii: Simd<f32, 8>) -> bool {
+        let radii = radii + Simd::splat(self.inflation);
+        self.trees.iter().any(|t| t.collides_simd(centers, radii))
+    }
+
+    #[must_use]
+    /// Get the total number of points stored across all the trees.
+    pub fn n_points(&self) -> usize {
+        self.trees.iter().map(Capt::len).sum()
+    }
+
+    #[must_use]
+    /// Get the number of points in each tree.
+    ///
+    /// Later trees only hold points from voxels with more than one point, so they shrink quickly
+    /// on sparse clouds.
+    pub fn tree_sizes(&self) -> [usize; T] {
+        std::array::from_fn(|t| self.trees[t].len())
+    }
+
+    #[must_use]
+    /// Get the total memory used by this forest, in bytes.
+    pub fn memory_used(&self) -> usize {
+        size_of::<Self>() - size_of::<[Capt<K>; T]>()
+            + self.trees.iter().map(Capt::memory_used).sum::<usize>()
+    }
+}
+
+impl<const K: usize, const T: usize> Collider<K, f32> for PkdForest<K, T> {
+    /// Determine whether a point in this forest might be within `radius` of `center`, as in
+    /// [`PkdForest::might_collide`].
+    ///
+    /// Unlike exact structures, a forest may miss collisions.
+    fn collides(&self, center: &[f32; K], radius: f32) -> bool {
+        self.might_collide(*center, radius * radius)
+    }
+}
+
+impl<const K: usize> Collider<K, f32> for DynPkdForest<K> {
+    /// Determine whether a point in this forest might be within `radius` of `center`, as in
+    /// [`DynPkdForest::might_collide`].
+    fn collides(&self, center: &[f32; K], radius: f32) -> bool {
+        self.might_collide(*center, radius * radius)
+    }
+}
+
+impl<const K: usize, const T: usize> Collider<K, f32> for AffordanceForest<K, T> {
+    fn collides(&self, center: &[f32; K], radius: f32) -> bool {
+        self.collides(center, radius)
     }
 }
 
@@ -131,13 +456,59 @@ impl<const K: usize> RandomizedTree<K> {
             tests,
             points: new_points.into_boxed_slice(),
             seed,
+            rotation: None,
+        }
+    }
+
+    /// Construct a new tree over `points` rotated by a random rotation drawn from `seed`.
+    pub fn new_rotated(points: &[[f32; K]], seed: u32) -> Self {
+        let rotation = random_rotation(seed);
+        let rotated: Vec<[f32; K]> = points.iter().map(|&p| rotate(&rotation, p)).collect();
+        Self {
+            rotation: Some(rotation),
+            ..Self::new(&rotated, seed)
         }
     }
 
+    /// Move `point` from the original space into the frame of this tree.
+    fn localize(&self, point: [f32; K]) -> [f32; K] {
+        self.rotation.as_ref().map_or(point, |r| rotate(r, point))
+    }
+
+    /// Move `point` from the frame of this tree back into the original space.
+    fn globalize(&self, point: [f32; K]) -> [f32; K] {
+        self.rotation.as_ref().map_or(point, |r| {
+            // the inverse of a rotation is its transpose
+            std::array::from_fn(|j| (0..K).map(|i| r[i][j] * point[i]).sum())
+        })
+    }
+
+    /// Move a set of SIMD needles from the original space into the frame of this tree.
+    fn localize_simd<const L: usize>(&self, needles: &[Simd<f32, L>; K]) -> [Simd<f32, L>; K] {
+        self.rotation.as_ref().map_or(*needles, |r| {
+            std::array::from_fn(|i| {
+                (0..K).fold(Simd::splat(0.0), |acc, j| {
+                    acc + Simd::splat(r[i][j]) * needles[j]
+                })
+            })
+        })
+    }
+
+    /// Get the number of points in this tree, not counting padding.
+    fn n_points(&self) -> usize {
+        self.points.partition_point(|p| p[0].is_finite())
+    }
+
+    /// Get the memory this tree uses outside of its own struct, in bytes.
+    fn heap_used(&self) -> usize {
+        self.tests.len() * size_of::<f32>() + self.points.len() * size_of::<[f32; K]>()
+    }
+
     fn forward_pass(&self, point: &[f32; K]) -> usize {
         let mut test_idx = 0;
-        let mut k = 0;
         let mut state = self.seed;
+        // the root splits along the same axis as in construction and in `mask_query`
+        let mut k = state as usize % K;
         for _ in 0..self.tests.len().trailing_ones() {
             test_idx = 2 * test_idx
                 + 1
@@ -157,10 +528,7 @@ impl<const K: usize> RandomizedTree<K> {
         &self,
         needles: &[Simd<f32, L>; K],
         mask: Mask<isize, L>,
-    ) -> Simd<isize, L>
-    where
-        LaneCount<L>: SupportedLaneCount,
-    {
+    ) -> Simd<isize, L> {
         let mut test_idxs: Simd<isize, L> = Simd::splat(0);
         let mut state = self.seed;
 
@@ -179,7 +547,7 @@ impl<const K: usize> RandomizedTree<K> {
             // TODO is there a faster way than using a conditional select?
             test_idxs <<= Simd::splat(1);
             test_idxs += Simd::splat(1);
-            test_idxs += cmp_results.to_int() & Simd::splat(1);
+            test_idxs += cmp_results.to_simd() & Simd::splat(1);
             state = xorshift(state);
         }
 
@@ -187,6 +555,104 @@ impl<const K: usize> RandomizedTree<K> {
     }
 }
 
+/// Get the nearest of the points stored in the leaf cells which `needle` falls into in each of
+/// `trees`, along with its squared distance to `needle`.
+///
+/// # Panics
+///
+/// This function will panic if `trees` is empty.
+fn approx_nearest<const K: usize>(
+    trees: &[RandomizedTree<K>],
+    needle: [f32; K],
+) -> ([f32; K], f32) {
+    trees
+        .iter()
+        .map(|t| t.globalize(t.points[t.forward_pass(&t.localize(needle))]))
+        .map(|point| (point, distsq(needle, point)))
+        .min_by(|(_, d1), (_, d2)| d1.partial_cmp(d2).unwrap())
+        .unwrap()
+}
+
+/// Determine whether the leaf cell containing `needle` in any of `trees` holds a point within a
+/// squared distance of `r_squared` of `needle`.
+fn might_collide<const K: usize>(
+    trees: &[RandomizedTree<K>],
+    needle: [f32; K],
+    r_squared: f32,
+) -> bool {
+    trees.iter().any(|t| {
+        let local = t.localize(needle);
+        distsq(t.points[t.forward_pass(&local)], local) <= r_squared
+    })
+}
+
+#[allow(clippy::cast_possible_wrap)]
+/// Determine whether the leaf cell containing any of `needles` in any of `trees` holds a point
+/// within the corresponding squared radius.
+fn might_collide_simd<const K: usize, const L: usize>(
+    trees: &[RandomizedTree<K>],
+    needles: &[Simd<f32, L>; K],
+    radii_squared: Simd<f32, L>,
+) -> bool {
+    let mut not_yet_collided = Mask::splat(true);
+
+    for tree in trees {
+        let needles = &tree.localize_simd(needles);
+        let indices = tree.mask_query(needles, not_yet_collided);
+        let mut dists_sq = Simd::splat(0.0);
+        // each point is `K` floats wide
+        let mut ptrs = Simd::splat(tree.points.as_ptr().cast())
+            .wrapping_offset(indices * Simd::splat(K as isize));
+        for needle_set in needles {
+            let diffs =
+                unsafe { Simd::gather_select_ptr(ptrs, not_yet_collided, Simd::splat(0.0)) }
+                    - needle_set;
+            dists_sq += diffs * diffs;
+            ptrs = ptrs.wrapping_add(Simd::splat(1));
+        }
+
+        not_yet_collided &= radii_squared.simd_lt(dists_sq).cast();
+
+        if !not_yet_collided.all() {
+            // at least one has collided - can return quickly
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Apply the rotation whose rows are `rotation` to `point`.
+fn rotate<const K: usize>(rotation: &[[f32; K]; K], point: [f32; K]) -> [f32; K] {
+    rotation.map(|row| row.iter().zip(point).map(|(r, x)| r * x).sum())
+}
+
+#[allow(clippy::cast_possible_truncation)]
+/// Draw a uniformly random orthogonal matrix from `seed`, given as its rows.
+///
+/// The rows are found by Gram-Schmidt orthogonalization of vectors of Gaussian samples, which is
+/// done in double precision so that the result stays orthogonal after rounding to `f32`.
+fn random_rotation<const K: usize>(seed: u32) -> [[f32; K]; K] {
+    let mut rng = ChaCha20Rng::seed_from_u64(u64::from(seed));
+    let mut rows = [[0.0f64; K]; K];
+    for i in 0..K {
+        loop {
+            let mut row: [f64; K] = std::array::from_fn(|_| StandardNormal.sample(&mut rng));
+            for prev in &rows[..i] {
+                let dot: f64 = row.iter().zip(prev).map(|(a, b)| a * b).sum();
+                row.iter_mut().zip(prev).for_each(|(a, b)| *a -= dot * b);
+            }
+            let norm = row.iter().map(|a| a * a).sum::<f64>().sqrt();
+            // a degenerate draw is vanishingly unlikely, but would divide by zero
+            if norm > 1e-6 {
+                rows[i] = row.map(|a| a / norm);
+                break;
+            }
+        }
+    }
+    rows.map(|row| row.map(|a| a as f32))
+}
+
 #[inline]
 /// Compute the next value in the xorshift sequence given the most recent value.
 const fn xorshift(mut x: u32) -> u32 {
@@ -220,4 +686,150 @@ mod tests {
         assert!((ndsq - 0.0005) < 1e-6);
         println!("{:?}", forest.approx_nearest([0.01, 0.02]));
     }
+
+    #[test]
+    fn sequential_matches_simd() {
+        let points: Vec<[f32; 3]> = (0..500)
+            .map(|i| {
+                let x = i as f32 * 0.618_034;
+                [x.fract(), (x * 1.7).fract(), (x * 2.3).fract()]
+            })
+            .collect();
+        let forest = PkdForest::<3, 4>::new(&points);
+
+        for i in 0..200 {
+            let x = i as f32 * 0.414_213_5;
+            let needle = [x.fract(), (x * 1.3).fract(), (x * 3.1).fract()];
+            let r_squared = 0.003;
+            let needles = needle.map(Simd::<f32, 1>::splat);
+            assert_eq!(
+                forest.might_collide(needle, r_squared),
+                forest.might_collide_simd(&needles, Simd::splat(r_squared))
+            );
+            let (nearest, d) = forest.approx_nearest(needle);
+            assert_eq!(d, distsq(needle, nearest));
+            assert_eq!(
+                Collider::collides(&forest, &needle, r_squared.sqrt()),
+                d <= r_squared
+            );
+        }
+    }
+
+    #[test]
+    fn dyn_forest_matches_static() {
+        let points: Vec<[f32; 3]> = (0..500)
+            .map(|i| {
+                let x = i as f32 * 0.618_034;
+                [x.fract(), (x * 1.7).fract(), (x * 2.3).fract()]
+            })
+            .collect();
+        let forest = PkdForest::<3, 3>::new_rotated(&points);
+        let dyn_forest = DynPkdForest::<3>::new_rotated(&points, 3);
+        assert_eq!(dyn_forest.n_trees(), 3);
+        assert_eq!(dyn_forest.tree_sizes(), forest.tree_sizes());
+
+        for i in 0..200 {
+            let x = i as f32 * 0.414_213_5;
+            let needle = [x.fract(), (x * 1.3).fract(), (x * 3.1).fract()];
+            let needles = needle.map(Simd::<f32, 1>::splat);
+            assert_eq!(
+                forest.approx_nearest(needle),
+                dyn_forest.approx_nearest(needle)
+            );
+            assert_eq!(
+                forest.might_collide(needle, 0.003),
+                dyn_forest.might_collide(needle, 0.003)
+            );
+            assert_eq!(
+                forest.might_collide_simd(&needles, Simd::splat(0.003)),
+                dyn_forest.might_collide_simd(&needles, Simd::splat(0.003))
+            );
+        }
+
+        let converted = DynPkdForest::from(forest);
+        assert_eq!(converted.tree_sizes(), dyn_forest.tree_sizes());
+    }
+
+    #[test]
+    fn rotations_are_orthogonal() {
+        for seed in 1..20 {
+            let r = random_rotation::<4>(seed);
+            for i in 0..4 {
+                for j in 0..4 {
+                    let dot: f32 = (0..4).map(|k| r[i][k] * r[j][k]).sum();
+                    let expected = if i == j { 1.0 } else { 0.0 };
+                    assert!((dot - expected).abs() < 1e-6);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rotated_forest_returns_original_points() {
+        let points: Vec<[f32; 3]> = (0..500)
+            .map(|i| {
+                let x = i as f32 * 0.618_034;
+                [x.fract(), (x * 1.7).fract(), (x * 2.3).fract()]
+            })
+            .collect();
+        let forest = PkdForest::<3, 4>::new_rotated(&points);
+
+        for i in 0..200 {
+            let x = i as f32 * 0.414_213_5;
+            let needle = [x.fract(), (x * 1.3).fract(), (x * 3.1).fract()];
+            let r_squared = 0.003;
+            let needles = needle.map(Simd::<f32, 1>::splat);
+            assert_eq!(
+                forest.might_collide(needle, r_squared),
+                forest.might_collide_simd(&needles, Simd::splat(r_squared))
+            );
+            let (nearest, _) = forest.approx_nearest(needle);
+            assert!(points.iter().any(|&p| distsq(p, nearest) < 1e-10));
+        }
+    }
+
+    #[test]
+    fn stats_bound_forest_misses() {
+        let points: Vec<[f32; 2]> = (0..300)
+            .map(|i| {
+                let x = i as f32 * 0.618_034;
+                [x.fract(), (x * 1.7).fract()]
+            })
+            .collect();
+        let needles: Vec<[f32; 2]> = (0..500)
+            .map(|i| {
+                let x = i as f32 * 0.414_213_5;
+                [x.fract(), (x * 1.3).fract()]
+            })
+            .collect();
+        let forest = PkdForest::<2, 3>::new(&points);
+        let stats = forest.stats(&needles, 0.001);
+
+        assert_eq!(stats.tree_sizes, [300; 3]);
+        assert_eq!(stats.memory_used, forest.memory_used());
+        // the forest only misses when every tree misses
+        for p in stats.tree_miss_probability {
+            assert!(stats.miss_probability <= p);
+        }
+        assert!(stats.predicted_miss_probability(3) <= stats.predicted_miss_probability(1));
+    }
+
+    #[test]
+    fn conservative_forest_never_misses() {
+        let points: Vec<[f32; 2]> = (0..50)
+            .flat_map(|i| (0..50).map(move |j| [i as f32 * 0.01, j as f32 * 0.01]))
+            .collect();
+        let spacing = 0.05;
+        let exact = Capt::<2>::new(&points, (0.01, 0.1));
+        let forest =
+            AffordanceForest::<2, 2>::new(&points, (0.01, 0.1), spacing, spacing * 2.0f32.sqrt());
+
+        assert!(forest.n_points() < points.len());
+        for i in 0..100 {
+            let center = [i as f32 * 0.006, (i % 7) as f32 * 0.08];
+            if exact.collides(&center, 0.02) {
+                assert!(forest.collides(&center, 0.02));
+            }
+        }
+    }
 }
@@ -4,25 +4,29 @@ use std::{
     env,
     error::Error,
     path::Path,
-    simd::{LaneCount, Simd, SupportedLaneCount},
+    simd::Simd,
     time::{Duration, Instant},
 };
 
-use captree::Axis;
+use captree::{Axis, RadiusRange, SimdQueryBatch};
+use hdrhistogram::Histogram;
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha20Rng;
 
 use rand_distr::{Distribution, Normal};
 
+pub mod arm;
+pub mod competitors;
 pub mod forest;
-pub mod kdt;
+#[cfg(feature = "plots")]
+pub mod plot;
 
 pub fn get_points(n_points_if_no_cloud: usize) -> Box<[[f32; 3]]> {
     let args: Vec<String> = env::args().collect();
     let mut rng = ChaCha20Rng::seed_from_u64(2707);
 
     if args.len() > 1 {
-        eprintln!("Loading pointcloud from {}", &args[1]);
+        eprintln!("Loading pointcloud from {}", args[1]);
         parse_pointcloud_csv(&args[1]).unwrap()
     } else {
         eprintln!("No pointcloud file! Using N={n_points_if_no_cloud}");
@@ -53,10 +57,7 @@ pub fn get_points(n_points_if_no_cloud: usize) -> Box<[[f32; 3]]> {
 pub fn make_needles<const D: usize, const L: usize>(
     rng: &mut impl Rng,
     n_trials: usize,
-) -> (Vec<[f32; D]>, Vec<[Simd<f32, L>; D]>)
-where
-    LaneCount<L>: SupportedLaneCount,
-{
+) -> (Vec<[f32; D]>, Vec<[Simd<f32, L>; D]>) {
     let mut seq_needles = Vec::new();
     let mut simd_needles = Vec::new();
 
@@ -64,7 +65,7 @@ where
         let mut simd_pts = [Simd::splat(0.0); D];
         for l in 0..L {
             let mut seq_needle = [0.0; D];
-            for d in 0..3 {
+            for d in 0..D {
                 let value = rng.gen_range::<f32, _>(0.0..1.0);
                 seq_needle[d] = value;
                 simd_pts[d].as_mut_array()[l] = value;
@@ -95,10 +96,7 @@ where
 pub fn make_correlated_needles<const D: usize, const L: usize>(
     rng: &mut impl Rng,
     n_trials: usize,
-) -> (Vec<[f32; D]>, Vec<[Simd<f32, L>; D]>)
-where
-    LaneCount<L>: SupportedLaneCount,
-{
+) -> (Vec<[f32; D]>, Vec<[Simd<f32, L>; D]>) {
     let mut seq_needles = Vec::new();
     let mut simd_needles = Vec::new();
 
@@ -139,9 +137,42 @@ pub fn stopwatch<F: FnOnce() -> R, R>(f: F) -> (R, Duration) {
     (r, Instant::now().duration_since(tic))
 }
 
+/// The quantiles of query latency reported by the benchmarks: the median, 99th and 99.9th
+/// percentiles, and the worst case.
+pub const LATENCY_QUANTILES: [f64; 4] = [0.5, 0.99, 0.999, 1.0];
+
+/// Time each call of `f` on an element of `items` separately, returning a histogram of the
+/// latencies in nanoseconds.
+///
+/// Timing every call adds the overhead of reading the clock to each latency, so throughput should
+/// still be measured over whole traces with [`stopwatch`].
+pub fn latency_histogram<T>(items: &[T], mut f: impl FnMut(&T)) -> Histogram<u64> {
+    // track latencies from 1 nanosecond to 1 minute to 3 significant figures
+    let mut histogram = Histogram::new_with_bounds(1, 60_000_000_000, 3).unwrap();
+    for item in items {
+        let tic = Instant::now();
+        f(item);
+        histogram.saturating_record(u64::try_from(tic.elapsed().as_nanos()).unwrap_or(u64::MAX));
+    }
+    histogram
+}
+
+/// Get the latencies at each of [`LATENCY_QUANTILES`] from `histogram`, in seconds.
+pub fn latency_percentiles(histogram: &Histogram<u64>) -> [f64; 4] {
+    LATENCY_QUANTILES.map(|q| histogram.value_at_quantile(q) as f64 * 1e-9)
+}
+
 pub fn parse_pointcloud_csv(
     p: impl AsRef<Path>,
 ) -> Result<Box<[[f32; 3]]>, Box<dyn std::error::Error>> {
+    let file = || std::fs::File::open(&p);
+    match p.as_ref().extension().and_then(|e| e.to_str()) {
+        Some("pcd") => return Ok(captree::io::read_pcd(file()?)?.into_boxed_slice()),
+        Some("ply") => return Ok(captree::io::read_ply(file()?)?.0.into_boxed_slice()),
+        Some("las") => return Ok(captree::io::read_las(file()?)?.0.into_boxed_slice()),
+        Some("npy") => return Ok(captree::io::read_npy(file()?)?.into_boxed_slice()),
+        _ => (),
+    }
     std::str::from_utf8(&std::fs::read(&p)?)?
         .lines()
         .map(|l| {
@@ -158,6 +189,21 @@ pub fn parse_pointcloud_csv(
 pub type Trace = [([f32; 3], f32)];
 
 pub fn parse_trace_csv(p: impl AsRef<Path>) -> Result<Box<Trace>, Box<dyn std::error::Error>> {
+    if p.as_ref().extension().is_some_and(|e| e == "captrace") {
+        return Ok(
+            captree::trace::read_trace::<3, f32>(std::fs::File::open(&p)?)
+                .map_err(|e| format!("{e:?}"))?
+                .into_iter()
+                .map(|r| (r.center, r.radius))
+                .collect(),
+        );
+    }
+    if p.as_ref().extension().is_some_and(|e| e == "npy") {
+        return Ok(captree::io::read_npy(std::fs::File::open(&p)?)?
+            .into_iter()
+            .map(|[x, y, z, r]| ([x, y, z], r))
+            .collect());
+    }
     std::str::from_utf8(&std::fs::read(&p)?)?
         .lines()
         .map(|l| {
@@ -174,39 +220,14 @@ pub fn parse_trace_csv(p: impl AsRef<Path>) -> Result<Box<Trace>, Box<dyn std::e
         .collect()
 }
 
-pub type SimdTrace<const L: usize> = [([Simd<f32, L>; 3], Simd<f32, L>)];
-
-pub fn simd_trace_new<const L: usize>(trace: &Trace) -> Box<SimdTrace<L>>
-where
-    LaneCount<L>: SupportedLaneCount,
-{
-    trace
-        .chunks(L)
-        .map(|w| {
-            let mut centers = [[0.0; L]; 3];
-            let mut radii = [0.0; L];
-            for (l, ([x, y, z], r)) in w.iter().copied().enumerate() {
-                centers[0][l] = x;
-                centers[1][l] = y;
-                centers[2][l] = z;
-                radii[l] = r;
-            }
-            (centers.map(Simd::from_array), Simd::from_array(radii))
-        })
-        .collect()
+pub type SimdTrace<const L: usize> = [SimdQueryBatch<3, L>];
+
+pub fn simd_trace_new<const L: usize>(trace: &Trace) -> Box<SimdTrace<L>> {
+    SimdQueryBatch::from_queries(trace)
 }
 
 pub fn trace_r_range(t: &Trace) -> (f32, f32) {
-    (
-        t.iter()
-            .map(|x| x.1)
-            .min_by(|a, b| a.partial_cmp(b).unwrap())
-            .unwrap_or(0.0),
-        t.iter()
-            .map(|x| x.1)
-            .max_by(|a, b| a.partial_cmp(b).unwrap())
-            .unwrap_or(f32::INFINITY),
-    )
+    RadiusRange::fit(t, 0.0).into()
 }
 
 pub fn fuzz_pointcloud(t: &mut [[f32; 3]], stddev: f32, rng: &mut impl Rng) {
@@ -229,21 +250,6 @@ fn median_partition<A: Axis, const K: usize>(points: &mut [[A; K]], k: usize) ->
     A::in_between(med_lo, med_hi[k])
 }
 
-#[inline]
-fn forward_pass<A: Axis, const K: usize>(tests: &[A], point: &[A; K]) -> usize {
-    // forward pass through the tree
-    let mut test_idx = 0;
-    let mut k = 0;
-    for _ in 0..tests.len().trailing_ones() {
-        test_idx =
-            2 * test_idx + 1 + usize::from(unsafe { *tests.get_unchecked(test_idx) } <= point[k]);
-        k = (k + 1) % K;
-    }
-
-    // retrieve affordance buffer location
-    test_idx - tests.len()
-}
-
 fn distsq<const K: usize>(a: [f32; K], b: [f32; K]) -> f32 {
     let mut total = 0.0f32;
     for i in 0..K {
@@ -0,0 +1,134 @@
+//! Plotting of benchmark results, enabled by the `plots` feature.
+
+use std::{error::Error, ops::Range, path::Path};
+
+use plotters::{coord::Shift, prelude::*};
+
+/// The format of a plot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum PlotFormat {
+    Svg,
+    Png,
+}
+
+impl PlotFormat {
+    /// Get the file extension for plots of this format.
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Svg => "svg",
+            Self::Png => "png",
+        }
+    }
+}
+
+/// A named line of `(x, y)` points in a plot.
+pub struct Series {
+    pub name: String,
+    pub points: Vec<(f64, f64)>,
+}
+
+/// The size of each plot, in pixels.
+const SIZE: (u32, u32) = (1024, 768);
+
+/// Draw `series` as lines on log-log axes, saving the plot to `path` with the extension of
+/// `format`.
+///
+/// Points with a nonpositive coordinate cannot be drawn on log axes, so they are skipped.
+///
+/// # Errors
+///
+/// Returns an error if the plot could not be drawn or written.
+pub fn line_plot(
+    path: &Path,
+    format: PlotFormat,
+    title: &str,
+    x_label: &str,
+    y_label: &str,
+    series: &[Series],
+) -> Result<(), Box<dyn Error>> {
+    let path = path.with_extension(format.extension());
+    match format {
+        PlotFormat::Svg => draw(
+            SVGBackend::new(&path, SIZE).into_drawing_area(),
+            title,
+            x_label,
+            y_label,
+            series,
+        ),
+        PlotFormat::Png => draw(
+            BitMapBackend::new(&path, SIZE).into_drawing_area(),
+            title,
+            x_label,
+            y_label,
+            series,
+        ),
+    }
+}
+
+/// Get the smallest range containing every positive value in `values`.
+fn positive_range(values: impl Iterator<Item = f64>) -> Range<f64> {
+    let (lo, hi) = values
+        .filter(|&v| v > 0.0)
+        .fold((f64::INFINITY, 0.0f64), |(lo, hi), v| {
+            (lo.min(v), hi.max(v))
+        });
+    if lo <= hi {
+        lo..hi
+    } else {
+        1.0..10.0
+    }
+}
+
+/// Draw a line plot of `series` onto `root`.
+fn draw<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    title: &str,
+    x_label: &str,
+    y_label: &str,
+    series: &[Series],
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let all_points = || series.iter().flat_map(|s| s.points.iter().copied());
+    let x_range = positive_range(all_points().map(|(x, _)| x));
+    let y_range = positive_range(all_points().map(|(_, y)| y));
+
+    root.fill(&WHITE)?;
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, ("sans-serif", 32))
+        .margin(16)
+        .x_label_area_size(48)
+        .y_label_area_size(96)
+        .build_cartesian_2d(x_range.log_scale(), y_range.log_scale())?;
+    chart
+        .configure_mesh()
+        .x_desc(x_label)
+        .y_desc(y_label)
+        .x_label_formatter(&|x| format!("{x:.0}"))
+        .y_label_formatter(&|y| format!("{y:.1e}"))
+        .y_labels(16)
+        .draw()?;
+
+    for (i, s) in series.iter().enumerate() {
+        let color = Palette99::pick(i).to_rgba();
+        chart
+            .draw_series(LineSeries::new(
+                s.points
+                    .iter()
+                    .copied()
+                    .filter(|&(x, y)| x > 0.0 && y > 0.0),
+                color.stroke_width(2),
+            ))?
+            .label(&s.name)
+            .legend(move |(x, y)| PathElement::new([(x, y), (x + 20, y)], color.stroke_width(2)));
+    }
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+    root.present()?;
+    Ok(())
+}
@@ -0,0 +1,98 @@
+#ifndef CAPTREE_H
+#define CAPTREE_H
+
+/* This file is generated by cbindgen from captree-capi. Do not edit it by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// An opaque handle to a collision-affording point tree over 3-dimensional points.
+typedef struct CaptreeCapt CaptreeCapt;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Construct a new tree containing `n_points` points read from `points`.
+//
+// `points` must hold `3 * n_points` values, with the coordinates of each point stored
+// contiguously.
+// `r_min` and `r_max` bound the radius of the spheres which will be queried against the tree.
+//
+// Returns a null pointer if any coordinate is not finite.
+//
+// # Safety
+//
+// `points` must be valid for reads of `3 * n_points` values.
+struct CaptreeCapt *captree_new(const float *points, size_t n_points, float r_min, float r_max);
+
+// Release a tree.
+//
+// # Safety
+//
+// `tree` must be null or a handle returned by this API which has not already been released.
+void captree_free(struct CaptreeCapt *tree);
+
+// Determine whether a point in `tree` is within a distance of `radius` to `center`.
+//
+// The result is only guaranteed to be correct if `radius` is inside the radius range used to
+// construct `tree`.
+//
+// # Safety
+//
+// `tree` must be a live handle, and `center` must be valid for reads of 3 values.
+bool captree_collides(const struct CaptreeCapt *tree, const float *center, float radius);
+
+// Check `n` spheres against `tree`, writing whether the `i`-th sphere collides to `out[i]`.
+//
+// `centers` holds the coordinates of each sphere's center contiguously, and `radii` the radius of
+// each sphere.
+//
+// Returns whether any sphere collides.
+//
+// # Safety
+//
+// `tree` must be a live handle.
+// `centers` must be valid for reads of `3 * n` values, `radii` for reads of `n` values, and `out`
+// for writes of `n` values.
+bool captree_collides_batch(const struct CaptreeCapt *tree,
+                            const float *centers,
+                            const float *radii,
+                            size_t n,
+                            bool *out);
+
+// Serialize `tree` in the portable format of `captree::file`.
+//
+// Returns a buffer of `*len` bytes, which must be released with [`captree_bytes_free`], or a null
+// pointer if the tree could not be written.
+//
+// # Safety
+//
+// `tree` must be a live handle, and `len` must be valid for writes.
+uint8_t *captree_serialize(const struct CaptreeCapt *tree, size_t *len);
+
+// Release a buffer returned by [`captree_serialize`].
+//
+// # Safety
+//
+// `bytes` must be null or a buffer of `len` bytes returned by [`captree_serialize`] which has not
+// already been released.
+void captree_bytes_free(uint8_t *bytes, size_t len);
+
+// Load a tree from `len` bytes written by [`captree_serialize`].
+//
+// Returns a null pointer if the bytes are not a valid tree.
+//
+// # Safety
+//
+// `bytes` must be valid for reads of `len` bytes.
+struct CaptreeCapt *captree_deserialize(const uint8_t *bytes, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CAPTREE_H */
@@ -0,0 +1,205 @@
+//! # A C API for collision-affording point trees
+//!
+//! This crate exposes 3-dimensional, single-precision [`Capt`]s to C and C++ through opaque
+//! handles.
+//! The header `include/captree.h` declares every function in this crate, and is generated with
+//! [cbindgen](https://github.com/mozilla/cbindgen):
+//!
+//! ```sh
+//! cbindgen --config cbindgen.toml --output include/captree.h
+//! ```
+//!
+//! Every tree returned by this API must be released with [`captree_free`], and every byte buffer
+//! with [`captree_bytes_free`].
+//! Functions which construct a tree return a null pointer on failure.
+
+#![warn(clippy::pedantic, clippy::nursery)]
+
+use std::{ptr, slice};
+
+use captree::Capt;
+
+/// The tree behind each handle.
+type Tree = Capt<3>;
+
+/// An opaque handle to a collision-affording point tree over 3-dimensional points.
+pub struct CaptreeCapt(Tree);
+
+#[no_mangle]
+/// Construct a new tree containing `n_points` points read from `points`.
+///
+/// `points` must hold `3 * n_points` values, with the coordinates of each point stored
+/// contiguously.
+/// `r_min` and `r_max` bound the radius of the spheres which will be queried against the tree.
+///
+/// Returns a null pointer if any coordinate is not finite.
+///
+/// # Safety
+///
+/// `points` must be valid for reads of `3 * n_points` values.
+pub unsafe extern "C" fn captree_new(
+    points: *const f32,
+    n_points: usize,
+    r_min: f32,
+    r_max: f32,
+) -> *mut CaptreeCapt {
+    let flat = if n_points == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(points, 3 * n_points)
+    };
+    Tree::try_from_flat(flat, 3, 0, (r_min, r_max))
+        .map_or(ptr::null_mut(), |t| Box::into_raw(Box::new(CaptreeCapt(t))))
+}
+
+#[no_mangle]
+/// Release a tree.
+///
+/// # Safety
+///
+/// `tree` must be null or a handle returned by this API which has not already been released.
+pub unsafe extern "C" fn captree_free(tree: *mut CaptreeCapt) {
+    if !tree.is_null() {
+        drop(Box::from_raw(tree));
+    }
+}
+
+#[no_mangle]
+/// Determine whether a point in `tree` is within a distance of `radius` to `center`.
+///
+/// The result is only guaranteed to be correct if `radius` is inside the radius range used to
+/// construct `tree`.
+///
+/// # Safety
+///
+/// `tree` must be a live handle, and `center` must be valid for reads of 3 values.
+pub unsafe extern "C" fn captree_collides(
+    tree: *const CaptreeCapt,
+    center: *const f32,
+    radius: f32,
+) -> bool {
+    (*tree).0.collides(&*center.cast::<[f32; 3]>(), radius)
+}
+
+#[no_mangle]
+/// Check `n` spheres against `tree`, writing whether the `i`-th sphere collides to `out[i]`.
+///
+/// `centers` holds the coordinates of each sphere's center contiguously, and `radii` the radius of
+/// each sphere.
+///
+/// Returns whether any sphere collides.
+///
+/// # Safety
+///
+/// `tree` must be a live handle.
+/// `centers` must be valid for reads of `3 * n` values, `radii` for reads of `n` values, and `out`
+/// for writes of `n` values.
+pub unsafe extern "C" fn captree_collides_batch(
+    tree: *const CaptreeCapt,
+    centers: *const f32,
+    radii: *const f32,
+    n: usize,
+    out: *mut bool,
+) -> bool {
+    if n == 0 {
+        return false;
+    }
+    let tree = &(*tree).0;
+    let centers = slice::from_raw_parts(centers.cast::<[f32; 3]>(), n);
+    let radii = slice::from_raw_parts(radii, n);
+    let out = slice::from_raw_parts_mut(out, n);
+    let mut any = false;
+    for ((center, &radius), o) in centers.iter().zip(radii).zip(out) {
+        *o = tree.collides(center, radius);
+        any |= *o;
+    }
+    any
+}
+
+#[no_mangle]
+/// Serialize `tree` in the portable format of `captree::file`.
+///
+/// Returns a buffer of `*len` bytes, which must be released with [`captree_bytes_free`], or a null
+/// pointer if the tree could not be written.
+///
+/// # Safety
+///
+/// `tree` must be a live handle, and `len` must be valid for writes.
+pub unsafe extern "C" fn captree_serialize(tree: *const CaptreeCapt, len: *mut usize) -> *mut u8 {
+    let mut bytes = Vec::new();
+    if (*tree).0.write_to(&mut bytes).is_err() {
+        return ptr::null_mut();
+    }
+    let bytes = Box::into_raw(bytes.into_boxed_slice());
+    *len = bytes.len();
+    bytes.cast()
+}
+
+#[no_mangle]
+/// Release a buffer returned by [`captree_serialize`].
+///
+/// # Safety
+///
+/// `bytes` must be null or a buffer of `len` bytes returned by [`captree_serialize`] which has not
+/// already been released.
+pub unsafe extern "C" fn captree_bytes_free(bytes: *mut u8, len: usize) {
+    if !bytes.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(bytes, len)));
+    }
+}
+
+#[no_mangle]
+/// Load a tree from `len` bytes written by [`captree_serialize`].
+///
+/// Returns a null pointer if the bytes are not a valid tree.
+///
+/// # Safety
+///
+/// `bytes` must be valid for reads of `len` bytes.
+pub unsafe extern "C" fn captree_deserialize(bytes: *const u8, len: usize) -> *mut CaptreeCapt {
+    let bytes = if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(bytes, len)
+    };
+    Tree::read_from(bytes).map_or(ptr::null_mut(), |t| Box::into_raw(Box::new(CaptreeCapt(t))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let points = [0.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+        let centers = [1.0, 1.0, 1.1, 0.5, 0.5, 0.5];
+        let radii = [0.2, 0.2];
+        let mut out = [false; 2];
+        unsafe {
+            let tree = captree_new(points.as_ptr(), 2, 0.0, 0.5);
+            assert!(!tree.is_null());
+            assert!(captree_collides(tree, centers.as_ptr(), 0.2));
+            assert!(captree_collides_batch(
+                tree,
+                centers.as_ptr(),
+                radii.as_ptr(),
+                2,
+                out.as_mut_ptr()
+            ));
+            assert_eq!(out, [true, false]);
+
+            let mut len = 0;
+            let bytes = captree_serialize(tree, &raw mut len);
+            let loaded = captree_deserialize(bytes, len);
+            assert!(!loaded.is_null());
+            assert_eq!((*loaded).0, (*tree).0);
+            assert!(captree_deserialize(bytes, len - 1).is_null());
+
+            captree_bytes_free(bytes, len);
+            captree_free(loaded);
+            captree_free(tree);
+
+            assert!(captree_new([f32::NAN; 3].as_ptr(), 1, 0.0, 0.5).is_null());
+        }
+    }
+}
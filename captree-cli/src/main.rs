@@ -0,0 +1,367 @@
+//! # A command-line interface for collision-affording point trees
+//!
+//! `captree-cli` builds 3-dimensional, single-precision trees from point cloud files, saves them
+//! in the format of `captree::file`, and checks traces of spheres against them, so that trees can
+//! be used from shell pipelines and from languages without bindings.
+//!
+//! ```sh
+//! captree-cli build cloud.pcd --r-max 0.1 -o tree.capt
+//! captree-cli query tree.capt trace.csv > hits.txt
+//! captree-cli stats tree.capt
+//! captree-cli geometry tree.capt -o cells.obj
+//! captree-cli check-trajectory tree.capt waypoints.csv --radius 0.05
+//! ```
+
+#![warn(clippy::pedantic, clippy::nursery)]
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use captree::{
+    io::{read_las, read_npy, read_pcd, read_ply},
+    trace::read_trace,
+    Capt,
+};
+use clap::{Parser, Subcommand};
+
+/// The tree built and loaded by every subcommand.
+type Tree = Capt<3>;
+
+#[derive(Parser)]
+#[command(version, about)]
+/// Build, query and inspect collision-affording point trees.
+struct Cli {
+    /// The subcommand to run.
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+/// The subcommands of the CLI.
+enum Command {
+    /// Build a tree from a point cloud and save it.
+    Build {
+        /// The point cloud, as a PCD, PLY, LAS or `.npy` file, or as CSV with one `x,y,z` point
+        /// per line.
+        cloud: PathBuf,
+        /// The path to save the tree to.
+        #[arg(short, long)]
+        output: PathBuf,
+        /// The smallest radius of the spheres which will be checked against the tree.
+        #[arg(long, default_value_t = 0.0)]
+        r_min: f32,
+        /// The largest radius of the spheres which will be checked against the tree.
+        #[arg(long)]
+        r_max: f32,
+    },
+    /// Check every sphere of a trace against a tree, printing `1` for each sphere which collides
+    /// and `0` for each sphere which does not, one per line.
+    Query {
+        /// The tree, as saved by `build`.
+        tree: PathBuf,
+        /// The spheres, as a recorded `.captrace` file, as a `.npy` file of shape `(N, 4)`, or
+        /// as CSV with one `x,y,z,r` sphere per line.
+        trace: PathBuf,
+    },
+    /// Print the shape and memory use of a tree.
+    Stats {
+        /// The tree, as saved by `build`.
+        tree: PathBuf,
+    },
+    /// Export the leaf cells, split planes and affordance buffer sizes of a tree for rendering.
+    Geometry {
+        /// The tree, as saved by `build`.
+        tree: PathBuf,
+        /// The path to write to, as a Wavefront `.obj` mesh or, for any other extension, as JSON.
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Sweep a sphere along a path of waypoints, reporting the first segment which collides and
+    /// the minimum clearance along the path.
+    ///
+    /// Exits with a failure status if any segment collides.
+    CheckTrajectory {
+        /// The tree, as saved by `build`.
+        tree: PathBuf,
+        /// The waypoints, as a `.npy` file of shape `(N, 3)` or as CSV with one `x,y,z` waypoint
+        /// per line.
+        waypoints: PathBuf,
+        /// The radius of the swept sphere.
+        #[arg(long)]
+        radius: f32,
+    },
+}
+
+/// Parse `text` as CSV with `N` numbers on each nonempty line.
+fn parse_csv<const N: usize>(text: &str) -> Result<Vec<[f32; N]>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let values = line
+                .split(',')
+                .map(|s| s.trim().parse())
+                .collect::<Result<Vec<f32>, _>>()
+                .map_err(|e| format!("line {}: {e}", i + 1))?;
+            <[f32; N]>::try_from(values)
+                .map_err(|v| format!("line {}: expected {N} values, found {}", i + 1, v.len()))
+        })
+        .collect()
+}
+
+/// Read a file of rows of `N` numbers, either from a `.npy` file or as CSV.
+fn read_rows<const N: usize>(path: &Path) -> Result<Vec<[f32; N]>, Box<dyn Error>> {
+    if path.extension().is_some_and(|e| e == "npy") {
+        Ok(read_npy(File::open(path)?)?)
+    } else {
+        Ok(parse_csv(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+/// Read a point cloud, choosing its format by the extension of `path`.
+fn read_cloud(path: &Path) -> Result<Vec<[f32; 3]>, Box<dyn Error>> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("pcd") => Ok(read_pcd(File::open(path)?)?),
+        Some("ply") => Ok(read_ply(File::open(path)?)?.0),
+        Some("las") => {
+            let (points, metadata) = read_las(File::open(path)?)?;
+            if metadata.offset.iter().any(|&x| x != 0.0) {
+                eprintln!(
+                    "points are relative to the LAS offset {:?}; queries must be too",
+                    metadata.offset
+                );
+            }
+            Ok(points)
+        }
+        _ => read_rows(path),
+    }
+}
+
+/// Build a tree from the cloud at `cloud` and save it to `output`.
+fn build(cloud: &Path, output: &Path, r_range: (f32, f32)) -> Result<ExitCode, Box<dyn Error>> {
+    let points = read_cloud(cloud)?;
+    let tree = Tree::try_new(&points, r_range).map_err(|e| format!("{e:?}"))?;
+    tree.save(output)?;
+    eprintln!("built a tree of {} points", points.len());
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Check every sphere in `trace` against the tree at `tree`.
+fn query(tree: &Path, trace: &Path) -> Result<ExitCode, Box<dyn Error>> {
+    let tree = Tree::load(tree).map_err(|e| format!("{e:?}"))?;
+    let trace = if trace.extension().is_some_and(|e| e == "captrace") {
+        read_trace::<3, f32>(File::open(trace)?)
+            .map_err(|e| format!("{e:?}"))?
+            .into_iter()
+            .map(|r| {
+                let [x, y, z] = r.center;
+                [x, y, z, r.radius]
+            })
+            .collect()
+    } else {
+        read_rows::<4>(trace)?
+    };
+    let (r_min, r_max) = tree.r_range();
+    let n_outside = trace
+        .iter()
+        .filter(|&&[.., r]| r < r_min || r_max < r)
+        .count();
+    if n_outside > 0 {
+        eprintln!(
+            "{n_outside} of {} spheres have a radius outside the tree's range [{r_min}, \
+             {r_max}], so their results may be incorrect",
+            trace.len()
+        );
+    }
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    let mut n_hits = 0;
+    for &[x, y, z, r] in &trace {
+        let hit = tree.collides(&[x, y, z], r);
+        n_hits += usize::from(hit);
+        writeln!(out, "{}", u8::from(hit))?;
+    }
+    out.flush()?;
+    eprintln!("{n_hits} of {} spheres collide", trace.len());
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the shape and memory use of the tree at `tree`.
+fn stats(tree: &Path) -> Result<ExitCode, Box<dyn Error>> {
+    let tree = Tree::load(tree).map_err(|e| format!("{e:?}"))?;
+    let stats = tree.stats();
+    let (r_min, r_max) = tree.r_range();
+    println!("points: {}", tree.points().count());
+    println!("radius range: [{r_min}, {r_max}]");
+    println!("depth: {}", stats.depth);
+    println!(
+        "leaves: {} ({} empty)",
+        stats.n_leaves, stats.n_ghost_leaves
+    );
+    println!(
+        "affordance buffer sizes: min {}, median {}, p99 {}, max {}",
+        stats.afforded_min, stats.afforded_median, stats.afforded_p99, stats.afforded_max
+    );
+    println!(
+        "memory: {} bytes ({} tests, {} starts, {} bounding boxes, {} afforded points)",
+        stats.tests_bytes + stats.starts_bytes + stats.aabbs_bytes + stats.afforded_bytes,
+        stats.tests_bytes,
+        stats.starts_bytes,
+        stats.aabbs_bytes,
+        stats.afforded_bytes
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Export the spatial decomposition of the tree at `tree` to `output`.
+fn geometry(tree: &Path, output: &Path) -> Result<ExitCode, Box<dyn Error>> {
+    let tree = Tree::load(tree).map_err(|e| format!("{e:?}"))?;
+    let geometry = tree.export_debug_geometry();
+    let mut out = BufWriter::new(File::create(output)?);
+    if output.extension().is_some_and(|e| e == "obj") {
+        geometry.write_obj(&mut out)?;
+    } else {
+        geometry.write_json(&mut out)?;
+    }
+    out.flush()?;
+    eprintln!(
+        "exported {} leaves and {} split planes",
+        geometry.leaves.len(),
+        geometry.planes.len()
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Get the distance from `p` to the nearest point in `tree`, clamped to the tree's radius range.
+fn clamped_distance(tree: &Tree, p: &[f32; 3]) -> f32 {
+    let (r_min, r_max) = tree.r_range();
+    tree.nearest_within_range(p).map_or_else(
+        || {
+            if tree.collides(p, r_min) {
+                r_min
+            } else {
+                r_max
+            }
+        },
+        |(_, d)| d,
+    )
+}
+
+/// Get the smallest distance from any point along the segment from `start` to `end` to the nearest
+/// point in `tree`, clamped to the tree's radius range.
+///
+/// The segment is sampled at most `step` apart, so the result is within `step / 2` of the true
+/// distance.
+fn segment_distance(tree: &Tree, start: [f32; 3], end: [f32; 3], step: f32) -> f32 {
+    let axis: [f32; 3] = std::array::from_fn(|k| end[k] - start[k]);
+    let length = axis.iter().map(|x| x * x).sum::<f32>().sqrt();
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let n = (length / step).ceil().max(1.0) as usize;
+    (0..=n)
+        .map(|i| {
+            #[allow(clippy::cast_precision_loss)]
+            let t = i as f32 / n as f32;
+            clamped_distance(tree, &std::array::from_fn(|k| axis[k].mul_add(t, start[k])))
+        })
+        .fold(f32::INFINITY, f32::min)
+}
+
+/// Sweep a sphere of radius `radius` along `waypoints` through the tree at `tree`.
+fn check_trajectory(
+    tree: &Path,
+    waypoints: &Path,
+    radius: f32,
+) -> Result<ExitCode, Box<dyn Error>> {
+    let tree = Tree::load(tree).map_err(|e| format!("{e:?}"))?;
+    let waypoints = read_rows::<3>(waypoints)?;
+    let (r_min, r_max) = tree.r_range();
+    if radius < r_min || r_max < radius * 1.12 {
+        eprintln!(
+            "the inflated radius {} is outside the tree's range [{r_min}, {r_max}], so the \
+             results may be incorrect",
+            radius * 1.12
+        );
+    }
+
+    let step = if radius > 0.0 { radius } else { r_max };
+    let mut first_collision = None;
+    // the smallest distance to any point, and the index of the segment where it was found
+    let mut nearest = (f32::INFINITY, 0);
+    for (i, segment) in waypoints.windows(2).enumerate() {
+        if first_collision.is_none() && tree.collides_swept(&segment[0], &segment[1], radius) {
+            first_collision = Some(i);
+        }
+        let d = segment_distance(&tree, segment[0], segment[1], step);
+        if d < nearest.0 {
+            nearest = (d, i);
+        }
+    }
+    if let [p] = waypoints.as_slice() {
+        nearest = (clamped_distance(&tree, p), 0);
+    }
+
+    let (distance, segment) = nearest;
+    if distance >= r_max {
+        println!(
+            "minimum clearance: at least {} (beyond the tree's radius range)",
+            r_max - radius
+        );
+    } else {
+        println!(
+            "minimum clearance: {} near segment {segment}",
+            distance - radius
+        );
+    }
+    if let Some(i) = first_collision {
+        println!(
+            "first collision: segment {i}, from {:?} to {:?}",
+            waypoints[i],
+            waypoints[i + 1]
+        );
+        return Ok(ExitCode::FAILURE);
+    }
+    println!("no collisions along {} waypoints", waypoints.len());
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> Result<ExitCode, Box<dyn Error>> {
+    match Cli::parse().command {
+        Command::Build {
+            cloud,
+            output,
+            r_min,
+            r_max,
+        } => build(&cloud, &output, (r_min, r_max)),
+        Command::Query { tree, trace } => query(&tree, &trace),
+        Command::Stats { tree } => stats(&tree),
+        Command::Geometry { tree, output } => geometry(&tree, &output),
+        Command::CheckTrajectory {
+            tree,
+            waypoints,
+            radius,
+        } => check_trajectory(&tree, &waypoints, radius),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_rows() {
+        assert_eq!(
+            parse_csv::<4>("0,1,2,0.5\n\n -1, 0.25,3e2 ,0\n"),
+            Ok(vec![[0.0, 1.0, 2.0, 0.5], [-1.0, 0.25, 300.0, 0.0]])
+        );
+        assert_eq!(
+            parse_csv::<3>("0,1,2\n0,1\n"),
+            Err("line 2: expected 3 values, found 2".into())
+        );
+        assert!(parse_csv::<3>("0,1,x\n").is_err());
+    }
+}
@@ -0,0 +1,126 @@
+//! # Python bindings for collision-affording point trees
+//!
+//! This crate builds the `captree` Python module, which exposes 3-dimensional, single-precision
+//! trees built from and queried with `numpy` arrays.
+//! Build and install it into the active environment with [maturin](https://www.maturin.rs):
+//!
+//! ```sh
+//! maturin develop --release -m captree-py/Cargo.toml
+//! ```
+//!
+//! ```python
+//! import numpy as np
+//! import captree
+//!
+//! points = np.random.rand(10_000, 3).astype(np.float32)
+//! tree = captree.Capt(points, 0.01, 0.1)
+//!
+//! centers = np.random.rand(1_000, 3).astype(np.float32)
+//! radii = np.full(1_000, 0.05, dtype=np.float32)
+//! hits = tree.collides(centers, radii)  # array of 1000 bools
+//! ```
+
+#![warn(clippy::pedantic, clippy::nursery)]
+
+use ::captree::{Capt, NewCaptError};
+use numpy::{PyArray1, PyReadonlyArray1, PyReadonlyArray2};
+use pyo3::{
+    exceptions::{PyOverflowError, PyValueError},
+    prelude::*,
+};
+
+#[pyclass(frozen, name = "Capt", module = "captree")]
+/// A collision-affording point tree over 3-dimensional points.
+struct PyCapt {
+    /// The tree being wrapped.
+    tree: Capt<3>,
+}
+
+/// Copy the rows of an `(N, 3)` array into points.
+fn rows(array: &PyReadonlyArray2<'_, f32>, name: &str) -> PyResult<Vec<[f32; 3]>> {
+    let array = array.as_array();
+    if array.ncols() != 3 {
+        return Err(PyValueError::new_err(format!(
+            "{name} must have shape (N, 3), not {:?}",
+            array.shape()
+        )));
+    }
+    Ok(array
+        .rows()
+        .into_iter()
+        .map(|r| [r[0], r[1], r[2]])
+        .collect())
+}
+
+#[pymethods]
+#[allow(clippy::needless_pass_by_value)] // arrays are extracted from Python by value
+impl PyCapt {
+    #[new]
+    /// Construct a new tree from an `(N, 3)` array of points, supporting query radii from `r_min`
+    /// to `r_max`.
+    ///
+    /// Raises a `ValueError` if `points` has the wrong shape or contains a non-finite value.
+    fn new(
+        py: Python<'_>,
+        points: PyReadonlyArray2<'_, f32>,
+        r_min: f32,
+        r_max: f32,
+    ) -> PyResult<Self> {
+        let points = rows(&points, "points")?;
+        let tree = py
+            .detach(|| Capt::try_new(&points, (r_min, r_max)))
+            .map_err(|e| match e {
+                NewCaptError::TooManyPoints => PyOverflowError::new_err("too many points"),
+                e => PyValueError::new_err(format!("could not build tree: {e:?}")),
+            })?;
+        Ok(Self { tree })
+    }
+
+    /// Determine whether each sphere, with a center from the `(M, 3)` array `centers` and a
+    /// radius from the length-`M` array `radii`, collides with a point in this tree.
+    ///
+    /// Returns a boolean array of length `M`.
+    /// The GIL is released while the queries run.
+    fn collides<'py>(
+        &self,
+        py: Python<'py>,
+        centers: PyReadonlyArray2<'py, f32>,
+        radii: PyReadonlyArray1<'py, f32>,
+    ) -> PyResult<Bound<'py, PyArray1<bool>>> {
+        let centers = rows(&centers, "centers")?;
+        let radii = radii.as_array();
+        if radii.len() != centers.len() {
+            return Err(PyValueError::new_err(format!(
+                "got {} centers but {} radii",
+                centers.len(),
+                radii.len()
+            )));
+        }
+        let radii = radii.to_vec();
+        let hits: Vec<bool> = py.detach(|| {
+            centers
+                .iter()
+                .zip(&radii)
+                .map(|(c, &r)| self.tree.collides(c, r))
+                .collect()
+        });
+        Ok(PyArray1::from_vec(py, hits))
+    }
+
+    /// Determine whether the sphere with the given center and radius collides with a point in
+    /// this tree.
+    fn collides_one(&self, center: [f32; 3], radius: f32) -> bool {
+        self.tree.collides(&center, radius)
+    }
+
+    /// Get the number of points in this tree.
+    fn __len__(&self) -> usize {
+        self.tree.points().count()
+    }
+}
+
+#[pymodule]
+/// Collision-affording point trees for fast collision checking against point clouds.
+fn captree(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyCapt>()
+}
@@ -0,0 +1,410 @@
+//! Zero-copy archiving of trees with [`rkyv`].
+
+use std::{array, slice};
+#[cfg(feature = "simd")]
+use std::{
+    ops::{AddAssign, Mul, Sub},
+    simd::{
+        cmp::{SimdPartialEq, SimdPartialOrd},
+        num::SimdInt,
+        Mask, Simd,
+    },
+};
+
+use elain::{Align, Alignment};
+use rkyv::{
+    munge::munge,
+    rancor::Fallible,
+    ser::{Allocator, Writer},
+    vec::{ArchivedVec, VecResolver},
+    Archive, Deserialize, Place, Portable, Serialize,
+};
+
+use crate::{distsq, Aabb, Alloc, Axis, Buf, Capt, MySimd, Scratch};
+#[cfg(feature = "simd")]
+use crate::{multiversion, AxisSimd};
+
+#[derive(Portable, rkyv::bytecheck::CheckBytes)]
+#[bytecheck(crate = rkyv::bytecheck)]
+#[repr(C)]
+/// A [`Capt`] archived with [`rkyv`], which can be queried in place without deserializing it.
+///
+/// Every buffer keeps the same layout as in the original tree, so each affordance buffer is
+/// still a contiguous run of whole lanes.
+///
+/// # Examples
+///
+/// ```
+/// use captree::{ArchivedCapt, Capt};
+///
+/// let points = [[0.0, 0.1], [0.4, -0.2], [-0.2, -0.1]];
+/// let t = Capt::<2>::new(&points, (0.0, 0.2));
+///
+/// let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&t).unwrap();
+///
+/// // no allocation or copying is needed to query the archived tree
+/// let archived = rkyv::access::<ArchivedCapt<2>, rkyv::rancor::Error>(&bytes).unwrap();
+/// assert!(!archived.collides(&[0.0, 0.3], 0.1));
+/// assert!(archived.collides(&[0.0, 0.2], 0.15));
+/// ```
+///
+/// With the `simd` feature, [`ArchivedCapt::collides_simd`] checks `L` queries at once, as
+/// [`Capt::collides_simd`] does.
+pub struct ArchivedCapt<const K: usize, const L: usize = 8, A = f32, I = usize>
+where
+    A: Archive,
+    I: Archive,
+{
+    /// The archived test values of the tree.
+    tests: ArchivedVec<A::Archived>,
+    /// The archived split axis of each test, which is empty if the axes cycle with depth.
+    split_dims: ArchivedVec<u8>,
+    /// The archived bounding boxes of the cells, stored as `[lo, hi]` pairs.
+    aabbs: ArchivedVec<[[A::Archived; K]; 2]>,
+    /// The archived starts of each affordance buffer.
+    starts: ArchivedVec<I::Archived>,
+    /// The archived lanes of afforded points, stored transposed.
+    afforded: [ArchivedVec<[A::Archived; L]>; K],
+    /// The archived minimum and maximum query radii of the tree.
+    r_range: [A::Archived; 2],
+}
+
+/// The resolver for an archived [`Capt`].
+pub struct CaptResolver<const K: usize, A: Archive = f32> {
+    tests: VecResolver,
+    split_dims: VecResolver,
+    aabbs: VecResolver,
+    starts: VecResolver,
+    afforded: [VecResolver; K],
+    r_range: [A::Resolver; 2],
+}
+
+impl<A, I, Al, const K: usize, const L: usize> Archive for Capt<K, L, A, I, Al>
+where
+    A: Archive,
+    I: Archive,
+    Al: Alloc,
+    Align<L>: Alignment,
+{
+    type Archived = ArchivedCapt<K, L, A, I>;
+    type Resolver = CaptResolver<K, A>;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        munge!(let ArchivedCapt { tests, split_dims, aabbs, starts, afforded, r_range } = out);
+        ArchivedVec::resolve_from_len(self.tests.len(), resolver.tests, tests);
+        ArchivedVec::resolve_from_len(self.split_dims.len(), resolver.split_dims, split_dims);
+        ArchivedVec::resolve_from_len(self.aabbs.len(), resolver.aabbs, aabbs);
+        ArchivedVec::resolve_from_len(self.starts.len(), resolver.starts, starts);
+        for (k, (ak, rk)) in self.afforded.iter().zip(resolver.afforded).enumerate() {
+            let out_k = unsafe {
+                // SAFETY: `k` is less than `K`.
+                afforded.index(k)
+            };
+            ArchivedVec::resolve_from_len(ak.len(), rk, out_k);
+        }
+        let [lo_resolver, hi_resolver] = resolver.r_range;
+        unsafe {
+            // SAFETY: both indices are less than 2.
+            self.r_range.0.resolve(lo_resolver, r_range.index(0));
+            self.r_range.1.resolve(hi_resolver, r_range.index(1));
+        }
+    }
+}
+
+impl<A, I, Al, S, const K: usize, const L: usize> Serialize<S> for Capt<K, L, A, I, Al>
+where
+    A: Serialize<S>,
+    I: Serialize<S>,
+    Al: Alloc,
+    S: Fallible + Allocator + Writer + ?Sized,
+    Align<L>: Alignment,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        let aabbs = unsafe {
+            // SAFETY: `Aabb` is `repr(C)`, so it has the same layout as `[[A; K]; 2]`.
+            slice::from_raw_parts(self.aabbs.as_ptr().cast::<[[A; K]; 2]>(), self.aabbs.len())
+        };
+        // buffers must be serialized in field order so that validation can claim them in order
+        let tests = ArchivedVec::serialize_from_slice(&self.tests, serializer)?;
+        let split_dims = ArchivedVec::serialize_from_slice(&self.split_dims, serializer)?;
+        let aabbs = ArchivedVec::serialize_from_slice(aabbs, serializer)?;
+        let starts = ArchivedVec::serialize_from_slice(&self.starts, serializer)?;
+        let mut afforded = Vec::with_capacity(K);
+        for ak in &self.afforded {
+            let lanes = unsafe {
+                // SAFETY: `MySimd` is `repr(C)` and its alignment divides `size_of::<[A; L]>()`,
+                // so it has the same layout as `[A; L]`.
+                slice::from_raw_parts(ak.as_ptr().cast::<[A; L]>(), ak.len())
+            };
+            afforded.push(ArchivedVec::serialize_from_slice(lanes, serializer)?);
+        }
+        let r_range = [
+            self.r_range.0.serialize(serializer)?,
+            self.r_range.1.serialize(serializer)?,
+        ];
+
+        Ok(CaptResolver {
+            tests,
+            split_dims,
+            aabbs,
+            starts,
+            afforded: afforded
+                .try_into()
+                .unwrap_or_else(|_| unreachable!("one resolver was made per dimension")),
+            r_range,
+        })
+    }
+}
+
+impl<A, I, D, const K: usize, const L: usize> Deserialize<Capt<K, L, A, I>, D>
+    for ArchivedCapt<K, L, A, I>
+where
+    A: Archive,
+    A::Archived: Deserialize<A, D>,
+    I: Archive,
+    I::Archived: Deserialize<I, D>,
+    D: Fallible + ?Sized,
+    Align<L>: Alignment,
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<Capt<K, L, A, I>, D::Error> {
+        let mut afforded = Vec::with_capacity(K);
+        for ak in &self.afforded {
+            afforded.push(
+                ak.iter()
+                    .map(|lane| {
+                        Ok(MySimd {
+                            data: lane.deserialize(deserializer)?,
+                            _align: Align::NEW,
+                        })
+                    })
+                    .collect::<Result<Buf<_>, _>>()?,
+            );
+        }
+
+        Ok(Capt {
+            tests: self
+                .tests
+                .iter()
+                .map(|x| x.deserialize(deserializer))
+                .collect::<Result<_, _>>()?,
+            split_dims: self.split_dims.to_vec().into(),
+            aabbs: self
+                .aabbs
+                .iter()
+                .map(|[lo, hi]| {
+                    Ok(Aabb {
+                        lo: lo.deserialize(deserializer)?,
+                        hi: hi.deserialize(deserializer)?,
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+            starts: self
+                .starts
+                .iter()
+                .map(|x| x.deserialize(deserializer))
+                .collect::<Result<_, _>>()?,
+            afforded: afforded
+                .try_into()
+                .unwrap_or_else(|_| unreachable!("one buffer was made per dimension")),
+            r_range: (
+                self.r_range[0].deserialize(deserializer)?,
+                self.r_range[1].deserialize(deserializer)?,
+            ),
+            scratch: Scratch::default(),
+        })
+    }
+}
+
+impl<A, I, const K: usize, const L: usize> ArchivedCapt<K, L, A, I>
+where
+    A: Axis + Archive + From<A::Archived>,
+    A::Archived: Copy,
+    I: Archive,
+    I::Archived: Copy,
+    usize: TryFrom<I::Archived>,
+{
+    #[must_use]
+    /// Determine whether a point in the archived tree is within a distance of `radius` to
+    /// `center`.
+    ///
+    /// See [`Capt::collides`] for further details.
+    ///
+    /// # Panics
+    ///
+    /// This function may panic if the archive passed validation but was not produced by
+    /// serializing a [`Capt`].
+    pub fn collides(&self, center: &[A; K], radius: A) -> bool {
+        let rsq = radius.square();
+
+        // forward pass through the tree
+        let tests = self.tests.as_slice();
+        let mut test_idx = 0;
+        let mut k = 0;
+        for _ in 0..tests.len().trailing_ones() {
+            let dim = self.split_dims.get(test_idx).map_or(k, |&d| usize::from(d));
+            test_idx = 2 * test_idx + 1 + usize::from(A::from(tests[test_idx]) <= center[dim]);
+            k = (k + 1) % K;
+        }
+        let i = test_idx - tests.len();
+
+        let [lo, hi] = self.aabbs[i];
+        let aabb = Aabb {
+            lo: lo.map(A::from),
+            hi: hi.map(A::from),
+        };
+        if aabb.closest_distsq_to(center) > rsq {
+            return false;
+        }
+
+        let to_usize = |x| {
+            usize::try_from(x)
+                .ok()
+                .expect("affordance buffer start must fit in a usize")
+        };
+        (to_usize(self.starts[i])..to_usize(self.starts[i + 1])).any(|l| {
+            (0..L).any(|j| {
+                let aff_pt = array::from_fn(|k| A::from(self.afforded[k][l][j]));
+                distsq(aff_pt, *center) <= rsq
+            })
+        })
+    }
+}
+
+#[cfg(feature = "simd")]
+impl<A, I, const K: usize, const L: usize> ArchivedCapt<K, L, A, I>
+where
+    A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask> + Archive + From<A::Archived>,
+    A::Archived: Copy,
+    I: Archive,
+    I::Archived: Copy,
+    usize: TryFrom<I::Archived>,
+    Simd<A, L>: SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+    Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+{
+    #[must_use]
+    /// Determine whether any sphere in the list of provided spheres intersects a point in the
+    /// archived tree.
+    ///
+    /// See [`Capt::collides_simd`] for further details.
+    /// Since archived values may be stored in a different byte order than native ones, every value
+    /// is converted as it is loaded, which is free on little-endian targets.
+    ///
+    /// # Panics
+    ///
+    /// This function may panic if the archive passed validation but was not produced by
+    /// serializing a [`Capt`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(portable_simd)]
+    /// use std::simd::Simd;
+    ///
+    /// use captree::{ArchivedCapt, Capt};
+    ///
+    /// let t = Capt::<2, 4>::new(&[[1.0, 2.0], [1.1, 1.1]], (0.0, 0.1));
+    /// let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&t).unwrap();
+    /// let archived = rkyv::access::<ArchivedCapt<2, 4>, rkyv::rancor::Error>(&bytes).unwrap();
+    ///
+    /// let centers = [
+    ///     Simd::from_array([1.0, 1.1, 1.2, 1.3]),
+    ///     Simd::from_array([1.0, 1.1, 1.2, 1.3]),
+    /// ];
+    /// assert!(archived.collides_simd(&centers, Simd::splat(0.05)));
+    /// ```
+    pub fn collides_simd(&self, centers: &[Simd<A, L>; K], radii: Simd<A, L>) -> bool {
+        multiversion(
+            #[inline(always)]
+            || self.collides_simd_inner(centers, radii),
+        )
+    }
+
+    #[allow(clippy::inline_always)]
+    #[inline(always)]
+    /// The body of [`ArchivedCapt::collides_simd`], which is compiled once per supported
+    /// instruction set.
+    fn collides_simd_inner(&self, centers: &[Simd<A, L>; K], radii: Simd<A, L>) -> bool {
+        // forward pass through the tree, gathering the test of each lane
+        let tests = self.tests.as_slice();
+        let one = Simd::splat(1);
+        let mut test_idxs: Simd<usize, L> = Simd::splat(0);
+        for depth in 0..tests.len().trailing_ones() as usize {
+            let idxs = test_idxs.to_array();
+            let relevant_tests = Simd::from_array(idxs.map(|i| A::from(tests[i])));
+            let lane_centers = if self.split_dims.is_empty() {
+                centers[depth % K]
+            } else {
+                Simd::from_array(array::from_fn(|j| {
+                    centers[usize::from(self.split_dims[idxs[j]])][j]
+                }))
+            };
+            let cmp_results: Mask<isize, L> = lane_centers.simd_ge(relevant_tests).into();
+            test_idxs = (test_idxs << one) + one + (cmp_results.to_simd().cast() & one);
+        }
+        let zs = (test_idxs - Simd::splat(tests.len())).to_array();
+
+        let mut inbounds = Mask::splat(true);
+        for (k, &center) in centers.iter().enumerate() {
+            let lo = Simd::from_array(zs.map(|z| A::from(self.aabbs[z][0][k])));
+            let hi = Simd::from_array(zs.map(|z| A::from(self.aabbs[z][1][k])));
+            inbounds &= Mask::from((lo - radii).simd_le(center));
+            inbounds &= Mask::from(hi.simd_ge(center - radii));
+        }
+
+        let to_usize = |x| {
+            usize::try_from(x)
+                .ok()
+                .expect("affordance buffer start must fit in a usize")
+        };
+        (0..L).filter(|&j| inbounds.test(j)).any(|j| {
+            let z = zs[j];
+            let n_center: [Simd<A, L>; K] = array::from_fn(|k| Simd::splat(centers[k][j]));
+            let rs_sq = Simd::splat(radii[j]) * Simd::splat(radii[j]);
+            (to_usize(self.starts[z])..to_usize(self.starts[z + 1])).any(|l| {
+                let mut dists_sq = Simd::splat(A::ZERO);
+                for (sk, ck) in self.afforded.iter().zip(n_center) {
+                    let diff = Simd::from_array(sk[l].map(A::from)) - ck;
+                    dists_sq += diff * diff;
+                }
+                A::any(dists_sq.simd_le(rs_sq))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+    use rkyv::rancor::Error;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..200)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let t = Capt::<3, 8, f32, u32>::new(&points, (0.01, 0.1));
+
+        let bytes = rkyv::to_bytes::<Error>(&t).unwrap();
+        let archived = rkyv::access::<ArchivedCapt<3, 8, f32, u32>, Error>(&bytes).unwrap();
+        for _ in 0..1000 {
+            let center = [rng.gen(), rng.gen(), rng.gen()];
+            let r = rng.gen_range(0.01..=0.1);
+            assert_eq!(archived.collides(&center, r), t.collides(&center, r));
+        }
+
+        #[cfg(feature = "simd")]
+        for _ in 0..1000 {
+            let centers = array::from_fn(|_| Simd::from_array(array::from_fn(|_| rng.gen())));
+            let radii = Simd::from_array(array::from_fn(|_| rng.gen_range(0.01..=0.1)));
+            assert_eq!(
+                archived.collides_simd(&centers, radii),
+                t.collides_simd(&centers, radii)
+            );
+        }
+
+        assert_eq!(rkyv::deserialize::<_, Error>(archived).unwrap(), t);
+    }
+}
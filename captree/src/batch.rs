@@ -0,0 +1,214 @@
+//! Batches of queries laid out for SIMD-parallel collision checking.
+
+use std::{
+    array,
+    simd::{Mask, Simd, SimdElement},
+};
+
+use crate::Collider;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// A batch of `L` ball queries, stored dimension-major so that each query occupies one lane.
+///
+/// This is the layout taken by [`Capt::collides_simd`](crate::Capt::collides_simd).
+///
+/// # Generic parameters
+///
+/// - `K`: The dimension of the space.
+/// - `L`: The number of queries in the batch.
+/// - `A`: The value of the axes of each point.
+///
+/// # Examples
+///
+/// ```
+/// #![feature(portable_simd)]
+/// use captree::{Capt, SimdQueryBatch};
+///
+/// let tree = Capt::<2, 4>::new(&[[1.0, 2.0], [1.1, 1.1]], (0.0, 0.1));
+///
+/// let queries = [([0.0, 0.0], 0.05), ([1.1, 1.15], 0.1), ([5.0, 5.0], 0.01)];
+/// let batches = SimdQueryBatch::<2, 4>::from_queries(&queries);
+///
+/// // the trailing lane is filled by repeating the last query, and marked inactive
+/// assert_eq!(batches.len(), 1);
+/// assert_eq!(batches[0].active.to_array(), [true, true, true, false]);
+/// assert!(tree.collides_batch(&batches[0]));
+/// ```
+pub struct SimdQueryBatch<const K: usize, const L: usize, A: SimdElement = f32> {
+    /// The centers of the queries: `centers[k][l]` is the `k`-th coordinate of the `l`-th query.
+    pub centers: [Simd<A, L>; K],
+    /// The radii of the queries.
+    pub radii: Simd<A, L>,
+    /// Which lanes hold real queries.
+    /// Only the last batch made by [`SimdQueryBatch::from_queries`] may have inactive lanes.
+    pub active: Mask<isize, L>,
+}
+
+impl<A: SimdElement, const K: usize, const L: usize> SimdQueryBatch<K, L, A> {
+    #[must_use]
+    /// Transpose a sequence of `(center, radius)` queries into batches of `L` queries each.
+    ///
+    /// If the number of queries is not a multiple of `L`, the last batch is padded by repeating
+    /// its final query, and the padding lanes are marked inactive.
+    /// Since the padding repeats a real query, it never changes the result of a collision check
+    /// even by functions which ignore `active`.
+    pub fn from_queries(queries: &[([A; K], A)]) -> Box<[Self]> {
+        queries.chunks(L).map(Self::new).collect()
+    }
+
+    #[must_use]
+    /// Transpose between 1 and `L` queries into a single batch, padded as by
+    /// [`SimdQueryBatch::from_queries`].
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `queries` is empty or has more than `L` queries.
+    pub fn new(queries: &[([A; K], A)]) -> Self {
+        assert!(
+            !queries.is_empty() && queries.len() <= L,
+            "a batch holds between 1 and L queries"
+        );
+        let query = |l: usize| queries.get(l).unwrap_or(&queries[queries.len() - 1]);
+        Self {
+            centers: array::from_fn(|k| Simd::from_array(array::from_fn(|l| query(l).0[k]))),
+            radii: Simd::from_array(array::from_fn(|l| query(l).1)),
+            active: Mask::from_array(array::from_fn(|l| l < queries.len())),
+        }
+    }
+}
+
+/// A structure which can check a [`SimdQueryBatch`] of `L` queries at once.
+///
+/// This extends [`Collider`] for the structures with SIMD queries, so that code answering many
+/// queries, such as a `QueryPool`, can check them `L` at a time without
+/// knowing which structure it is given.
+///
+/// # Examples
+///
+/// ```
+/// #![feature(portable_simd)]
+/// use captree::{BatchCollider, Capt};
+///
+/// let capt = Capt::<2, 4>::new(&[[0.0, 0.0]], (0.0, 0.5));
+///
+/// let queries = [([0.1, 0.0], 0.2), ([1.0, 0.0], 0.2), ([0.0, 0.3], 0.4)];
+/// let mut results = [false; 3];
+/// BatchCollider::collides_each(&capt, &queries, &mut results);
+///
+/// assert_eq!(results, [true, false, true]);
+/// ```
+pub trait BatchCollider<const K: usize, const L: usize, A: SimdElement>: Collider<K, A> {
+    #[must_use]
+    /// Determine whether any active query in `batch` collides with a point in this structure.
+    fn collides_batch(&self, batch: &SimdQueryBatch<K, L, A>) -> bool;
+
+    /// Determine whether each query in `queries` collides, writing the results to the
+    /// corresponding elements of `results`.
+    ///
+    /// Queries are checked `L` at a time, and only the groups containing a collision are checked
+    /// again one query at a time, so queries which rarely collide are answered at SIMD speed.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `queries` and `results` have different lengths.
+    fn collides_each(&self, queries: &[([A; K], A)], results: &mut [bool]) {
+        assert_eq!(
+            queries.len(),
+            results.len(),
+            "every query needs a slot for its result"
+        );
+        for (group, results) in queries.chunks(L).zip(results.chunks_mut(L)) {
+            if self.collides_batch(&SimdQueryBatch::new(group)) {
+                for ((center, radius), result) in group.iter().zip(results) {
+                    *result = self.collides(center, *radius);
+                }
+            } else {
+                results.fill(false);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+    use crate::{Capt, CompactCapt, IndexedCapt, Octree, PkdTree};
+
+    #[test]
+    fn pads_with_last_query() {
+        let queries = [([0.0, 1.0], 0.5), ([2.0, 3.0], 0.25), ([4.0, 5.0], 0.125)];
+        let batches = SimdQueryBatch::<2, 2, f64>::from_queries(&queries);
+
+        assert_eq!(
+            &*batches,
+            [
+                SimdQueryBatch {
+                    centers: [Simd::from_array([0.0, 2.0]), Simd::from_array([1.0, 3.0])],
+                    radii: Simd::from_array([0.5, 0.25]),
+                    active: Mask::splat(true),
+                },
+                SimdQueryBatch {
+                    centers: [Simd::splat(4.0), Simd::splat(5.0)],
+                    radii: Simd::splat(0.125),
+                    active: Mask::from_array([true, false]),
+                },
+            ]
+        );
+    }
+    #[test]
+    fn inactive_lanes_ignored() {
+        let capt = Capt::<2, 4>::new(&[[0.0, 0.0]], (0.0, 0.1));
+        let mut batch = SimdQueryBatch::<2, 4>::from_queries(&[([5.0, 5.0], 0.05); 4])[0];
+        batch.centers[0][2] = 0.0;
+        batch.centers[1][2] = 0.0;
+        assert!(capt.collides_batch(&batch));
+
+        let indexed = IndexedCapt::<2, 4>::new(&[[0.0, 0.0]], (0.0, 0.1));
+        let compact = CompactCapt::<2, 4>::new(&[[0.0, 0.0]], (0.0, 0.1));
+        let octree = Octree::<2, 4>::new(&[[0.0, 0.0]], (0.0, 0.1));
+        let colliders: [&dyn BatchCollider<2, 4, f32>; 4] = [&capt, &indexed, &compact, &octree];
+        for collider in colliders {
+            assert!(collider.collides_batch(&batch));
+        }
+
+        batch.active.set(2, false);
+        for collider in colliders {
+            assert!(!collider.collides_batch(&batch));
+        }
+        assert!(!PkdTree::new(&[[0.0, 0.0]]).might_collide_batch(&batch));
+    }
+
+    #[test]
+    fn same_batch_for_both_trees() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..500)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let capt = Capt::<3, 8>::new(&points, (0.01, 0.05));
+        let pkdt = PkdTree::new(&points);
+
+        let queries: Vec<([f32; 3], f32)> = (0..1003)
+            .map(|_| {
+                (
+                    [rng.gen(), rng.gen(), rng.gen()],
+                    rng.gen_range(0.01..=0.05),
+                )
+            })
+            .collect();
+        for (batch, chunk) in SimdQueryBatch::<3, 8>::from_queries(&queries)
+            .iter()
+            .zip(queries.chunks(8))
+        {
+            assert_eq!(
+                capt.collides_batch(batch),
+                chunk.iter().any(|(c, r)| capt.collides(c, *r))
+            );
+            assert_eq!(
+                pkdt.might_collide_batch(batch),
+                chunk.iter().any(|(c, r)| pkdt.might_collide(*c, r * r))
+            );
+        }
+    }
+}
@@ -0,0 +1,477 @@
+//! Storage for the buffers of a tree, with control over where and how they are allocated.
+
+use std::{
+    alloc::{self, Layout},
+    fmt::{self, Debug},
+    marker::PhantomData,
+    mem::{align_of, size_of, ManuallyDrop},
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+    slice,
+};
+
+#[cfg(any(feature = "prefetch", feature = "aligned"))]
+/// The minimum alignment of the first element of a buffer: the size of a cache line, in bytes,
+/// which is also the width of the widest vector registers.
+const MIN_ALIGN: usize = 64;
+
+#[cfg(not(any(feature = "prefetch", feature = "aligned")))]
+/// The minimum alignment of the first element of a buffer.
+const MIN_ALIGN: usize = 1;
+
+/// A source of memory for the buffers of a tree.
+///
+/// Implementing this trait allows a tree's buffers to be placed in memory other than the global
+/// heap, such as huge pages, pinned device-visible memory, or shared memory segments.
+/// Temporary allocations made during construction still use the global allocator.
+///
+/// # Safety
+///
+/// Implementors must return either a null pointer or a pointer to a fresh allocation which fits
+/// `layout` from [`Alloc::allocate`], and the memory must stay valid until it is passed to
+/// [`Alloc::deallocate`] on this allocator or any of its clones.
+pub unsafe trait Alloc: Clone {
+    /// Allocate a block of memory for `layout`, which always has nonzero size.
+    /// Returns a null pointer if allocation fails.
+    fn allocate(&self, layout: Layout) -> *mut u8;
+
+    /// Deallocate a block of memory.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by [`Alloc::allocate`] on this allocator or one of its clones,
+    /// called with `layout`, and must not have been deallocated already.
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout);
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// The global allocator, which is used by trees by default.
+pub struct Global;
+
+unsafe impl Alloc for Global {
+    fn allocate(&self, layout: Layout) -> *mut u8 {
+        unsafe {
+            // SAFETY: `layout` has nonzero size.
+            alloc::alloc(layout)
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        alloc::dealloc(ptr, layout);
+    }
+}
+
+/// A boxed slice allocated by `Al`, which may keep spare capacity so that it can be refilled
+/// without reallocating.
+///
+/// If the `prefetch` or `aligned` feature is enabled, its first element is aligned to the start of
+/// a cache line.
+pub struct Buf<T, Al: Alloc = Global> {
+    ptr: NonNull<T>,
+    len: usize,
+    cap: usize,
+    alloc: Al,
+    _phantom: PhantomData<T>,
+}
+
+unsafe impl<T: Send, Al: Alloc + Send> Send for Buf<T, Al> {}
+unsafe impl<T: Sync, Al: Alloc + Sync> Sync for Buf<T, Al> {}
+
+impl<T, Al: Alloc> Buf<T, Al> {
+    /// The alignment of the first element of every buffer.
+    pub const ALIGN: usize = if MIN_ALIGN < align_of::<T>() {
+        align_of::<T>()
+    } else {
+        MIN_ALIGN
+    };
+
+    /// Get the layout used to allocate a buffer of `len` elements.
+    fn layout(len: usize) -> Layout {
+        Layout::from_size_align(size_of::<T>() * len, Self::ALIGN).expect("buffer size overflowed")
+    }
+
+    /// Allocate space for `cap` elements from `alloc`, or return a dangling pointer if no space is
+    /// needed.
+    fn allocate(cap: usize, alloc: &Al) -> NonNull<T> {
+        let layout = Self::layout(cap);
+        if layout.size() == 0 {
+            // SAFETY: The alignment of a layout is never zero.
+            unsafe { NonNull::new_unchecked(ptr::without_provenance_mut(layout.align())) }
+        } else {
+            let Some(ptr) = NonNull::new(alloc.allocate(layout).cast::<T>()) else {
+                alloc::handle_alloc_error(layout);
+            };
+            ptr
+        }
+    }
+
+    /// Replace the allocation of this buffer with a new one with space for `cap` elements,
+    /// moving its elements over.
+    fn reallocate(&mut self, cap: usize) {
+        debug_assert!(self.len <= cap);
+        let ptr = Self::allocate(cap, &self.alloc);
+        let old = Self::layout(self.cap);
+        unsafe {
+            // SAFETY: The new allocation has space for all `len` elements, which are moved out of
+            // the old allocation before it is freed.
+            ptr::copy_nonoverlapping(self.ptr.as_ptr(), ptr.as_ptr(), self.len);
+            if old.size() != 0 {
+                self.alloc.deallocate(self.ptr.as_ptr().cast(), old);
+            }
+        }
+        self.ptr = ptr;
+        self.cap = cap;
+    }
+
+    /// Move the contents of `v` into a new buffer allocated by `alloc`.
+    pub fn from_vec_in(v: Vec<T>, alloc: Al) -> Self {
+        let mut v = ManuallyDrop::new(v);
+        let len = v.len();
+        let ptr = Self::allocate(len, &alloc);
+        unsafe {
+            // SAFETY: `ptr` is large enough for `len` elements, and the elements of `v` are moved
+            // out and never dropped there.
+            ptr::copy_nonoverlapping(v.as_ptr(), ptr.as_ptr(), len);
+        }
+
+        unsafe {
+            // SAFETY: The elements of `v` were moved out above, so only the allocation remains.
+            v.set_len(0);
+            ManuallyDrop::drop(&mut v);
+        }
+
+        Self {
+            ptr,
+            len,
+            cap: len,
+            alloc,
+            _phantom: PhantomData,
+        }
+    }
+
+    #[must_use]
+    /// Get the number of elements in this buffer.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    /// Get the number of elements this buffer can hold without reallocating.
+    pub const fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    /// Remove every element of this buffer, keeping its allocation.
+    pub fn clear(&mut self) {
+        let len = self.len;
+        self.len = 0;
+        unsafe {
+            // SAFETY: The first `len` elements were initialized, and are no longer reachable.
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.ptr.as_ptr(), len));
+        }
+    }
+
+    /// Append `x` to the end of this buffer, doubling its capacity if it is full.
+    pub fn push(&mut self, x: T) {
+        if self.len == self.cap {
+            self.reallocate((2 * self.cap).max(4));
+        }
+        unsafe {
+            // SAFETY: `len < cap`, so the slot after the last element is allocated and unused.
+            self.ptr.as_ptr().add(self.len).write(x);
+        }
+        self.len += 1;
+    }
+
+    #[must_use]
+    /// Move the contents of `chunks` into a new buffer allocated by `alloc`.
+    ///
+    /// Each chunk is freed as soon as it has been moved, so the peak memory use is at most one
+    /// chunk more than the size of the new buffer plus the chunks not yet moved.
+    pub fn from_chunked_in(chunks: Chunked<T>, alloc: Al) -> Self {
+        let len = chunks.len;
+        let ptr = Self::allocate(len, &alloc);
+
+        let mut offset = 0;
+        for chunk in chunks.chunks {
+            let mut chunk = ManuallyDrop::new(chunk);
+            unsafe {
+                // SAFETY: The chunks hold `len` elements in total, so this chunk fits after the
+                // `offset` elements already moved, and its elements are never dropped there.
+                ptr::copy_nonoverlapping(chunk.as_ptr(), ptr.as_ptr().add(offset), chunk.len());
+                offset += chunk.len();
+                chunk.set_len(0);
+                ManuallyDrop::drop(&mut chunk);
+            }
+        }
+
+        Self {
+            ptr,
+            len,
+            cap: len,
+            alloc,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Get the allocator which owns this buffer.
+    pub const fn allocator(&self) -> &Al {
+        &self.alloc
+    }
+}
+
+/// A growable sequence stored as a list of chunks, so that pushing never moves the elements which
+/// were already pushed.
+///
+/// Chunks double in capacity up to a fixed maximum, so a small sequence stays small while a large
+/// one wastes at most one chunk of spare capacity, instead of up to half of a `Vec`.
+pub struct Chunked<T> {
+    /// The chunks of the sequence, each of which is full except the last.
+    chunks: Vec<Vec<T>>,
+    /// The total number of elements in all chunks.
+    len: usize,
+}
+
+impl<T> Chunked<T> {
+    /// The largest capacity of a single chunk.
+    const MAX_CHUNK: usize = 1 << 16;
+
+    /// Create a new, empty sequence.
+    pub const fn new() -> Self {
+        Self {
+            chunks: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Append `x` to the end of this sequence.
+    pub fn push(&mut self, x: T) {
+        match self.chunks.last_mut() {
+            Some(chunk) if chunk.len() < chunk.capacity() => chunk.push(x),
+            _ => {
+                let mut chunk = Vec::with_capacity(self.len.clamp(16, Self::MAX_CHUNK));
+                chunk.push(x);
+                self.chunks.push(chunk);
+            }
+        }
+        self.len += 1;
+    }
+}
+
+impl<T: Copy, Al: Alloc> Buf<T, Al> {
+    /// Resize this buffer to `len` elements, each equal to `value`.
+    ///
+    /// The allocation of this buffer is reused if it has space for `len` elements.
+    pub fn reset(&mut self, len: usize, value: T) {
+        // elements are `Copy`, so they never need to be dropped
+        self.len = 0;
+        if self.cap < len {
+            self.reallocate(len);
+        }
+        for i in 0..len {
+            unsafe {
+                // SAFETY: `i < len <= cap`, so this slot is allocated.
+                self.ptr.as_ptr().add(i).write(value);
+            }
+        }
+        self.len = len;
+    }
+}
+
+impl<T> From<Vec<T>> for Buf<T> {
+    fn from(v: Vec<T>) -> Self {
+        Self::from_vec_in(v, Global)
+    }
+}
+
+impl<T> FromIterator<T> for Buf<T> {
+    fn from_iter<It: IntoIterator<Item = T>>(iter: It) -> Self {
+        iter.into_iter().collect::<Vec<_>>().into()
+    }
+}
+
+impl<T, Al: Alloc + Default> Default for Buf<T, Al> {
+    fn default() -> Self {
+        Self::from_vec_in(Vec::new(), Al::default())
+    }
+}
+
+impl<T, Al: Alloc> Drop for Buf<T, Al> {
+    fn drop(&mut self) {
+        let layout = Self::layout(self.cap);
+        unsafe {
+            // SAFETY: `self` owns `len` initialized elements, in space for `cap` elements allocated
+            // by `alloc` with `layout`.
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.ptr.as_ptr(), self.len));
+            if layout.size() != 0 {
+                self.alloc.deallocate(self.ptr.as_ptr().cast(), layout);
+            }
+        }
+    }
+}
+
+impl<T, Al: Alloc> Deref for Buf<T, Al> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe {
+            // SAFETY: `self` owns `len` initialized elements.
+            slice::from_raw_parts(self.ptr.as_ptr(), self.len)
+        }
+    }
+}
+
+impl<T, Al: Alloc> DerefMut for Buf<T, Al> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe {
+            // SAFETY: `self` uniquely owns `len` initialized elements.
+            slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len)
+        }
+    }
+}
+
+impl<T: Clone, Al: Alloc> Clone for Buf<T, Al> {
+    fn clone(&self) -> Self {
+        Self::from_vec_in(self.to_vec(), self.alloc.clone())
+    }
+}
+
+impl<T: Debug, Al: Alloc> Debug for Buf<T, Al> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: PartialEq, Al: Alloc> PartialEq for Buf<T, Al> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq, Al: Alloc> Eq for Buf<T, Al> {}
+
+#[cfg(all(feature = "prefetch", target_arch = "x86_64"))]
+#[inline]
+/// Hint to the processor that the memory at `p` will soon be read.
+pub fn prefetch<T>(p: *const T) {
+    unsafe {
+        // SAFETY: Prefetching never faults, even on invalid addresses.
+        std::arch::x86_64::_mm_prefetch::<{ std::arch::x86_64::_MM_HINT_T0 }>(p.cast());
+    }
+}
+
+#[cfg(all(feature = "prefetch", target_arch = "aarch64"))]
+#[inline]
+/// Hint to the processor that the memory at `p` will soon be read.
+pub fn prefetch<T>(p: *const T) {
+    unsafe {
+        // SAFETY: Prefetching never faults, even on invalid addresses, and writes no registers or
+        // memory.
+        std::arch::asm!(
+            "prfm pldl1keep, [{p}]",
+            p = in(reg) p,
+            options(nostack, readonly, preserves_flags),
+        );
+    }
+}
+
+#[cfg(not(all(
+    feature = "prefetch",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+#[inline]
+/// Hint to the processor that the memory at `p` will soon be read.
+/// Only x86-64 and 64-bit ARM targets with the `prefetch` feature issue hints; elsewhere this does
+/// nothing.
+pub const fn prefetch<T>(_: *const T) {}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn aligned() {
+        for len in [0, 1, 3, 100] {
+            let b: Buf<u8> = (0..len).collect();
+            assert_eq!(b.len(), usize::from(len));
+            assert!(b.iter().copied().eq(0..len));
+            assert!((b.as_ptr() as usize).is_multiple_of(MIN_ALIGN));
+            assert_eq!(b.clone(), b);
+        }
+    }
+
+    #[test]
+    fn drops_elements() {
+        let b: Buf<String> = vec!["a".into(), "b".into()].into();
+        assert_eq!(&*b, ["a", "b"]);
+    }
+
+    #[derive(Clone)]
+    struct Counting<'a>(&'a Cell<usize>);
+
+    unsafe impl Alloc for Counting<'_> {
+        fn allocate(&self, layout: Layout) -> *mut u8 {
+            self.0.set(self.0.get() + 1);
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+            self.0.set(self.0.get() - 1);
+            Global.deallocate(ptr, layout);
+        }
+    }
+
+    #[test]
+    fn custom_allocator() {
+        let live = Cell::new(0);
+        let b = Buf::from_vec_in(vec![1, 2, 3], Counting(&live));
+        let c = b.clone();
+        assert_eq!(live.get(), 2);
+        drop(b);
+        assert_eq!(&*c, [1, 2, 3]);
+        drop(c);
+        assert_eq!(live.get(), 0);
+    }
+
+    #[test]
+    fn chunked_round_trip() {
+        let mut chunks = Chunked::new();
+        for i in 0..200_000 {
+            chunks.push(i);
+        }
+        assert!(chunks
+            .chunks
+            .iter()
+            .all(|c| c.capacity() <= Chunked::<u32>::MAX_CHUNK));
+
+        let b = Buf::from_chunked_in(chunks, Global);
+        assert!(b.iter().copied().eq(0..200_000));
+    }
+
+    #[test]
+    fn reuses_capacity() {
+        let live = Cell::new(0);
+        let mut b = Buf::from_vec_in(vec![1; 100], Counting(&live));
+        let ptr = b.as_ptr();
+
+        b.reset(60, 2);
+        assert_eq!(&*b, [2; 60]);
+        b.clear();
+        for i in 0..100 {
+            b.push(i);
+        }
+        assert!(b.iter().copied().eq(0..100));
+        assert_eq!((b.as_ptr(), b.capacity(), live.get()), (ptr, 100, 1));
+
+        b.push(100);
+        assert!(b.iter().copied().eq(0..=100));
+        assert_eq!((b.capacity(), live.get()), (200, 1));
+
+        b.reset(300, 3);
+        assert_eq!(&*b, [3; 300]);
+        drop(b);
+        assert_eq!(live.get(), 0);
+    }
+}
@@ -0,0 +1,272 @@
+//! Segmentation of point clouds into clusters of nearby points.
+//!
+//! The clustering algorithms here are built on the fixed-radius pair search of [`PkdTree`], so
+//! they scale to clouds of the size which are used for collision checking.
+
+use std::collections::HashMap;
+
+use crate::{Axis, PkdTree};
+
+/// A disjoint-set forest over the leaf indices of a [`PkdTree`].
+struct UnionFind {
+    /// The parent of each element; roots are their own parents.
+    parents: Vec<usize>,
+}
+
+impl UnionFind {
+    /// Construct a forest where each of `n` elements is in its own set.
+    fn new(n: usize) -> Self {
+        Self {
+            parents: (0..n).collect(),
+        }
+    }
+
+    /// Find the root of the set containing `i`, compressing the path to it.
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parents[i] != i {
+            self.parents[i] = self.parents[self.parents[i]];
+            i = self.parents[i];
+        }
+        i
+    }
+
+    /// Merge the sets containing `i` and `j`.
+    fn union(&mut self, i: usize, j: usize) {
+        let (ri, rj) = (self.find(i), self.find(j));
+        if ri < rj {
+            self.parents[rj] = ri;
+        } else {
+            self.parents[ri] = rj;
+        }
+    }
+}
+
+/// Find the index of a leaf of `tree` holding exactly `point`, for each point in `points`.
+///
+/// Duplicated points may all map to the same leaf, which is harmless for clustering since
+/// duplicates are always within any radius of one another.
+fn leaves_of<A: Axis, const K: usize>(tree: &PkdTree<K, A>, points: &[[A; K]]) -> Vec<usize> {
+    points.iter().map(|&p| tree.query1_exact(p)).collect()
+}
+
+/// Number clusters in the order in which they first appear in `roots`, where `roots[i]` is the
+/// identifier of the set containing the `i`-th point, or `None` if it is in no cluster.
+fn number_clusters(roots: impl IntoIterator<Item = Option<usize>>) -> Vec<Vec<usize>> {
+    let mut cluster_of_root = HashMap::new();
+    let mut clusters: Vec<Vec<usize>> = Vec::new();
+    for (i, root) in roots.into_iter().enumerate() {
+        if let Some(root) = root {
+            let c = *cluster_of_root.entry(root).or_insert_with(|| {
+                clusters.push(Vec::new());
+                clusters.len() - 1
+            });
+            clusters[c].push(i);
+        }
+    }
+    clusters
+}
+
+#[must_use]
+/// Partition `points` into clusters, where two points are in the same cluster if they are
+/// connected by a chain of points, each within a distance of `r` of the next.
+///
+/// Each cluster is given as a list of indices into `points`, in ascending order.
+/// Clusters with fewer than `min_size` points are discarded, and the remaining clusters are
+/// ordered by their smallest index.
+///
+/// # Panics
+///
+/// This function will panic if any point in `points` is not finite.
+///
+/// # Examples
+///
+/// ```
+/// use captree::cluster::euclidean_clusters;
+///
+/// let points = [
+///     [0.0, 0.0],
+///     [5.0, 5.0],
+///     [0.1, 0.0],
+///     [0.2, 0.0],
+///     [9.0, 9.0],
+///     [5.0, 5.1],
+/// ];
+///
+/// assert_eq!(
+///     euclidean_clusters(&points, 0.15, 2),
+///     [vec![0, 2, 3], vec![1, 5]]
+/// );
+/// ```
+pub fn euclidean_clusters<A: Axis, const K: usize>(
+    points: &[[A; K]],
+    r: A,
+    min_size: usize,
+) -> Vec<Vec<usize>> {
+    let tree = PkdTree::new(points);
+    let mut sets = UnionFind::new(tree.points().len());
+    tree.for_each_pair_within(r, |i, j| sets.union(i, j));
+
+    let mut clusters = number_clusters(
+        leaves_of(&tree, points)
+            .into_iter()
+            .map(|leaf| Some(sets.find(leaf))),
+    );
+    clusters.retain(|c| min_size <= c.len());
+    clusters
+}
+
+#[must_use]
+/// Cluster `points` with DBSCAN (density-based spatial clustering of applications with noise).
+///
+/// A point is a core point if at least `min_pts` points, including itself, are within a distance
+/// of `eps` of it.
+/// Core points within `eps` of one another are in the same cluster, and every other point within
+/// `eps` of a core point joins the cluster of one such core point.
+/// All remaining points are noise.
+///
+/// Returns the label of each point in `points`: `Some(c)` if it is in the `c`-th cluster, or
+/// `None` if it is noise.
+/// Clusters are numbered in the order in which they first appear in `points`.
+///
+/// # Panics
+///
+/// This function will panic if any point in `points` is not finite.
+///
+/// # Examples
+///
+/// ```
+/// use captree::cluster::dbscan;
+///
+/// let points = [[0.0, 0.0], [0.1, 0.0], [0.0, 0.1], [3.0, 3.0], [0.25, 0.0]];
+///
+/// assert_eq!(
+///     dbscan(&points, 0.15, 3),
+///     [Some(0), Some(0), Some(0), None, Some(0)]
+/// );
+/// ```
+pub fn dbscan<A: Axis, const K: usize>(
+    points: &[[A; K]],
+    eps: A,
+    min_pts: usize,
+) -> Vec<Option<usize>> {
+    let tree = PkdTree::new(points);
+    let is_core: Vec<bool> = tree
+        .points()
+        .iter()
+        .map(|&p| min_pts <= tree.count_within(p, eps))
+        .collect();
+
+    let mut sets = UnionFind::new(is_core.len());
+    // a core point adjacent to each border point
+    let mut border_core = vec![None; is_core.len()];
+    tree.for_each_pair_within(eps, |i, j| match (is_core[i], is_core[j]) {
+        (true, true) => sets.union(i, j),
+        (true, false) => {
+            border_core[j].get_or_insert(i);
+        }
+        (false, true) => {
+            border_core[i].get_or_insert(j);
+        }
+        (false, false) => (),
+    });
+
+    let clusters = number_clusters(leaves_of(&tree, points).into_iter().map(|leaf| {
+        let core = if is_core[leaf] {
+            Some(leaf)
+        } else {
+            border_core[leaf]
+        };
+        core.map(|c| sets.find(c))
+    }));
+
+    let mut labels = vec![None; points.len()];
+    for (c, cluster) in clusters.iter().enumerate() {
+        for &i in cluster {
+            labels[i] = Some(c);
+        }
+    }
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+    use crate::distsq;
+
+    /// Label each point with the smallest index in its connected component, by brute force.
+    fn brute_force_components(points: &[[f32; 2]], r: f32) -> Vec<usize> {
+        let mut labels: Vec<usize> = (0..points.len()).collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in 0..points.len() {
+                for j in 0..points.len() {
+                    if distsq(points[i], points[j]) <= r * r && labels[j] < labels[i] {
+                        labels[i] = labels[j];
+                        changed = true;
+                    }
+                }
+            }
+        }
+        labels
+    }
+
+    #[test]
+    fn components_match_brute_force() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 2]> = (0..300).map(|_| [rng.gen(), rng.gen()]).collect();
+        let r = 0.04;
+
+        let labels = brute_force_components(&points, r);
+        let clusters = euclidean_clusters(&points, r, 1);
+        assert_eq!(clusters.iter().map(Vec::len).sum::<usize>(), points.len());
+        for c in &clusters {
+            assert!(c.iter().all(|&i| labels[i] == c[0]));
+        }
+        let mut firsts: Vec<usize> = clusters.iter().map(|c| c[0]).collect();
+        firsts.dedup();
+        assert_eq!(firsts.len(), clusters.len());
+    }
+
+    #[test]
+    fn duplicates_and_small_clusters() {
+        let points = [[1.0], [1.0], [3.0], [1.0]];
+        assert_eq!(
+            euclidean_clusters(&points, 0.5, 1),
+            [vec![0, 1, 3], vec![2]]
+        );
+        assert_eq!(euclidean_clusters(&points, 0.5, 2), [vec![0, 1, 3]]);
+        assert!(euclidean_clusters::<f32, 1>(&[], 0.5, 1).is_empty());
+    }
+
+    #[test]
+    fn dbscan_cores_match_brute_force() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 2]> = (0..500).map(|_| [rng.gen(), rng.gen()]).collect();
+        let (eps, min_pts) = (0.05, 5);
+
+        let labels = dbscan(&points, eps, min_pts);
+        let points = &points;
+        let neighbors = |i: usize| {
+            (0..points.len()).filter(move |&j| distsq(points[i], points[j]) <= eps * eps)
+        };
+        let is_core = |i: usize| min_pts <= neighbors(i).count();
+
+        for i in 0..points.len() {
+            if is_core(i) {
+                assert!(labels[i].is_some());
+                for j in neighbors(i) {
+                    if is_core(j) {
+                        assert_eq!(labels[i], labels[j]);
+                    }
+                }
+            } else if neighbors(i).any(is_core) {
+                assert!(neighbors(i).any(|j| is_core(j) && labels[j] == labels[i]));
+            } else {
+                assert_eq!(labels[i], None);
+            }
+        }
+    }
+}
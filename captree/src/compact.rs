@@ -0,0 +1,281 @@
+//! Collision-affording point trees with automatically-selected index widths.
+
+#[cfg(feature = "simd")]
+use std::{
+    ops::{AddAssign, Mul, Sub},
+    simd::{
+        cmp::{SimdPartialEq, SimdPartialOrd},
+        Mask, Simd,
+    },
+};
+
+use elain::{Align, Alignment};
+
+use crate::{Alloc, Axis, Buf, Capt, Collider, Index, NewCaptError};
+
+#[cfg(feature = "simd")]
+use crate::{AxisSimd, BatchCollider, SimdQueryBatch};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+/// A collision-affording point tree whose index type is selected at construction time to be the
+/// narrowest unsigned integer which can address all of its affordance buffers.
+///
+/// For small point clouds, the buffer of affordance buffer start positions can make up a large
+/// fraction of the memory used by a [`Capt`], so using a narrow index type can substantially
+/// reduce memory usage.
+/// A `CompactCapt` selects the index width automatically, at the cost of a single branch per
+/// query.
+///
+/// # Examples
+///
+/// ```
+/// use captree::CompactCapt;
+///
+/// let points = [[0.0, 0.1], [0.4, -0.2], [-0.2, -0.1]];
+/// let t = CompactCapt::<2>::new(&points, (0.0, 0.2));
+///
+/// // three points don't need more than a byte to index
+/// assert!(matches!(t, CompactCapt::U8(_)));
+///
+/// assert!(!t.collides(&[0.0, 0.3], 0.1));
+/// assert!(t.collides(&[0.0, 0.2], 0.15));
+/// ```
+pub enum CompactCapt<const K: usize, const L: usize = 8, A = f32>
+where
+    Align<L>: Alignment,
+{
+    /// A tree indexed by `u8`.
+    U8(Capt<K, L, A, u8>),
+    /// A tree indexed by `u16`.
+    U16(Capt<K, L, A, u16>),
+    /// A tree indexed by `u32`.
+    U32(Capt<K, L, A, u32>),
+    /// A tree indexed by `u64`.
+    U64(Capt<K, L, A, u64>),
+}
+
+/// Apply the same expression to the tree inside any variant of a [`CompactCapt`].
+macro_rules! dispatch {
+    ($self: expr, $t: ident => $e: expr) => {
+        match $self {
+            CompactCapt::U8($t) => $e,
+            CompactCapt::U16($t) => $e,
+            CompactCapt::U32($t) => $e,
+            CompactCapt::U64($t) => $e,
+        }
+    };
+}
+
+impl<A, const K: usize, const L: usize> CompactCapt<K, L, A>
+where
+    A: Axis,
+    Align<L>: Alignment,
+{
+    /// Construct a new CAPT containing all the points in `points`, using the narrowest index type
+    /// which can address the whole tree.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if any points contain non-finite non-real value, or if the tree is
+    /// too large to be indexed by a `u64`.
+    pub fn new(points: &[[A; K]], r_range: (A, A)) -> Self {
+        Self::try_new(points, r_range).expect("failed to construct CAPT")
+    }
+
+    /// Construct a new CAPT containing all the points in `points`, using the narrowest index type
+    /// which can address the whole tree.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error under the same conditions as [`Capt::try_new`] for a
+    /// tree indexed by `u64`.
+    pub fn try_new(points: &[[A; K]], r_range: (A, A)) -> Result<Self, NewCaptError> {
+        let t = Capt::<K, L, A, u64>::try_new(points, r_range)?;
+        let t = match t.try_reindex() {
+            Ok(t) => return Ok(Self::U8(t)),
+            Err(t) => t,
+        };
+        let t = match t.try_reindex() {
+            Ok(t) => return Ok(Self::U16(t)),
+            Err(t) => t,
+        };
+        Ok(match t.try_reindex() {
+            Ok(t) => Self::U32(t),
+            Err(t) => Self::U64(t),
+        })
+    }
+
+    #[must_use]
+    /// Determine whether a point in this tree is within a distance of `radius` to `center`.
+    ///
+    /// See [`Capt::collides`] for further details.
+    pub fn collides(&self, center: &[A; K], radius: A) -> bool {
+        dispatch!(self, t => t.collides(center, radius))
+    }
+
+    #[must_use]
+    /// Get the number of points in this tree.
+    ///
+    /// See [`Capt::len`] for further details.
+    pub fn len(&self) -> usize {
+        dispatch!(self, t => t.len())
+    }
+
+    #[must_use]
+    /// Determine whether this tree contains no points.
+    pub fn is_empty(&self) -> bool {
+        dispatch!(self, t => t.is_empty())
+    }
+
+    #[must_use]
+    /// Get the dimension of the points in this tree, `K`.
+    pub const fn dimension(&self) -> usize {
+        K
+    }
+
+    #[must_use]
+    /// Get the number of tests on the path from the root of this tree to any leaf.
+    pub const fn depth(&self) -> u32 {
+        dispatch!(self, t => t.depth())
+    }
+
+    #[must_use]
+    /// Get the `(minimum, maximum)` range of query radii which this tree was built to support.
+    pub const fn r_range(&self) -> (A, A) {
+        dispatch!(self, t => t.r_range())
+    }
+
+    #[must_use]
+    #[doc(hidden)]
+    /// Get the total memory used (stack + heap) by this structure, measured in bytes.
+    /// This function should not be considered stable; it is only used internally for benchmarks.
+    pub const fn memory_used(&self) -> usize {
+        dispatch!(self, t => t.memory_used())
+    }
+}
+
+impl<A, const K: usize, const L: usize> Collider<K, A> for CompactCapt<K, L, A>
+where
+    A: Axis,
+    Align<L>: Alignment,
+{
+    fn collides(&self, center: &[A; K], radius: A) -> bool {
+        self.collides(center, radius)
+    }
+}
+
+#[cfg(feature = "simd")]
+impl<A, const K: usize, const L: usize> BatchCollider<K, L, A> for CompactCapt<K, L, A>
+where
+    A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask> + Mul<Output = A>,
+    Align<L>: Alignment,
+    Simd<A, L>: SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+    Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+{
+    fn collides_batch(&self, batch: &SimdQueryBatch<K, L, A>) -> bool {
+        dispatch!(self, t => t.collides_batch(batch))
+    }
+}
+
+impl<A, const K: usize, const L: usize> Default for CompactCapt<K, L, A>
+where
+    A: Axis,
+    Align<L>: Alignment,
+{
+    /// Construct an empty tree, which supports no query radius and never collides.
+    fn default() -> Self {
+        Self::new(&[], (A::ZERO, A::ZERO))
+    }
+}
+
+#[allow(clippy::mismatching_type_param_order)]
+#[cfg(feature = "simd")]
+impl<A, const K: usize, const L: usize> CompactCapt<K, L, A>
+where
+    A: Mul<Output = A>,
+    Align<L>: Alignment,
+{
+    #[must_use]
+    /// Determine whether any sphere in the list of provided spheres intersects a point in this
+    /// tree.
+    ///
+    /// See [`Capt::collides_simd`] for further details.
+    pub fn collides_simd(&self, centers: &[Simd<A, L>; K], radii: Simd<A, L>) -> bool
+    where
+        Simd<A, L>:
+            SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+        Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+        A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask>,
+    {
+        dispatch!(self, t => t.collides_simd(centers, radii))
+    }
+}
+
+impl<A, I, Al, const K: usize, const L: usize> Capt<K, L, A, I, Al>
+where
+    I: Index,
+    Al: Alloc,
+    Align<L>: Alignment,
+{
+    /// Convert this tree to use a different index type `I2`, returning the original tree if some
+    /// index cannot be represented by `I2`.
+    fn try_reindex<I2: Index>(self) -> Result<Capt<K, L, A, I2, Al>, Self> {
+        // the last start is the largest
+        if self
+            .starts
+            .last()
+            .is_some_and(|&i| I2::try_from(to_usize(i)).is_err())
+        {
+            return Err(self);
+        }
+
+        let starts = self
+            .starts
+            .iter()
+            .map(|&i| unsafe {
+                // SAFETY: every start is less than or equal to the last, which fits in `I2`.
+                I2::try_from(to_usize(i)).unwrap_unchecked()
+            })
+            .collect();
+        Ok(Capt {
+            starts: Buf::from_vec_in(starts, self.starts.allocator().clone()),
+            tests: self.tests,
+            split_dims: self.split_dims,
+            aabbs: self.aabbs,
+            afforded: self.afforded,
+            r_range: self.r_range,
+            scratch: self.scratch,
+        })
+    }
+}
+
+/// Convert an index of a tree back into a `usize`.
+fn to_usize<I: Index>(i: I) -> usize {
+    unsafe {
+        // SAFETY: every index in a tree was originally converted from a `usize`.
+        i.try_into().unwrap_unchecked()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn widens_for_big_clouds() {
+        #[allow(clippy::cast_precision_loss)]
+        let points: Vec<[f32; 1]> = (0..1000).map(|i| [i as f32]).collect();
+
+        let t = CompactCapt::<1>::new(&points, (0.0, 0.5));
+        assert!(matches!(t, CompactCapt::U16(_)));
+        assert!(t.collides(&[10.2], 0.25));
+        assert!(!t.collides(&[10.5], 0.25));
+    }
+}
@@ -0,0 +1,339 @@
+//! Exporting the spatial decomposition of a tree for rendering in external tools.
+
+use std::{
+    fmt::Display,
+    io::{self, Write},
+};
+
+use elain::{Align, Alignment};
+
+use crate::{Aabb, Alloc, Axis, Capt, Index, LeafView};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// One of the split planes of a [`Capt`], as reported in a [`DebugGeometry`].
+pub struct SplitPlane<A, const K: usize> {
+    /// The index of the test which makes this split, in breadth-first order from the root.
+    pub test: usize,
+    /// The axis which the plane is perpendicular to.
+    pub axis: usize,
+    /// The position of the plane along `axis`.
+    ///
+    /// Splits which only separate padding from the rest of the tree are placed at infinity.
+    pub value: A,
+    /// The volume of space which this plane divides in two.
+    pub cell: Aabb<A, K>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// The spatial decomposition of a [`Capt`], as returned by [`Capt::export_debug_geometry`].
+///
+/// This can be written out as JSON for custom tooling, or, for three-dimensional trees, as a
+/// Wavefront OBJ mesh which can be opened in `RViz` or Blender.
+/// Large affordance buffers usually show up as cells whose `bounds` reach far beyond their
+/// `cell`, or as thin slivers of space between closely spaced split planes.
+pub struct DebugGeometry<A, const K: usize> {
+    /// Every leaf cell of the tree, including padding cells, in left-to-right order.
+    pub leaves: Vec<LeafView<A, K>>,
+    /// Every split plane of the tree, in breadth-first order from the root.
+    pub planes: Vec<SplitPlane<A, K>>,
+}
+
+impl<A, I, Al, const K: usize, const L: usize> Capt<K, L, A, I, Al>
+where
+    A: Axis,
+    I: Index,
+    Al: Alloc,
+    Align<L>: Alignment,
+{
+    #[must_use]
+    /// Export the leaf cells and split planes of this tree, along with the size of the affordance
+    /// buffer of each cell, for visualizing how this tree divides space.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let capt = captree::Capt::<2>::new(&[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]], (0.0, 0.1));
+    /// let geometry = capt.export_debug_geometry();
+    ///
+    /// assert_eq!(geometry.leaves.len(), 4);
+    /// assert_eq!(geometry.planes.len(), 3);
+    /// assert_eq!(geometry.planes[0].axis, 0);
+    ///
+    /// let mut json = Vec::new();
+    /// geometry.write_json(&mut json).unwrap();
+    /// assert!(json.starts_with(b"{\"dimension\":2,"));
+    /// ```
+    pub fn export_debug_geometry(&self) -> DebugGeometry<A, K> {
+        let mut planes: Vec<SplitPlane<A, K>> = Vec::with_capacity(self.tests.len());
+        for (i, &value) in self.tests.iter().enumerate() {
+            let depth = (i + 1).ilog2() as usize;
+            let axis = self
+                .split_dims
+                .get(i)
+                .map_or(depth % K, |&d| usize::from(d));
+            let cell = if i == 0 {
+                Aabb::ALL
+            } else {
+                let parent = &planes[(i - 1) / 2];
+                let (lo, hi) = parent.cell.split(parent.value, parent.axis);
+                if i % 2 == 1 {
+                    lo
+                } else {
+                    hi
+                }
+            };
+            planes.push(SplitPlane {
+                test: i,
+                axis,
+                value,
+                cell,
+            });
+        }
+
+        DebugGeometry {
+            leaves: self.leaves().collect(),
+            planes,
+        }
+    }
+}
+
+impl<A, const K: usize> DebugGeometry<A, K>
+where
+    A: Axis + Display,
+{
+    /// Write this geometry as a JSON object.
+    ///
+    /// The object has the fields `dimension`, `leaves` and `planes`.
+    /// Each leaf has the fields `index`, `cell`, `bounds`, `representative` and `n_afforded`, and
+    /// each plane has the fields `test`, `axis`, `value` and `cell`, matching [`LeafView`] and
+    /// [`SplitPlane`].
+    /// Boxes are objects with the fields `lo` and `hi`.
+    /// Infinite values, which JSON cannot represent, are written as `null`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing to `w` fails.
+    pub fn write_json(&self, mut w: impl Write) -> io::Result<()> {
+        write!(w, "{{\"dimension\":{K},\"leaves\":[")?;
+        for (i, leaf) in self.leaves.iter().enumerate() {
+            if i > 0 {
+                write!(w, ",")?;
+            }
+            write!(w, "{{\"index\":{},\"cell\":", leaf.index)?;
+            write_json_aabb(&mut w, &leaf.cell)?;
+            write!(w, ",\"bounds\":")?;
+            write_json_aabb(&mut w, &leaf.bounds)?;
+            write!(w, ",\"representative\":")?;
+            match &leaf.representative {
+                Some(p) => write_json_point(&mut w, p)?,
+                None => write!(w, "null")?,
+            }
+            write!(w, ",\"n_afforded\":{}}}", leaf.n_afforded)?;
+        }
+        write!(w, "],\"planes\":[")?;
+        for (i, plane) in self.planes.iter().enumerate() {
+            if i > 0 {
+                write!(w, ",")?;
+            }
+            write!(
+                w,
+                "{{\"test\":{},\"axis\":{},\"value\":",
+                plane.test, plane.axis
+            )?;
+            write_json_value(&mut w, plane.value)?;
+            write!(w, ",\"cell\":")?;
+            write_json_aabb(&mut w, &plane.cell)?;
+            write!(w, "}}")?;
+        }
+        write!(w, "]}}")
+    }
+}
+
+impl<A> DebugGeometry<A, 3>
+where
+    A: Axis + Display,
+{
+    /// Write this geometry as a Wavefront OBJ mesh.
+    ///
+    /// Each leaf cell which contains a point becomes a box named `leaf_<index>_afforded_<n>`,
+    /// where `n` is the size of its affordance buffer, and each finite split plane becomes a
+    /// rectangle named `split_<test>`.
+    /// Since the outermost cells extend to infinity, every box and rectangle is clipped to the
+    /// bounding box of all the affordance buffers.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing to `w` fails.
+    pub fn write_obj(&self, mut w: impl Write) -> io::Result<()> {
+        let mut leaves = self.leaves.iter().filter(|l| l.representative.is_some());
+        let Some(first) = leaves.next() else {
+            return Ok(());
+        };
+        let mut clip = first.bounds;
+        for leaf in leaves {
+            for k in 0..3 {
+                clip.lo[k] = min(clip.lo[k], leaf.bounds.lo[k]);
+                clip.hi[k] = max(clip.hi[k], leaf.bounds.hi[k]);
+            }
+        }
+
+        // OBJ vertex indices start at 1 and count every vertex written so far
+        let mut n_vertices = 0;
+        for leaf in self.leaves.iter().filter(|l| l.representative.is_some()) {
+            writeln!(w, "o leaf_{}_afforded_{}", leaf.index, leaf.n_afforded)?;
+            let cell = clipped(&leaf.cell, &clip);
+            for c in 0..8 {
+                let v: [A; 3] = std::array::from_fn(|k| {
+                    if (c >> k) & 1 == 0 {
+                        cell.lo[k]
+                    } else {
+                        cell.hi[k]
+                    }
+                });
+                writeln!(w, "v {} {} {}", v[0], v[1], v[2])?;
+            }
+            for face in [
+                [0, 2, 6, 4],
+                [1, 5, 7, 3],
+                [0, 4, 5, 1],
+                [2, 3, 7, 6],
+                [0, 1, 3, 2],
+                [4, 6, 7, 5],
+            ] {
+                let [v0, v1, v2, v3] = face.map(|i| n_vertices + i + 1);
+                writeln!(w, "f {v0} {v1} {v2} {v3}")?;
+            }
+            n_vertices += 8;
+        }
+
+        for plane in self.planes.iter().filter(|p| p.value.is_finite()) {
+            writeln!(w, "o split_{}", plane.test)?;
+            let cell = clipped(&plane.cell, &clip);
+            let (b, c) = ((plane.axis + 1) % 3, (plane.axis + 2) % 3);
+            for (on_b, on_c) in [(false, false), (true, false), (true, true), (false, true)] {
+                let mut v = [plane.value; 3];
+                v[b] = if on_b { cell.hi[b] } else { cell.lo[b] };
+                v[c] = if on_c { cell.hi[c] } else { cell.lo[c] };
+                writeln!(w, "v {} {} {}", v[0], v[1], v[2])?;
+            }
+            writeln!(
+                w,
+                "f {} {} {} {}",
+                n_vertices + 1,
+                n_vertices + 2,
+                n_vertices + 3,
+                n_vertices + 4
+            )?;
+            n_vertices += 4;
+        }
+
+        Ok(())
+    }
+}
+
+/// Get the lesser of `a` and `b`.
+fn min<A: PartialOrd>(a: A, b: A) -> A {
+    if b < a {
+        b
+    } else {
+        a
+    }
+}
+
+/// Get the greater of `a` and `b`.
+fn max<A: PartialOrd>(a: A, b: A) -> A {
+    if a < b {
+        b
+    } else {
+        a
+    }
+}
+
+/// Clip `aabb` to lie within `clip`.
+fn clipped<A: Axis, const K: usize>(aabb: &Aabb<A, K>, clip: &Aabb<A, K>) -> Aabb<A, K> {
+    Aabb {
+        lo: std::array::from_fn(|k| max(aabb.lo[k], clip.lo[k])),
+        hi: std::array::from_fn(|k| min(aabb.hi[k], clip.hi[k])),
+    }
+}
+
+/// Write a single value as a JSON number, or `null` if it is not finite.
+fn write_json_value<A: Axis + Display>(w: &mut impl Write, x: A) -> io::Result<()> {
+    if x.is_finite() {
+        write!(w, "{x}")
+    } else {
+        write!(w, "null")
+    }
+}
+
+/// Write a point as a JSON array.
+fn write_json_point<A: Axis + Display, const K: usize>(
+    w: &mut impl Write,
+    p: &[A; K],
+) -> io::Result<()> {
+    write!(w, "[")?;
+    for (k, &x) in p.iter().enumerate() {
+        if k > 0 {
+            write!(w, ",")?;
+        }
+        write_json_value(w, x)?;
+    }
+    write!(w, "]")
+}
+
+/// Write a bounding box as a JSON object with the fields `lo` and `hi`.
+fn write_json_aabb<A: Axis + Display, const K: usize>(
+    w: &mut impl Write,
+    aabb: &Aabb<A, K>,
+) -> io::Result<()> {
+    write!(w, "{{\"lo\":")?;
+    write_json_point(w, &aabb.lo)?;
+    write!(w, ",\"hi\":")?;
+    write_json_point(w, &aabb.hi)?;
+    write!(w, "}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use crate::Capt;
+
+    #[test]
+    fn planes_bound_leaves() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..300)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let t = Capt::<3>::new(&points, (0.01, 0.05));
+        let geometry = t.export_debug_geometry();
+
+        // the last level of planes divides each pair of sibling leaves
+        let n_tests = geometry.planes.len();
+        for (z, leaf) in geometry.leaves.iter().enumerate() {
+            let parent = &geometry.planes[(n_tests + z - 1) / 2];
+            let (lo, hi) = parent.cell.split(parent.value, parent.axis);
+            assert_eq!(leaf.cell, if z % 2 == 0 { lo } else { hi });
+        }
+
+        let mut obj = Vec::new();
+        geometry.write_obj(&mut obj).unwrap();
+        let obj = String::from_utf8(obj).unwrap();
+        let n_vertices = obj.lines().filter(|l| l.starts_with("v ")).count();
+        for face in obj.lines().filter(|l| l.starts_with("f ")) {
+            assert!(face[2..]
+                .split(' ')
+                .all(|i| (1..=n_vertices).contains(&i.parse::<usize>().unwrap())));
+        }
+        assert!(!obj.contains("inf"));
+
+        let mut json = Vec::new();
+        geometry.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(!json.contains("inf"));
+        assert_eq!(
+            json.matches("\"n_afforded\"").count(),
+            geometry.leaves.len()
+        );
+    }
+}
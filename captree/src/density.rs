@@ -0,0 +1,109 @@
+//! Kernel density estimates over the affordance sets of a tree.
+
+use elain::{Align, Alignment};
+
+use crate::{distsq, Alloc, Capt, Index};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The kernel used to weight each point in [`Capt::density_at`].
+pub enum Kernel {
+    /// Every point within the bandwidth has weight 1, and every other point has weight 0.
+    Tophat,
+    /// A point at distance `d` has weight `exp(-d² / (2 * bandwidth²))`.
+    Gaussian,
+}
+
+/// Implement `density_at` for `Capt`s with axes of type `$a`.
+macro_rules! impl_density {
+    ($a: ty) => {
+        impl<I, Al, const K: usize, const L: usize> Capt<K, L, $a, I, Al>
+        where
+            I: Index,
+            Al: Alloc,
+            Align<L>: Alignment,
+        {
+            #[must_use]
+            /// Estimate the density of points around `center`, summing the weights given by
+            /// `kernel` to every point afforded to the cell containing `center`.
+            ///
+            /// The result is not normalized: with [`Kernel::Tophat`], it is the number of
+            /// afforded points within `bandwidth` of `center`.
+            ///
+            /// This reuses the traversal of a collision check, so it only sees the affordance set.
+            /// Points farther than the largest radius the tree was built for are not afforded and
+            /// never contribute, so the Gaussian kernel is truncated there.
+            /// Similarly, a cell which lies within the smallest radius of its representative
+            /// affords only that representative, so densities in such cells are underestimated.
+            ///
+            /// # Examples
+            ///
+            /// ```
+            /// use captree::{Capt, Kernel};
+            ///
+            /// let points = [[0.0, 0.0], [0.1, 0.0], [0.0, 0.1], [3.0, 3.0]];
+            /// let capt = Capt::<2>::new(&points, (0.0, 0.5));
+            ///
+            /// assert_eq!(capt.density_at(&[0.05, 0.0], 0.1, Kernel::Tophat), 2.0);
+            /// assert_eq!(capt.density_at(&[1.5, 1.5], 0.1, Kernel::Tophat), 0.0);
+            /// ```
+            pub fn density_at(&self, center: &[$a; K], bandwidth: $a, kernel: Kernel) -> $a {
+                let z = self.leaf_containing(center);
+                let range = unsafe {
+                    // SAFETY: The conversion worked the first way.
+                    self.starts[z].try_into().unwrap_unchecked()
+                        ..self.starts[z + 1].try_into().unwrap_unchecked()
+                };
+
+                let bw_sq = bandwidth * bandwidth;
+                let mut density = 0.0;
+                for i in range {
+                    for j in 0..L {
+                        let aff_pt = std::array::from_fn(|k| self.afforded[k][i].data[j]);
+                        let dsq = distsq(aff_pt, *center);
+                        density += match kernel {
+                            Kernel::Tophat if dsq <= bw_sq => 1.0,
+                            Kernel::Tophat => 0.0,
+                            Kernel::Gaussian => (-dsq / (2.0 * bw_sq)).exp(),
+                        };
+                    }
+                }
+                density
+            }
+        }
+    };
+}
+
+impl_density!(f32);
+impl_density!(f64);
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+
+    #[test]
+    #[allow(clippy::cast_precision_loss, clippy::float_cmp)]
+    fn tophat_counts_neighbors() {
+        let mut rng = thread_rng();
+        let points: Vec<[f64; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let capt = Capt::<3, 8, f64>::new(&points, (0.0, 0.1));
+
+        for _ in 0..200 {
+            let center = [rng.gen(), rng.gen(), rng.gen()];
+            let bandwidth = rng.gen_range(0.01..0.1);
+            let expected = points
+                .iter()
+                .filter(|&&p| distsq(p, center) <= bandwidth * bandwidth)
+                .count();
+            assert_eq!(
+                capt.density_at(&center, bandwidth, Kernel::Tophat),
+                expected as f64
+            );
+            let gaussian = capt.density_at(&center, bandwidth, Kernel::Gaussian);
+            assert!(expected as f64 * (-0.5f64).exp() <= gaussian);
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Trees whose dimension is only known at runtime.
+
+use elain::{Align, Alignment};
+
+use crate::{Axis, Capt, Index, NewCaptError};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A collision-affording point tree whose dimension is chosen at runtime.
+///
+/// Points and query centers are flat slices rather than arrays, which is convenient when the
+/// dimension of a cloud is only learned by loading it, and spares downstream code from being
+/// generic over the dimension.
+/// Internally, this wraps a [`Capt`] of the matching dimension, so queries run the same algorithm
+/// at the cost of a branch on the dimension and a length check on each center.
+///
+/// Dimensions from 1 up to [`DynCapt::MAX_DIM`] are supported.
+///
+/// # Generic parameters
+///
+/// - `L`: The lane size of the underlying tree.
+/// - `A`: The value of the axes of each point.
+/// - `I`: The index integer of the underlying tree.
+///
+/// # Examples
+///
+/// ```
+/// use captree::DynCapt;
+///
+/// // two points in 4 dimensions
+/// let flat = [0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0];
+/// let t = DynCapt::<8>::new(&flat, 4, (0.0, 0.5));
+///
+/// assert_eq!(t.dim(), 4);
+/// assert!(t.collides(&[1.0, 1.0, 1.0, 1.1], 0.2));
+/// assert!(!t.collides(&[0.5, 0.5, 0.5, 0.5], 0.2));
+/// ```
+pub struct DynCapt<const L: usize = 8, A = f32, I = usize>
+where
+    Align<L>: Alignment,
+{
+    /// The tree of the matching dimension.
+    inner: Inner<L, A, I>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A tree of each supported dimension.
+enum Inner<const L: usize, A, I>
+where
+    Align<L>: Alignment,
+{
+    D1(Capt<1, L, A, I>),
+    D2(Capt<2, L, A, I>),
+    D3(Capt<3, L, A, I>),
+    D4(Capt<4, L, A, I>),
+    D5(Capt<5, L, A, I>),
+    D6(Capt<6, L, A, I>),
+    D7(Capt<7, L, A, I>),
+    D8(Capt<8, L, A, I>),
+}
+
+/// Evaluate `$body` with `$t` bound to the inner tree and `K` to its dimension.
+macro_rules! dispatch {
+    ($inner: expr, $t: ident => $body: expr) => {
+        match $inner {
+            Inner::D1($t) => {
+                #[allow(dead_code)]
+                const K: usize = 1;
+                $body
+            }
+            Inner::D2($t) => {
+                #[allow(dead_code)]
+                const K: usize = 2;
+                $body
+            }
+            Inner::D3($t) => {
+                #[allow(dead_code)]
+                const K: usize = 3;
+                $body
+            }
+            Inner::D4($t) => {
+                #[allow(dead_code)]
+                const K: usize = 4;
+                $body
+            }
+            Inner::D5($t) => {
+                #[allow(dead_code)]
+                const K: usize = 5;
+                $body
+            }
+            Inner::D6($t) => {
+                #[allow(dead_code)]
+                const K: usize = 6;
+                $body
+            }
+            Inner::D7($t) => {
+                #[allow(dead_code)]
+                const K: usize = 7;
+                $body
+            }
+            Inner::D8($t) => {
+                #[allow(dead_code)]
+                const K: usize = 8;
+                $body
+            }
+        }
+    };
+}
+
+impl<A, I, const L: usize> DynCapt<L, A, I>
+where
+    A: Axis,
+    I: Index,
+    Align<L>: Alignment,
+{
+    /// The largest dimension supported by this tree.
+    pub const MAX_DIM: usize = 8;
+
+    #[must_use]
+    /// Construct a new tree from points of dimension `dim` stored contiguously in `flat`.
+    ///
+    /// The `i`-th point's coordinates are `flat[i * dim..][..dim]`.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `dim` is zero or greater than [`DynCapt::MAX_DIM`], if the
+    /// length of `flat` is not a multiple of `dim`, or under the same conditions as [`Capt::new`].
+    pub fn new(flat: &[A], dim: usize, r_range: (A, A)) -> Self {
+        Self::try_new(flat, dim, r_range)
+            .expect("index type I must be able to support all points in CAPT during construction")
+    }
+
+    /// Construct a new tree from points of dimension `dim` stored contiguously in `flat`,
+    /// checking for index overflow.
+    ///
+    /// See [`DynCapt::new`] for further details.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error under the same conditions as [`Capt::try_new`].
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `dim` is zero or greater than [`DynCapt::MAX_DIM`], or if the
+    /// length of `flat` is not a multiple of `dim`.
+    pub fn try_new(flat: &[A], dim: usize, r_range: (A, A)) -> Result<Self, NewCaptError> {
+        assert!(
+            (1..=Self::MAX_DIM).contains(&dim),
+            "dimension {dim} is not supported"
+        );
+        assert_eq!(
+            flat.len() % dim,
+            0,
+            "length of point buffer must be a multiple of the dimension"
+        );
+        let inner = match dim {
+            1 => Inner::D1(Capt::try_from_flat(flat, dim, 0, r_range)?),
+            2 => Inner::D2(Capt::try_from_flat(flat, dim, 0, r_range)?),
+            3 => Inner::D3(Capt::try_from_flat(flat, dim, 0, r_range)?),
+            4 => Inner::D4(Capt::try_from_flat(flat, dim, 0, r_range)?),
+            5 => Inner::D5(Capt::try_from_flat(flat, dim, 0, r_range)?),
+            6 => Inner::D6(Capt::try_from_flat(flat, dim, 0, r_range)?),
+            7 => Inner::D7(Capt::try_from_flat(flat, dim, 0, r_range)?),
+            _ => Inner::D8(Capt::try_from_flat(flat, dim, 0, r_range)?),
+        };
+        Ok(Self { inner })
+    }
+
+    #[must_use]
+    /// Get the dimension of the points in this tree.
+    pub const fn dim(&self) -> usize {
+        dispatch!(&self.inner, _t => K)
+    }
+
+    #[must_use]
+    /// Get the number of points in this tree.
+    ///
+    /// See [`Capt::len`] for further details.
+    pub fn len(&self) -> usize {
+        dispatch!(&self.inner, t => t.len())
+    }
+
+    #[must_use]
+    /// Determine whether this tree contains no points.
+    pub fn is_empty(&self) -> bool {
+        dispatch!(&self.inner, t => t.is_empty())
+    }
+
+    #[must_use]
+    /// Get the number of tests on the path from the root of this tree to any leaf.
+    pub const fn depth(&self) -> u32 {
+        dispatch!(&self.inner, t => t.depth())
+    }
+
+    #[must_use]
+    /// Get the `(minimum, maximum)` range of query radii which this tree was built to support.
+    pub const fn r_range(&self) -> (A, A) {
+        dispatch!(&self.inner, t => t.r_range())
+    }
+
+    #[must_use]
+    /// Determine whether a point in this tree is within a distance of `radius` to `center`.
+    ///
+    /// As with [`Capt::collides`], the result is only guaranteed to be correct if `radius` is
+    /// inside the radius range used to construct this tree.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the length of `center` is not the dimension of this tree.
+    pub fn collides(&self, center: &[A], radius: A) -> bool {
+        dispatch!(&self.inner, t => {
+            let center: &[A; K] = center
+                .try_into()
+                .expect("query center must have the same dimension as the tree");
+            t.collides(center, radius)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = thread_rng();
+        let (r_min, r_max) = (0.05, 0.3);
+        for dim in 1..=DynCapt::<8>::MAX_DIM {
+            let flat: Vec<f32> = (0..200 * dim).map(|_| rng.gen()).collect();
+            let t = DynCapt::<8>::new(&flat, dim, (r_min, r_max));
+            assert_eq!(t.dim(), dim);
+
+            for _ in 0..200 {
+                let center: Vec<f32> = (0..dim).map(|_| rng.gen()).collect();
+                let r = rng.gen_range(r_min..=r_max);
+                let collides = flat.chunks(dim).any(|p| {
+                    p.iter()
+                        .zip(&center)
+                        .map(|(a, b)| (a - b).powi(2))
+                        .sum::<f32>()
+                        <= r * r
+                });
+                assert_eq!(collides, t.collides(&center, r));
+            }
+        }
+    }
+}
@@ -0,0 +1,694 @@
+//! Construction of tree files for point clouds which do not fit in memory.
+//!
+//! [`Capt::build_file`] writes a tree in the format of [`file`](crate::file) without ever holding
+//! the whole point cloud or the whole tree in memory.
+//! Points are spilled to temporary files, which are split about their medians in repeated passes
+//! until each subtree is small enough to build in memory; the affordance buffers of each leaf are
+//! then appended to more temporary files, which are stitched together into the final file.
+
+use std::{
+    array, fmt,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use elain::{Align, Alignment};
+
+use crate::{
+    afford_help, distsq,
+    file::{crc32_update, write_header, FileElement},
+    pack_lanes, partition, Aabb, Axis, Capt, Index, NewCaptError,
+};
+
+#[derive(Debug)]
+#[non_exhaustive]
+/// The errors which can occur when building a tree file with [`Capt::build_file`].
+pub enum BuildFileError {
+    /// Reading or writing a temporary file or the output failed.
+    Io(io::Error),
+    /// The points could not be made into a tree, for the same reasons as in [`Capt::try_new`].
+    Build(NewCaptError),
+}
+
+impl From<io::Error> for BuildFileError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<NewCaptError> for BuildFileError {
+    fn from(e: NewCaptError) -> Self {
+        Self::Build(e)
+    }
+}
+
+impl fmt::Display for BuildFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to write tree: {e}"),
+            Self::Build(e) => write!(f, "failed to build tree: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for BuildFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Build(e) => Some(e),
+        }
+    }
+}
+
+impl<A, I, const K: usize, const L: usize> Capt<K, L, A, I>
+where
+    A: Axis + FileElement,
+    I: Index + FileElement,
+    Align<L>: Alignment,
+{
+    /// Build a tree containing all the points in `points` and write it to `w` in the format of
+    /// [`file`](crate::file), holding at most about `max_points` points in memory at once.
+    ///
+    /// Points are spilled to temporary files in the directory `spill_dir`, which must already
+    /// exist and have room for a few copies of the point cloud and the finished tree.
+    /// Every temporary file is removed before returning, even on failure.
+    ///
+    /// The written tree answers every query exactly as [`Capt::new`] would for the same points,
+    /// and can be loaded with [`Capt::load`] or [`Capt::read_from`].
+    /// It has the same split planes, but points in its affordance buffers may be ordered
+    /// differently.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a temporary file cannot be created, read or written,
+    /// if writing to `w` fails, or under the same conditions as [`Capt::try_new`].
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `max_points` is less than 2.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use captree::Capt;
+    ///
+    /// let points = (0..1000).map(|i| [i as f32, 0.0]);
+    /// let mut bytes = Vec::new();
+    /// Capt::<2>::build_file(points, (0.0, 0.5), std::env::temp_dir(), 64, &mut bytes).unwrap();
+    ///
+    /// let capt = Capt::<2>::read_from(&bytes[..]).unwrap();
+    /// assert!(capt.collides(&[500.2, 0.0], 0.3));
+    /// assert!(!capt.collides(&[500.5, 0.4], 0.3));
+    /// ```
+    pub fn build_file(
+        points: impl IntoIterator<Item = [A; K]>,
+        r_range: (A, A),
+        spill_dir: impl AsRef<Path>,
+        max_points: usize,
+        mut w: impl Write,
+    ) -> Result<(), BuildFileError> {
+        assert!(max_points >= 2, "at least 2 points must fit in memory");
+        let dir = spill_dir.as_ref();
+
+        let mut spilled = Spill::create(dir)?;
+        for p in points {
+            if p.iter().any(|x| !x.is_finite()) {
+                return Err(NewCaptError::NonFinite.into());
+            }
+            spilled.push(p)?;
+        }
+        // pad with infinity to make it a power of 2, as in memory
+        let n2 = spilled.len.next_power_of_two();
+        for _ in spilled.len..n2 {
+            spilled.push([A::INFINITY; K])?;
+        }
+
+        let tests = TempFile::create(dir)?;
+        tests.file.set_len((n2 - 1) as u64 * A::SIZE as u64)?;
+        let mut builder = Builder {
+            dir,
+            max_points,
+            r_range,
+            tests,
+            aabbs: Spill::create(dir)?,
+            starts: Spill::create(dir)?,
+            lanes: (0..K)
+                .map(|_| Spill::<A, L>::create(dir))
+                .collect::<io::Result<Vec<_>>>()?
+                .try_into()
+                .unwrap_or_else(|_| unreachable!("there is one spill of lanes per dimension")),
+            n_lanes: 0,
+        };
+        builder.starts.push([I::ZERO])?;
+        let halo = Spill::create(dir)?;
+        builder.node(0, spilled, halo, Aabb::ALL)?;
+
+        let Builder {
+            mut tests,
+            mut aabbs,
+            mut starts,
+            lanes,
+            n_lanes,
+            ..
+        } = builder;
+
+        let mut header = Vec::new();
+        write_header::<A, I, K, L>(&mut header, r_range, [n2 - 1, 0, n2 + 1, n_lanes]);
+        let mut out = Checksummed {
+            inner: &mut w,
+            crc: !0,
+        };
+        out.write_all(&header)?;
+        tests.file.seek(SeekFrom::Start(0))?;
+        io::copy(&mut BufReader::new(&tests.file), &mut out)?;
+        aabbs.copy_to(&mut out)?;
+        starts.copy_to(&mut out)?;
+        for mut lanes in lanes {
+            lanes.copy_to(&mut out)?;
+        }
+
+        let mut checksum = Vec::new();
+        (!out.crc).write_le(&mut checksum);
+        w.write_all(&checksum)?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+/// The state of an out-of-core construction.
+struct Builder<'a, A, I, const K: usize, const L: usize> {
+    /// The directory to spill temporary files to.
+    dir: &'a Path,
+    /// The number of points which may be held in memory at once.
+    max_points: usize,
+    /// The range of query radii.
+    r_range: (A, A),
+    /// The tests of the tree, in heap order, which are written as their subtrees are split.
+    tests: TempFile,
+    /// The bounding box of each leaf written so far, as its lower then its upper corner.
+    aabbs: Spill<A, K>,
+    /// The start of each affordance buffer written so far.
+    starts: Spill<I, 1>,
+    /// The lanes of every affordance buffer written so far, for each dimension.
+    lanes: [Spill<A, L>; K],
+    /// The number of lanes written so far.
+    n_lanes: usize,
+}
+
+impl<A, I, const K: usize, const L: usize> Builder<'_, A, I, K, L>
+where
+    A: Axis + FileElement,
+    I: Index + FileElement,
+{
+    /// Build the subtree rooted at test `i`, containing the points in `owned` and covering
+    /// `cell`, whose leaves may also afford the points in `halo`.
+    ///
+    /// `owned` must have a power-of-two length.
+    fn node(
+        &mut self,
+        i: usize,
+        mut owned: Spill<A, K>,
+        mut halo: Spill<A, K>,
+        cell: Aabb<A, K>,
+    ) -> Result<(), BuildFileError> {
+        let m = owned.len;
+        let k = (i + 1).ilog2() as usize % K;
+        if m == 1 && self.max_points < 1 + halo.len {
+            let rep = owned.load()?[0];
+            return if cell.contained_by_ball(&rep, self.r_range.0.square()) {
+                self.leaf(rep, Vec::new())
+            } else {
+                self.leaf_from(rep, &mut halo)
+            };
+        }
+        if m + halo.len <= self.max_points {
+            return self.subtree(i, owned, halo, cell);
+        }
+
+        let mid = m / 2;
+        let med_hi = self.select(&mut owned, k, mid)?;
+        let (mut n_less, mut max_less) = (0, A::NEG_INFINITY);
+        owned.for_each(|p| {
+            if p[k] < med_hi {
+                n_less += 1;
+                if max_less < p[k] {
+                    max_less = p[k];
+                }
+            }
+            Ok(())
+        })?;
+        let med_lo = if n_less < mid { med_hi } else { max_less };
+        let test = A::in_between(med_lo, med_hi);
+        self.write_test(i, &[test])?;
+
+        // the same filters as `afford_help`, applied to whole files at once
+        let (lo_vol, hi_vol) = cell.split(test, k);
+        let rsq_min = self.r_range.0.square();
+        let lo_afford = rsq_min < distsq(lo_vol.lo, lo_vol.hi);
+        let hi_afford = rsq_min < distsq(hi_vol.lo, hi_vol.hi);
+        let r_max = self.r_range.1;
+        let lo_keep = |p: &[A; K]| lo_afford && p[k].is_finite() && p[k] <= test + r_max;
+        let hi_keep = |p: &[A; K]| hi_afford && p[k].is_finite() && test - r_max <= p[k];
+
+        let mut lo_owned = Spill::create(self.dir)?;
+        let mut hi_owned = Spill::create(self.dir)?;
+        let mut lo_halo = Spill::create(self.dir)?;
+        let mut hi_halo = Spill::create(self.dir)?;
+        // tied points fill out the lower half, wherever they are
+        let mut n_tied_lo = mid - n_less.min(mid);
+        owned.for_each(|p| {
+            if p[k] < med_hi || (p[k] == med_hi && n_tied_lo > 0) {
+                if p[k] == med_hi {
+                    n_tied_lo -= 1;
+                }
+                lo_owned.push(p)?;
+                if hi_keep(&p) {
+                    hi_halo.push(p)?;
+                }
+            } else {
+                hi_owned.push(p)?;
+                if lo_keep(&p) {
+                    lo_halo.push(p)?;
+                }
+            }
+            Ok(())
+        })?;
+        halo.for_each(|p| {
+            if lo_keep(&p) {
+                lo_halo.push(p)?;
+            }
+            if hi_keep(&p) {
+                hi_halo.push(p)?;
+            }
+            Ok(())
+        })?;
+        drop((owned, halo));
+
+        self.node(2 * i + 1, lo_owned, lo_halo, lo_vol)?;
+        self.node(2 * i + 2, hi_owned, hi_halo, hi_vol)
+    }
+
+    /// Build the subtree rooted at test `i` in memory, as [`Builder::node`] does.
+    fn subtree(
+        &mut self,
+        i: usize,
+        mut owned: Spill<A, K>,
+        mut halo: Spill<A, K>,
+        cell: Aabb<A, K>,
+    ) -> Result<(), BuildFileError> {
+        let k = (i + 1).ilog2() as usize % K;
+        let mut points = owned.load()?;
+        let m = points.len();
+        let mut tests = vec![A::INFINITY; m - 1];
+        unsafe {
+            // SAFETY: Every point was checked to be finite or is padding at infinity.
+            partition(&mut points, &mut tests, k);
+        }
+        // each level of the subtree is a contiguous run of the tree's tests
+        for depth in 0..m.ilog2() {
+            let level = (1 << depth) - 1..(2 << depth) - 1;
+            self.write_test(((i + 1) << depth) - 1, &tests[level])?;
+        }
+
+        points.extend(halo.load()?);
+        drop((owned, halo));
+        afford_help(
+            &points,
+            &tests,
+            &[],
+            self.r_range,
+            &mut |z, in_range| self.leaf(points[z], in_range.iter().map(|&j| points[j])),
+            k,
+            0,
+            0..m,
+            (m..points.len()).collect(),
+            cell,
+        )
+    }
+
+    /// Write the affordance buffer of the next leaf, whose representative is `rep` and which
+    /// affords the points in `afforded`.
+    fn leaf(
+        &mut self,
+        rep: [A; K],
+        afforded: impl IntoIterator<Item = [A; K]>,
+    ) -> Result<(), BuildFileError> {
+        let aabb = if rep[0].is_finite() {
+            let (lanes, n_lanes) = (&mut self.lanes, &mut self.n_lanes);
+            pack_lanes(rep, afforded, |news| {
+                for (lanes, lane) in lanes.iter_mut().zip(news) {
+                    lanes.push(lane)?;
+                }
+                *n_lanes += 1;
+                Ok::<_, io::Error>(())
+            })?
+        } else {
+            Aabb { lo: rep, hi: rep }
+        };
+        self.aabbs.push(aabb.lo)?;
+        self.aabbs.push(aabb.hi)?;
+        let start = I::try_from(self.n_lanes).map_err(|_| NewCaptError::TooManyPoints)?;
+        self.starts.push([start])?;
+        Ok(())
+    }
+
+    /// Write the affordance buffer of the next leaf, whose representative is `rep`, streaming the
+    /// points it affords from `halo`.
+    fn leaf_from(&mut self, rep: [A; K], halo: &mut Spill<A, K>) -> Result<(), BuildFileError> {
+        let mut err = None;
+        let afforded = halo
+            .records()?
+            .map_while(|p| p.map_err(|e| err = Some(e)).ok());
+        self.leaf(rep, afforded)?;
+        err.map_or(Ok(()), |e| Err(e.into()))
+    }
+
+    /// Write `tests` as the tests of the tree starting from index `i`.
+    fn write_test(&mut self, i: usize, tests: &[A]) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(tests.len() * A::SIZE);
+        for &t in tests {
+            t.write_le(&mut bytes);
+        }
+        self.tests
+            .file
+            .seek(SeekFrom::Start(i as u64 * A::SIZE as u64))?;
+        self.tests.file.write_all(&bytes)
+    }
+
+    /// Find the value along axis `k` of the point of rank `rank` in `points`, counting from 0 in
+    /// increasing order.
+    ///
+    /// Each pass over `points` narrows the range of values which could hold the answer, by
+    /// choosing pivots near the answer from a sample of at most `max_points` values, until few
+    /// enough values remain to select from in memory.
+    fn select(&self, points: &mut Spill<A, K>, k: usize, mut rank: usize) -> io::Result<A> {
+        // exclusive bounds on the values which could be the answer
+        let (mut lo, mut hi) = (None, None);
+        loop {
+            let within = |x: A| lo.is_none_or(|lo| lo < x) && hi.is_none_or(|hi| x < hi);
+            let mut sample = Vec::new();
+            let (mut n_within, mut stride) = (0, 1);
+            points.for_each(|p| {
+                if within(p[k]) {
+                    if n_within % stride == 0 {
+                        // thin out the sample once it is full, keeping it evenly spaced
+                        if sample.len() == self.max_points {
+                            let mut j = 0;
+                            sample.retain(|_| {
+                                j += 1;
+                                j % 2 == 1
+                            });
+                            stride *= 2;
+                        }
+                        if n_within % stride == 0 {
+                            sample.push(p[k]);
+                        }
+                    }
+                    n_within += 1;
+                }
+                Ok(())
+            })?;
+
+            let cmp = |a: &A, b: &A| a.partial_cmp(b).unwrap();
+            if stride == 1 {
+                return Ok(*sample.select_nth_unstable_by(rank, cmp).1);
+            }
+            sample.sort_unstable_by(cmp);
+            let pos = rank * sample.len() / n_within;
+            let pivot_lo = sample[pos.saturating_sub(2)];
+            let pivot_hi = sample[(pos + 2).min(sample.len() - 1)];
+
+            let [mut n_below, mut n_lo, mut n_between, mut n_hi] = [0; 4];
+            points.for_each(|p| {
+                let x = p[k];
+                if within(x) {
+                    if x < pivot_lo {
+                        n_below += 1;
+                    } else if x == pivot_lo {
+                        n_lo += 1;
+                    } else if x < pivot_hi {
+                        n_between += 1;
+                    } else if x == pivot_hi {
+                        n_hi += 1;
+                    }
+                }
+                Ok(())
+            })?;
+
+            if rank < n_below {
+                hi = Some(pivot_lo);
+            } else if rank < n_below + n_lo {
+                return Ok(pivot_lo);
+            } else if rank < n_below + n_lo + n_between {
+                rank -= n_below + n_lo;
+                (lo, hi) = (Some(pivot_lo), Some(pivot_hi));
+            } else if rank < n_below + n_lo + n_between + n_hi {
+                return Ok(pivot_hi);
+            } else {
+                rank -= n_below + n_lo + n_between + n_hi;
+                lo = Some(pivot_hi);
+            }
+        }
+    }
+}
+
+/// A temporary file in a spill directory, which is removed when dropped.
+struct TempFile {
+    /// The path to the file.
+    path: PathBuf,
+    /// The open file, for reading and writing.
+    file: File,
+}
+
+impl TempFile {
+    /// Create a new, empty temporary file in `dir`.
+    fn create(dir: &Path) -> io::Result<Self> {
+        /// The number of temporary files created by this process, which makes their names unique.
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let id = COUNT.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("captree-{}-{id}.spill", process::id()));
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(Self { path, file })
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A sequence of records of `N` values each, spilled to a temporary file.
+struct Spill<T, const N: usize> {
+    /// The file holding the records.
+    file: TempFile,
+    /// A buffered writer appending to `file`.
+    writer: BufWriter<File>,
+    /// The number of records written.
+    len: usize,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: FileElement + Copy, const N: usize> Spill<T, N> {
+    /// Create an empty spill in `dir`.
+    fn create(dir: &Path) -> io::Result<Self> {
+        let file = TempFile::create(dir)?;
+        let writer = BufWriter::new(file.file.try_clone()?);
+        Ok(Self {
+            file,
+            writer,
+            len: 0,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Append a record.
+    fn push(&mut self, record: [T; N]) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(N * T::SIZE);
+        for x in record {
+            x.write_le(&mut bytes);
+        }
+        self.len += 1;
+        self.writer.write_all(&bytes)
+    }
+
+    /// Iterate over every record, in the order they were written.
+    fn records(&mut self) -> io::Result<impl Iterator<Item = io::Result<[T; N]>>> {
+        let mut reader = self.reader()?;
+        let mut bytes = vec![0; N * T::SIZE];
+        let len = self.len;
+        Ok((0..len).map(move |_| {
+            reader.read_exact(&mut bytes)?;
+            let mut values = bytes.chunks_exact(T::SIZE).map(T::read_le);
+            let record = array::from_fn(|_| values.next().flatten());
+            if record.iter().any(Option::is_none) {
+                return Err(io::ErrorKind::InvalidData.into());
+            }
+            Ok(record.map(Option::unwrap))
+        }))
+    }
+
+    /// Call `f` on every record, in the order they were written.
+    fn for_each(&mut self, mut f: impl FnMut([T; N]) -> io::Result<()>) -> io::Result<()> {
+        self.records()?.try_for_each(|record| f(record?))
+    }
+
+    /// Read every record into memory.
+    fn load(&mut self) -> io::Result<Vec<[T; N]>> {
+        self.records()?.collect()
+    }
+
+    /// Copy the bytes of every record to `w`.
+    fn copy_to(&mut self, w: &mut impl Write) -> io::Result<()> {
+        io::copy(&mut self.reader()?, w)?;
+        Ok(())
+    }
+
+    /// Flush every record to the file and open a reader from its start.
+    fn reader(&mut self) -> io::Result<BufReader<File>> {
+        self.writer.flush()?;
+        // a separate handle, so that reading never moves the position of the writer
+        Ok(BufReader::new(File::open(&self.file.path)?))
+    }
+}
+
+/// A writer which computes the CRC-32 of everything written through it.
+struct Checksummed<W> {
+    /// The writer to pass bytes on to.
+    inner: W,
+    /// The state of the CRC-32 computation.
+    crc: u32,
+}
+
+impl<W: Write> Write for Checksummed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.crc = crc32_update(self.crc, &buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{seq::SliceRandom, thread_rng, Rng};
+
+    use super::*;
+
+    /// Make an empty spill directory unique to `name`.
+    fn spill_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("captree-{name}-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Build a tree file in `dir` and load it back.
+    fn build<const K: usize>(
+        points: &[[f32; K]],
+        r_range: (f32, f32),
+        dir: &Path,
+        max_points: usize,
+    ) -> Capt<K> {
+        let mut bytes = Vec::new();
+        Capt::<K>::build_file(points.iter().copied(), r_range, dir, max_points, &mut bytes)
+            .unwrap();
+        Capt::read_from(&bytes[..]).unwrap()
+    }
+
+    #[test]
+    fn matches_in_memory() {
+        let mut rng = thread_rng();
+        // distinct coordinates, so that every split is the same as in memory
+        let mut axes: [Vec<usize>; 3] = array::from_fn(|_| (0..1000).collect());
+        for axis in &mut axes {
+            axis.shuffle(&mut rng);
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|i| array::from_fn(|k| axes[k][i] as f32 / 1000.0))
+            .collect();
+        let r_range = (0.01, 0.08);
+        let expected = Capt::<3>::new(&points, r_range);
+        let dir = spill_dir("matches-in-memory");
+
+        for max_points in [2, 16, 100, 5000] {
+            let t = build(&points, r_range, &dir, max_points);
+            assert_eq!(t.validate(), Ok(()));
+            assert_eq!(t.tests, expected.tests);
+            assert_eq!(t.aabbs, expected.aabbs);
+            assert_eq!(t.len(), expected.len());
+
+            for _ in 0..1000 {
+                let center = [rng.gen(), rng.gen(), rng.gen()];
+                let r = rng.gen_range(r_range.0..=r_range.1);
+                assert_eq!(t.collides(&center, r), expected.collides(&center, r));
+            }
+        }
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn handles_ties() {
+        #[allow(clippy::cast_precision_loss)]
+        let points: Vec<[f32; 2]> = (0..300)
+            .map(|i| [(i % 4) as f32 * 0.1, (i / 4 % 4) as f32 * 0.1])
+            .collect();
+        let r_range = (0.0, 0.15);
+        let dir = spill_dir("handles-ties");
+
+        for max_points in [2, 8, 64] {
+            let t = build(&points, r_range, &dir, max_points);
+            assert_eq!(t.validate(), Ok(()));
+            assert_eq!(t.len(), points.len());
+
+            let mut rng = thread_rng();
+            for _ in 0..1000 {
+                let center = [rng.gen_range(-0.1..0.4), rng.gen_range(-0.1..0.4)];
+                let r = rng.gen_range(r_range.0..=r_range.1);
+                let rsq = r * r;
+                let hit = points.iter().any(|&p| distsq(p, center) <= rsq);
+                assert_eq!(t.collides(&center, r), hit);
+            }
+        }
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn removes_spills_on_error() {
+        let dir = spill_dir("removes-spills");
+        let points = [[0.0, 1.0], [f32::NAN, 0.0]];
+        let result = Capt::<2>::build_file(points, (0.0, 0.1), &dir, 16, io::sink());
+
+        assert!(matches!(
+            result,
+            Err(BuildFileError::Build(NewCaptError::NonFinite))
+        ));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn empty_cloud() {
+        let dir = spill_dir("empty-cloud");
+        let t = build::<2>(&[], (0.0, 0.1), &dir, 16);
+
+        assert_eq!(t, Capt::<2>::new(&[], (0.0, 0.1)));
+        fs::remove_dir(&dir).unwrap();
+    }
+}
@@ -0,0 +1,606 @@
+//! A versioned, portable binary file format for trees.
+//!
+//! A file consists of a header, the buffers of the tree, and a checksum.
+//! All integers and floating-point values are stored in little-endian order.
+//!
+//! | Field              | Encoding                                             |
+//! | ------------------ | ---------------------------------------------------- |
+//! | magic number       | the bytes `CAPT`                                     |
+//! | format version     | `u16`                                                |
+//! | dimension `K`      | `u32`                                                |
+//! | lane width `L`     | `u32`                                                |
+//! | axis type          | `u8` tag (see [`FileElement::TAG`])                  |
+//! | index type         | `u8` tag                                             |
+//! | radius range       | two axis values: the minimum then maximum radius     |
+//! | buffer lengths     | `u64` each, for tests, split axes, starts, and lanes |
+//! | tests              | one axis value per test                              |
+//! | split axes         | one `u8` per test, or none if axes cycle with depth  |
+//! | bounding boxes     | `K` lower bounds then `K` upper bounds per leaf      |
+//! | starts             | one index per start                                  |
+//! | afforded points    | for each dimension, `L` axis values per lane         |
+//! | checksum           | `u32` CRC-32 of every preceding byte                 |
+//!
+//! Version 2 of the format added the radius range, and version 3 added the split axes.
+//! Files of version 2 have neither the length of the split axes nor the split axes themselves,
+//! and are loaded as trees whose axes cycle with depth, as every tree written in that version did.
+//! Files of version 1 cannot be loaded, since their trees' radius ranges are unknown.
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use elain::{Align, Alignment};
+
+use crate::{Aabb, Alloc, Axis, Capt, Index, MySimd, Scratch};
+
+/// The magic number at the start of every file.
+const MAGIC: [u8; 4] = *b"CAPT";
+
+/// The current version of the file format.
+pub const FORMAT_VERSION: u16 = 3;
+
+/// The oldest version of the file format which can still be loaded.
+const MIN_FORMAT_VERSION: u16 = 2;
+
+#[derive(Debug)]
+#[non_exhaustive]
+/// The errors which can occur when loading a tree from a file.
+pub enum LoadError {
+    /// Reading the file failed.
+    Io(io::Error),
+    /// The file did not start with the expected magic number, so it is probably not a tree.
+    BadMagic,
+    /// The file was written with an unsupported version of the format.
+    UnsupportedVersion(u16),
+    /// The file's tree has a different dimension than the requested one.
+    DimensionMismatch {
+        /// The dimension of the requested tree.
+        expected: usize,
+        /// The dimension of the tree in the file.
+        found: usize,
+    },
+    /// The file's tree has a different lane width than the requested one.
+    LaneMismatch {
+        /// The lane width of the requested tree.
+        expected: usize,
+        /// The lane width of the tree in the file.
+        found: usize,
+    },
+    /// The file's tree stores a different axis or index type than the requested one.
+    TypeMismatch,
+    /// The checksum of the file did not match its contents.
+    Checksum {
+        /// The checksum stored in the file.
+        expected: u32,
+        /// The checksum of the file's contents.
+        found: u32,
+    },
+    /// The file's contents passed the checksum, but do not describe a valid tree.
+    Corrupt,
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read tree: {e}"),
+            Self::BadMagic => write!(f, "file is not a tree"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported format version {v}"),
+            Self::DimensionMismatch { expected, found } => {
+                write!(f, "expected a tree of dimension {expected}, found {found}")
+            }
+            Self::LaneMismatch { expected, found } => {
+                write!(f, "expected a tree with {expected} lanes, found {found}")
+            }
+            Self::TypeMismatch => write!(f, "tree has a different axis or index type"),
+            Self::Checksum { expected, found } => write!(
+                f,
+                "checksum mismatch: expected {expected:#010x}, found {found:#010x}"
+            ),
+            Self::Corrupt => write!(f, "tree is corrupt"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// A value which can be stored in a tree file.
+///
+/// This is implemented for all the primitive types which implement [`Axis`] or [`Index`].
+pub trait FileElement: Sized {
+    /// A tag identifying this type in a file header.
+    const TAG: u8;
+    /// The number of bytes used to store this value.
+    const SIZE: usize;
+
+    /// Append this value to `out` in little-endian order.
+    fn write_le(self, out: &mut Vec<u8>);
+
+    /// Read a value from its little-endian representation in `bytes`, which has length
+    /// [`FileElement::SIZE`].
+    /// Returns `None` if the bytes do not represent a value of this type.
+    fn read_le(bytes: &[u8]) -> Option<Self>;
+}
+
+/// Implement [`FileElement`] for a primitive type.
+macro_rules! impl_file_element {
+    ($t: ty, $tag: literal) => {
+        impl FileElement for $t {
+            const TAG: u8 = $tag;
+            const SIZE: usize = size_of::<$t>();
+
+            fn write_le(self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn read_le(bytes: &[u8]) -> Option<Self> {
+                Some(Self::from_le_bytes(bytes.try_into().ok()?))
+            }
+        }
+    };
+}
+
+impl_file_element!(f32, 0);
+impl_file_element!(f64, 1);
+impl_file_element!(u8, 2);
+impl_file_element!(u16, 3);
+impl_file_element!(u32, 4);
+impl_file_element!(u64, 5);
+
+#[allow(clippy::use_self)]
+impl FileElement for usize {
+    // stored as a `u64` so that files are portable across pointer widths
+    const TAG: u8 = u64::TAG;
+    const SIZE: usize = u64::SIZE;
+
+    fn write_le(self, out: &mut Vec<u8>) {
+        (self as u64).write_le(out);
+    }
+
+    fn read_le(bytes: &[u8]) -> Option<Self> {
+        u64::read_le(bytes)?.try_into().ok()
+    }
+}
+
+/// The lookup table for computing CRC-32 checksums.
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        #[allow(clippy::cast_possible_truncation)]
+        let mut c = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            c = if c & 1 == 0 {
+                c >> 1
+            } else {
+                0xedb8_8320 ^ (c >> 1)
+            };
+            j += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+
+/// Compute the CRC-32 (as used by zlib and PNG) of `bytes`.
+fn crc32(bytes: &[u8]) -> u32 {
+    !crc32_update(!0, bytes)
+}
+
+/// Continue a CRC-32 computation in state `crc` over `bytes`.
+///
+/// The state starts at `!0`, and the checksum is the complement of the final state.
+pub(crate) fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+    bytes.iter().fold(crc, |c, &b| {
+        CRC_TABLE[((c ^ u32::from(b)) & 0xff) as usize] ^ (c >> 8)
+    })
+}
+
+/// Append the header of a file for a tree with radius range `r_range` and the given buffer
+/// lengths to `out`.
+pub(crate) fn write_header<A, I, const K: usize, const L: usize>(
+    out: &mut Vec<u8>,
+    r_range: (A, A),
+    [n_tests, n_dims, n_starts, n_lanes]: [usize; 4],
+) where
+    A: FileElement,
+    I: FileElement,
+{
+    out.extend_from_slice(&MAGIC);
+    FORMAT_VERSION.write_le(out);
+    #[allow(clippy::cast_possible_truncation)]
+    {
+        (K as u32).write_le(out);
+        (L as u32).write_le(out);
+    }
+    A::TAG.write_le(out);
+    I::TAG.write_le(out);
+    r_range.0.write_le(out);
+    r_range.1.write_le(out);
+
+    n_tests.write_le(out);
+    n_dims.write_le(out);
+    n_starts.write_le(out);
+    n_lanes.write_le(out);
+}
+
+/// A cursor over the bytes of a file being loaded.
+pub(crate) struct Reader<'a>(pub(crate) &'a [u8]);
+
+impl Reader<'_> {
+    /// Read a single element, failing if the file is too short or the element is invalid.
+    pub(crate) fn read<T: FileElement>(&mut self) -> Result<T, LoadError> {
+        if self.0.len() < T::SIZE {
+            return Err(LoadError::Corrupt);
+        }
+        let (head, tail) = self.0.split_at(T::SIZE);
+        self.0 = tail;
+        T::read_le(head).ok_or(LoadError::Corrupt)
+    }
+
+    /// Read a length, failing if it cannot possibly fit in the rest of the file.
+    fn read_len(&mut self, elem_size: usize) -> Result<usize, LoadError> {
+        let len: usize = self.read()?;
+        if len.saturating_mul(elem_size) > self.0.len() {
+            return Err(LoadError::Corrupt);
+        }
+        Ok(len)
+    }
+
+    /// Read `n` elements.
+    fn read_n<T: FileElement>(&mut self, n: usize) -> Result<Vec<T>, LoadError> {
+        (0..n).map(|_| self.read()).collect()
+    }
+}
+
+impl<A, I, Al, const K: usize, const L: usize> Capt<K, L, A, I, Al>
+where
+    A: Axis + FileElement,
+    I: Index + FileElement,
+    Al: Alloc,
+    Align<L>: Alignment,
+{
+    /// Write this tree to the file at `path` in the portable binary format described in
+    /// [`file`](crate::file).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be created or written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use captree::Capt;
+    ///
+    /// let path = std::env::temp_dir().join("save_example.capt");
+    /// let t = Capt::<2>::new(&[[0.0, 0.1], [0.4, -0.2]], (0.0, 0.2));
+    /// t.save(&path).unwrap();
+    ///
+    /// assert_eq!(Capt::<2>::load(&path).unwrap(), t);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.write_to(BufWriter::new(File::create(path)?))
+    }
+
+    /// Write this tree to `w` in the portable binary format described in
+    /// [`file`](crate::file).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing to `w` fails.
+    pub fn write_to(&self, mut w: impl Write) -> io::Result<()> {
+        let mut out = Vec::new();
+        write_header::<A, I, K, L>(
+            &mut out,
+            self.r_range,
+            [
+                self.tests.len(),
+                self.split_dims.len(),
+                self.starts.len(),
+                self.afforded[0].len(),
+            ],
+        );
+
+        for &t in self.tests.iter() {
+            t.write_le(&mut out);
+        }
+        for &d in self.split_dims.iter() {
+            d.write_le(&mut out);
+        }
+        for aabb in self.aabbs.iter() {
+            for &x in aabb.lo.iter().chain(&aabb.hi) {
+                x.write_le(&mut out);
+            }
+        }
+        for &s in self.starts.iter() {
+            s.write_le(&mut out);
+        }
+        for ak in &self.afforded {
+            for lane in ak.iter() {
+                for &x in &lane.data {
+                    x.write_le(&mut out);
+                }
+            }
+        }
+
+        crc32(&out).write_le(&mut out);
+        w.write_all(&out)?;
+        w.flush()
+    }
+}
+
+impl<A, I, const K: usize, const L: usize> Capt<K, L, A, I>
+where
+    A: Axis + FileElement,
+    I: Index + FileElement,
+    Align<L>: Alignment,
+{
+    /// Load a tree from the file at `path`, which was written by [`Capt::save`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be read, if it was written for a tree
+    /// with different parameters, or if it is corrupt.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Read a tree from `r`, which contains a tree written by [`Capt::write_to`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error under the same conditions as [`Capt::load`].
+    pub fn read_from(mut r: impl Read) -> Result<Self, LoadError> {
+        let mut bytes = Vec::new();
+        r.read_to_end(&mut bytes)?;
+
+        if bytes.get(..MAGIC.len()) != Some(&MAGIC) {
+            return Err(LoadError::BadMagic);
+        }
+        let mut reader = Reader(&bytes[MAGIC.len()..]);
+        let version: u16 = reader.read()?;
+        if !(MIN_FORMAT_VERSION..=FORMAT_VERSION).contains(&version) {
+            return Err(LoadError::UnsupportedVersion(version));
+        }
+
+        let Some(body_len) = bytes.len().checked_sub(u32::SIZE) else {
+            return Err(LoadError::Corrupt);
+        };
+        let (body, checksum) = bytes.split_at(body_len);
+        let expected = u32::read_le(checksum).ok_or(LoadError::Corrupt)?;
+        let found = crc32(body);
+        if expected != found {
+            return Err(LoadError::Checksum { expected, found });
+        }
+        let mut reader = Reader(&body[MAGIC.len() + u16::SIZE..]);
+
+        let k = reader.read::<u32>()? as usize;
+        if k != K {
+            return Err(LoadError::DimensionMismatch {
+                expected: K,
+                found: k,
+            });
+        }
+        let l = reader.read::<u32>()? as usize;
+        if l != L {
+            return Err(LoadError::LaneMismatch {
+                expected: L,
+                found: l,
+            });
+        }
+        if reader.read::<u8>()? != A::TAG || reader.read::<u8>()? != I::TAG {
+            return Err(LoadError::TypeMismatch);
+        }
+        let r_range = (reader.read()?, reader.read()?);
+
+        let n_tests = reader.read_len(A::SIZE)?;
+        // version 2 predates split axes, so its trees always cycle through axes with depth
+        let n_dims = if version >= 3 {
+            reader.read_len(u8::SIZE)?
+        } else {
+            0
+        };
+        let n_starts = reader.read_len(I::SIZE)?;
+        let n_lanes = reader.read_len(A::SIZE)?;
+
+        // the tree must be a complete binary tree
+        let n2 = n_tests + 1;
+        if !n2.is_power_of_two() || n_starts != n2 + 1 || (n_dims != 0 && n_dims != n_tests) {
+            return Err(LoadError::Corrupt);
+        }
+
+        let tests = reader.read_n(n_tests)?;
+        let split_dims: Vec<u8> = reader.read_n(n_dims)?;
+        if split_dims.iter().any(|&d| usize::from(d) >= K) {
+            return Err(LoadError::Corrupt);
+        }
+        let aabbs = (0..n2)
+            .map(|_| {
+                let lo = reader.read_n(K)?;
+                let hi = reader.read_n(K)?;
+                Ok(Aabb {
+                    lo: lo.try_into().map_err(|_| LoadError::Corrupt)?,
+                    hi: hi.try_into().map_err(|_| LoadError::Corrupt)?,
+                })
+            })
+            .collect::<Result<_, LoadError>>()?;
+        let starts: Vec<I> = reader.read_n(n_starts)?;
+        check_starts(&starts, n_lanes)?;
+
+        let mut afforded = Vec::with_capacity(K);
+        for _ in 0..K {
+            afforded.push(
+                (0..n_lanes)
+                    .map(|_| {
+                        Ok(MySimd {
+                            data: reader
+                                .read_n(L)?
+                                .try_into()
+                                .map_err(|_| LoadError::Corrupt)?,
+                            _align: Align::NEW,
+                        })
+                    })
+                    .collect::<Result<_, LoadError>>()?,
+            );
+        }
+
+        if !reader.0.is_empty() {
+            return Err(LoadError::Corrupt);
+        }
+
+        Ok(Self {
+            tests: tests.into(),
+            split_dims: split_dims.into(),
+            aabbs,
+            starts: starts.into(),
+            afforded: afforded.try_into().map_err(|_| LoadError::Corrupt)?,
+            r_range,
+            scratch: Scratch::default(),
+        })
+    }
+}
+
+/// Check that `starts` is a valid list of affordance buffer starts for a tree with `n_lanes`
+/// lanes per dimension: nondecreasing, and ending at `n_lanes`.
+///
+/// Queries skip bounds checks, so every affordance buffer must be in bounds.
+fn check_starts<I: Index>(starts: &[I], n_lanes: usize) -> Result<(), LoadError> {
+    let mut prev = 0;
+    for &s in starts {
+        let s: usize = s.try_into().map_err(|_| LoadError::Corrupt)?;
+        if s < prev || n_lanes < s {
+            return Err(LoadError::Corrupt);
+        }
+        prev = s;
+    }
+    if prev == n_lanes {
+        Ok(())
+    } else {
+        Err(LoadError::Corrupt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+
+    fn to_bytes<const K: usize, const L: usize, A, I>(t: &Capt<K, L, A, I>) -> Vec<u8>
+    where
+        A: Axis + FileElement,
+        I: Index + FileElement,
+        Align<L>: Alignment,
+    {
+        let mut bytes = Vec::new();
+        t.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn crc_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut rng = thread_rng();
+        let points: Vec<[f64; 3]> = (0..200)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let t = Capt::<3, 4, f64, u16>::new(&points, (0.01, 0.1));
+        let bytes = to_bytes(&t);
+
+        assert_eq!(Capt::read_from(bytes.as_slice()).unwrap(), t);
+    }
+
+    #[test]
+    fn typed_errors() {
+        let t = Capt::<2>::new(&[[0.0, 0.1], [0.4, -0.2]], (0.0, 0.2));
+        let bytes = to_bytes(&t);
+
+        assert!(matches!(
+            Capt::<3>::read_from(bytes.as_slice()),
+            Err(LoadError::DimensionMismatch {
+                expected: 3,
+                found: 2
+            })
+        ));
+        assert!(matches!(
+            Capt::<2, 4>::read_from(bytes.as_slice()),
+            Err(LoadError::LaneMismatch {
+                expected: 4,
+                found: 8
+            })
+        ));
+        assert!(matches!(
+            Capt::<2, 8, f64>::read_from(bytes.as_slice()),
+            Err(LoadError::TypeMismatch)
+        ));
+        assert!(matches!(
+            Capt::<2, 8, f32, u32>::read_from(bytes.as_slice()),
+            Err(LoadError::TypeMismatch)
+        ));
+
+        let mut flipped = bytes.clone();
+        flipped[40] ^= 1;
+        assert!(matches!(
+            Capt::<2>::read_from(flipped.as_slice()),
+            Err(LoadError::Checksum { .. })
+        ));
+
+        let mut bad_version = bytes;
+        bad_version[4] = 99;
+        assert!(matches!(
+            Capt::<2>::read_from(bad_version.as_slice()),
+            Err(LoadError::UnsupportedVersion(99))
+        ));
+
+        assert!(matches!(
+            Capt::<2>::read_from(&b"nope"[..]),
+            Err(LoadError::BadMagic)
+        ));
+    }
+
+    #[test]
+    fn loads_version_2() {
+        // `Capt::<2, 2, f32, u32>::new(&[[0.0, 0.0], [1.0, 0.5]], (0.0, 0.2))`, as written by
+        // version 2 of the format
+        let bytes: [u8; 132] = [
+            67, 65, 80, 84, 2, 0, 2, 0, 0, 0, 2, 0, 0, 0, 0, 4, 0, 0, 0, 0, 205, 204, 76, 62, 1, 0,
+            0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 63, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 128, 63, 0, 0, 0, 63, 0, 0, 128, 63, 0, 0,
+            0, 63, 0, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 128, 127, 0, 0, 128, 63,
+            0, 0, 128, 127, 0, 0, 0, 0, 0, 0, 128, 127, 0, 0, 0, 63, 0, 0, 128, 127, 245, 254, 122,
+            149,
+        ];
+        let expected = Capt::<2, 2, f32, u32>::new(&[[0.0, 0.0], [1.0, 0.5]], (0.0, 0.2));
+
+        let t = Capt::<2, 2, f32, u32>::read_from(bytes.as_slice()).unwrap();
+        assert_eq!(t, expected);
+        assert_eq!(t.validate(), Ok(()));
+
+        // saving the tree again upgrades it to the current version
+        let upgraded = to_bytes(&t);
+        assert_eq!(upgraded[4..6], FORMAT_VERSION.to_le_bytes());
+        assert_eq!(Capt::read_from(upgraded.as_slice()).unwrap(), expected);
+    }
+}
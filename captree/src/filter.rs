@@ -0,0 +1,351 @@
+//! Downsampling of point clouds before they are used to build a tree.
+//!
+//! Dense sensor clouds contain far more points than are needed for conservative collision
+//! checking, and every extra point makes construction slower and affordance buffers larger.
+//! The filters in this module thin out a cloud while keeping every removed point close to a point
+//! which remains.
+
+use std::{collections::HashSet, ops::BitOr};
+
+use crate::PkdTree;
+
+/// The orderings of the axes used by each pass of [`morton_filter`].
+pub const PERMUTATIONS_3D: [[u8; 3]; 6] = [
+    [0, 1, 2],
+    [0, 2, 1],
+    [1, 0, 2],
+    [1, 2, 0],
+    [2, 0, 1],
+    [2, 1, 0],
+];
+
+/// Filter out `points` such that points within `min_separation` of each other may be removed.
+///
+/// This sorts the points along a Morton (Z-order) curve and removes each point which is within
+/// `min_separation` of the last point kept before it on the curve.
+/// This is repeated once for each ordering of the axes in [`PERMUTATIONS_3D`], since a single
+/// curve leaves many nearby points far apart in its order.
+///
+/// # Guarantees
+///
+/// After filtering, `points` contains a subset of the original points (in no particular order),
+/// and every original point is within a distance of `PERMUTATIONS_3D.len() * min_separation` of
+/// some point which remains.
+/// Accordingly, a collision-checking query against the filtered cloud is conservative if its
+/// radius is inflated by that distance.
+/// In practice, removed points are nearly always within `min_separation` of a remaining point.
+///
+/// This does not guarantee that the remaining points are `min_separation` apart from one
+/// another.
+///
+/// # Examples
+///
+/// ```
+/// use captree::filter::morton_filter;
+///
+/// let mut points = vec![[0.0, 0.0, 0.0], [0.001, 0.0, 0.0], [1.0, 1.0, 1.0]];
+/// morton_filter(&mut points, 0.01);
+///
+/// assert_eq!(points.len(), 2);
+/// assert!(points.contains(&[1.0, 1.0, 1.0]));
+/// ```
+pub fn morton_filter(points: &mut Vec<[f32; 3]>, min_separation: f32) {
+    for permutation in PERMUTATIONS_3D {
+        filter_permutation(points, min_separation, permutation);
+    }
+}
+
+/// Perform a single pass of [`morton_filter`], ordering the axes of the Morton curve by `perm`.
+///
+/// Every point removed by this pass is within `min_separation` of a point which remains.
+pub fn filter_permutation(points: &mut Vec<[f32; 3]>, min_separation: f32, perm: [u8; 3]) {
+    let mut aabb_min = [f32::INFINITY; 3];
+    let mut aabb_max = [f32::NEG_INFINITY; 3];
+    let rsq = min_separation * min_separation;
+
+    for point in points.iter() {
+        for k in 0..3 {
+            if point[k] < aabb_min[k] {
+                aabb_min[k] = point[k];
+            }
+            if point[k] > aabb_max[k] {
+                aabb_max[k] = point[k];
+            }
+        }
+    }
+
+    points.sort_by_cached_key(|point| morton_index(point, &aabb_min, &aabb_max, perm));
+    let mut i = 0;
+    let mut j = 1;
+    while j < points.len() {
+        if distsq(&points[i], &points[j]) > rsq {
+            i += 1;
+            points[i] = points[j];
+        }
+        j += 1;
+    }
+    points.truncate(i + 1);
+}
+
+fn distsq<const K: usize>(a: &[f32; K], b: &[f32; K]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| (a - b).powi(2)).sum()
+}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+/// Compute the position of `point` along the Morton curve through the box from `aabb_min` to
+/// `aabb_max`, interleaving the bits of the axes in the order given by `permutation`.
+fn morton_index(
+    point: &[f32; 3],
+    aabb_min: &[f32; 3],
+    aabb_max: &[f32; 3],
+    permutation: [u8; 3],
+) -> u32 {
+    const WIDTH: u32 = u32::BITS / 3;
+    const MASK: u32 = 0b001_001_001_001_001_001_001_001_001_001;
+
+    permutation
+        .map(usize::from)
+        .into_iter()
+        .enumerate()
+        .map(|(i, k)| {
+            pdep(
+                (((point[k] - aabb_min[k]) / (aabb_max[k] - aabb_min[k]))
+                    * ((1 << WIDTH) - 1) as f32) as u32,
+                MASK << i,
+            )
+        })
+        .fold(0, BitOr::bitor)
+}
+
+#[allow(clippy::cast_possible_truncation)]
+/// Keep only the first point in each occupied voxel of a grid with side length `cell_size`.
+///
+/// The grid is aligned with the axes and has a vertex at the origin.
+/// The remaining points stay in their original order.
+/// Points which are not finite are removed.
+///
+/// # Guarantees
+///
+/// After filtering, `points` contains a subset of the original finite points, no two of which
+/// share a voxel, and every original finite point is within a distance of
+/// `cell_size * sqrt(K)` (the diagonal of a voxel) of some point which remains.
+///
+/// # Panics
+///
+/// This function will panic if `cell_size` is not positive and finite.
+///
+/// # Examples
+///
+/// ```
+/// use captree::filter::voxel_downsample;
+///
+/// let mut points = vec![[0.1, 0.1], [0.4, 0.3], [1.2, 0.1], [0.2, 0.9]];
+/// voxel_downsample(&mut points, 0.5);
+///
+/// assert_eq!(points, vec![[0.1, 0.1], [1.2, 0.1], [0.2, 0.9]]);
+/// ```
+pub fn voxel_downsample<const K: usize>(points: &mut Vec<[f32; K]>, cell_size: f32) {
+    assert!(
+        cell_size > 0.0 && cell_size.is_finite(),
+        "voxel size must be positive and finite"
+    );
+
+    let mut occupied = HashSet::new();
+    points.retain(|p| {
+        p.iter().all(|x| x.is_finite())
+            && occupied.insert(p.map(|x| (x / cell_size).floor() as i64))
+    });
+}
+
+#[allow(clippy::cast_precision_loss)]
+/// Remove every point whose mean distance to its `n_neighbors` nearest neighbors is greater than
+/// `max_mean_distance`.
+///
+/// Isolated points, such as stray lidar returns, would otherwise show up as phantom
+/// obstacles.
+/// The neighbors of each point are found with [`PkdTree::query_k_exact`], so this takes time
+/// roughly proportional to `n_neighbors` times `n log n` for `n` points.
+/// The remaining points stay in their original order.
+///
+/// If there are no more than `n_neighbors` points, the mean is taken over all the other points.
+/// A lone point is never removed.
+///
+/// # Panics
+///
+/// This function will panic if any point in `points` is not finite.
+///
+/// # Examples
+///
+/// ```
+/// use captree::filter::remove_outliers;
+///
+/// let mut points = vec![[0.0, 0.0], [0.1, 0.0], [0.0, 0.1], [0.1, 0.1], [5.0, 5.0]];
+/// remove_outliers(&mut points, 2, 0.5);
+///
+/// assert_eq!(points, vec![[0.0, 0.0], [0.1, 0.0], [0.0, 0.1], [0.1, 0.1]]);
+/// ```
+pub fn remove_outliers<const K: usize>(
+    points: &mut Vec<[f32; K]>,
+    n_neighbors: usize,
+    max_mean_distance: f32,
+) {
+    let tree = PkdTree::new(points);
+    let keep: Vec<bool> = points
+        .iter()
+        .map(|&p| {
+            // the nearest point to `p` is `p` itself, or a duplicate of it
+            let neighbors = tree.query_k_exact(p, n_neighbors + 1);
+            let n = neighbors.len() - 1;
+            let total: f32 = neighbors[1..]
+                .iter()
+                .map(|&id| distsq(&tree.get_point(id), &p).sqrt())
+                .sum();
+            n == 0 || total <= max_mean_distance * n as f32
+        })
+        .collect();
+
+    let mut i = 0;
+    points.retain(|_| {
+        i += 1;
+        keep[i - 1]
+    });
+}
+
+/// Deposit the low bits of `a` into the set bits of `mask`.
+fn pdep(a: u32, mut mask: u32) -> u32 {
+    #[cfg(target_feature = "bmi2")]
+    {
+        unsafe {
+            return core::arch::x86_64::_pdep_u32(a, mask);
+        }
+    }
+    #[cfg(not(target_feature = "bmi2"))]
+    {
+        let mut out = 0;
+        for i in 0..mask.count_ones() {
+            let bit = mask & !(mask - 1);
+            if a & (1 << i) != 0 {
+                out |= bit;
+            }
+            mask ^= bit;
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+
+    #[test]
+    fn one_point() {
+        let mut points = vec![[0.0; 3]];
+        morton_filter(&mut points, 0.01);
+        assert_eq!(points, vec![[0.0; 3]]);
+    }
+
+    #[test]
+    fn duplicate() {
+        let mut points = vec![[0.0; 3]; 2];
+        morton_filter(&mut points, 0.01);
+        assert_eq!(points, vec![[0.0; 3]]);
+    }
+
+    #[test]
+    fn too_close() {
+        let mut points = vec![[0.0; 3], [0.001; 3]];
+        morton_filter(&mut points, 0.01);
+        assert_eq!(points, vec![[0.0; 3]]);
+    }
+
+    #[test]
+    fn too_far() {
+        let mut points = vec![[0.0; 3], [0.01; 3]];
+        morton_filter(&mut points, 0.01);
+        assert_eq!(points, vec![[0.0; 3], [0.01; 3]]);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn voxels_cover_original() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..2000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let cell_size = 0.1;
+
+        let mut filtered = points.clone();
+        voxel_downsample(&mut filtered, cell_size);
+        assert!(filtered.len() <= 1000);
+
+        let rsq = 3.0 * cell_size * cell_size;
+        for p in &points {
+            assert!(filtered.iter().any(|q| distsq(p, q) <= rsq));
+        }
+        for (i, p) in filtered.iter().enumerate() {
+            for q in &filtered[i + 1..] {
+                assert_ne!(
+                    p.map(|x| (x / cell_size).floor()),
+                    q.map(|x| (x / cell_size).floor())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn voxels_drop_non_finite() {
+        let mut points = vec![[0.0, 0.0], [f32::NAN, 0.0], [0.0, f32::INFINITY]];
+        voxel_downsample(&mut points, 1.0);
+        assert_eq!(points, vec![[0.0, 0.0]]);
+    }
+
+    #[test]
+    fn outliers_removed() {
+        let mut rng = thread_rng();
+        let mut points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let outliers = [[10.0, 0.0, 0.0], [-10.0, 5.0, 0.0], [0.0, 0.0, 20.0]];
+        points.extend(outliers);
+
+        let mut filtered = points.clone();
+        remove_outliers(&mut filtered, 8, 1.0);
+        assert_eq!(filtered, points[..1000]);
+    }
+
+    #[test]
+    fn outliers_in_tiny_cloud() {
+        let mut points = vec![[0.0; 2]];
+        remove_outliers(&mut points, 4, 0.1);
+        assert_eq!(points, vec![[0.0; 2]]);
+
+        let mut points = vec![[0.0; 2], [1.0; 2]];
+        remove_outliers(&mut points, 4, 0.1);
+        assert!(points.is_empty());
+    }
+
+    #[test]
+    fn covers_original() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..2000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let min_sep = 0.05;
+
+        let mut filtered = points.clone();
+        morton_filter(&mut filtered, min_sep);
+        assert!(filtered.len() < points.len());
+        assert!(filtered.iter().all(|p| points.contains(p)));
+
+        let rsq = (6.0 * min_sep).powi(2);
+        for p in &points {
+            assert!(filtered.iter().any(|q| distsq(p, q) <= rsq));
+        }
+    }
+}
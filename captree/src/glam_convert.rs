@@ -0,0 +1,163 @@
+//! Construction and queries of [`Capt`]s with the vectors of the [`glam`](::glam) crate.
+
+use ::glam::{DVec3, Vec3, Vec3A};
+use elain::{Align, Alignment};
+
+use crate::{Capt, Index, NewCaptError};
+
+impl<I, const L: usize> Capt<3, L, f32, I>
+where
+    I: Index,
+    Align<L>: Alignment,
+{
+    #[must_use]
+    /// Construct a new CAPT containing all the points in `points`.
+    ///
+    /// A `Vec3` has the same layout as a `[f32; 3]`, so `points` is read in place without being
+    /// copied.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic under the same conditions as [`Capt::new`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glam::Vec3;
+    ///
+    /// let points = [Vec3::ZERO, Vec3::ONE];
+    /// let capt = captree::Capt::<3>::from_vec3(&points, (0.0, 0.5));
+    ///
+    /// assert!(capt.collides_vec3(Vec3::new(1.0, 1.0, 1.1), 0.2));
+    /// assert!(!capt.collides_vec3(Vec3::splat(0.5), 0.2));
+    /// ```
+    pub fn from_vec3(points: &[Vec3], r_range: (f32, f32)) -> Self {
+        Self::new(bytemuck::cast_slice(points), r_range)
+    }
+
+    /// Construct a new CAPT containing all the points in `points`, checking for index overflow.
+    ///
+    /// See [`Capt::from_vec3`] for further details.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error under the same conditions as [`Capt::try_new`].
+    pub fn try_from_vec3(points: &[Vec3], r_range: (f32, f32)) -> Result<Self, NewCaptError> {
+        Self::try_new(bytemuck::cast_slice(points), r_range)
+    }
+
+    #[must_use]
+    /// Construct a new CAPT containing all the points in `points`.
+    ///
+    /// Since a `Vec3A` is padded to 16 bytes, each point is copied out of `points`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic under the same conditions as [`Capt::new`].
+    pub fn from_vec3a(points: &[Vec3A], r_range: (f32, f32)) -> Self {
+        Self::try_from_vec3a(points, r_range)
+            .expect("index type I must be able to support all points in CAPT during construction")
+    }
+
+    /// Construct a new CAPT containing all the points in `points`, checking for index overflow.
+    ///
+    /// See [`Capt::from_vec3a`] for further details.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error under the same conditions as [`Capt::try_new`].
+    pub fn try_from_vec3a(points: &[Vec3A], r_range: (f32, f32)) -> Result<Self, NewCaptError> {
+        Self::try_from_points(points.iter().map(Vec3A::to_array), r_range)
+    }
+
+    #[must_use]
+    /// Determine whether a point in this tree is within a distance of `radius` to `center`.
+    ///
+    /// `center` may be a `Vec3` or a `Vec3A`.
+    /// This is the same as [`Capt::collides`].
+    pub fn collides_vec3(&self, center: impl Into<Vec3>, radius: f32) -> bool {
+        self.collides(&center.into().to_array(), radius)
+    }
+
+    #[must_use]
+    /// Find the nearest point in this tree to `center`, so long as its distance is within the
+    /// radius range used to construct this tree.
+    ///
+    /// This is the same as [`Capt::nearest_within_range`].
+    pub fn nearest_vec3_within_range(&self, center: impl Into<Vec3>) -> Option<(Vec3, f32)> {
+        self.nearest_within_range(&center.into().to_array())
+            .map(|(p, d)| (Vec3::from_array(p), d))
+    }
+}
+
+impl<I, const L: usize> Capt<3, L, f64, I>
+where
+    I: Index,
+    Align<L>: Alignment,
+{
+    #[must_use]
+    /// Construct a new CAPT containing all the points in `points`.
+    ///
+    /// A `DVec3` has the same layout as a `[f64; 3]`, so `points` is read in place without being
+    /// copied.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic under the same conditions as [`Capt::new`].
+    pub fn from_dvec3(points: &[DVec3], r_range: (f64, f64)) -> Self {
+        Self::new(bytemuck::cast_slice(points), r_range)
+    }
+
+    /// Construct a new CAPT containing all the points in `points`, checking for index overflow.
+    ///
+    /// See [`Capt::from_dvec3`] for further details.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error under the same conditions as [`Capt::try_new`].
+    pub fn try_from_dvec3(points: &[DVec3], r_range: (f64, f64)) -> Result<Self, NewCaptError> {
+        Self::try_new(bytemuck::cast_slice(points), r_range)
+    }
+
+    #[must_use]
+    /// Determine whether a point in this tree is within a distance of `radius` to `center`.
+    ///
+    /// This is the same as [`Capt::collides`].
+    pub fn collides_dvec3(&self, center: DVec3, radius: f64) -> bool {
+        self.collides(&center.to_array(), radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+
+    #[test]
+    fn matches_arrays() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..500)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let packed: Vec<Vec3> = points.iter().copied().map(Vec3::from_array).collect();
+        let padded: Vec<Vec3A> = points.iter().copied().map(Vec3A::from_array).collect();
+        let r_range = (0.01, 0.2);
+
+        let capt = Capt::<3>::new(&points, r_range);
+        assert_eq!(capt, Capt::from_vec3(&packed, r_range));
+        assert_eq!(capt, Capt::from_vec3a(&padded, r_range));
+
+        for _ in 0..1000 {
+            let center = [rng.gen(), rng.gen(), rng.gen()];
+            let r = rng.gen_range(r_range.0..=r_range.1);
+            assert_eq!(
+                capt.collides(&center, r),
+                capt.collides_vec3(Vec3A::from_array(center), r)
+            );
+        }
+    }
+}
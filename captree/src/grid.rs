@@ -0,0 +1,504 @@
+//! Collision checking with affordance buffers over the cells of a uniform grid.
+
+use std::{array, mem::size_of};
+
+#[cfg(feature = "simd")]
+use std::{
+    ops::{AddAssign, Mul, Sub},
+    simd::{
+        cmp::{SimdPartialEq, SimdPartialOrd},
+        Mask, Simd,
+    },
+};
+
+use elain::{Align, Alignment};
+
+use crate::{distsq, prefetch, Aabb, Buf, Collider, Index, MySimd, NewCaptError};
+
+#[cfg(feature = "simd")]
+use crate::{collides_leaves_simd, multiversion, AxisSimd, IndexSimd};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+/// A collision-checking structure which bins space into a dense grid of equally-sized cells, each
+/// with its own affordance buffer.
+///
+/// A query finds its cell with a little arithmetic instead of descending through a tree, so for
+/// roughly uniform clouds, such as indoor scans, a `GridCapt` can answer queries faster than a
+/// [`Capt`](crate::Capt).
+/// In exchange, its memory grows with the volume of the bounding box of the cloud rather than
+/// with the number of points, and dense clusters of points produce long affordance buffers.
+///
+/// The cells on the faces of the grid extend out to infinity, so queries outside the bounding box
+/// of the cloud are still answered correctly.
+///
+/// The generic parameters have the same meaning as for [`Capt`](crate::Capt).
+///
+/// # Examples
+///
+/// ```
+/// let points = [[0.0, 0.1], [0.4, -0.2], [-0.2, -0.1]];
+///
+/// let t = captree::GridCapt::<2>::new(&points, (0.0, 0.2), 0.1);
+///
+/// assert!(!t.collides(&[0.0, 0.3], 0.1));
+/// assert!(t.collides(&[0.0, 0.2], 0.15));
+/// ```
+pub struct GridCapt<const K: usize, const L: usize = 8, A = f32, I = usize>
+where
+    Align<L>: Alignment,
+{
+    /// The lowest corner of the cell at grid index zero.
+    origin: [A; K],
+    /// The side length of each cell.
+    cell_size: A,
+    /// The number of cells along each axis.
+    dims: [usize; K],
+    /// Axis-aligned bounding boxes containing the set of afforded points for each cell.
+    aabbs: Buf<Aabb<A, K>>,
+    /// Indexes for the starts of the affordance buffer subsequence of `afforded` corresponding to
+    /// each cell, with the first axis varying fastest.
+    /// This buffer is padded with one extra element at the end with the length of `afforded`.
+    starts: Buf<I>,
+    /// The afforded points for each cell, in lanes padded out with infinite points.
+    afforded: [Buf<MySimd<A, L>>; K],
+    /// The number of points in the grid.
+    n_points: usize,
+    /// The `(minimum, maximum)` range of query radii which this grid was built to support.
+    r_range: (A, A),
+}
+
+/// Call `f` on every grid index in the box from `lo` to `hi`, inclusive.
+fn for_each_index<const K: usize>(lo: [usize; K], hi: [usize; K], mut f: impl FnMut([usize; K])) {
+    let mut idx = lo;
+    loop {
+        f(idx);
+        let mut k = 0;
+        loop {
+            if k == K {
+                return;
+            }
+            if idx[k] < hi[k] {
+                idx[k] += 1;
+                break;
+            }
+            idx[k] = lo[k];
+            k += 1;
+        }
+    }
+}
+
+/// Implement `GridCapt` for axes of type `$a`.
+macro_rules! impl_grid {
+    ($a: ty) => {
+        impl<I, const K: usize, const L: usize> GridCapt<K, L, $a, I>
+        where
+            I: Index,
+            Align<L>: Alignment,
+        {
+            /// Construct a new grid containing all the points in `points`, with cells of side
+            /// length `cell_size`.
+            ///
+            /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on
+            /// the radius of the balls which will be queried against the grid.
+            ///
+            /// # Panics
+            ///
+            /// This function will panic if `cell_size` is not positive and finite, or under the
+            /// same conditions as [`GridCapt::try_new`] returns an error.
+            pub fn new(points: &[[$a; K]], r_range: ($a, $a), cell_size: $a) -> Self {
+                Self::try_new(points, r_range, cell_size).expect(
+                    "index type I must be able to support all points in CAPT during construction",
+                )
+            }
+
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            /// Construct a new grid containing all the points in `points`, checking for index
+            /// overflow.
+            ///
+            /// See [`GridCapt::new`] for further details.
+            ///
+            /// # Errors
+            ///
+            /// This function will return `Err(NewCaptError::TooManyPoints)` if the grid has too
+            /// many cells to count, or too many afforded points to be indexed by `I`.
+            /// It will return `Err(NewCaptError::NonFinite)` if any element of `points` is
+            /// non-finite.
+            ///
+            /// # Panics
+            ///
+            /// This function will panic if `cell_size` is not positive and finite.
+            pub fn try_new(
+                points: &[[$a; K]],
+                r_range: ($a, $a),
+                cell_size: $a,
+            ) -> Result<Self, NewCaptError> {
+                assert!(
+                    cell_size > 0.0 && cell_size.is_finite(),
+                    "cell size must be positive and finite"
+                );
+                if points.iter().any(|p| p.iter().any(|x| !x.is_finite())) {
+                    return Err(NewCaptError::NonFinite);
+                }
+
+                let mut bounds = Aabb {
+                    lo: points.first().copied().unwrap_or([0.0; K]),
+                    hi: points.first().copied().unwrap_or([0.0; K]),
+                };
+                for p in points {
+                    bounds.insert(p);
+                }
+                let origin = bounds.lo;
+                let dims: [usize; K] =
+                    array::from_fn(|k| ((bounds.hi[k] - origin[k]) / cell_size) as usize + 1);
+                let n_cells = dims
+                    .iter()
+                    .try_fold(1usize, |n, &d| n.checked_mul(d))
+                    .ok_or(NewCaptError::TooManyPoints)?;
+
+                let mut grid = Self {
+                    origin,
+                    cell_size,
+                    dims,
+                    aabbs: Buf::default(),
+                    starts: Buf::default(),
+                    afforded: array::from_fn(|_| Buf::default()),
+                    n_points: points.len(),
+                    r_range,
+                };
+
+                // the indices of the points within the maximum radius of each cell
+                let mut in_range = vec![Vec::new(); n_cells];
+                let rsq_max = r_range.1 * r_range.1;
+                for (i, p) in points.iter().enumerate() {
+                    let lo = array::from_fn(|k| grid.axis_index(p[k] - r_range.1, k));
+                    let hi = array::from_fn(|k| grid.axis_index(p[k] + r_range.1, k));
+                    for_each_index(lo, hi, |idx| {
+                        if grid.region(idx).closest_distsq_to(p) <= rsq_max {
+                            in_range[grid.flatten(idx)].push(i);
+                        }
+                    });
+                }
+
+                let rsq_min = r_range.0 * r_range.0;
+                let mut aabbs = Vec::with_capacity(n_cells);
+                let mut starts = Vec::with_capacity(n_cells + 1);
+                let mut afforded: [Vec<MySimd<$a, L>>; K] = array::from_fn(|_| Vec::new());
+                starts.push(I::ZERO);
+                for (z, mut cands) in in_range.into_iter().enumerate() {
+                    let region = grid.region(grid.unflatten(z));
+
+                    // one point covers everything if the whole cell is within the minimum radius
+                    if let Some(&i) = cands
+                        .iter()
+                        .find(|&&i| region.contained_by_ball(&points[i], rsq_min))
+                    {
+                        cands = vec![i];
+                    }
+
+                    let mut aabb = match cands.first() {
+                        Some(&i) => Aabb {
+                            lo: points[i],
+                            hi: points[i],
+                        },
+                        None => Aabb {
+                            lo: [<$a>::INFINITY; K],
+                            hi: [<$a>::INFINITY; K],
+                        },
+                    };
+                    for chunk in cands.chunks(L) {
+                        let mut news = [[<$a>::INFINITY; L]; K];
+                        for (j, &i) in chunk.iter().enumerate() {
+                            aabb.insert(&points[i]);
+                            for (nk, &x) in news.iter_mut().zip(&points[i]) {
+                                nk[j] = x;
+                            }
+                        }
+                        for (ak, data) in afforded.iter_mut().zip(news) {
+                            ak.push(MySimd {
+                                data,
+                                _align: Align::NEW,
+                            });
+                        }
+                    }
+
+                    aabbs.push(aabb);
+                    starts.push(
+                        afforded[0]
+                            .len()
+                            .try_into()
+                            .map_err(|_| NewCaptError::TooManyPoints)?,
+                    );
+                }
+
+                grid.aabbs = aabbs.into();
+                grid.starts = starts.into();
+                grid.afforded = afforded.map(Buf::from);
+                Ok(grid)
+            }
+
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            /// Find the index along axis `k` of the cell containing the coordinate `x`.
+            fn axis_index(&self, x: $a, k: usize) -> usize {
+                let i = ((x - self.origin[k]) / self.cell_size).floor();
+                if i > 0.0 {
+                    (i as usize).min(self.dims[k] - 1)
+                } else {
+                    0
+                }
+            }
+
+            /// Find the index of the cell containing `center`.
+            fn cell_containing(&self, center: &[$a; K]) -> usize {
+                self.flatten(array::from_fn(|k| self.axis_index(center[k], k)))
+            }
+
+            #[allow(clippy::cast_precision_loss)]
+            /// Compute the volume of space covered by the cell at grid index `idx`.
+            fn region(&self, idx: [usize; K]) -> Aabb<$a, K> {
+                let mut region = Aabb::ALL;
+                for k in 0..K {
+                    let lo = self.cell_size.mul_add(idx[k] as $a, self.origin[k]);
+                    if idx[k] > 0 {
+                        region.lo[k] = lo;
+                    }
+                    if idx[k] + 1 < self.dims[k] {
+                        region.hi[k] = lo + self.cell_size;
+                    }
+                }
+                region
+            }
+
+            #[must_use]
+            /// Determine whether a point in this grid is within a distance of `radius` to
+            /// `center`.
+            ///
+            /// As with [`Capt::collides`](crate::Capt::collides), the result is only guaranteed
+            /// to be correct if `radius` is inside the radius range used to construct this grid.
+            ///
+            /// # Examples
+            ///
+            /// ```
+            /// let points = [[0.0; 3], [1.0; 3], [0.1, 0.5, 1.0]];
+            /// let t = captree::GridCapt::<3>::new(&points, (0.0, 1.0), 0.25);
+            ///
+            /// assert!(t.collides(&[1.1; 3], 0.2));
+            /// assert!(!t.collides(&[2.0; 3], 1.0));
+            /// ```
+            pub fn collides(&self, center: &[$a; K], radius: $a) -> bool {
+                let rsq = radius * radius;
+                let z = self.cell_containing(center);
+                let aabb = unsafe { self.aabbs.get_unchecked(z) };
+                if aabb.closest_distsq_to(center) > rsq {
+                    return false;
+                }
+
+                let mut range = unsafe {
+                    // SAFETY: The conversion worked the first way.
+                    self.starts[z].try_into().unwrap_unchecked()
+                        ..self.starts[z + 1].try_into().unwrap_unchecked()
+                };
+                for sk in &self.afforded {
+                    prefetch(sk.as_ptr().wrapping_add(range.start));
+                }
+
+                range.any(|i| {
+                    (0..L).any(|j| {
+                        let aff_pt = array::from_fn(|k| self.afforded[k][i].data[j]);
+                        distsq(aff_pt, *center) <= rsq
+                    })
+                })
+            }
+
+            #[must_use]
+            /// Get the number of cells along each axis of this grid.
+            pub const fn dims(&self) -> [usize; K] {
+                self.dims
+            }
+        }
+
+        impl<I, const K: usize, const L: usize> Collider<K, $a> for GridCapt<K, L, $a, I>
+        where
+            I: Index,
+            Align<L>: Alignment,
+        {
+            fn collides(&self, center: &[$a; K], radius: $a) -> bool {
+                self.collides(center, radius)
+            }
+        }
+
+        #[cfg(feature = "simd")]
+        impl<I, const K: usize, const L: usize> GridCapt<K, L, $a, I>
+        where
+            I: Index + IndexSimd,
+            Align<L>: Alignment,
+        {
+            #[must_use]
+            /// Determine whether any sphere in the list of provided spheres intersects a point in
+            /// this grid.
+            ///
+            /// The cell containing each sphere is found one sphere at a time; the bounding box
+            /// pruning and the scan of the affordance buffers are vectorized as in
+            /// [`Capt::collides_simd`](crate::Capt::collides_simd).
+            pub fn collides_simd(&self, centers: &[Simd<$a, L>; K], radii: Simd<$a, L>) -> bool
+            where
+                Simd<$a, L>: SimdPartialOrd
+                    + Sub<Output = Simd<$a, L>>
+                    + Mul<Output = Simd<$a, L>>
+                    + AddAssign,
+                Mask<isize, L>: From<<Simd<$a, L> as SimdPartialEq>::Mask>,
+                $a: AxisSimd<<Simd<$a, L> as SimdPartialEq>::Mask>,
+            {
+                multiversion(
+                    #[inline(always)]
+                    #[allow(clippy::cast_possible_wrap)]
+                    || {
+                        let zs = Simd::from_array(array::from_fn(|j| {
+                            self.cell_containing(&array::from_fn(|k| centers[k][j])) as isize
+                        }));
+                        unsafe {
+                            // SAFETY: Every center is in some cell, whose affordance buffer range
+                            // lies within `afforded`.
+                            collides_leaves_simd(
+                                &self.aabbs,
+                                &self.starts,
+                                &self.afforded,
+                                zs,
+                                centers,
+                                radii,
+                                Mask::splat(true),
+                            )
+                        }
+                    },
+                )
+            }
+        }
+    };
+}
+
+impl_grid!(f32);
+impl_grid!(f64);
+
+impl<A, I, const K: usize, const L: usize> GridCapt<K, L, A, I>
+where
+    A: Copy,
+    Align<L>: Alignment,
+{
+    /// Convert a grid index to the index of its cell in the affordance buffers.
+    fn flatten(&self, idx: [usize; K]) -> usize {
+        idx.iter()
+            .zip(&self.dims)
+            .rev()
+            .fold(0, |z, (&i, &d)| z * d + i)
+    }
+
+    /// Convert the index of a cell in the affordance buffers to its grid index.
+    fn unflatten(&self, mut z: usize) -> [usize; K] {
+        array::from_fn(|k| {
+            let i = z % self.dims[k];
+            z /= self.dims[k];
+            i
+        })
+    }
+
+    #[must_use]
+    /// Get the number of points in this grid.
+    pub const fn len(&self) -> usize {
+        self.n_points
+    }
+
+    #[must_use]
+    /// Determine whether this grid contains no points.
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[must_use]
+    /// Get the dimension of the points in this grid, `K`.
+    pub const fn dimension(&self) -> usize {
+        K
+    }
+
+    #[must_use]
+    /// Get the `(minimum, maximum)` range of query radii which this grid was built to support.
+    pub const fn r_range(&self) -> (A, A) {
+        self.r_range
+    }
+
+    #[must_use]
+    #[doc(hidden)]
+    /// Get the total memory used (stack + heap) by this structure, measured in bytes.
+    /// This function should not be considered stable; it is only used internally for benchmarks.
+    pub const fn memory_used(&self) -> usize {
+        size_of::<Self>()
+            + K * self.afforded[0].len() * size_of::<MySimd<A, L>>()
+            + self.starts.len() * size_of::<I>()
+            + self.aabbs.len() * size_of::<Aabb<A, K>>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+    use crate::Capt;
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let (r_min, r_max) = (0.005, 0.08);
+        let t = GridCapt::<3>::new(&points, (r_min, r_max), 0.05);
+        let capt = Capt::<3>::new(&points, (r_min, r_max));
+        assert_eq!(t.dims().map(|d| d.clamp(20, 21)), t.dims());
+
+        for _ in 0..10_000 {
+            let center = [
+                rng.gen_range(-0.2..1.2),
+                rng.gen_range(-0.2..1.2),
+                rng.gen_range(-0.2..1.2),
+            ];
+            let r = rng.gen_range(r_min..=r_max);
+            let collides = points.iter().any(|p| distsq(*p, center) <= r * r);
+            assert_eq!(collides, t.collides(&center, r));
+            assert_eq!(collides, capt.collides(&center, r));
+        }
+    }
+
+    #[test]
+    fn same_trait() {
+        fn check<C: Collider<2, f64>>(c: &C) {
+            assert!(c.collides(&[1.2, 1.2], 0.3));
+            assert!(!c.collides(&[1.5, 1.5], 0.5));
+            assert!(!c.collides(&[-100.0, 0.0], 0.5));
+        }
+
+        let points = [[1.0, 1.0], [1.0, 1.0], [0.0, 0.2]];
+        check(&GridCapt::<2, 4, f64, u16>::new(&points, (0.0, 0.5), 0.1));
+        check(&Capt::<2, 4, f64, u16>::new(&points, (0.0, 0.5)));
+
+        let empty = GridCapt::<2>::new(&[], (0.0, 0.5), 1.0);
+        assert!(!empty.collides(&[0.0, 0.0], 0.5));
+    }
+
+    #[test]
+    #[cfg(feature = "simd")]
+    fn simd_matches_collides() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..500)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let t = GridCapt::<3, 8, f32, u32>::new(&points, (0.01, 0.05), 0.04);
+
+        for _ in 0..200 {
+            let centers: [Simd<f32, 8>; 3] =
+                array::from_fn(|_| Simd::from_array(array::from_fn(|_| rng.gen())));
+            let radii = Simd::from_array(array::from_fn(|_| rng.gen_range(0.01..=0.05)));
+            let expected = (0..8).any(|j| t.collides(&array::from_fn(|k| centers[k][j]), radii[j]));
+            assert_eq!(t.collides_simd(&centers, radii), expected);
+        }
+    }
+}
@@ -0,0 +1,467 @@
+//! Collision-affording point trees whose affordance buffers refer to points by index.
+
+use std::{array, mem::size_of};
+
+#[cfg(feature = "simd")]
+use std::{
+    ops::{AddAssign, Mul, Sub},
+    simd::{
+        cmp::{SimdPartialEq, SimdPartialOrd},
+        num::SimdUint,
+        Mask, Simd,
+    },
+};
+
+use elain::{Align, Alignment};
+
+use crate::{
+    afford, distsq, forward_pass, partition, prefetch, Aabb, Axis, Buf, Collider, Index, MySimd,
+    NewCaptError,
+};
+
+#[cfg(feature = "simd")]
+use crate::{
+    aabb_prune_simd, buffer_ranges_simd, forward_pass_simd, multiversion, AxisSimd, BatchCollider,
+    IndexSimd, SimdQueryBatch,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+/// A collision-affording point tree whose affordance buffers store `u32` indices into a single
+/// shared array of points, rather than copies of the points themselves.
+///
+/// Since each point in a dense cloud is typically afforded by many cells, a [`Capt`](crate::Capt)
+/// stores many copies of each point.
+/// An `IndexedCapt` stores each point only once, so each afforded point costs 4 bytes instead of
+/// `K * size_of::<A>()`.
+/// In exchange, queries must perform an extra indirection (a gather, in the SIMD case) for each
+/// afforded point, which makes them somewhat slower.
+///
+/// The generic parameters have the same meaning as for [`Capt`](crate::Capt).
+///
+/// # Examples
+///
+/// ```
+/// let points = [[0.0, 0.1], [0.4, -0.2], [-0.2, -0.1]];
+///
+/// let t = captree::IndexedCapt::<2>::new(&points, (0.0, 0.2));
+///
+/// assert!(!t.collides(&[0.0, 0.3], 0.1));
+/// assert!(t.collides(&[0.0, 0.2], 0.15));
+/// ```
+pub struct IndexedCapt<const K: usize, const L: usize = 8, A = f32, I = usize>
+where
+    Align<L>: Alignment,
+{
+    /// The test values for determining which part of the tree to enter.
+    /// This has the same layout as the tests of a [`Capt`](crate::Capt).
+    tests: Buf<A>,
+    /// Axis-aligned bounding boxes containing the set of afforded points for each cell.
+    aabbs: Buf<Aabb<A, K>>,
+    /// Indexes for the starts of the affordance buffer subsequence of `afforded` corresponding to
+    /// each leaf cell in the tree.
+    /// This buffer is padded with one extra element at the end with the length of `afforded`.
+    starts: Buf<I>,
+    /// The indices into `points` of the afforded points for each cell.
+    /// Unused lanes refer to the last element of `points`, which is always infinite.
+    afforded: Buf<MySimd<u32, L>>,
+    /// The points in the tree, stored dimension-major.
+    /// The `z`-th point is the representative of the `z`-th leaf cell.
+    points: [Buf<A>; K],
+    /// The `(minimum, maximum)` range of query radii which this tree was built to support.
+    r_range: (A, A),
+}
+
+impl<A, I, const K: usize, const L: usize> IndexedCapt<K, L, A, I>
+where
+    A: Axis,
+    I: Index,
+    Align<L>: Alignment,
+{
+    /// Construct a new indexed CAPT containing all the points in `points`.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if there are too many points in the tree to be addressed by `I` or
+    /// by a `u32`, or if any points contain non-finite non-real value.
+    pub fn new(points: &[[A; K]], r_range: (A, A)) -> Self {
+        Self::try_new(points, r_range)
+            .expect("index types must be able to support all points in CAPT during construction")
+    }
+
+    /// Construct a new indexed CAPT containing all the points in `points`, checking for index
+    /// overflow.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Err(NewCaptError::TooManyPoints)` if there are too many points to
+    /// be indexed by `I` or by a `u32`.
+    /// It will return `Err(NewCaptError::NonFinite)` if any element of `points` is non-finite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let points = [[0.0]; 256];
+    ///
+    /// let opt = captree::IndexedCapt::<1, 8, f32, u8>::try_new(&points, (0.0, f32::INFINITY));
+    ///
+    /// assert!(opt.is_err());
+    /// ```
+    pub fn try_new(points: &[[A; K]], r_range: (A, A)) -> Result<Self, NewCaptError> {
+        let n2 = points.len().next_power_of_two();
+
+        if points.iter().any(|p| p.iter().any(|x| !x.is_finite())) {
+            return Err(NewCaptError::NonFinite);
+        }
+
+        // the extra point at index `n2` is used to pad out lanes
+        let pad = u32::try_from(n2).map_err(|_| NewCaptError::TooManyPoints)?;
+
+        let mut tests: Buf<A> = vec![A::INFINITY; n2 - 1].into();
+        let mut points2 = vec![[A::INFINITY; K]; n2 + 1].into_boxed_slice();
+        points2[..points.len()].copy_from_slice(points);
+
+        let mut afforded = Vec::with_capacity(n2);
+        let mut starts: Buf<I> = vec![I::ZERO; n2 + 1].into();
+        let mut aabbs: Buf<Aabb<A, K>> = vec![Aabb::ALL; n2].into();
+
+        unsafe {
+            // SAFETY: We tested that `points` contains no `NaN` values.
+            partition(&mut points2[..n2], &mut tests, 0);
+        }
+
+        afford(&points2[..n2], &tests, &[], r_range, &mut |z, in_range| {
+            let rep = points2[z];
+            let aabb = &mut aabbs[z];
+            *aabb = Aabb { lo: rep, hi: rep };
+            if rep[0].is_finite() {
+                // lanes of afforded point indices
+                let mut news = [pad; L];
+                news[0] = u32::try_from(z).map_err(|_| NewCaptError::TooManyPoints)?;
+                let mut j = 1;
+
+                afforded.reserve(in_range.len() / L);
+                for i in in_range {
+                    aabb.insert(&points2[i]);
+                    if j == L {
+                        afforded.push(MySimd {
+                            data: news,
+                            _align: Align::NEW,
+                        });
+                        news = [pad; L];
+                        j = 0;
+                    }
+
+                    news[j] = u32::try_from(i).map_err(|_| NewCaptError::TooManyPoints)?;
+                    j += 1;
+                }
+
+                afforded.push(MySimd {
+                    data: news,
+                    _align: Align::NEW,
+                });
+            }
+
+            starts[z + 1] = afforded
+                .len()
+                .try_into()
+                .map_err(|_| NewCaptError::TooManyPoints)?;
+            Ok(())
+        })?;
+
+        Ok(Self {
+            tests,
+            aabbs,
+            starts,
+            afforded: afforded.into(),
+            points: array::from_fn(|k| points2.iter().map(|p| p[k]).collect()),
+            r_range,
+        })
+    }
+
+    #[must_use]
+    /// Determine whether a point in this tree is within a distance of `radius` to `center`.
+    ///
+    /// As with [`Capt::collides`](crate::Capt::collides), the result is only guaranteed to be
+    /// correct if `radius` is inside the radius range used to construct this tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let points = [[0.0; 3], [1.0; 3], [0.1, 0.5, 1.0]];
+    /// let capt = captree::IndexedCapt::<3>::new(&points, (0.0, 1.0));
+    ///
+    /// assert!(capt.collides(&[1.1; 3], 0.2));
+    /// assert!(!capt.collides(&[2.0; 3], 1.0));
+    /// ```
+    pub fn collides(&self, center: &[A; K], radius: A) -> bool {
+        let rsq = radius.square();
+        let i = forward_pass(&self.tests, center);
+        let aabb = unsafe { self.aabbs.get_unchecked(i) };
+        if aabb.closest_distsq_to(center) > rsq {
+            return false;
+        }
+
+        let range = unsafe {
+            // SAFETY: The conversion worked the first way.
+            self.starts[i].try_into().unwrap_unchecked()
+                ..self.starts[i + 1].try_into().unwrap_unchecked()
+        };
+        prefetch(self.afforded.as_ptr().wrapping_add(range.start));
+
+        // check affordance buffer
+        self.afforded[range].iter().any(|lane| {
+            lane.data.iter().any(|&j| {
+                let aff_pt = array::from_fn(|k| self.points[k][j as usize]);
+                distsq(aff_pt, *center) <= rsq
+            })
+        })
+    }
+
+    #[must_use]
+    /// Get the number of points in this tree, not counting the padding which fills out the tree
+    /// to a power of two.
+    pub fn len(&self) -> usize {
+        // padding points are infinite, and always stored after every real point
+        self.points[0].partition_point(|x| x.is_finite())
+    }
+
+    #[must_use]
+    /// Determine whether this tree contains no points.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[must_use]
+    /// Get the dimension of the points in this tree, `K`.
+    pub const fn dimension(&self) -> usize {
+        K
+    }
+
+    #[must_use]
+    /// Get the `(minimum, maximum)` range of query radii which this tree was built to support.
+    pub const fn r_range(&self) -> (A, A) {
+        self.r_range
+    }
+
+    #[must_use]
+    /// Get the number of tests on the path from the root of this tree to any leaf.
+    pub const fn depth(&self) -> u32 {
+        self.tests.len().trailing_ones()
+    }
+
+    #[must_use]
+    #[doc(hidden)]
+    /// Get the total memory used (stack + heap) by this structure, measured in bytes.
+    /// This function should not be considered stable; it is only used internally for benchmarks.
+    pub const fn memory_used(&self) -> usize {
+        size_of::<Self>()
+            + self.afforded.len() * size_of::<MySimd<u32, L>>()
+            + K * self.points[0].len() * size_of::<A>()
+            + self.starts.len() * size_of::<I>()
+            + self.tests.len() * size_of::<A>()
+            + self.aabbs.len() * size_of::<Aabb<A, K>>()
+    }
+}
+
+impl<A, I, const K: usize, const L: usize> Collider<K, A> for IndexedCapt<K, L, A, I>
+where
+    A: Axis,
+    I: Index,
+    Align<L>: Alignment,
+{
+    fn collides(&self, center: &[A; K], radius: A) -> bool {
+        self.collides(center, radius)
+    }
+}
+
+#[cfg(feature = "simd")]
+impl<A, I, const K: usize, const L: usize> BatchCollider<K, L, A> for IndexedCapt<K, L, A, I>
+where
+    I: Index + IndexSimd,
+    A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask> + Mul<Output = A>,
+    Align<L>: Alignment,
+    Simd<A, L>: SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+    Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+{
+    fn collides_batch(&self, batch: &SimdQueryBatch<K, L, A>) -> bool {
+        multiversion(
+            #[inline(always)]
+            || self.collides_simd_inner(&batch.centers, batch.radii, batch.active),
+        )
+    }
+}
+
+impl<A, I, const K: usize, const L: usize> Default for IndexedCapt<K, L, A, I>
+where
+    A: Axis,
+    I: Index,
+    Align<L>: Alignment,
+{
+    /// Construct an empty tree, which supports no query radius and never collides.
+    fn default() -> Self {
+        Self::new(&[], (A::ZERO, A::ZERO))
+    }
+}
+
+#[allow(clippy::mismatching_type_param_order)]
+#[cfg(feature = "simd")]
+impl<A, I, const K: usize, const L: usize> IndexedCapt<K, L, A, I>
+where
+    I: IndexSimd,
+    A: Mul<Output = A>,
+    Align<L>: Alignment,
+{
+    #[must_use]
+    /// Determine whether any sphere in the list of provided spheres intersects a point in this
+    /// tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(portable_simd)]
+    /// use std::simd::Simd;
+    ///
+    /// let points = [[1.0, 2.0], [1.1, 1.1]];
+    ///
+    /// let centers = [
+    ///     Simd::from_array([1.0, 1.1, 1.2, 1.3]), // x-positions
+    ///     Simd::from_array([1.0, 1.1, 1.2, 1.3]), // y-positions
+    /// ];
+    /// let radii = Simd::splat(0.05);
+    ///
+    /// let tree = captree::IndexedCapt::<2, 4, f32, u32>::new(&points, (0.0, 0.1));
+    ///
+    /// assert!(tree.collides_simd(&centers, radii));
+    /// ```
+    pub fn collides_simd(&self, centers: &[Simd<A, L>; K], radii: Simd<A, L>) -> bool
+    where
+        Simd<A, L>:
+            SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+        Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+        A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask>,
+    {
+        multiversion(
+            #[inline(always)]
+            || self.collides_simd_inner(centers, radii, Mask::splat(true)),
+        )
+    }
+
+    #[allow(clippy::inline_always)]
+    #[inline(always)]
+    /// The body of [`IndexedCapt::collides_simd`], which is compiled once per supported instruction
+    /// set.
+    fn collides_simd_inner(
+        &self,
+        centers: &[Simd<A, L>; K],
+        radii: Simd<A, L>,
+        active: Mask<isize, L>,
+    ) -> bool
+    where
+        Simd<A, L>:
+            SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+        Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+        A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask>,
+    {
+        let zs = forward_pass_simd(&self.tests, centers);
+
+        let inbounds = unsafe { aabb_prune_simd(&self.aabbs, zs, centers, radii) } & active;
+        if !inbounds.any() {
+            return false;
+        }
+
+        let (starts, ends) = unsafe { buffer_ranges_simd(&self.starts, zs) };
+        for (&start, in_bounds) in starts.iter().zip(inbounds.to_array()) {
+            if in_bounds {
+                prefetch(self.afforded.as_ptr().wrapping_add(start));
+            }
+        }
+
+        starts
+            .into_iter()
+            .zip(ends)
+            .zip(inbounds.to_array())
+            .enumerate()
+            .filter_map(|(j, r)| r.1.then_some((j, r.0)))
+            .any(|(j, (start, end))| {
+                let n_center: [Simd<A, L>; K] = array::from_fn(|k| Simd::splat(centers[k][j]));
+                let rs = Simd::splat(radii[j]);
+                let rs_sq = rs * rs;
+                (start..end).any(|i| {
+                    let idxs: Simd<usize, L> =
+                        Simd::from_array(unsafe { self.afforded.get_unchecked(i) }.data).cast();
+                    let mut dists_sq = Simd::splat(A::ZERO);
+                    for (points_k, center_k) in self.points.iter().zip(n_center) {
+                        // SAFETY: every index in `afforded` is a valid index into `points`.
+                        let vals = unsafe {
+                            Simd::gather_select_unchecked(
+                                points_k,
+                                Mask::splat(true),
+                                idxs,
+                                Simd::splat(A::ZERO),
+                            )
+                        };
+                        let diff = vals - center_k;
+                        dists_sq += diff * diff;
+                    }
+                    A::any(dists_sq.simd_le(rs_sq))
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+    use crate::Capt;
+
+    #[test]
+    fn matches_capt() {
+        const R: f32 = 0.08;
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| {
+                [
+                    rng.gen_range(0.0..1.0),
+                    rng.gen_range(0.0..1.0),
+                    rng.gen_range(0.0..1.0),
+                ]
+            })
+            .collect();
+
+        let capt = Capt::<3>::new(&points, (0.0, R));
+        let indexed = IndexedCapt::<3>::new(&points, (0.0, R));
+
+        assert!(indexed.memory_used() < capt.memory_used());
+
+        for _ in 0..10_000 {
+            let center = [
+                rng.gen_range(0.0..1.0),
+                rng.gen_range(0.0..1.0),
+                rng.gen_range(0.0..1.0),
+            ];
+            let r = rng.gen_range(0.0..R);
+            let collides = points.iter().any(|p| distsq(*p, center) <= r * r);
+            assert_eq!(collides, capt.collides(&center, r));
+            assert_eq!(collides, indexed.collides(&center, r));
+        }
+    }
+
+    #[test]
+    fn not_a_power_of_two() {
+        let points = [[0.0], [2.0], [4.0]];
+        let t = IndexedCapt::<1, 4, f32, u8>::new(&points, (0.0, 1.0));
+
+        assert!(t.collides(&[4.5], 0.6));
+        assert!(!t.collides(&[5.5], 1.0));
+        assert!(!t.collides(&[100.0], 1.0));
+    }
+}
@@ -0,0 +1,1061 @@
+//! Reading and writing point clouds in common file formats.
+//!
+//! The supported formats are:
+//!
+//! - PCD, the format of the Point Cloud Library, via [`read_pcd`] and [`write_pcd`]. Both `ascii`
+//!   and `binary` data are supported, but `binary_compressed` is not.
+//! - PLY, via [`read_ply`], in its ASCII and both binary encodings.
+//! - LAS, via [`read_las`], for versions 1.0 through 1.4. Compressed LAZ files are not supported.
+//! - `NumPy`'s `.npy`, via [`read_npy`] and [`write_npy`], for exchanging point clouds and query
+//!   traces with Python.
+//!
+//! Only the `x`, `y` and `z` coordinates of each point are read from PCD, PLY and LAS files; any
+//! other fields, such as intensity or color, are skipped.
+//!
+//! # Examples
+//!
+//! ```
+//! use captree::io::{read_pcd, write_pcd, PcdData};
+//!
+//! let points = [[0.0, 1.0, 2.0], [-1.5, 0.25, 8.0]];
+//! let mut bytes = Vec::new();
+//! write_pcd(&mut bytes, &points, PcdData::Binary).unwrap();
+//!
+//! assert_eq!(read_pcd(&bytes[..]).unwrap(), points);
+//! ```
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The encoding of the points of a PCD file.
+pub enum PcdData {
+    /// Points are written as whitespace-separated text, one point per line.
+    Ascii,
+    /// Points are written as packed little-endian values.
+    Binary,
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+/// The errors which can occur when reading a point cloud file.
+pub enum ReadError {
+    /// Reading the file failed.
+    Io(io::Error),
+    /// The header is malformed, such as by a missing or invalid entry.
+    BadHeader(String),
+    /// The file has no field or property with this name.
+    MissingField(&'static str),
+    /// The file uses an encoding which is not supported, such as PCD's `binary_compressed` or LAZ.
+    UnsupportedData(String),
+    /// A value in the data could not be parsed, or the data ended before every point was read.
+    BadData,
+}
+
+impl From<io::Error> for ReadError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read point cloud: {e}"),
+            Self::BadHeader(line) => write!(f, "malformed header: {line}"),
+            Self::MissingField(name) => write!(f, "point cloud has no field `{name}`"),
+            Self::UnsupportedData(kind) => write!(f, "unsupported encoding `{kind}`"),
+            Self::BadData => write!(f, "malformed or truncated point data"),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The type of a numeric value in a binary file.
+pub(crate) struct Scalar {
+    /// The kind of value: `I` for signed integers, `U` for unsigned, or `F` for floats.
+    pub(crate) kind: u8,
+    /// The size of the value, in bytes: 1, 2, 4 or 8.
+    pub(crate) size: usize,
+}
+
+impl Scalar {
+    /// Decode one value of this type from the first `self.size` bytes of `bytes`.
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_possible_wrap
+    )]
+    pub(crate) fn decode(self, bytes: &[u8], big_endian: bool) -> Option<f64> {
+        let mut buf = [0; 8];
+        buf[..self.size].copy_from_slice(bytes.get(..self.size)?);
+        if big_endian {
+            buf[..self.size].reverse();
+        }
+        let unsigned = u64::from_le_bytes(buf);
+        Some(match (self.kind, self.size) {
+            (b'F', 4) => f64::from(f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]])),
+            (b'F', 8) => f64::from_le_bytes(buf),
+            (b'U', _) => unsigned as f64,
+            (b'I', n) => {
+                // sign-extend from the value's width
+                let shift = 64 - 8 * n as u32;
+                ((unsigned << shift) as i64 >> shift) as f64
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// Split the first line off of `rest`, returning it with surrounding whitespace trimmed.
+fn header_line<'a>(rest: &mut &'a [u8], missing: &str) -> Result<&'a str, ReadError> {
+    let end = rest
+        .iter()
+        .position(|&b| b == b'\n')
+        .ok_or_else(|| ReadError::BadHeader(format!("missing {missing}")))?;
+    let line = std::str::from_utf8(&rest[..end])
+        .map_err(|_| ReadError::BadHeader("header is not text".into()))?;
+    *rest = &rest[end + 1..];
+    Ok(line.trim())
+}
+
+/// The layout of one field of each point of a PCD file.
+#[derive(Clone, Debug)]
+struct Field {
+    /// The name of the field.
+    name: String,
+    /// The type of each element of the field.
+    scalar: Scalar,
+    /// The number of elements in the field.
+    count: usize,
+}
+
+/// The header of a PCD file.
+struct Header<'a> {
+    /// The fields of each point.
+    fields: Vec<Field>,
+    /// The number of points in the file.
+    n_points: usize,
+    /// The encoding of the points, such as `ascii` or `binary`.
+    data: &'a str,
+    /// The bytes following the header.
+    rest: &'a [u8],
+}
+
+/// Parse the header at the start of `bytes`.
+fn parse_header(bytes: &[u8]) -> Result<Header<'_>, ReadError> {
+    let mut fields: Vec<Field> = Vec::new();
+    let mut n_points = None;
+    let mut rest = bytes;
+    let data = loop {
+        let line = header_line(&mut rest, "DATA entry")?;
+        let mut words = line.split_whitespace();
+        let Some(key) = words.next() else { continue };
+        let values: Vec<&str> = words.collect();
+        let bad = || ReadError::BadHeader(line.to_string());
+        match key {
+            "FIELDS" => {
+                fields = values
+                    .iter()
+                    .map(|name| Field {
+                        name: (*name).to_string(),
+                        scalar: Scalar {
+                            kind: b'F',
+                            size: 4,
+                        },
+                        count: 1,
+                    })
+                    .collect();
+            }
+            "SIZE" | "TYPE" | "COUNT" => {
+                if values.len() != fields.len() {
+                    return Err(bad());
+                }
+                for (field, value) in fields.iter_mut().zip(&values) {
+                    match key {
+                        "SIZE" => {
+                            field.scalar.size = value.parse().map_err(|_| bad())?;
+                            if ![1, 2, 4, 8].contains(&field.scalar.size) {
+                                return Err(bad());
+                            }
+                        }
+                        "TYPE" => match value.as_bytes() {
+                            [kind @ (b'I' | b'U' | b'F')] => field.scalar.kind = *kind,
+                            _ => return Err(bad()),
+                        },
+                        _ => field.count = value.parse().map_err(|_| bad())?,
+                    }
+                }
+            }
+            "POINTS" => {
+                n_points = Some(
+                    values
+                        .first()
+                        .and_then(|v| v.parse::<usize>().ok())
+                        .ok_or_else(bad)?,
+                );
+            }
+            "DATA" => break values.first().copied().ok_or_else(bad)?,
+            _ => (),
+        }
+    };
+    let n_points = n_points.ok_or_else(|| ReadError::BadHeader("missing POINTS entry".into()))?;
+    Ok(Header {
+        fields,
+        n_points,
+        data,
+        rest,
+    })
+}
+
+/// Read the `x`, `y` and `z` fields of every point of the PCD file in `r`, dropping any point with
+/// a non-finite coordinate.
+///
+/// Coordinates of any numeric type are converted to `f32`.
+///
+/// # Errors
+///
+/// This function will return an error if reading from `r` fails, if the file is malformed or
+/// lacks an `x`, `y` or `z` field, or if its data is `binary_compressed`.
+pub fn read_pcd(mut r: impl Read) -> Result<Vec<[f32; 3]>, ReadError> {
+    let mut bytes = Vec::new();
+    r.read_to_end(&mut bytes)?;
+
+    let Header {
+        fields,
+        n_points,
+        data,
+        rest,
+    } = parse_header(&bytes)?;
+
+    // the index of each coordinate's field
+    let mut coords = [0; 3];
+    for (c, name) in coords.iter_mut().zip(["x", "y", "z"]) {
+        *c = fields
+            .iter()
+            .position(|f| f.name == name)
+            .ok_or(ReadError::MissingField(name))?;
+    }
+
+    // `n_points` comes from the file, so it only bounds the number of points once the data has
+    // been checked to hold them
+    let mut points = Vec::new();
+    match data {
+        "ascii" => {
+            // every point takes at least one byte
+            points.reserve(n_points.min(rest.len()));
+            // the index of the first token of each field within a line
+            let starts: Vec<usize> = fields
+                .iter()
+                .scan(0, |start, f| {
+                    let s = *start;
+                    *start += f.count;
+                    Some(s)
+                })
+                .collect();
+            let text = std::str::from_utf8(rest).map_err(|_| ReadError::BadData)?;
+            let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+            for _ in 0..n_points {
+                let tokens: Vec<&str> = lines
+                    .next()
+                    .ok_or(ReadError::BadData)?
+                    .split_whitespace()
+                    .collect();
+                let mut p = [0.0f32; 3];
+                for (x, &c) in p.iter_mut().zip(&coords) {
+                    *x = tokens
+                        .get(starts[c])
+                        .and_then(|t| t.parse().ok())
+                        .ok_or(ReadError::BadData)?;
+                }
+                if p.iter().all(|x| x.is_finite()) {
+                    points.push(p);
+                }
+            }
+        }
+        "binary" => {
+            let offsets: Vec<usize> = fields
+                .iter()
+                .scan(0, |offset, f| {
+                    let o = *offset;
+                    *offset += f.scalar.size * f.count;
+                    Some(o)
+                })
+                .collect();
+            let step: usize = fields.iter().map(|f| f.scalar.size * f.count).sum();
+            if step == 0 || step.checked_mul(n_points).is_none_or(|n| rest.len() < n) {
+                return Err(ReadError::BadData);
+            }
+            points.reserve(n_points);
+            for record in rest.chunks_exact(step).take(n_points) {
+                let mut p = [0.0f32; 3];
+                for (x, &c) in p.iter_mut().zip(&coords) {
+                    #[allow(clippy::cast_possible_truncation)]
+                    {
+                        *x = fields[c]
+                            .scalar
+                            .decode(&record[offsets[c]..], false)
+                            .ok_or(ReadError::BadData)? as f32;
+                    }
+                }
+                if p.iter().all(|x| x.is_finite()) {
+                    points.push(p);
+                }
+            }
+        }
+        other => return Err(ReadError::UnsupportedData(other.to_string())),
+    }
+    Ok(points)
+}
+
+/// Write `points` to `w` as a PCD file with `x`, `y` and `z` fields of type `f32`.
+///
+/// # Errors
+///
+/// This function will return an error if writing to `w` fails.
+pub fn write_pcd(mut w: impl Write, points: &[[f32; 3]], data: PcdData) -> io::Result<()> {
+    let n = points.len();
+    let encoding = match data {
+        PcdData::Ascii => "ascii",
+        PcdData::Binary => "binary",
+    };
+    let mut out = format!(
+        "# .PCD v0.7 - Point Cloud Data file format\n\
+         VERSION 0.7\n\
+         FIELDS x y z\n\
+         SIZE 4 4 4\n\
+         TYPE F F F\n\
+         COUNT 1 1 1\n\
+         WIDTH {n}\n\
+         HEIGHT 1\n\
+         VIEWPOINT 0 0 0 1 0 0 0\n\
+         POINTS {n}\n\
+         DATA {encoding}\n"
+    )
+    .into_bytes();
+    for p in points {
+        match data {
+            PcdData::Ascii => writeln!(out, "{} {} {}", p[0], p[1], p[2])?,
+            PcdData::Binary => out.extend(p.iter().flat_map(|x| x.to_le_bytes())),
+        }
+    }
+    w.write_all(&out)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// The metadata of a PLY file, as returned by [`read_ply`].
+pub struct PlyMetadata {
+    /// The encoding of the file: `ascii`, `binary_little_endian` or `binary_big_endian`.
+    pub format: String,
+    /// The text of each `comment` and `obj_info` line of the header, in order.
+    pub comments: Vec<String>,
+    /// The number of vertices declared by the header, including any dropped for being non-finite.
+    pub n_vertices: usize,
+    /// The names of the properties of each vertex, in order.
+    pub properties: Vec<String>,
+}
+
+/// A property of each instance of an element of a PLY file.
+#[derive(Clone, Copy, Debug)]
+enum PlyProperty {
+    /// A single value.
+    Scalar(Scalar),
+    /// A list of values, stored as its length followed by its items.
+    List {
+        /// The type of the length of the list.
+        len: Scalar,
+        /// The type of each item of the list.
+        item: Scalar,
+    },
+}
+
+/// An element of a PLY file, such as `vertex` or `face`.
+#[derive(Clone, Debug)]
+struct PlyElement {
+    /// The name of the element.
+    name: String,
+    /// The number of instances of the element.
+    count: usize,
+    /// The name and type of each property of the element, in order.
+    properties: Vec<(String, PlyProperty)>,
+}
+
+/// Get the type of a PLY property from its name, such as `float` or `uint8`.
+fn ply_scalar(name: &str) -> Option<Scalar> {
+    let (kind, size) = match name {
+        "char" | "int8" => (b'I', 1),
+        "uchar" | "uint8" => (b'U', 1),
+        "short" | "int16" => (b'I', 2),
+        "ushort" | "uint16" => (b'U', 2),
+        "int" | "int32" => (b'I', 4),
+        "uint" | "uint32" => (b'U', 4),
+        "float" | "float32" => (b'F', 4),
+        "double" | "float64" => (b'F', 8),
+        _ => return None,
+    };
+    Some(Scalar { kind, size })
+}
+
+/// The values following the header of a PLY file.
+enum PlyBody<'a> {
+    /// Whitespace-separated text.
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    /// Packed binary values.
+    Binary {
+        /// The values not yet read.
+        rest: &'a [u8],
+        /// Whether values are stored in big-endian order.
+        big_endian: bool,
+    },
+}
+
+impl PlyBody<'_> {
+    /// Read the next value, which has type `scalar`.
+    fn next(&mut self, scalar: Scalar) -> Result<f64, ReadError> {
+        match self {
+            Self::Ascii(tokens) => tokens
+                .next()
+                .and_then(|t| t.parse().ok())
+                .ok_or(ReadError::BadData),
+            Self::Binary { rest, big_endian } => {
+                let x = scalar.decode(rest, *big_endian).ok_or(ReadError::BadData)?;
+                *rest = &rest[scalar.size..];
+                Ok(x)
+            }
+        }
+    }
+
+    /// Read every property of one instance of an element, returning the value of each scalar
+    /// property and `NaN` for each list.
+    fn instance(&mut self, properties: &[(String, PlyProperty)]) -> Result<Vec<f64>, ReadError> {
+        let mut values = Vec::with_capacity(properties.len());
+        for &(_, property) in properties {
+            values.push(match property {
+                PlyProperty::Scalar(scalar) => self.next(scalar)?,
+                PlyProperty::List { len, item } => {
+                    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                    for _ in 0..self.next(len)? as usize {
+                        self.next(item)?;
+                    }
+                    f64::NAN
+                }
+            });
+        }
+        Ok(values)
+    }
+}
+
+/// Parse the header at the start of `bytes`, returning its elements, its metadata (without the
+/// vertex count and properties filled in) and the bytes following it.
+fn parse_ply_header(bytes: &[u8]) -> Result<(Vec<PlyElement>, PlyMetadata, &[u8]), ReadError> {
+    let mut rest = bytes;
+    if header_line(&mut rest, "PLY signature")? != "ply" {
+        return Err(ReadError::BadHeader("missing PLY signature".into()));
+    }
+    let mut elements: Vec<PlyElement> = Vec::new();
+    let mut metadata = PlyMetadata {
+        format: String::new(),
+        comments: Vec::new(),
+        n_vertices: 0,
+        properties: Vec::new(),
+    };
+    loop {
+        let line = header_line(&mut rest, "end_header")?;
+        let values: Vec<&str> = line.split_whitespace().collect();
+        let bad = || ReadError::BadHeader(line.to_string());
+        match values.as_slice() {
+            ["end_header"] => break,
+            ["format", format, _] => metadata.format = (*format).to_string(),
+            ["comment" | "obj_info", ..] => metadata.comments.push(
+                line.split_once(char::is_whitespace)
+                    .map_or("", |(_, text)| text.trim())
+                    .to_string(),
+            ),
+            ["element", name, count] => elements.push(PlyElement {
+                name: (*name).to_string(),
+                count: count.parse().map_err(|_| bad())?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", len, item, name] => {
+                let property = PlyProperty::List {
+                    len: ply_scalar(len).ok_or_else(bad)?,
+                    item: ply_scalar(item).ok_or_else(bad)?,
+                };
+                let element = elements.last_mut().ok_or_else(bad)?;
+                element.properties.push(((*name).to_string(), property));
+            }
+            ["property", kind, name] => {
+                let property = PlyProperty::Scalar(ply_scalar(kind).ok_or_else(bad)?);
+                let element = elements.last_mut().ok_or_else(bad)?;
+                element.properties.push(((*name).to_string(), property));
+            }
+            _ => return Err(bad()),
+        }
+    }
+    Ok((elements, metadata, rest))
+}
+
+/// Read the `x`, `y` and `z` properties of every vertex of the PLY file in `r`, dropping any vertex
+/// with a non-finite coordinate.
+///
+/// Coordinates of any numeric type are converted to `f32`, and elements other than `vertex`, such
+/// as faces, are skipped.
+///
+/// # Errors
+///
+/// This function will return an error if reading from `r` fails, if the file is malformed, if it
+/// has no `vertex` element or its vertices lack an `x`, `y` or `z` property, or if its format is
+/// unknown.
+pub fn read_ply(mut r: impl Read) -> Result<(Vec<[f32; 3]>, PlyMetadata), ReadError> {
+    let mut bytes = Vec::new();
+    r.read_to_end(&mut bytes)?;
+
+    let (elements, mut metadata, rest) = parse_ply_header(&bytes)?;
+    let mut body = match metadata.format.as_str() {
+        "ascii" => PlyBody::Ascii(
+            std::str::from_utf8(rest)
+                .map_err(|_| ReadError::BadData)?
+                .split_ascii_whitespace(),
+        ),
+        "binary_little_endian" => PlyBody::Binary {
+            rest,
+            big_endian: false,
+        },
+        "binary_big_endian" => PlyBody::Binary {
+            rest,
+            big_endian: true,
+        },
+        other => return Err(ReadError::UnsupportedData(other.to_string())),
+    };
+
+    for element in elements {
+        if element.name != "vertex" {
+            // an element without properties takes no space, however many instances it has
+            if element.properties.is_empty() {
+                continue;
+            }
+            for _ in 0..element.count {
+                body.instance(&element.properties)?;
+            }
+            continue;
+        }
+
+        let mut coords = [0; 3];
+        for (c, name) in coords.iter_mut().zip(["x", "y", "z"]) {
+            *c = element
+                .properties
+                .iter()
+                .position(|(n, p)| n == name && matches!(p, PlyProperty::Scalar(_)))
+                .ok_or(ReadError::MissingField(name))?;
+        }
+        // the count comes from the header, but every vertex takes at least one byte
+        let mut points = Vec::with_capacity(element.count.min(rest.len()));
+        for _ in 0..element.count {
+            let values = body.instance(&element.properties)?;
+            #[allow(clippy::cast_possible_truncation)]
+            let p = coords.map(|c| values[c] as f32);
+            if p.iter().all(|x| x.is_finite()) {
+                points.push(p);
+            }
+        }
+        metadata.n_vertices = element.count;
+        metadata.properties = element.properties.into_iter().map(|(n, _)| n).collect();
+        return Ok((points, metadata));
+    }
+    Err(ReadError::MissingField("vertex"))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// The metadata of a LAS file, as returned by [`read_las`].
+pub struct LasMetadata {
+    /// The `(major, minor)` version of the LAS specification which the file follows.
+    pub version: (u8, u8),
+    /// The format of each point record, from 0 to 10.
+    pub point_format: u8,
+    /// The system which produced the points.
+    pub system_identifier: String,
+    /// The software which wrote the file.
+    pub generating_software: String,
+    /// The factor by which each stored integer coordinate is multiplied.
+    pub scale: [f64; 3],
+    /// The absolute coordinates of the origin of the returned points.
+    pub offset: [f64; 3],
+    /// The smallest absolute coordinates of any point, as recorded in the header.
+    pub min: [f64; 3],
+    /// The largest absolute coordinates of any point, as recorded in the header.
+    pub max: [f64; 3],
+}
+
+/// Copy the `N` bytes at offset `at` out of `bytes`, which must contain them.
+fn array_at<const N: usize>(bytes: &[u8], at: usize) -> [u8; N] {
+    let mut a = [0; N];
+    a.copy_from_slice(&bytes[at..at + N]);
+    a
+}
+
+/// Read a fixed-length, NUL-padded string from a LAS header.
+fn las_string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches('\0')
+        .trim()
+        .to_string()
+}
+
+/// Read the coordinates of every point of the LAS file in `r`.
+///
+/// Georeferenced surveys commonly have coordinates far too large to be stored precisely as
+/// `f32`s, so each point is returned relative to the offset recorded in the file's header, which
+/// is [`LasMetadata::offset`].
+/// Add the offset to a point to recover its absolute coordinates.
+///
+/// # Errors
+///
+/// This function will return an error if reading from `r` fails, if the file is malformed or
+/// truncated, or if it is compressed as a LAZ file.
+pub fn read_las(mut r: impl Read) -> Result<(Vec<[f32; 3]>, LasMetadata), ReadError> {
+    /// The length of the header of a version 1.2 file, which all later versions extend.
+    const MIN_HEADER_LEN: usize = 227;
+    /// The length of the header of a version 1.4 file.
+    const HEADER_LEN_1_4: usize = 375;
+
+    let mut bytes = Vec::new();
+    r.read_to_end(&mut bytes)?;
+    if bytes.len() < MIN_HEADER_LEN || &bytes[..4] != b"LASF" {
+        return Err(ReadError::BadHeader("missing LAS signature".into()));
+    }
+
+    let version = (bytes[24], bytes[25]);
+    let point_format = bytes[104];
+    if point_format & 0xc0 != 0 {
+        // the high bits of the point format mark a LAZ-compressed file
+        return Err(ReadError::UnsupportedData("LAZ".into()));
+    }
+    let f64_at = |at| f64::from_le_bytes(array_at(&bytes, at));
+    let metadata = LasMetadata {
+        version,
+        point_format,
+        system_identifier: las_string(&bytes[26..58]),
+        generating_software: las_string(&bytes[58..90]),
+        scale: [f64_at(131), f64_at(139), f64_at(147)],
+        offset: [f64_at(155), f64_at(163), f64_at(171)],
+        min: [f64_at(187), f64_at(203), f64_at(219)],
+        max: [f64_at(179), f64_at(195), f64_at(211)],
+    };
+
+    let data_start = u32::from_le_bytes(array_at(&bytes, 96)) as usize;
+    let record_len = usize::from(u16::from_le_bytes(array_at(&bytes, 105)));
+    let n_points = if version >= (1, 4) {
+        if bytes.len() < HEADER_LEN_1_4 {
+            return Err(ReadError::BadHeader("truncated LAS 1.4 header".into()));
+        }
+        usize::try_from(u64::from_le_bytes(array_at(&bytes, 247)))
+            .map_err(|_| ReadError::BadData)?
+    } else {
+        u32::from_le_bytes(array_at(&bytes, 107)) as usize
+    };
+    if record_len < 12 {
+        return Err(ReadError::BadHeader(format!(
+            "point records of {record_len} bytes cannot hold coordinates"
+        )));
+    }
+
+    let data = bytes.get(data_start..).ok_or(ReadError::BadData)?;
+    if n_points
+        .checked_mul(record_len)
+        .is_none_or(|n| data.len() < n)
+    {
+        return Err(ReadError::BadData);
+    }
+    let points = data
+        .chunks_exact(record_len)
+        .take(n_points)
+        .map(|record| {
+            #[allow(clippy::cast_possible_truncation)]
+            [0, 1, 2].map(|i| {
+                (f64::from(i32::from_le_bytes(array_at(record, 4 * i))) * metadata.scale[i]) as f32
+            })
+        })
+        .collect();
+    Ok((points, metadata))
+}
+
+/// Find the value of `key` in the dictionary `header` of a `.npy` file, returning the text which
+/// follows it.
+fn npy_entry<'a>(header: &'a str, key: &str) -> Result<&'a str, ReadError> {
+    ["'", "\""]
+        .iter()
+        .find_map(|q| header.split_once(&format!("{q}{key}{q}")))
+        .and_then(|(_, rest)| rest.trim_start().strip_prefix(':'))
+        .map(str::trim_start)
+        .ok_or_else(|| ReadError::BadHeader(format!("missing `{key}` in {header}")))
+}
+
+/// Read a 2-dimensional array of shape `(N, C)` from the `NumPy` `.npy` file in `r`, returning its
+/// rows.
+///
+/// Point clouds are stored with `C = 3`, and query traces with `C = 4`, as the center of each
+/// query followed by its radius.
+/// Arrays of `float32` or `float64` values in either byte order and in either C or Fortran order
+/// are supported, and values are converted to `f32`.
+/// Unlike the other readers in this module, no rows are dropped, even if they are not finite.
+///
+/// # Errors
+///
+/// This function will return an error if reading from `r` fails, if the file is malformed or
+/// truncated, if the array's type is not a float, or if it does not have exactly `C` columns.
+///
+/// # Examples
+///
+/// ```
+/// use captree::io::{read_npy, write_npy};
+///
+/// let trace = [[0.0, 1.0, 2.0, 0.05], [3.0, 4.0, 5.0, 0.1]];
+/// let mut bytes = Vec::new();
+/// write_npy(&mut bytes, &trace).unwrap();
+///
+/// assert_eq!(read_npy::<4>(&bytes[..]).unwrap(), trace);
+/// assert!(read_npy::<3>(&bytes[..]).is_err());
+/// ```
+pub fn read_npy<const C: usize>(mut r: impl Read) -> Result<Vec<[f32; C]>, ReadError> {
+    let mut bytes = Vec::new();
+    r.read_to_end(&mut bytes)?;
+    if bytes.len() < 10 || &bytes[..6] != b"\x93NUMPY" {
+        return Err(ReadError::BadHeader("missing NumPy signature".into()));
+    }
+    let (header_len, header_start) = match bytes[6] {
+        1 => (usize::from(u16::from_le_bytes(array_at(&bytes, 8))), 10),
+        2 | 3 if bytes.len() >= 12 => (u32::from_le_bytes(array_at(&bytes, 8)) as usize, 12),
+        v => return Err(ReadError::UnsupportedData(format!(".npy version {v}"))),
+    };
+    let header = bytes
+        .get(header_start..header_start + header_len)
+        .and_then(|h| std::str::from_utf8(h).ok())
+        .ok_or_else(|| ReadError::BadHeader("truncated .npy header".into()))?;
+    let data = &bytes[header_start + header_len..];
+
+    let bad = || ReadError::BadHeader(header.trim().to_string());
+    let descr = npy_entry(header, "descr")?;
+    let (big_endian, size) = match descr.get(1..4) {
+        Some("<f4") => (false, 4),
+        Some(">f4") => (true, 4),
+        Some("<f8") => (false, 8),
+        Some(">f8") => (true, 8),
+        _ => return Err(ReadError::UnsupportedData(descr.to_string())),
+    };
+    let scalar = Scalar { kind: b'F', size };
+    let fortran_order = npy_entry(header, "fortran_order")?.starts_with("True");
+    let shape = npy_entry(header, "shape")?
+        .strip_prefix('(')
+        .and_then(|s| s.split_once(')'))
+        .ok_or_else(bad)?
+        .0
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::parse)
+        .collect::<Result<Vec<usize>, _>>()
+        .map_err(|_| bad())?;
+    let &[n_rows, n_cols] = shape.as_slice() else {
+        return Err(bad());
+    };
+    if n_cols != C {
+        return Err(ReadError::BadHeader(format!(
+            "expected an array of shape (N, {C}), not {shape:?}"
+        )));
+    }
+
+    if n_rows
+        .checked_mul(C * size)
+        .is_none_or(|len| data.len() < len)
+    {
+        return Err(ReadError::BadData);
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    let value = |row: usize, col: usize| {
+        let i = if fortran_order {
+            col * n_rows + row
+        } else {
+            row * C + col
+        };
+        scalar
+            .decode(&data[i * size..], big_endian)
+            .unwrap_or(f64::NAN) as f32
+    };
+    Ok((0..n_rows)
+        .map(|row| std::array::from_fn(|col| value(row, col)))
+        .collect())
+}
+
+/// Write `rows` to `w` as a `NumPy` `.npy` file containing a `float32` array of shape `(N, C)`.
+///
+/// The file can be loaded with `numpy.load`, and read back with [`read_npy`].
+///
+/// # Errors
+///
+/// This function will return an error if writing to `w` fails.
+pub fn write_npy<const C: usize>(mut w: impl Write, rows: &[[f32; C]]) -> io::Result<()> {
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {C}), }}",
+        rows.len()
+    );
+    // pad so that the data starts on a 64-byte boundary, with room for the trailing newline
+    while (10 + header.len() + 1) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+    let header_len = u16::try_from(header.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "array shape is too long"))?;
+
+    let mut out = b"\x93NUMPY\x01\x00".to_vec();
+    out.extend(header_len.to_le_bytes());
+    out.extend(header.as_bytes());
+    out.extend(rows.iter().flatten().flat_map(|x| x.to_le_bytes()));
+    w.write_all(&out)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..100)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        for data in [PcdData::Ascii, PcdData::Binary] {
+            let mut bytes = Vec::new();
+            write_pcd(&mut bytes, &points, data).unwrap();
+            assert_eq!(read_pcd(&bytes[..]).unwrap(), points);
+        }
+    }
+
+    #[test]
+    fn extra_fields() {
+        let ascii = b"VERSION .7\nFIELDS rgb x y z normal\nSIZE 4 4 4 4 4\nTYPE U F F F F\n\
+            COUNT 1 1 1 1 3\nWIDTH 3\nHEIGHT 1\nPOINTS 3\nDATA ascii\n\
+            7 1 2 3 0 0 1\n8 nan nan nan 0 0 1\n9 -4.5 0.25 6 0 0 1\n";
+        assert_eq!(
+            read_pcd(&ascii[..]).unwrap(),
+            [[1.0, 2.0, 3.0], [-4.5, 0.25, 6.0]]
+        );
+
+        let mut binary =
+            b"FIELDS i x y z\nSIZE 2 8 1 4\nTYPE I F I U\nPOINTS 2\nDATA binary\n".to_vec();
+        for (i, x, y, z) in [(-3i16, 1.5f64, -2i8, 7u32), (0, -0.5, 100, 0)] {
+            binary.extend(i.to_le_bytes());
+            binary.extend(x.to_le_bytes());
+            binary.extend(y.to_le_bytes());
+            binary.extend(z.to_le_bytes());
+        }
+        assert_eq!(
+            read_pcd(&binary[..]).unwrap(),
+            [[1.5, -2.0, 7.0], [-0.5, 100.0, 0.0]]
+        );
+        assert!(matches!(
+            read_pcd(&binary[..binary.len() - 1]),
+            Err(ReadError::BadData)
+        ));
+    }
+
+    #[test]
+    fn pcd_counts_are_untrusted() {
+        for n in [usize::MAX, 1 << 40, 2] {
+            for data in ["ascii", "binary"] {
+                let bytes = format!(
+                    "FIELDS x y z\nSIZE 4 4 4\nTYPE F F F\nPOINTS {n}\nDATA {data}\n0 0 0\n"
+                );
+                assert!(matches!(
+                    read_pcd(bytes.as_bytes()),
+                    Err(ReadError::BadData)
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn io_errors_are_sources() {
+        /// A reader which always fails.
+        struct Broken;
+
+        impl Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("unplugged"))
+            }
+        }
+
+        let e = read_pcd(Broken).unwrap_err();
+        assert!(matches!(e, ReadError::Io(_)));
+        let source = std::error::Error::source(&e).unwrap();
+        assert_eq!(source.to_string(), "unplugged");
+    }
+
+    #[test]
+    fn ply_encodings() {
+        let header = |format| {
+            format!(
+                "ply\nformat {format} 1.0\ncomment made by hand\nelement camera 1\n\
+                 property list uchar int indices\nelement vertex 3\nproperty double z\n\
+                 property float x\nproperty uchar red\nproperty short y\nend_header\n"
+            )
+            .into_bytes()
+        };
+        let expected = [[1.5, -2.0, 7.0], [0.25, 100.0, -3.0]];
+
+        let mut ascii = header("ascii");
+        ascii.extend(b"2 10 20\n7 1.5 255 -2\nnan 0 0 0\n-3 0.25 0 100\n");
+        let (points, metadata) = read_ply(&ascii[..]).unwrap();
+        assert_eq!(points, expected);
+        assert_eq!(
+            metadata,
+            PlyMetadata {
+                format: "ascii".into(),
+                comments: vec!["made by hand".into()],
+                n_vertices: 3,
+                properties: vec!["z".into(), "x".into(), "red".into(), "y".into()],
+            }
+        );
+
+        for big_endian in [false, true] {
+            let mut binary = header(if big_endian {
+                "binary_big_endian"
+            } else {
+                "binary_little_endian"
+            });
+            let mut push = |bytes: &[u8]| {
+                let mut bytes = bytes.to_vec();
+                if big_endian {
+                    bytes.reverse();
+                }
+                binary.extend(bytes);
+            };
+            push(&[2]);
+            push(&10i32.to_le_bytes());
+            push(&20i32.to_le_bytes());
+            for (z, x, y) in [
+                (7.0f64, 1.5f32, -2i16),
+                (f64::NAN, 0.0, 0),
+                (-3.0, 0.25, 100),
+            ] {
+                push(&z.to_le_bytes());
+                push(&x.to_le_bytes());
+                push(&[255]);
+                push(&y.to_le_bytes());
+            }
+            assert_eq!(read_ply(&binary[..]).unwrap().0, expected);
+            assert!(matches!(
+                read_ply(&binary[..binary.len() - 1]),
+                Err(ReadError::BadData)
+            ));
+        }
+    }
+
+    #[test]
+    fn ply_counts_are_untrusted() {
+        let header = |format, n| {
+            format!(
+                "ply\nformat {format} 1.0\nelement empty {}\nelement vertex {n}\n\
+                 property float x\nproperty float y\nproperty float z\nend_header\n",
+                usize::MAX
+            )
+            .into_bytes()
+        };
+
+        let mut one = header("ascii", 1);
+        one.extend(b"1 2 3\n");
+        assert_eq!(read_ply(&one[..]).unwrap().0, [[1.0, 2.0, 3.0]]);
+
+        for n in [usize::MAX, 1 << 40] {
+            let mut ascii = header("ascii", n);
+            ascii.extend(b"1 2 3\n");
+            assert!(matches!(read_ply(&ascii[..]), Err(ReadError::BadData)));
+
+            let mut binary = header("binary_little_endian", n);
+            binary.extend([1.0f32, 2.0, 3.0].iter().flat_map(|x| x.to_le_bytes()));
+            assert!(matches!(read_ply(&binary[..]), Err(ReadError::BadData)));
+        }
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn las_points() {
+        let mut bytes = vec![0; 227];
+        bytes[..4].copy_from_slice(b"LASF");
+        bytes[24..26].copy_from_slice(&[1, 2]);
+        bytes[58..64].copy_from_slice(b"survey");
+        bytes[96..100].copy_from_slice(&227u32.to_le_bytes());
+        bytes[105..107].copy_from_slice(&20u16.to_le_bytes());
+        bytes[107..111].copy_from_slice(&2u32.to_le_bytes());
+        for (i, x) in [0.01, 0.01, 0.001, 5e5, 4e6, 10.0].into_iter().enumerate() {
+            bytes[131 + 8 * i..][..8].copy_from_slice(&f64::to_le_bytes(x));
+        }
+        for record in [[100i32, -250, 3000], [-1, 0, 1]] {
+            for v in record {
+                bytes.extend(v.to_le_bytes());
+            }
+            bytes.extend([0; 8]);
+        }
+
+        let (points, metadata) = read_las(&bytes[..]).unwrap();
+        assert_eq!(points, [[1.0, -2.5, 3.0], [-0.01, 0.0, 0.001]]);
+        assert_eq!(metadata.version, (1, 2));
+        assert_eq!(metadata.generating_software, "survey");
+        assert_eq!(metadata.offset, [5e5, 4e6, 10.0]);
+
+        assert!(matches!(
+            read_las(&bytes[..bytes.len() - 1]),
+            Err(ReadError::BadData)
+        ));
+        bytes[104] = 0x80;
+        assert!(matches!(
+            read_las(&bytes[..]),
+            Err(ReadError::UnsupportedData(_))
+        ));
+    }
+
+    #[test]
+    fn npy_layouts() {
+        let mut rng = thread_rng();
+        let trace: Vec<[f32; 4]> = (0..100)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let mut bytes = Vec::new();
+        write_npy(&mut bytes, &trace).unwrap();
+        assert_eq!((10 + usize::from(bytes[8])) % 64, 0);
+        assert_eq!(read_npy::<4>(&bytes[..]).unwrap(), trace);
+        assert!(matches!(
+            read_npy::<4>(&bytes[..bytes.len() - 1]),
+            Err(ReadError::BadData)
+        ));
+
+        // as written by `np.asfortranarray(np.array([[1, 2, 3], [4, 5, 6]], dtype=">f8"))`
+        let header = b"{'descr': '>f8', 'fortran_order': True, 'shape': (2, 3), }";
+        let mut fortran = b"\x93NUMPY\x01\x00".to_vec();
+        fortran.extend(u16::try_from(header.len()).unwrap().to_le_bytes());
+        fortran.extend(header);
+        for x in [1.0f64, 4.0, 2.0, 5.0, 3.0, 6.0] {
+            fortran.extend(x.to_be_bytes());
+        }
+        assert_eq!(
+            read_npy::<3>(&fortran[..]).unwrap(),
+            [[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]
+        );
+        assert!(matches!(
+            read_npy::<4>(&fortran[..]),
+            Err(ReadError::BadHeader(_))
+        ));
+    }
+}
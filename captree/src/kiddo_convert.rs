@@ -0,0 +1,75 @@
+//! Conversions between [`PkdTree`]s and the trees of the [`kiddo`](::kiddo) crate.
+
+use ::kiddo::ImmutableKdTree;
+
+use crate::{Axis, NewCaptError, PkdTree};
+
+/// Implement conversions between `PkdTree`s and `kiddo::ImmutableKdTree`s over `$a`.
+macro_rules! impl_kiddo {
+    ($a: ty) => {
+        impl<const K: usize> From<&PkdTree<K, $a>> for ImmutableKdTree<$a, K> {
+            /// Build a kiddo tree containing the points of `pkdt`.
+            ///
+            /// The item stored with each point is its index in [`PkdTree::points`].
+            fn from(pkdt: &PkdTree<K, $a>) -> Self {
+                Self::new_from_slice(pkdt.points())
+            }
+        }
+
+        impl<const K: usize> TryFrom<&ImmutableKdTree<$a, K>> for PkdTree<K, $a> {
+            type Error = NewCaptError;
+
+            /// Build a `PkdTree` containing the points of a kiddo tree, discarding their items.
+            ///
+            /// # Errors
+            ///
+            /// This function will return `Err(NewCaptError::NonFinite)` if any point in `tree` is
+            /// not finite.
+            fn try_from(tree: &ImmutableKdTree<$a, K>) -> Result<Self, Self::Error> {
+                let points: Vec<[$a; K]> = tree.iter().map(|(_, p)| p).collect();
+                if points
+                    .iter()
+                    .any(|p| p.iter().any(|x| !Axis::is_finite(*x)))
+                {
+                    return Err(NewCaptError::NonFinite);
+                }
+                Ok(Self::build(points))
+            }
+        }
+    };
+}
+
+impl_kiddo!(f32);
+impl_kiddo!(f64);
+
+#[cfg(test)]
+mod tests {
+    use ::kiddo::SquaredEuclidean;
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+    use crate::distsq;
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn round_trip() {
+        let mut rng = thread_rng();
+        let points: Vec<[f64; 3]> = (0..500)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let pkdt = PkdTree::new(&points);
+
+        let kdt = ImmutableKdTree::from(&pkdt);
+        assert_eq!(kdt.size(), points.len());
+        for _ in 0..100 {
+            let needle = [rng.gen(), rng.gen(), rng.gen()];
+            let nearest = kdt.nearest_one::<SquaredEuclidean>(&needle);
+            #[allow(clippy::cast_possible_truncation)]
+            let id = nearest.item as usize;
+            assert_eq!(pkdt.points()[id], pkdt.get_point(pkdt.query1_exact(needle)));
+            assert!((distsq(pkdt.points()[id], needle) - nearest.distance).abs() < 1e-12);
+        }
+
+        assert_eq!(PkdTree::try_from(&kdt).unwrap(), pkdt);
+    }
+}
@@ -57,6 +57,57 @@
 //! assert!(capt.collides(&center, radius1));
 //! ```
 //!
+//! ## Features
+//!
+//! - `simd`: enable SIMD-parallel collision checking via [`Capt::collides_simd`], with queries
+//!   batched by `SimdQueryBatch`, and via `Capt::collides_any`, which picks its vector width at
+//!   runtime. Requires a nightly compiler.
+//! - `simd128`: enable `simd` and compile its query kernels with WASM SIMD128 instructions on
+//!   wasm32 targets.
+//! - `wide`: enable vectorized collision checking on stable Rust via `Capt::collides_wide` and
+//!   `PkdTree::might_collide_wide`, built on the [`wide`](https://docs.rs/wide) crate.
+//! - `rkyv`: enable zero-copy archiving of [`Capt`]s with [`rkyv`](https://docs.rs/rkyv), so that
+//!   an archived tree can be validated and queried in place as an `ArchivedCapt`.
+//! - `kiddo`: enable conversions between [`PkdTree`]s and the `ImmutableKdTree`s of the [`kiddo`](https://docs.rs/kiddo)
+//!   crate.
+//! - `nalgebra`: enable construction of [`Capt`]s from the points and matrices of [`nalgebra`](https://docs.rs/nalgebra),
+//!   queries over its points, and rebuilding a tree under an isometry with `Capt::transformed`.
+//! - `glam`: enable construction of [`Capt`]s from slices of [`glam`](https://docs.rs/glam)
+//!   vectors, reading `Vec3` and `DVec3` slices in place, and queries over its vectors.
+//! - `parry3d`: enable collision checking of [`Capt`]s against balls, capsules and compounds from [`parry3d`](https://docs.rs/parry3d)
+//!   via `Capt::collides_shape`.
+//! - `ros`: enable building trees from point clouds laid out as ROS `sensor_msgs/PointCloud2`
+//!   messages, via the `ros` module.
+//! - `stats`: enable instrumented queries, such as `Capt::collides_with_stats`, which count the
+//!   work done by each query in a `QueryStats`.
+//! - `tracing`: emit [`tracing`](https://docs.rs/tracing) spans around the phases of construction
+//!   (partitioning, affordance filtering and buffer layout) and around batched queries.
+//! - `testing`: enable the `testing` module of point cloud and query generators and brute-force
+//!   oracles, for checking trees built from your own clouds and radius ranges.
+//! - `pool`: enable `QueryPool`, which owns worker threads that answer batches of queries against
+//!   any [`Collider`], returning results through futures or callbacks.
+//! - `stream`: enable `Capt::collides_stream`, which answers an async [`Stream`](https://docs.rs/futures-core)
+//!   of queries in groups of SIMD width. Implies `simd`.
+//! - `prefetch`: align the buffers of each tree to cache lines and issue prefetch hints for
+//!   affordance buffers as soon as their locations are known during a query. Hints are only issued
+//!   on x86-64 and 64-bit ARM; other targets still get the alignment.
+//! - `aligned`: align the buffers of each tree to cache lines. Affordance buffers are always stored
+//!   transposed in whole SIMD lanes, so this aligns every lane to the vector width (up to 64
+//!   bytes), and vectorized queries then scan them with aligned loads instead of unaligned ones.
+//!
+//! ## WebAssembly
+//!
+//! This crate builds for `wasm32-unknown-unknown`, so that browser-based simulators and
+//! visualizers can run the same collision checks client-side.
+//! That target has no file system or clock, so the `file` and `trace` modules, along with
+//! `Capt::build_file` and [`Capt::try_new_with_deadline`], are unavailable there; trees can still
+//! be moved in and out of byte buffers with the `rkyv` feature.
+//! It also has no threads, so `sdf::bake` samples its grid on the calling thread, and the `pool`
+//! feature should not be enabled since `QueryPool` cannot start its workers.
+//! The `simd` feature lowers to WASM SIMD128 instructions when built with
+//! `RUSTFLAGS="-C target-feature=+simd128"`, or for the query kernels alone with the `simd128`
+//! feature; either way, the module then only loads on engines which support SIMD128.
+//!
 //! ## License
 //!
 //! This work is licensed to you under the Polyform Non-Commercial License.
@@ -66,24 +117,124 @@
 
 use std::{
     array,
-    fmt::Debug,
+    fmt::{self, Debug},
     mem::size_of,
-    ops::{Add, Sub},
+    ops::{Add, ControlFlow, Range, Sub},
 };
 
 #[cfg(feature = "simd")]
 use std::{
     ops::{AddAssign, Mul},
-    ptr,
     simd::{
         cmp::{SimdPartialEq, SimdPartialOrd},
         ptr::SimdConstPtr,
-        LaneCount, Mask, Simd, SimdElement, SupportedLaneCount,
+        Mask, Simd, SimdElement,
     },
 };
 
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::Instant;
+
 use elain::{Align, Alignment};
 
+/// Enter a [`tracing`](https://docs.rs/tracing) span at `$level` which lasts until the end of the
+/// enclosing block.
+/// Does nothing if the `tracing` feature is disabled.
+macro_rules! span {
+    ($level: ident, $name: literal $(, $($fields: tt)*)?) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::span!(tracing::Level::$level, $name $(, $($fields)*)?).entered();
+    };
+}
+
+#[cfg(feature = "rkyv")]
+mod archive;
+#[cfg(feature = "simd")]
+mod batch;
+mod buf;
+pub mod cluster;
+mod compact;
+mod debug;
+mod density;
+mod dynamic;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+mod external;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub mod file;
+pub mod filter;
+#[cfg(feature = "glam")]
+mod glam_convert;
+mod grid;
+mod indexed;
+pub mod io;
+#[cfg(feature = "kiddo")]
+mod kiddo_convert;
+mod mahalanobis;
+mod moving;
+mod multires;
+#[cfg(feature = "nalgebra")]
+mod nalgebra_convert;
+mod nearest;
+mod octree;
+#[cfg(feature = "parry3d")]
+mod parry_shapes;
+mod pkdt;
+#[cfg(feature = "pool")]
+mod pool;
+mod radius;
+#[cfg(feature = "ros")]
+pub mod ros;
+mod scaled;
+pub mod sdf;
+mod spacetime;
+mod split;
+#[cfg(feature = "wide")]
+mod stable_simd;
+#[cfg(feature = "wide")]
+pub use stable_simd::AxisWide;
+#[cfg(feature = "stats")]
+mod stats;
+#[cfg(feature = "stream")]
+mod stream;
+mod swept;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub mod trace;
+mod transform;
+mod validate;
+
+#[cfg(feature = "rkyv")]
+pub use archive::{ArchivedCapt, CaptResolver};
+#[cfg(feature = "simd")]
+pub use batch::{BatchCollider, SimdQueryBatch};
+pub use buf::{Alloc, Global};
+pub use compact::CompactCapt;
+pub use debug::{DebugGeometry, SplitPlane};
+pub use density::Kernel;
+pub use dynamic::DynCapt;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub use external::BuildFileError;
+pub use grid::GridCapt;
+pub use indexed::IndexedCapt;
+pub use mahalanobis::MahalanobisCapt;
+pub use moving::MovingCapt;
+pub use multires::MultiResTree;
+pub use octree::Octree;
+pub use pkdt::{AnytimeNearest, PkdTree};
+#[cfg(feature = "pool")]
+pub use pool::{QueryPanicked, QueryPool, QueryTicket};
+pub use radius::{Radius, RadiusRange, RadiusSq};
+pub use scaled::ScaledCapt;
+pub use spacetime::SpaceTimeCapt;
+pub use split::SplitPolicy;
+#[cfg(feature = "stats")]
+pub use stats::QueryStats;
+#[cfg(feature = "stream")]
+pub use stream::CollisionStream;
+pub use transform::AxisIsometry;
+pub use validate::InvariantError;
+
 /// A generic trait representing values which may be used as an "axis;" that is, elements of a
 /// vector representing a point.
 ///
@@ -216,9 +367,45 @@ pub trait IndexSimd: SimdElement + Default {
     /// # Safety
     ///
     /// This function is only safe if all values of `x` are valid when converted to a `usize`.
-    unsafe fn to_simd_usize_unchecked<const L: usize>(x: Simd<Self, L>) -> Simd<usize, L>
-    where
-        LaneCount<L>: SupportedLaneCount;
+    unsafe fn to_simd_usize_unchecked<const L: usize>(x: Simd<Self, L>) -> Simd<usize, L>;
+}
+
+/// A structure which can check whether any of its points is inside a ball.
+///
+/// Every collision-checking structure in this crate implements this trait, so that code which
+/// only needs single queries can be written once and run against each of them, such as when
+/// comparing their performance on the same data.
+///
+/// # Examples
+///
+/// ```
+/// use captree::{Capt, Collider, GridCapt, Octree};
+///
+/// fn count_hits(c: &impl Collider<3, f32>, centers: &[[f32; 3]], r: f32) -> usize {
+///     centers
+///         .iter()
+///         .filter(|center| c.collides(center, r))
+///         .count()
+/// }
+///
+/// let points = [[0.0; 3], [1.0; 3]];
+/// let centers = [[0.1; 3], [0.5; 3], [0.9; 3]];
+///
+/// let capt = Capt::<3>::new(&points, (0.0, 0.5));
+/// let octree = Octree::<3>::new(&points, (0.0, 0.5));
+/// let grid = GridCapt::<3>::new(&points, (0.0, 0.5), 0.25);
+///
+/// assert_eq!(count_hits(&capt, &centers, 0.2), 2);
+/// assert_eq!(count_hits(&octree, &centers, 0.2), 2);
+/// assert_eq!(count_hits(&grid, &centers, 0.2), 2);
+/// ```
+pub trait Collider<const K: usize, A> {
+    #[must_use]
+    /// Determine whether a point in this structure is within a distance of `radius` to `center`.
+    ///
+    /// As with [`Capt::collides`], the result is only guaranteed to be correct if `radius` is
+    /// inside the radius range used to construct this structure.
+    fn collides(&self, center: &[A; K], radius: A) -> bool;
 }
 
 macro_rules! impl_axis {
@@ -241,10 +428,7 @@ macro_rules! impl_axis {
         }
 
         #[cfg(feature = "simd")]
-        impl<const L: usize> AxisSimd<Mask<$tm, L>> for $t
-        where
-            LaneCount<L>: SupportedLaneCount,
-        {
+        impl<const L: usize> AxisSimd<Mask<$tm, L>> for $t {
             fn any(mask: Mask<$tm, L>) -> bool {
                 Mask::<$tm, L>::any(mask)
             }
@@ -260,11 +444,7 @@ macro_rules! impl_idx {
 
         #[cfg(feature = "simd")]
         impl IndexSimd for $t {
-            #[must_use]
-            unsafe fn to_simd_usize_unchecked<const L: usize>(x: Simd<Self, L>) -> Simd<usize, L>
-            where
-                LaneCount<L>: SupportedLaneCount,
-            {
+            unsafe fn to_simd_usize_unchecked<const L: usize>(x: Simd<Self, L>) -> Simd<usize, L> {
                 x.to_array().map(|a| a.try_into().unwrap_unchecked()).into()
             }
         }
@@ -280,6 +460,8 @@ impl_idx!(u32);
 impl_idx!(u64);
 impl_idx!(usize);
 
+use buf::{prefetch, Buf, Chunked};
+
 /// Clamp a floating-point number.
 fn clamp<A: PartialOrd>(x: A, min: A, max: A) -> A {
     if x < min {
@@ -291,6 +473,42 @@ fn clamp<A: PartialOrd>(x: A, min: A, max: A) -> A {
     }
 }
 
+#[inline]
+/// Find the index of the leaf cell containing `center` in a tree with tests `tests`.
+fn forward_pass<A: Axis, const K: usize>(tests: &[A], center: &[A; K]) -> usize {
+    let mut test_idx = 0;
+    let mut k = 0;
+    for _ in 0..tests.len().trailing_ones() {
+        test_idx =
+            2 * test_idx + 1 + usize::from(unsafe { *tests.get_unchecked(test_idx) } <= center[k]);
+        k = (k + 1) % K;
+    }
+
+    test_idx - tests.len()
+}
+
+#[inline]
+/// Find the index of the leaf cell containing `center` in a tree with tests `tests`, where the
+/// `i`-th test splits along axis `split_dims[i]`.
+fn forward_pass_dims<A: Axis, const K: usize>(
+    tests: &[A],
+    split_dims: &[u8],
+    center: &[A; K],
+) -> usize {
+    let mut test_idx = 0;
+    for _ in 0..tests.len().trailing_ones() {
+        let (test, k) = unsafe {
+            (
+                *tests.get_unchecked(test_idx),
+                usize::from(*split_dims.get_unchecked(test_idx)),
+            )
+        };
+        test_idx = 2 * test_idx + 1 + usize::from(test <= center[k]);
+    }
+
+    test_idx - tests.len()
+}
+
 #[inline]
 #[allow(clippy::cast_possible_wrap)]
 #[cfg(feature = "simd")]
@@ -302,7 +520,6 @@ where
     Simd<A, L>: SimdPartialOrd,
     Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
     A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask>,
-    LaneCount<L>: SupportedLaneCount,
 {
     let mut test_idxs: Simd<isize, L> = Simd::splat(0);
     let mut k = 0;
@@ -312,16 +529,268 @@ where
         let cmp_results: Mask<isize, L> = centers[k % K].simd_ge(relevant_tests).into();
 
         let one = Simd::splat(1);
-        test_idxs = (test_idxs << one) + one + (cmp_results.to_int() & Simd::splat(1));
+        test_idxs = (test_idxs << one) + one + (cmp_results.to_simd() & Simd::splat(1));
         k = (k + 1) % K;
     }
 
     test_idxs - Simd::splat(tests.len() as isize)
 }
 
+#[inline]
+#[allow(clippy::cast_possible_wrap)]
+#[cfg(feature = "simd")]
+/// A SIMD version of [`forward_pass_dims`].
+fn forward_pass_simd_dims<A, const K: usize, const L: usize>(
+    tests: &[A],
+    split_dims: &[u8],
+    centers: &[Simd<A, L>; K],
+) -> Simd<isize, L>
+where
+    Simd<A, L>: SimdPartialOrd,
+    Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+    A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask>,
+{
+    let mut test_idxs: Simd<isize, L> = Simd::splat(0);
+    for _ in 0..tests.len().trailing_ones() {
+        let test_ptrs = Simd::splat(tests.as_ptr()).wrapping_offset(test_idxs);
+        let relevant_tests: Simd<A, L> = unsafe { Simd::gather_ptr(test_ptrs) };
+        let dim_ptrs = Simd::splat(split_dims.as_ptr()).wrapping_offset(test_idxs);
+        let dims = unsafe { Simd::gather_ptr(dim_ptrs) }.to_array();
+        let lane_centers = Simd::from_array(array::from_fn(|j| centers[usize::from(dims[j])][j]));
+        let cmp_results: Mask<isize, L> = lane_centers.simd_ge(relevant_tests).into();
+
+        let one = Simd::splat(1);
+        test_idxs = (test_idxs << one) + one + (cmp_results.to_simd() & Simd::splat(1));
+    }
+
+    test_idxs - Simd::splat(tests.len() as isize)
+}
+
+#[allow(clippy::inline_always)]
+#[inline(always)]
+#[cfg(feature = "simd")]
+/// Run `f`, compiled for the newest vector instruction set supported by the running CPU.
+///
+/// On x86-64, this detects AVX-512 and AVX2 at runtime, so that a binary built for a baseline
+/// target can use their encodings, such as fused multiply-adds and mask registers.
+/// This never changes the number of lanes `f` works on: a kernel over `L` lanes is only split
+/// across fewer registers, so lanes narrower than the detected registers leave them partly idle.
+/// Kernels which pick their own number of lanes, as [`Capt::collides_any`] does, should choose it
+/// by [`vector_width`].
+/// NEON is always available on 64-bit ARM, so no detection is needed there.
+/// WASM has no runtime detection, since a module using SIMD128 fails to load on engines without
+/// it, so on wasm32 SIMD128 is used only if the `simd128` feature or the target feature is
+/// enabled.
+fn multiversion<R>(f: impl FnOnce() -> R) -> R {
+    #[cfg(all(
+        feature = "simd128",
+        target_arch = "wasm32",
+        not(target_feature = "simd128")
+    ))]
+    let f = || {
+        #[target_feature(enable = "simd128")]
+        unsafe fn simd128<R>(f: impl FnOnce() -> R) -> R {
+            f()
+        }
+
+        // SAFETY: enabling the `simd128` feature requires that the engine supports SIMD128.
+        unsafe { simd128(f) }
+    };
+
+    #[cfg(all(target_arch = "x86_64", not(target_feature = "avx512f")))]
+    {
+        #[target_feature(enable = "avx512f,avx512vl,avx512bw,avx2,fma")]
+        unsafe fn avx512<R>(f: impl FnOnce() -> R) -> R {
+            f()
+        }
+
+        #[cfg(not(target_feature = "avx2"))]
+        #[target_feature(enable = "avx2,fma")]
+        unsafe fn avx2<R>(f: impl FnOnce() -> R) -> R {
+            f()
+        }
+
+        if is_x86_feature_detected!("avx512f")
+            && is_x86_feature_detected!("avx512vl")
+            && is_x86_feature_detected!("avx512bw")
+        {
+            // SAFETY: the required target features were just detected.
+            return unsafe { avx512(f) };
+        }
+
+        #[cfg(not(target_feature = "avx2"))]
+        if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+            // SAFETY: the required target features were just detected.
+            return unsafe { avx2(f) };
+        }
+    }
+
+    f()
+}
+
+#[cfg(feature = "simd")]
+#[cfg_attr(not(target_arch = "x86_64"), allow(clippy::missing_const_for_fn))]
+/// Get the width, in bytes, of the widest vector registers which [`multiversion`] compiles for on
+/// the running CPU.
+fn vector_width() -> usize {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx512f")
+            && is_x86_feature_detected!("avx512vl")
+            && is_x86_feature_detected!("avx512bw")
+        {
+            return 64;
+        }
+        if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+            return 32;
+        }
+    }
+
+    // SSE2, NEON and SIMD128 are all 128 bits wide
+    16
+}
+
+#[inline]
+#[cfg(feature = "simd")]
+/// Determine which lanes of `centers` might collide with a point afforded by their respective leaf
+/// cells `zs`, by testing their balls against the bounding boxes of the cells in `aabbs`.
+///
+/// # Safety
+///
+/// This function will result in undefined behavior if any element of `zs` is not a valid index
+/// into `aabbs`.
+unsafe fn aabb_prune_simd<A, const K: usize, const L: usize>(
+    aabbs: &[Aabb<A, K>],
+    zs: Simd<isize, L>,
+    centers: &[Simd<A, L>; K],
+    radii: Simd<A, L>,
+) -> Mask<isize, L>
+where
+    Simd<A, L>: SimdPartialOrd + Sub<Output = Simd<A, L>>,
+    Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+    A: Axis + SimdElement,
+{
+    let mut inbounds = Mask::splat(true);
+
+    let mut aabb_ptrs = Simd::splat(aabbs.as_ptr()).wrapping_offset(zs).cast();
+
+    for center in centers {
+        inbounds &= Mask::<isize, L>::from(
+            (Simd::gather_select_ptr(aabb_ptrs, inbounds, Simd::splat(A::NEG_INFINITY)) - radii)
+                .simd_le(*center),
+        );
+        aabb_ptrs = aabb_ptrs.wrapping_add(Simd::splat(1));
+    }
+    for center in centers {
+        inbounds &= Mask::<isize, L>::from(
+            Simd::gather_select_ptr(aabb_ptrs, inbounds, Simd::splat(A::NEG_INFINITY))
+                .simd_ge(*center - radii),
+        );
+        aabb_ptrs = aabb_ptrs.wrapping_add(Simd::splat(1));
+    }
+
+    inbounds
+}
+
+#[inline]
+#[cfg(feature = "simd")]
+/// Retrieve the start and end indices of the affordance buffers for each of the leaf cells `zs`.
+///
+/// # Safety
+///
+/// This function will result in undefined behavior if any element of `zs` is not a valid leaf
+/// index for a tree whose affordance buffers start at `starts`.
+unsafe fn buffer_ranges_simd<I: IndexSimd, const L: usize>(
+    starts: &[I],
+    zs: Simd<isize, L>,
+) -> ([usize; L], [usize; L]) {
+    let start_ptrs = Simd::splat(starts.as_ptr()).wrapping_offset(zs);
+    let starts = I::to_simd_usize_unchecked(Simd::gather_ptr(start_ptrs)).to_array();
+    let ends =
+        I::to_simd_usize_unchecked(Simd::gather_ptr(start_ptrs.wrapping_add(Simd::splat(1))))
+            .to_array();
+
+    (starts, ends)
+}
+
+#[allow(clippy::inline_always)]
+#[inline(always)]
+#[cfg(feature = "simd")]
+/// Determine whether any ball in `centers` and `radii` contains a point in the affordance buffer
+/// of its respective leaf cell in `zs`.
+///
+/// This is the body of every SIMD query once the leaf containing each center has been found:
+/// `aabbs` and `starts` are indexed by leaf, and `afforded` holds the lanes of afforded points
+/// along each axis, as in a [`Capt`].
+/// The `M` queries need not be as many as the `L` afforded points in each lane.
+///
+/// # Safety
+///
+/// This function will result in undefined behavior if any element of `zs` is not a valid index
+/// into `aabbs` and `starts`, or if any affordance buffer range in `starts` does not lie within
+/// `afforded`.
+unsafe fn collides_leaves_simd<A, I, Al, const K: usize, const L: usize, const M: usize>(
+    aabbs: &[Aabb<A, K>],
+    starts: &[I],
+    afforded: &[Buf<MySimd<A, L>, Al>; K],
+    zs: Simd<isize, M>,
+    centers: &[Simd<A, M>; K],
+    radii: Simd<A, M>,
+    active: Mask<isize, M>,
+) -> bool
+where
+    I: IndexSimd,
+    Al: Alloc,
+    Simd<A, M>: SimdPartialOrd + Sub<Output = Simd<A, M>>,
+    Mask<isize, M>: From<<Simd<A, M> as SimdPartialEq>::Mask>,
+    Simd<A, L>: SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+    A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask>,
+    Align<L>: Alignment,
+{
+    let inbounds = aabb_prune_simd(aabbs, zs, centers, radii) & active;
+    if !inbounds.any() {
+        return false;
+    }
+
+    // retrieve start/end pointers for the affordance buffer
+    let (starts, ends) = buffer_ranges_simd(starts, zs);
+    for (&start, in_bounds) in starts.iter().zip(inbounds.to_array()) {
+        if in_bounds {
+            for sk in afforded {
+                prefetch(sk.as_ptr().wrapping_add(start));
+            }
+        }
+    }
+
+    starts
+        .into_iter()
+        .zip(ends)
+        .zip(inbounds.to_array())
+        .enumerate()
+        .filter_map(|(j, r)| r.1.then_some((j, r.0)))
+        .any(|(j, (start, end))| {
+            let mut n_center = [Simd::splat(A::ZERO); K];
+            for k in 0..K {
+                n_center[k] = Simd::splat(centers[k][j]);
+            }
+            let rs = Simd::splat(radii[j]);
+            let rs_sq = rs * rs;
+            (start..end).any(|i| {
+                let mut dists_sq = Simd::splat(A::ZERO);
+                #[allow(clippy::needless_range_loop)]
+                for k in 0..K {
+                    let vals: Simd<A, L> = load_lane(&afforded[k], i, Simd::from_array);
+                    let diff = vals - n_center[k];
+                    dists_sq += diff * diff;
+                }
+                A::any(dists_sq.simd_le(rs_sq))
+            })
+        })
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-/// A stable-safe wrapper for `[A; L]` which is aligned to `L`.
+/// A stable-safe wrapper for `[A; L]` which is aligned to `L` bytes.
 /// Equivalent to a `Simd`, but easier to work with.
 struct MySimd<A, const L: usize>
 where
@@ -331,6 +800,33 @@ where
     _align: Align<L>,
 }
 
+#[cfg(any(feature = "simd", feature = "wide"))]
+#[inline]
+/// Load lane `i` of `lanes` as a vector, built from its elements by `new` unless `lanes` is
+/// aligned well enough for a vector to be loaded from it directly.
+///
+/// # Safety
+///
+/// `i` must be a valid index into `lanes`, and `V` must have the same layout as `[A; L]`, except
+/// possibly for its alignment.
+unsafe fn load_lane<A: Copy, V, Al: Alloc, const L: usize>(
+    lanes: &Buf<MySimd<A, L>, Al>,
+    i: usize,
+    new: impl FnOnce([A; L]) -> V,
+) -> V
+where
+    Align<L>: Alignment,
+{
+    let lane = lanes.as_ptr().add(i);
+    if size_of::<V>() == size_of::<[A; L]>() && align_of::<V>() <= Buf::<MySimd<A, L>, Al>::ALIGN {
+        // SAFETY: Every lane is `size_of::<V>()` bytes wide, which is a multiple of the alignment
+        // of `V`, and the first lane is aligned to at least that.
+        lane.cast::<V>().read()
+    } else {
+        new((*lane).data)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[allow(clippy::module_name_repetitions)]
 /// A collision-affording point tree (CAPT), which allows for efficient collision-checking in a
@@ -359,7 +855,7 @@ where
 /// assert!(!t.collides(&[0.0, 0.3], 0.1));
 /// assert!(t.collides(&[0.0, 0.2], 0.15));
 /// ```
-pub struct Capt<const K: usize, const L: usize = 8, A = f32, I = usize>
+pub struct Capt<const K: usize, const L: usize = 8, A = f32, I = usize, Al: Alloc = Global>
 where
     Align<L>: Alignment,
 {
@@ -371,21 +867,83 @@ where
     /// `tests[idx]`, we advance to `2 * idx + 1`; otherwise, we go to `2 * idx + 2`.
     ///
     /// The length of `tests` must be `N`, rounded up to the next power of 2, minus one.
-    tests: Box<[A]>,
+    tests: Buf<A, Al>,
+    /// The axis which each test in `tests` splits along.
+    ///
+    /// If this is empty, as it is for trees built with [`SplitPolicy::Median`], the axis of a test
+    /// is its depth in the tree modulo `K`.
+    /// Otherwise, it has the same length as `tests`.
+    split_dims: Buf<u8, Al>,
     /// Axis-aligned bounding boxes containing the set of afforded points for each cell.
-    aabbs: Box<[Aabb<A, K>]>,
+    aabbs: Buf<Aabb<A, K>, Al>,
     /// Indexes for the starts of the affordance buffer subsequence of `points` corresponding to
     /// each leaf cell in the tree.
     /// This buffer is padded with one extra `usize` at the end with the maximum length of `points`
     /// for the sake of branchless computation.
-    starts: Box<[I]>,
-    /// The sets of afforded points for each cell.
-    afforded: [Box<[MySimd<A, L>]>; K],
+    starts: Buf<I, Al>,
+    /// The sets of afforded points for each cell, stored transposed: `afforded[k]` contains the
+    /// `k`-th coordinate of every afforded point.
+    ///
+    /// The afforded points of each cell occupy a contiguous run of lanes, the last of which is
+    /// padded out with infinite points.
+    /// Accordingly, scanning the affordance buffer of a cell in a SIMD query only requires
+    /// contiguous loads of whole lanes, and never a gather.
+    /// If the `aligned` feature is enabled, those loads are also aligned.
+    afforded: [Buf<MySimd<A, L>, Al>; K],
+    /// The `(minimum, maximum)` range of query radii which this tree was built to support.
+    r_range: (A, A),
+    /// Space for sorting points in [`Capt::rebuild`], kept so that later rebuilds need not
+    /// allocate it again.
+    scratch: Scratch<[A; K]>,
+}
+
+/// Storage which is kept by a structure between operations, but is not part of its contents.
+///
+/// Accordingly, all scratch buffers compare equal, and cloning one makes an empty buffer.
+struct Scratch<T>(Vec<T>);
+
+impl<T> Default for Scratch<T> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<T> Clone for Scratch<T> {
+    fn clone(&self) -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<T> PartialEq for Scratch<T> {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl<T> Eq for Scratch<T> {}
+
+impl<T> Debug for Scratch<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Scratch")
+    }
 }
 
+/// A [`Capt`] over points in the plane.
+///
+/// Planar trees take an unrolled path through [`Capt::collides`], which is worthwhile for mobile
+/// robots and games that never leave the plane.
+///
+/// # Examples
+///
+/// ```
+/// let t: captree::Capt2d = captree::Capt2d::new(&[[0.0, 0.1], [0.4, -0.2]], (0.0, 0.2));
+///
+/// assert!(t.collides(&[0.0, 0.2], 0.15));
+/// ```
+pub type Capt2d<const L: usize = 8, A = f32, I = usize, Al = Global> = Capt<2, L, A, I, Al>;
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-#[doc(hidden)]
 /// A prismatic bounding volume.
 pub struct Aabb<A, const K: usize> {
     /// The lower bound on the volume.
@@ -395,7 +953,7 @@ pub struct Aabb<A, const K: usize> {
 }
 
 #[non_exhaustive]
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 /// The errors which can occur when calling [`Capt::try_new`].
 pub enum NewCaptError {
     /// There were too many points in the provided cloud to be represented without integer
@@ -403,6 +961,106 @@ pub enum NewCaptError {
     TooManyPoints,
     /// At least one of the points had a non-finite value.
     NonFinite,
+    /// The affordance buffers of some cells were longer than the cap passed to
+    /// [`Capt::try_new_capped`].
+    /// Contains a report for every cell which overflowed, in order of cell index.
+    AffordanceOverflow(Box<[OverflowedCell]>),
+    /// The progress callback passed to construction returned [`ControlFlow::Break`].
+    Cancelled,
+}
+
+impl fmt::Display for NewCaptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooManyPoints => write!(f, "too many points for the index type"),
+            Self::NonFinite => write!(f, "point cloud contains a non-finite value"),
+            Self::AffordanceOverflow(cells) => write!(
+                f,
+                "{} cells have affordance buffers longer than the cap",
+                cells.len()
+            ),
+            Self::Cancelled => write!(f, "construction was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for NewCaptError {}
+
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// A phase of the construction of a [`Capt`], as reported to the progress callback of
+/// [`Capt::try_new_with_progress`].
+///
+/// Phases always run in the order they are listed here.
+pub enum BuildPhase {
+    /// The points are being sorted into the leaf cells of the tree.
+    Partition,
+    /// The affordance buffer of each leaf cell is being computed.
+    /// This is usually by far the longest phase.
+    Afford,
+    /// The affordance buffers are being moved into their final, contiguous storage.
+    Layout,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// A report on a cell whose affordance buffer exceeded the cap passed to [`Capt::try_new_capped`].
+///
+/// Overflowing cells are typically the result of dense clusters of points combined with a wide
+/// radius range.
+/// If these are common, consider shrinking the maximum radius or filtering the point cloud.
+pub struct OverflowedCell {
+    /// The index of the leaf cell, in left-to-right order of the leaves of the tree.
+    pub cell: usize,
+    /// The number of points (including the cell's representative) which would have been stored in
+    /// the cell's affordance buffer.
+    pub n_afforded: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// A report on the shape and memory use of a [`Capt`], as returned by [`Capt::stats`].
+///
+/// Quantiles of affordance buffer sizes are taken only over leaves which contain a point, and
+/// count the representative of each cell.
+pub struct TreeStats {
+    /// The number of tests on the path from the root to any leaf.
+    pub depth: u32,
+    /// The total number of leaf cells.
+    pub n_leaves: usize,
+    /// The number of leaf cells which only exist to pad the tree to a power of two.
+    pub n_ghost_leaves: usize,
+    /// The smallest number of points in an affordance buffer.
+    pub afforded_min: usize,
+    /// The median number of points in an affordance buffer.
+    pub afforded_median: usize,
+    /// The 99th-percentile number of points in an affordance buffer.
+    pub afforded_p99: usize,
+    /// The largest number of points in an affordance buffer.
+    pub afforded_max: usize,
+    /// The memory used by the test values, in bytes.
+    pub tests_bytes: usize,
+    /// The memory used by the start indices of the affordance buffers, in bytes.
+    pub starts_bytes: usize,
+    /// The memory used by the bounding boxes of the affordance buffers, in bytes.
+    pub aabbs_bytes: usize,
+    /// The memory used by the afforded points, including padding in partially-filled lanes, in
+    /// bytes.
+    pub afforded_bytes: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// A view of one leaf cell of a [`Capt`], as returned by [`Capt::leaves`].
+pub struct LeafView<A, const K: usize> {
+    /// The index of the leaf cell, in left-to-right order of the leaves of the tree.
+    pub index: usize,
+    /// The volume of space covered by the cell, bounded by the split planes above it.
+    pub cell: Aabb<A, K>,
+    /// The bounding box of the points in the cell's affordance buffer.
+    /// Queries whose balls do not reach this box are rejected without scanning the buffer.
+    pub bounds: Aabb<A, K>,
+    /// The representative point of the cell, or `None` if the cell only exists as padding.
+    pub representative: Option<[A; K]>,
+    /// The number of points (including the representative) in the cell's affordance buffer.
+    pub n_afforded: usize,
 }
 
 impl<A, I, const K: usize, const L: usize> Capt<K, L, A, I>
@@ -480,198 +1138,632 @@ where
     /// assert!(opt.is_err());
     /// ```
     pub fn try_new(points: &[[A; K]], r_range: (A, A)) -> Result<Self, NewCaptError> {
-        let n2 = points.len().next_power_of_two();
-
-        if points.iter().any(|p| p.iter().any(|x| !x.is_finite())) {
-            return Err(NewCaptError::NonFinite);
-        }
-
-        let mut tests = vec![A::INFINITY; n2 - 1].into_boxed_slice();
-
-        // hack: just pad with infinity to make it a power of 2
-        let mut points2 = vec![[A::INFINITY; K]; n2].into_boxed_slice();
-        points2[..points.len()].copy_from_slice(points);
-        // hack - reduce number of reallocations by allocating a lot of points from the start
-        let mut afforded = array::from_fn(|_| Vec::with_capacity(n2 * 100));
-        let mut starts = vec![I::ZERO; n2 + 1].into_boxed_slice();
-
-        let mut aabbs = vec![
-            Aabb {
-                lo: [A::NEG_INFINITY; K],
-                hi: [A::INFINITY; K],
-            };
-            n2
-        ]
-        .into_boxed_slice();
-
-        unsafe {
-            // SAFETY: We tested that `points` contains no `NaN` values.
-            Self::new_help(
-                &mut points2,
-                &mut tests,
-                &mut aabbs,
-                &mut afforded,
-                &mut starts,
-                0,
-                0,
-                r_range,
-                Vec::new(),
-                Aabb::ALL,
-            )?;
-        }
-
-        Ok(Self {
-            tests,
-            starts,
-            afforded: afforded.map(Vec::into_boxed_slice),
-            aabbs,
-        })
+        Self::try_new_capped(points, r_range, usize::MAX)
     }
 
-    #[allow(clippy::too_many_arguments, clippy::too_many_lines)]
-    /// # Safety
+    /// Construct a new CAPT containing all the points in `points`, limiting the length of the
+    /// affordance buffer of each cell to at most `max_afforded` points.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Err(NewCaptError::AffordanceOverflow(cells))` if any cell would
+    /// afford more than `max_afforded` points, where `cells` lists every overflowing cell.
+    /// Otherwise, it fails under the same conditions as [`Capt::try_new`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use captree::{Capt, NewCaptError};
+    ///
+    /// let points = [[0.0], [0.1], [0.2], [0.3]];
     ///
-    /// This function will contain undefined behavior if `points` contains any `NaN` values.
-    unsafe fn new_help(
-        points: &mut [[A; K]],
-        tests: &mut [A],
-        aabbs: &mut [Aabb<A, K>],
-        afforded: &mut [Vec<MySimd<A, L>>; K],
-        starts: &mut [I],
-        k: usize,
-        i: usize,
+    /// // with a tiny radius range, each cell only needs its own point
+    /// assert!(Capt::<1>::try_new_capped(&points, (0.0, 0.01), 1).is_ok());
+    ///
+    /// // with a wide radius range, every cell must afford every point
+    /// let Err(NewCaptError::AffordanceOverflow(cells)) =
+    ///     Capt::<1>::try_new_capped(&points, (0.0, 1.0), 2)
+    /// else {
+    ///     panic!("expected an overflow");
+    /// };
+    /// assert_eq!(cells.len(), 4);
+    /// assert_eq!(cells[0].n_afforded, 4);
+    /// ```
+    pub fn try_new_capped(
+        points: &[[A; K]],
         r_range: (A, A),
-        in_range: Vec<[A; K]>,
-        cell: Aabb<A, K>,
-    ) -> Result<(), NewCaptError> {
-        let rsq_min = r_range.0.square();
-        if let [rep] = *points {
-            let z = i - tests.len();
-            let aabb = &mut aabbs[z];
-            *aabb = Aabb { lo: rep, hi: rep };
-            if rep[0].is_finite() {
-                // lanes for afforded points
-                let mut news = [[A::INFINITY; L]; K];
-                for k in 0..K {
-                    news[k][0] = rep[k];
-                }
-
-                // index into the current lane
-                let mut j = 1;
-
-                // populate affordance buffer if the representative doesn't cover everything
-                if !cell.contained_by_ball(&rep, rsq_min) {
-                    for ak in afforded.iter_mut() {
-                        ak.reserve(ak.len() + in_range.len() / L);
-                    }
-                    for p in in_range {
-                        aabb.insert(&p);
-
-                        // start a new lane if it's full
-                        if j == L {
-                            for k in 0..K {
-                                afforded[k].push(MySimd {
-                                    data: news[k],
-                                    _align: Align::NEW,
-                                });
-                            }
-                            j = 0;
-                        }
-
-                        // add this point to the lane
-                        for k in 0..K {
-                            news[k][j] = p[k];
-                        }
+        max_afforded: usize,
+    ) -> Result<Self, NewCaptError> {
+        let mut points2 = Vec::with_capacity(points.len().next_power_of_two());
+        points2.extend_from_slice(points);
+        Self::build(points2, r_range, max_afforded, Global, &mut no_progress)
+    }
 
-                        j += 1;
-                    }
-                }
+    /// Construct a new CAPT containing all the points yielded by `points`.
+    ///
+    /// Unlike [`Capt::new`], this does not require the points to be collected into a slice first:
+    /// they are moved directly into the buffer used for construction.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic under the same conditions as [`Capt::new`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let capt = captree::Capt::<1>::from_points((0..10).map(|i| [i as f32]), (0.0, 0.5));
+    ///
+    /// assert!(capt.collides(&[3.1], 0.2));
+    /// assert!(!capt.collides(&[3.5], 0.2));
+    /// ```
+    pub fn from_points(points: impl IntoIterator<Item = [A; K]>, r_range: (A, A)) -> Self {
+        Self::try_from_points(points, r_range)
+            .expect("index type I must be able to support all points in CAPT during construction")
+    }
 
-                // fill out the last lane with infinities
-                for k in 0..K {
-                    afforded[k].push(MySimd {
-                        data: news[k],
-                        _align: Align::NEW,
-                    });
-                }
+    /// Construct a new CAPT containing all the points yielded by `points`, checking for index
+    /// overflow.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error under the same conditions as [`Capt::try_new`].
+    pub fn try_from_points(
+        points: impl IntoIterator<Item = [A; K]>,
+        r_range: (A, A),
+    ) -> Result<Self, NewCaptError> {
+        Self::build(
+            points.into_iter().collect(),
+            r_range,
+            usize::MAX,
+            Global,
+            &mut no_progress,
+        )
+    }
+
+    /// Construct a new CAPT over a very large point cloud, taking ownership of `points` and
+    /// reporting progress to `progress` as described in [`Capt::try_new_with_progress`].
+    ///
+    /// The allocation of `points` is reused for sorting, so the cloud is never copied if `points`
+    /// already has capacity for `points.len().next_power_of_two()` points.
+    /// Affordance buffers are gathered in fixed-size chunks while they are built, and each chunk is
+    /// freed as soon as it is moved into the finished tree, so the peak memory use of
+    /// construction is about twice the size of the finished tree rather than several times it.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error under the same conditions as
+    /// [`Capt::try_new_with_progress`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::ControlFlow;
+    ///
+    /// let points: Vec<[f32; 2]> = (0..1000).map(|i| [i as f32, 0.0]).collect();
+    /// let capt =
+    ///     captree::Capt::<2>::try_new_large(points, (0.0, 0.5), |_, _| ControlFlow::Continue(()))
+    ///         .unwrap();
+    ///
+    /// assert!(capt.collides(&[500.2, 0.0], 0.3));
+    /// ```
+    pub fn try_new_large(
+        points: Vec<[A; K]>,
+        r_range: (A, A),
+        mut progress: impl FnMut(BuildPhase, f32) -> ControlFlow<()>,
+    ) -> Result<Self, NewCaptError> {
+        Self::build(points, r_range, usize::MAX, Global, &mut progress)
+    }
+
+    /// Construct a new CAPT containing all the points in `points`, reporting progress to
+    /// `progress` so that long constructions can be displayed and cancelled.
+    ///
+    /// `progress(phase, fraction)` is called repeatedly with the current [`BuildPhase`] and the
+    /// fraction of that phase which has been completed, from 0 to 1.
+    /// If it ever returns [`ControlFlow::Break`], construction stops as soon as possible.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Err(NewCaptError::Cancelled)` if `progress` returns
+    /// [`ControlFlow::Break`], and will otherwise return an error under the same conditions as
+    /// [`Capt::try_new`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::ControlFlow;
+    ///
+    /// use captree::{BuildPhase, Capt, NewCaptError};
+    ///
+    /// let points: Vec<[f32; 2]> = (0..1000).map(|i| [i as f32, 0.0]).collect();
+    ///
+    /// // give up halfway through computing affordance buffers
+    /// let result = Capt::<2>::try_new_with_progress(&points, (0.0, 0.5), |phase, fraction| {
+    ///     if phase == BuildPhase::Afford && fraction >= 0.5 {
+    ///         ControlFlow::Break(())
+    ///     } else {
+    ///         ControlFlow::Continue(())
+    ///     }
+    /// });
+    /// assert_eq!(result, Err(NewCaptError::Cancelled));
+    /// ```
+    pub fn try_new_with_progress(
+        points: &[[A; K]],
+        r_range: (A, A),
+        mut progress: impl FnMut(BuildPhase, f32) -> ControlFlow<()>,
+    ) -> Result<Self, NewCaptError> {
+        let mut points2 = Vec::with_capacity(points.len().next_power_of_two());
+        points2.extend_from_slice(points);
+        Self::build(points2, r_range, usize::MAX, Global, &mut progress)
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    /// Construct a new CAPT containing all the points in `points`, giving up if construction is
+    /// still running at `deadline`.
+    ///
+    /// The clock is checked after each leaf cell is finished, so construction may overrun
+    /// `deadline` by about the time taken to partition the points plus the time to build a single
+    /// affordance buffer.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Err(NewCaptError::Cancelled)` if `deadline` passes before
+    /// construction finishes, and will otherwise return an error under the same conditions as
+    /// [`Capt::try_new`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    ///
+    /// use captree::{Capt, NewCaptError};
+    ///
+    /// let points = [[0.0, 0.1], [0.4, -0.2], [-0.2, -0.1]];
+    ///
+    /// let later = Instant::now() + Duration::from_secs(60);
+    /// assert!(Capt::<2>::try_new_with_deadline(&points, (0.0, 0.2), later).is_ok());
+    ///
+    /// let past = Instant::now();
+    /// assert_eq!(
+    ///     Capt::<2>::try_new_with_deadline(&points, (0.0, 0.2), past),
+    ///     Err(NewCaptError::Cancelled)
+    /// );
+    /// ```
+    pub fn try_new_with_deadline(
+        points: &[[A; K]],
+        r_range: (A, A),
+        deadline: Instant,
+    ) -> Result<Self, NewCaptError> {
+        Self::try_new_with_progress(points, r_range, |_, _| {
+            if Instant::now() < deadline {
+                ControlFlow::Continue(())
+            } else {
+                ControlFlow::Break(())
             }
+        })
+    }
+
+    /// Construct a new CAPT from points stored in an interleaved buffer, such as those produced
+    /// by sensor drivers.
+    ///
+    /// The `i`-th point's coordinates are `flat[offset + i * stride..][..K]`.
+    /// Any trailing elements which are too few to make up a whole point are ignored.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `stride` is less than `K`, or under the same conditions as
+    /// [`Capt::new`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// // x, y, z, intensity
+    /// let flat = [0.0, 0.0, 0.0, 0.9, 1.0, 1.0, 1.0, 0.2];
+    ///
+    /// let capt = captree::Capt::<3>::from_flat(&flat, 4, 0, (0.0, 0.5));
+    ///
+    /// assert!(capt.collides(&[1.0, 1.0, 1.1], 0.2));
+    /// assert!(!capt.collides(&[0.5, 0.5, 0.5], 0.2));
+    /// ```
+    pub fn from_flat(flat: &[A], stride: usize, offset: usize, r_range: (A, A)) -> Self {
+        Self::from_points(strided(flat, stride, offset), r_range)
+    }
+
+    /// Construct a new CAPT from points stored in an interleaved buffer, checking for index
+    /// overflow.
+    ///
+    /// See [`Capt::from_flat`] for the layout of `flat`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error under the same conditions as [`Capt::try_new`].
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `stride` is less than `K`.
+    pub fn try_from_flat(
+        flat: &[A],
+        stride: usize,
+        offset: usize,
+        r_range: (A, A),
+    ) -> Result<Self, NewCaptError> {
+        Self::try_from_points(strided(flat, stride, offset), r_range)
+    }
+
+    #[must_use]
+    /// Construct a new CAPT containing all the points in `points`, supporting query radii from
+    /// `r_min` to `r_max`.
+    ///
+    /// This is the same as [`Capt::new`], but the types of the bounds make it clear that they are
+    /// radii and not squared radii.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic under the same conditions as [`Capt::new`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use captree::{Capt, Radius};
+    ///
+    /// let capt = Capt::<1>::new_with_radii(&[[0.0]], Radius(0.0), Radius(0.5));
+    ///
+    /// assert!(capt.collides_r(&[0.3], Radius(0.4)));
+    /// ```
+    pub fn new_with_radii(points: &[[A; K]], r_min: Radius<A>, r_max: Radius<A>) -> Self {
+        Self::new(points, (r_min.0, r_max.0))
+    }
 
-            starts[z + 1] = afforded[0]
-                .len()
-                .try_into()
-                .map_err(|_| NewCaptError::TooManyPoints)?;
-            return Ok(());
+    #[must_use]
+    /// Construct a new CAPT containing all the points in `points`, supporting every query radius
+    /// in a representative `trace` of `(center, radius)` pairs.
+    ///
+    /// The range of radii is found by [`RadiusRange::fit`], widened by `margin` on both ends.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic under the same conditions as [`Capt::new`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let points = [[0.0, 0.0], [1.0, 1.0]];
+    /// let trace = [([0.5, 0.5], 0.1), ([0.9, 0.9], 0.2)];
+    ///
+    /// let capt = captree::Capt::<2>::new_for_trace(&points, &trace, 0.05);
+    ///
+    /// assert!(capt.collides(&[0.9, 0.9], 0.2));
+    /// ```
+    pub fn new_for_trace(points: &[[A; K]], trace: &[([A; K], A)], margin: A) -> Self {
+        Self::new(points, RadiusRange::fit(trace, margin).into())
+    }
+
+    #[must_use]
+    /// Construct a new CAPT containing the same points as `pkdt`.
+    ///
+    /// Since a [`PkdTree`] has the same spatial partition as a CAPT, this reuses its sorted points
+    /// and tests instead of partitioning the points again.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if there are too many points in the tree to be addressed by `I`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use captree::{Capt, PkdTree};
+    ///
+    /// let points = [[0.0, 0.1], [0.4, -0.2], [-0.2, -0.1]];
+    /// let pkdt = PkdTree::new(&points);
+    /// let capt = Capt::<2>::from_pkdt(&pkdt, (0.0, 0.2));
+    ///
+    /// assert_eq!(capt, Capt::new(&points, (0.0, 0.2)));
+    /// ```
+    pub fn from_pkdt(pkdt: &PkdTree<K, A>, r_range: (A, A)) -> Self {
+        Self::try_from_pkdt(pkdt, r_range)
+            .expect("index type I must be able to support all points in CAPT during construction")
+    }
+
+    /// Construct a new CAPT containing the same points as `pkdt`, checking for index overflow.
+    ///
+    /// See [`Capt::from_pkdt`] for further details.
+    ///
+    /// # Errors
+    ///
+    /// This function will return a [`NewCaptError::TooManyPoints`] if there are too many points to
+    /// be indexed by `I`.
+    pub fn try_from_pkdt(pkdt: &PkdTree<K, A>, r_range: (A, A)) -> Result<Self, NewCaptError> {
+        let mut t = Self {
+            tests: pkdt.tests.to_vec().into(),
+            split_dims: Buf::default(),
+            aabbs: Buf::default(),
+            starts: Buf::default(),
+            afforded: array::from_fn(|_| Buf::default()),
+            r_range,
+            scratch: Scratch::default(),
+        };
+        t.afford_partitioned(&pkdt.points, r_range, usize::MAX, &mut no_progress)?;
+        Ok(t)
+    }
+}
+
+impl<A, I, Al, const K: usize, const L: usize> Capt<K, L, A, I, Al>
+where
+    A: Axis,
+    I: Index,
+    Al: Alloc,
+    Align<L>: Alignment,
+{
+    /// Construct a new CAPT containing all the points in `points`, whose buffers are allocated by
+    /// `alloc`.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic under the same conditions as [`Capt::new`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use captree::{Capt, Global};
+    ///
+    /// let capt = Capt::<1, 8, f32, usize, Global>::new_in(&[[0.0]], (0.0, 1.0), Global);
+    ///
+    /// assert!(capt.collides(&[0.5], 0.6));
+    /// ```
+    pub fn new_in(points: &[[A; K]], r_range: (A, A), alloc: Al) -> Self {
+        Self::try_new_in(points, r_range, alloc)
+            .expect("index type I must be able to support all points in CAPT during construction")
+    }
+
+    /// Construct a new CAPT containing all the points in `points`, whose buffers are allocated by
+    /// `alloc`, checking for index overflow.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error under the same conditions as [`Capt::try_new`].
+    pub fn try_new_in(points: &[[A; K]], r_range: (A, A), alloc: Al) -> Result<Self, NewCaptError> {
+        let mut points2 = Vec::with_capacity(points.len().next_power_of_two());
+        points2.extend_from_slice(points);
+        Self::build(points2, r_range, usize::MAX, alloc, &mut no_progress)
+    }
+
+    /// Rebuild this CAPT to contain exactly the points in `points`, reusing its existing
+    /// allocations where possible.
+    ///
+    /// Every buffer of the tree is reused if it has capacity for the new tree, and affordance
+    /// lanes are written directly into the existing affordance buffers.
+    /// The space used to sort `points` is also kept in the tree, so once a tree has been rebuilt
+    /// from a cloud at least as large as each of the next ones, rebuilding it does not allocate.
+    /// This removes allocator traffic when a tree is reconstructed for every frame of a sensor,
+    /// at the cost of holding on to the memory of the largest frame seen.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error under the same conditions as [`Capt::try_new`].
+    /// If it does, this tree will be left empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut capt = captree::Capt::<1>::new(&[[0.0], [1.0]], (0.0, 0.5));
+    /// assert!(capt.collides(&[0.1], 0.2));
+    ///
+    /// capt.rebuild(&[[2.0], [3.0]], (0.0, 0.5)).unwrap();
+    /// assert!(!capt.collides(&[0.1], 0.2));
+    /// assert!(capt.collides(&[2.1], 0.2));
+    /// ```
+    pub fn rebuild(&mut self, points: &[[A; K]], r_range: (A, A)) -> Result<(), NewCaptError> {
+        let mut points2 = std::mem::take(&mut self.scratch.0);
+        points2.clear();
+        points2.extend_from_slice(points);
+        let result = self.build_in(&mut points2, r_range, usize::MAX, &mut no_progress);
+        if result.is_err() {
+            self.clear(r_range);
         }
+        self.scratch.0 = points2;
+        result
+    }
 
-        let test = median_partition(points, k);
-        tests[i] = test;
+    /// Overwrite this CAPT with an empty tree, which never collides.
+    fn clear(&mut self, r_range: (A, A)) {
+        // an empty tree has a single leaf, a ghost whose representative is at infinity
+        self.tests.reset(0, A::INFINITY);
+        self.split_dims.reset(0, 0);
+        self.aabbs.reset(
+            1,
+            Aabb {
+                lo: [A::INFINITY; K],
+                hi: [A::INFINITY; K],
+            },
+        );
+        self.starts.reset(2, I::ZERO);
+        for afforded in &mut self.afforded {
+            afforded.clear();
+        }
+        self.r_range = r_range;
+    }
 
-        let (lhs, rhs) = points.split_at_mut(points.len() / 2);
-        let (lo_vol, hi_vol) = cell.split(test, k);
+    /// Construct a new CAPT from the points in `points2`, reusing its allocation for sorting.
+    fn build(
+        mut points2: Vec<[A; K]>,
+        r_range: (A, A),
+        max_afforded: usize,
+        alloc: Al,
+        progress: &mut dyn FnMut(BuildPhase, f32) -> ControlFlow<()>,
+    ) -> Result<Self, NewCaptError> {
+        let mut t = Self {
+            afforded: array::from_fn(|_| Buf::from_vec_in(Vec::new(), alloc.clone())),
+            tests: Buf::from_vec_in(Vec::new(), alloc.clone()),
+            split_dims: Buf::from_vec_in(Vec::new(), alloc.clone()),
+            aabbs: Buf::from_vec_in(Vec::new(), alloc.clone()),
+            starts: Buf::from_vec_in(Vec::new(), alloc),
+            r_range,
+            scratch: Scratch::default(),
+        };
+        t.build_in(&mut points2, r_range, max_afforded, progress)?;
+        Ok(t)
+    }
 
-        let lo_too_small = distsq(lo_vol.lo, lo_vol.hi) <= rsq_min;
-        let hi_too_small = distsq(hi_vol.lo, hi_vol.hi) <= rsq_min;
+    /// Overwrite this CAPT with a tree containing the points in `points2`, reusing the
+    /// allocations of both `points2` and `self` where possible.
+    ///
+    /// If this returns an error, the contents of `self` are unspecified.
+    fn build_in(
+        &mut self,
+        points2: &mut Vec<[A; K]>,
+        r_range: (A, A),
+        max_afforded: usize,
+        progress: &mut dyn FnMut(BuildPhase, f32) -> ControlFlow<()>,
+    ) -> Result<(), NewCaptError> {
+        span!(DEBUG, "build", n_points = points2.len());
+        if points2.iter().any(|p| p.iter().any(|x| !x.is_finite())) {
+            return Err(NewCaptError::NonFinite);
+        }
 
-        // retain only points which might be in the affordance buffer for the split-out cells
-        let (lo_afford, hi_afford) = match (lo_too_small, hi_too_small) {
-            (false, false) => {
-                let mut lo_afford = in_range;
-                let mut hi_afford = lo_afford.clone();
-                lo_afford.retain(|pt| pt[k] <= test + r_range.1);
-                lo_afford.extend(rhs.iter().filter(|pt| pt[k] <= test + r_range.1));
-                hi_afford.retain(|pt| pt[k].is_finite() && test - r_range.1 <= pt[k]);
-                hi_afford.extend(
-                    lhs.iter()
-                        .filter(|pt| pt[k].is_finite() && test - r_range.1 <= pt[k]),
-                );
+        // hack: just pad with infinity to make it a power of 2
+        let n2 = points2.len().next_power_of_two();
+        points2.resize(n2, [A::INFINITY; K]);
 
-                (lo_afford, hi_afford)
+        {
+            span!(DEBUG, "partition");
+            report(progress, BuildPhase::Partition, 0.0)?;
+            self.tests.reset(n2 - 1, A::INFINITY);
+            self.split_dims.reset(0, 0);
+            unsafe {
+                // SAFETY: We tested that `points` contains no `NaN` values.
+                partition(points2, &mut self.tests, 0);
             }
-            (false, true) => {
-                let mut lo_afford = in_range;
-                lo_afford.retain(|pt| pt[k] <= test + r_range.1);
-                lo_afford.extend(rhs.iter().filter(|pt| pt[k] <= test + r_range.1));
+        }
 
-                (lo_afford, Vec::new())
-            }
-            (true, false) => {
-                let mut hi_afford = in_range;
-                hi_afford.retain(|pt| test - r_range.1 <= pt[k]);
-                hi_afford.extend(
-                    lhs.iter()
-                        .filter(|pt| pt[k].is_finite() && test - r_range.1 <= pt[k]),
-                );
+        self.afford_partitioned(points2, r_range, max_afforded, progress)
+    }
 
-                (Vec::new(), hi_afford)
+    /// Fill out the affordance buffers of this CAPT, given that `self.tests` has already been
+    /// built by partitioning `points2`, as is done by [`partition`].
+    ///
+    /// Progress is reported to `progress` after the affordance buffer of each leaf cell is built
+    /// and when the buffers are laid out.
+    ///
+    /// If this returns an error, the contents of `self` are unspecified.
+    fn afford_partitioned(
+        &mut self,
+        points2: &[[A; K]],
+        r_range: (A, A),
+        max_afforded: usize,
+        progress: &mut dyn FnMut(BuildPhase, f32) -> ControlFlow<()>,
+    ) -> Result<(), NewCaptError> {
+        let n2 = points2.len();
+        self.r_range = r_range;
+        self.starts.reset(n2 + 1, I::ZERO);
+        self.aabbs.reset(n2, Aabb::ALL);
+        let tests = &self.tests;
+        let split_dims = &self.split_dims;
+        let starts = &mut self.starts;
+        let aabbs = &mut self.aabbs;
+
+        // A tree which already has affordance buffers is being rebuilt, so its lanes are written
+        // in place, and its buffers only grow if they are too small.
+        // Otherwise, lanes are gathered in chunks, since the final size of each buffer is unknown
+        // until the end, and growing a single buffer would briefly need twice its memory.
+        let in_place = self.afforded[0].capacity() > 0;
+        let reused = &mut self.afforded;
+        for lanes in reused.iter_mut() {
+            lanes.clear();
+        }
+        let mut chunked: [Chunked<MySimd<A, L>>; K] = array::from_fn(|_| Chunked::new());
+        let mut n_lanes = 0;
+        let mut push_lanes = |news: [[A; L]; K]| {
+            for (k, data) in news.into_iter().enumerate() {
+                let lane = MySimd {
+                    data,
+                    _align: Align::NEW,
+                };
+                if in_place {
+                    reused[k].push(lane);
+                } else {
+                    chunked[k].push(lane);
+                }
             }
-            (true, true) => (Vec::new(), Vec::new()),
         };
 
-        let next_k = (k + 1) % K;
-        Self::new_help(
-            lhs,
-            tests,
-            aabbs,
-            afforded,
-            starts,
-            next_k,
-            2 * i + 1,
-            r_range,
-            lo_afford,
-            lo_vol,
-        )?;
-        Self::new_help(
-            rhs,
-            tests,
-            aabbs,
-            afforded,
-            starts,
-            next_k,
-            2 * i + 2,
-            r_range,
-            hi_afford,
-            hi_vol,
-        )?;
+        let mut overflows = Vec::new();
+
+        {
+            span!(DEBUG, "afford");
+            afford(points2, tests, split_dims, r_range, &mut |z, in_range| {
+                let rep = points2[z];
+                let aabb = &mut aabbs[z];
+                *aabb = Aabb { lo: rep, hi: rep };
+                let n_afforded = 1 + in_range.len();
+                if rep[0].is_finite() && n_afforded > max_afforded {
+                    // don't bother building the buffer, since this tree will never be returned
+                    overflows.push(OverflowedCell {
+                        cell: z,
+                        n_afforded,
+                    });
+                } else if rep[0].is_finite() {
+                    *aabb = pack_lanes(rep, in_range.into_iter().map(|i| points2[i]), |news| {
+                        push_lanes(news);
+                        n_lanes += 1;
+                        Ok::<_, NewCaptError>(())
+                    })?;
+                }
+
+                starts[z + 1] = n_lanes
+                    .try_into()
+                    .map_err(|_| NewCaptError::TooManyPoints)?;
+                #[allow(clippy::cast_precision_loss)]
+                report(progress, BuildPhase::Afford, (z + 1) as f32 / n2 as f32)?;
+                Ok(())
+            })?;
+        }
+
+        if !overflows.is_empty() {
+            return Err(NewCaptError::AffordanceOverflow(
+                overflows.into_boxed_slice(),
+            ));
+        }
+
+        span!(DEBUG, "layout");
+        for (k, (old, new)) in self.afforded.iter_mut().zip(chunked).enumerate() {
+            if !in_place {
+                *old = Buf::from_chunked_in(new, old.allocator().clone());
+            }
+            #[allow(clippy::cast_precision_loss)]
+            report(progress, BuildPhase::Layout, (k + 1) as f32 / K as f32)?;
+        }
 
         Ok(())
     }
@@ -700,19 +1792,9 @@ where
     /// );
     /// ```
     pub fn collides(&self, center: &[A; K], radius: A) -> bool {
-        // forward pass through the tree
-        let mut test_idx = 0;
-        let mut k = 0;
-        for _ in 0..self.tests.len().trailing_ones() {
-            test_idx = 2 * test_idx
-                + 1
-                + usize::from(unsafe { *self.tests.get_unchecked(test_idx) } <= center[k]);
-            k = (k + 1) % K;
-        }
-
         // retrieve affordance buffer location
         let rsq = radius.square();
-        let i = test_idx - self.tests.len();
+        let i = self.leaf_containing(center);
         let aabb = unsafe { self.aabbs.get_unchecked(i) };
         if aabb.closest_distsq_to(center) > rsq {
             return false;
@@ -723,6 +1805,19 @@ where
             self.starts[i].try_into().unwrap_unchecked()
                 ..self.starts[i + 1].try_into().unwrap_unchecked()
         };
+        for sk in &self.afforded {
+            prefetch(sk.as_ptr().wrapping_add(range.start));
+        }
+
+        if K == 2 {
+            // planar trees skip assembling each afforded point and read both axes in lockstep
+            let [xs, ys] = [&self.afforded[0], &self.afforded[1]];
+            let [cx, cy] = [center[0], center[1]];
+            return range.any(|i| {
+                let (xs, ys) = (&xs[i].data, &ys[i].data);
+                (0..L).any(|j| (xs[j] - cx).square() + (ys[j] - cy).square() <= rsq)
+            });
+        }
 
         // check affordance buffer
         range.any(|i| {
@@ -736,40 +1831,426 @@ where
         })
     }
 
+    #[must_use]
+    /// Determine whether a point in this tree is within a distance of `radius` to `center`.
+    ///
+    /// This is the same as [`Capt::collides`], but the type of `radius` makes it clear that it is
+    /// not a squared radius.
+    pub fn collides_r(&self, center: &[A; K], radius: Radius<A>) -> bool {
+        self.collides(center, radius.0)
+    }
+
+    #[inline]
+    /// Find the index of the leaf cell containing `center`, following the split axis of each
+    /// test.
+    pub(crate) fn leaf_containing(&self, center: &[A; K]) -> usize {
+        if self.split_dims.is_empty() {
+            forward_pass(&self.tests, center)
+        } else {
+            forward_pass_dims(&self.tests, &self.split_dims, center)
+        }
+    }
+
+    /// Get an iterator over every point in this tree, in the order of the leaf cells which
+    /// contain them.
+    ///
+    /// Each point is the first entry in the affordance buffer of its cell, so no extra storage is
+    /// needed to recover the point cloud a tree was built from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let capt = captree::Capt::<1>::new(&[[2.0], [0.0], [1.0]], (0.0, 0.5));
+    ///
+    /// assert_eq!(capt.points().collect::<Vec<_>>(), [[0.0], [1.0], [2.0]]);
+    /// ```
+    pub fn points(&self) -> impl Iterator<Item = [A; K]> + '_ {
+        self.starts.windows(2).filter_map(|w| {
+            let (start, end): (usize, usize) = unsafe {
+                // SAFETY: The conversion worked the first way.
+                (
+                    w[0].try_into().unwrap_unchecked(),
+                    w[1].try_into().unwrap_unchecked(),
+                )
+            };
+            (start < end).then(|| array::from_fn(|k| self.afforded[k][start].data[0]))
+        })
+    }
+
+    #[must_use]
+    /// Get the `(minimum, maximum)` range of query radii which this tree was built to support.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let capt = captree::Capt::<1>::new(&[[0.0]], (0.1, 0.5));
+    ///
+    /// assert_eq!(capt.r_range(), (0.1, 0.5));
+    /// ```
+    pub const fn r_range(&self) -> (A, A) {
+        self.r_range
+    }
+
+    #[must_use]
+    /// Get the number of points in this tree, not counting the padding which fills out the tree
+    /// to a power of two.
+    ///
+    /// This takes time linear in the number of leaf cells.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let capt = captree::Capt::<1>::new(&[[0.0], [1.0], [2.0]], (0.0, 0.5));
+    ///
+    /// assert_eq!(capt.len(), 3);
+    /// assert!(!capt.is_empty());
+    /// ```
+    pub fn len(&self) -> usize {
+        self.points().count()
+    }
+
+    #[must_use]
+    /// Determine whether this tree contains no points.
+    pub fn is_empty(&self) -> bool {
+        self.points().next().is_none()
+    }
+
+    #[must_use]
+    /// Get the dimension of the points in this tree, `K`.
+    pub const fn dimension(&self) -> usize {
+        K
+    }
+
+    #[must_use]
+    /// Get the number of tests on the path from the root of this tree to any leaf.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let capt = captree::Capt::<1>::new(&[[0.0], [1.0], [2.0]], (0.0, 0.5));
+    ///
+    /// // three points are padded to four leaves
+    /// assert_eq!(capt.depth(), 2);
+    /// ```
+    pub const fn depth(&self) -> u32 {
+        self.tests.len().trailing_ones()
+    }
+
+    /// Get an iterator over the leaf cells of this tree, in left-to-right order.
+    ///
+    /// This is intended for visualization and for diagnosing cells with unusually large
+    /// affordance buffers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let capt = captree::Capt::<1>::new(&[[0.0], [1.0], [2.0]], (0.0, 0.6));
+    ///
+    /// let leaves: Vec<_> = capt.leaves().collect();
+    /// // three points are padded to a power of two
+    /// assert_eq!(leaves.len(), 4);
+    /// assert_eq!(leaves[1].representative, Some([1.0]));
+    /// assert_eq!(leaves[1].n_afforded, 3);
+    /// assert_eq!(leaves[3].representative, None);
+    /// ```
+    pub fn leaves(&self) -> impl Iterator<Item = LeafView<A, K>> + '_ {
+        let depth = self.tests.len().trailing_ones();
+        (0..self.aabbs.len()).map(move |z| {
+            // walk down from the root, following the bits of `z` from most to least significant
+            let mut cell = Aabb::ALL;
+            let mut i = 0;
+            for (level, k) in (0..depth).rev().zip((0..K).cycle()) {
+                let k = self.split_dims.get(i).map_or(k, |&d| usize::from(d));
+                let (lo, hi) = cell.split(self.tests[i], k);
+                if (z >> level) & 1 == 0 {
+                    cell = lo;
+                    i = 2 * i + 1;
+                } else {
+                    cell = hi;
+                    i = 2 * i + 2;
+                }
+            }
+
+            let (start, end): (usize, usize) = unsafe {
+                // SAFETY: The conversion worked the first way.
+                (
+                    self.starts[z].try_into().unwrap_unchecked(),
+                    self.starts[z + 1].try_into().unwrap_unchecked(),
+                )
+            };
+            LeafView {
+                index: z,
+                cell,
+                bounds: self.aabbs[z],
+                representative: (start < end)
+                    .then(|| array::from_fn(|k| self.afforded[k][start].data[0])),
+                n_afforded: self.afforded[0][start..end]
+                    .iter()
+                    .map(|lane| lane.data.iter().filter(|x| x.is_finite()).count())
+                    .sum(),
+            }
+        })
+    }
+
+    #[must_use]
+    /// Construct a new CAPT containing the points of both `self` and `other`.
+    ///
+    /// The points of each tree are read straight out of their leaves, so neither original point
+    /// cloud needs to be kept around.
+    /// The buffers of the new tree are allocated by the allocator of `self`.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the merged tree.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if there are too many points in the merged tree to be addressed
+    /// by `I`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use captree::Capt;
+    ///
+    /// let a = Capt::<2>::new(&[[0.0, 0.0], [1.0, 0.0]], (0.0, 0.5));
+    /// let b = Capt::<2>::new(&[[5.0, 5.0]], (0.0, 0.5));
+    /// let merged = a.merge(&b, (0.0, 0.5));
+    ///
+    /// assert!(merged.collides(&[0.9, 0.1], 0.2));
+    /// assert!(merged.collides(&[5.1, 5.0], 0.2));
+    /// ```
+    pub fn merge(&self, other: &Self, r_range: (A, A)) -> Self {
+        self.try_merge(other, r_range)
+            .expect("index type I must be able to support all points in CAPT during construction")
+    }
+
+    /// Construct a new CAPT containing the points of both `self` and `other`, checking for index
+    /// overflow.
+    ///
+    /// See [`Capt::merge`] for further details.
+    ///
+    /// # Errors
+    ///
+    /// This function will return a [`NewCaptError::TooManyPoints`] if there are too many points to
+    /// be indexed by `I`.
+    pub fn try_merge(&self, other: &Self, r_range: (A, A)) -> Result<Self, NewCaptError> {
+        let mut points2 =
+            Vec::with_capacity((self.aabbs.len() + other.aabbs.len()).next_power_of_two());
+        points2.extend(self.points());
+        points2.extend(other.points());
+        Self::build(
+            points2,
+            r_range,
+            usize::MAX,
+            self.tests.allocator().clone(),
+            &mut no_progress,
+        )
+    }
+
+    #[must_use]
+    /// Construct a new CAPT containing only the points of this tree which lie inside the
+    /// axis-aligned box from `lower` to `upper`, inclusive.
+    ///
+    /// This is useful for planning in a moving window over a large static map.
+    /// The buffers of the new tree are allocated by the allocator of `self`.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the cropped tree.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if there are too many points in the cropped tree to be addressed
+    /// by `I`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let capt = captree::Capt::<2>::new(&[[0.0, 0.0], [1.0, 1.0], [5.0, 5.0]], (0.0, 0.5));
+    /// let window = capt.crop(&[-1.0, -1.0], &[2.0, 2.0], (0.0, 0.5));
+    ///
+    /// assert!(window.collides(&[1.1, 1.0], 0.2));
+    /// assert!(!window.collides(&[5.0, 5.0], 0.2));
+    /// ```
+    pub fn crop(&self, lower: &[A; K], upper: &[A; K], r_range: (A, A)) -> Self {
+        self.try_crop(lower, upper, r_range)
+            .expect("index type I must be able to support all points in CAPT during construction")
+    }
+
+    /// Construct a new CAPT containing only the points of this tree which lie inside the
+    /// axis-aligned box from `lower` to `upper`, checking for index overflow.
+    ///
+    /// See [`Capt::crop`] for further details.
+    ///
+    /// # Errors
+    ///
+    /// This function will return a [`NewCaptError::TooManyPoints`] if there are too many points to
+    /// be indexed by `I`.
+    pub fn try_crop(
+        &self,
+        lower: &[A; K],
+        upper: &[A; K],
+        r_range: (A, A),
+    ) -> Result<Self, NewCaptError> {
+        let points2 = self
+            .points()
+            .filter(|p| (0..K).all(|k| lower[k] <= p[k] && p[k] <= upper[k]))
+            .collect();
+        Self::build(
+            points2,
+            r_range,
+            usize::MAX,
+            self.tests.allocator().clone(),
+            &mut no_progress,
+        )
+    }
+
     #[must_use]
     #[doc(hidden)]
     /// Get the total memory used (stack + heap) by this structure, measured in bytes.
     /// This function should not be considered stable; it is only used internally for benchmarks.
     pub const fn memory_used(&self) -> usize {
         size_of::<Self>()
-            + K * self.afforded[0].len() * size_of::<A>()
-            + self.starts.len() * size_of::<I>()
-            + self.tests.len() * size_of::<I>()
-            + self.aabbs.len() * size_of::<Aabb<A, K>>()
+            + K * self.afforded[0].capacity() * size_of::<MySimd<A, L>>()
+            + self.starts.capacity() * size_of::<I>()
+            + self.tests.capacity() * size_of::<A>()
+            + self.split_dims.capacity()
+            + self.aabbs.capacity() * size_of::<Aabb<A, K>>()
+            + self.scratch.0.capacity() * size_of::<[A; K]>()
     }
 
     #[must_use]
     #[doc(hidden)]
     #[allow(clippy::cast_precision_loss)]
     /// Get the average number of affordances per point.
+    /// Ghost leaves, which pad the tree out to a power of two, are not counted as points.
     /// This function should not be considered stable; it is only used internally for benchmarks.
     pub fn affordance_size(&self) -> f64 {
-        self.afforded.len() as f64 / (self.tests.len() + 1) as f64
+        (self.afforded[0].len() * L) as f64 / self.len().max(1) as f64
+    }
+
+    #[must_use]
+    /// Compute a report on the shape of this tree and the distribution of its affordance buffers.
+    ///
+    /// An average affordance size hides the few very large buffers which dominate worst-case
+    /// query latency, so the report includes quantiles of the buffer sizes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let capt = captree::Capt::<1>::new(&[[0.0], [1.0], [2.0]], (0.0, 0.6));
+    /// let stats = capt.stats();
+    ///
+    /// assert_eq!(stats.depth, 2);
+    /// assert_eq!(stats.n_ghost_leaves, 1);
+    /// assert_eq!(stats.afforded_max, 3);
+    /// ```
+    pub fn stats(&self) -> TreeStats {
+        let mut sizes: Vec<usize> = self
+            .leaves()
+            .filter(|leaf| leaf.representative.is_some())
+            .map(|leaf| leaf.n_afforded)
+            .collect();
+        sizes.sort_unstable();
+        let quantile = |num: usize, den: usize| {
+            sizes
+                .get(sizes.len().saturating_sub(1) * num / den)
+                .copied()
+                .unwrap_or(0)
+        };
+
+        TreeStats {
+            depth: self.depth(),
+            n_leaves: self.aabbs.len(),
+            n_ghost_leaves: self.aabbs.len() - sizes.len(),
+            afforded_min: quantile(0, 1),
+            afforded_median: quantile(1, 2),
+            afforded_p99: quantile(99, 100),
+            afforded_max: quantile(1, 1),
+            tests_bytes: self.tests.len() * size_of::<A>(),
+            starts_bytes: self.starts.len() * size_of::<I>(),
+            aabbs_bytes: self.aabbs.len() * size_of::<Aabb<A, K>>(),
+            afforded_bytes: K * self.afforded[0].len() * size_of::<MySimd<A, L>>(),
+        }
+    }
+}
+
+impl<A, I, Al, const K: usize, const L: usize> Collider<K, A> for Capt<K, L, A, I, Al>
+where
+    A: Axis,
+    I: Index,
+    Al: Alloc,
+    Align<L>: Alignment,
+{
+    fn collides(&self, center: &[A; K], radius: A) -> bool {
+        self.collides(center, radius)
     }
 }
 
+#[cfg(feature = "simd")]
+impl<A, I, Al, const K: usize, const L: usize> BatchCollider<K, L, A> for Capt<K, L, A, I, Al>
+where
+    I: Index + IndexSimd,
+    A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask> + Mul<Output = A>,
+    Al: Alloc,
+    Align<L>: Alignment,
+    Simd<A, L>: SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+    Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+{
+    fn collides_batch(&self, batch: &SimdQueryBatch<K, L, A>) -> bool {
+        self.collides_batch(batch)
+    }
+}
+
+impl<A, I, Al, const K: usize, const L: usize> Default for Capt<K, L, A, I, Al>
+where
+    A: Axis,
+    I: Index,
+    Al: Alloc + Default,
+    Align<L>: Alignment,
+{
+    /// Construct an empty tree, which supports no query radius and never collides.
+    fn default() -> Self {
+        Self::new_in(&[], (A::ZERO, A::ZERO), Al::default())
+    }
+}
+
+// Every structure may be shared between and moved across threads, so that planners can store
+// trees in standard containers and swap them behind an `Arc`.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Capt<3>>();
+    assert_send_sync::<CompactCapt<3>>();
+    assert_send_sync::<DynCapt>();
+    assert_send_sync::<GridCapt<3>>();
+    assert_send_sync::<IndexedCapt<3>>();
+    assert_send_sync::<MahalanobisCapt<3>>();
+    assert_send_sync::<MovingCapt<3>>();
+    assert_send_sync::<MultiResTree>();
+    assert_send_sync::<Octree>();
+    assert_send_sync::<PkdTree<3>>();
+    assert_send_sync::<ScaledCapt<3>>();
+    assert_send_sync::<SpaceTimeCapt>();
+};
+
 #[allow(clippy::mismatching_type_param_order)]
 #[cfg(feature = "simd")]
-impl<A, I, const K: usize, const L: usize> Capt<K, L, A, I>
+impl<A, I, Al, const K: usize, const L: usize> Capt<K, L, A, I, Al>
 where
     I: IndexSimd,
     A: Mul<Output = A>,
+    Al: Alloc,
     Align<L>: Alignment,
 {
     #[must_use]
     /// Determine whether any sphere in the list of provided spheres intersects a point in this
     /// tree.
     ///
+    /// The query kernel is compiled for several instruction sets, and the newest one supported by
+    /// the running CPU is selected at runtime.
+    /// This does not widen the kernel: the lane count `L` is fixed when the tree is constructed,
+    /// so it should be chosen to fill the widest vector registers expected on the target machines.
+    /// [`Capt::collides_any`] instead chooses how many queries to check at once at runtime.
+    ///
     /// # Examples
     ///
     /// ```
@@ -784,82 +2265,309 @@ where
     /// ];
     /// let radii = Simd::splat(0.05);
     ///
-    /// let tree = captree::Capt::<2, 4, f32, u32>::new(&points, (0.0, 0.1));
+    /// let tree = captree::Capt::<2, 4, f32, u32>::new(&points, (0.0, 0.1));
+    ///
+    /// println!("{tree:?}");
+    ///
+    /// assert!(tree.collides_simd(&centers, radii));
+    /// ```
+    pub fn collides_simd(&self, centers: &[Simd<A, L>; K], radii: Simd<A, L>) -> bool
+    where
+        Simd<A, L>:
+            SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+        Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+        A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask>,
+    {
+        multiversion(
+            #[inline(always)]
+            || self.collides_simd_inner(centers, radii, Mask::splat(true)),
+        )
+    }
+
+    #[must_use]
+    /// Determine whether any sphere in the list of provided spheres whose lane is set in `active`
+    /// intersects a point in this tree.
+    ///
+    /// This is the same as [`Capt::collides_simd`], except that inactive lanes are ignored, so a
+    /// final batch with fewer than `L` queries needs no padding.
+    /// The contents of inactive lanes are never compared against any point, so they may hold any
+    /// value, including `NaN`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(portable_simd)]
+    /// use std::simd::{Mask, Simd};
     ///
-    /// println!("{tree:?}");
+    /// let tree = captree::Capt::<1, 4, f32, u32>::new(&[[0.0]], (0.0, 0.1));
     ///
-    /// assert!(tree.collides_simd(&centers, radii));
+    /// let centers = [Simd::from_array([5.0, 6.0, 0.0, f32::NAN])];
+    /// let radii = Simd::splat(0.05);
+    ///
+    /// assert!(tree.collides_simd_masked(&centers, radii, Mask::from_array([true; 4])));
+    /// assert!(!tree.collides_simd_masked(
+    ///     &centers,
+    ///     radii,
+    ///     Mask::from_array([true, true, false, false])
+    /// ));
     /// ```
-    pub fn collides_simd(&self, centers: &[Simd<A, L>; K], radii: Simd<A, L>) -> bool
+    pub fn collides_simd_masked(
+        &self,
+        centers: &[Simd<A, L>; K],
+        radii: Simd<A, L>,
+        active: Mask<isize, L>,
+    ) -> bool
     where
-        LaneCount<L>: SupportedLaneCount,
         Simd<A, L>:
             SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
         Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
         A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask>,
     {
-        let zs = forward_pass_simd(&self.tests, centers);
-
-        let mut inbounds = Mask::splat(true);
+        multiversion(
+            #[inline(always)]
+            || self.collides_simd_inner(centers, radii, active),
+        )
+    }
 
-        let mut aabb_ptrs = Simd::splat(self.aabbs.as_ptr()).wrapping_offset(zs).cast();
+    #[must_use]
+    /// Determine whether any ball in `batch` intersects a point in this tree.
+    ///
+    /// This is the same as [`Capt::collides_simd`], taking its queries from a
+    /// [`SimdQueryBatch`], which can also be passed to [`PkdTree::might_collide_batch`].
+    pub fn collides_batch(&self, batch: &SimdQueryBatch<K, L, A>) -> bool
+    where
+        Simd<A, L>:
+            SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+        Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+        A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask>,
+    {
+        span!(TRACE, "collides_batch");
+        self.collides_simd_masked(&batch.centers, batch.radii, batch.active)
+    }
 
+    #[allow(clippy::inline_always)]
+    #[inline(always)]
+    /// The body of [`Capt::collides_simd_masked`], which is compiled once per supported
+    /// instruction set.
+    fn collides_simd_inner(
+        &self,
+        centers: &[Simd<A, L>; K],
+        radii: Simd<A, L>,
+        active: Mask<isize, L>,
+    ) -> bool
+    where
+        Simd<A, L>:
+            SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+        Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+        A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask>,
+    {
+        let zs = self.leaves_containing_simd(centers);
         unsafe {
-            for center in centers {
-                inbounds &= Mask::<isize, L>::from(
-                    (Simd::gather_select_ptr(aabb_ptrs, inbounds, Simd::splat(A::NEG_INFINITY))
-                        - radii)
-                        .simd_le(*center),
-                );
-                aabb_ptrs = aabb_ptrs.wrapping_add(Simd::splat(1));
+            // SAFETY: The forward pass always finds a valid leaf, whose affordance buffer range
+            // lies within `afforded`.
+            collides_leaves_simd(
+                &self.aabbs,
+                &self.starts,
+                &self.afforded,
+                zs,
+                centers,
+                radii,
+                active,
+            )
+        }
+    }
+
+    #[inline]
+    /// Find the index of the leaf cell containing each of `centers`, following the split axis of
+    /// each test.
+    pub(crate) fn leaves_containing_simd<const M: usize>(
+        &self,
+        centers: &[Simd<A, M>; K],
+    ) -> Simd<isize, M>
+    where
+        Simd<A, M>: SimdPartialOrd,
+        Mask<isize, M>: From<<Simd<A, M> as SimdPartialEq>::Mask>,
+        A: Axis + AxisSimd<<Simd<A, M> as SimdPartialEq>::Mask>,
+    {
+        if self.split_dims.is_empty() {
+            forward_pass_simd(&self.tests, centers)
+        } else {
+            forward_pass_simd_dims(&self.tests, &self.split_dims, centers)
+        }
+    }
+}
+
+/// Implement [`Capt::collides_any`] for an axis type.
+macro_rules! impl_collides_any {
+    ($a: ty) => {
+        #[cfg(feature = "simd")]
+        impl<I, Al, const K: usize, const L: usize> Capt<K, L, $a, I, Al>
+        where
+            I: IndexSimd,
+            Al: Alloc,
+            Align<L>: Alignment,
+            Simd<$a, L>:
+                SimdPartialOrd + Sub<Output = Simd<$a, L>> + Mul<Output = Simd<$a, L>> + AddAssign,
+            $a: AxisSimd<<Simd<$a, L> as SimdPartialEq>::Mask>,
+        {
+            #[must_use]
+            /// Determine whether any ball in `queries` intersects a point in this tree.
+            ///
+            /// Unlike [`Capt::collides_simd`], whose vectors have the `L` lanes of the tree, this
+            /// checks queries in groups as wide as the vector registers of the running CPU, which
+            /// are detected at runtime: 64 bytes with AVX-512, 32 with AVX2, and 16 otherwise, as
+            /// with SSE2, NEON or SIMD128.
+            /// Only the scans of affordance buffers stay `L` lanes wide, so a single binary gets
+            /// the widest vectors available for every other part of the query.
+            ///
+            /// # Examples
+            ///
+            /// ```
+            /// let tree = captree::Capt::<2>::new(&[[0.0, 0.0], [1.0, 1.0]], (0.0, 0.2));
+            ///
+            /// assert!(tree.collides_any(&[([0.5, 0.5], 0.1), ([0.9, 1.0], 0.15)]));
+            /// assert!(!tree.collides_any(&[([0.5, 0.5], 0.1); 20]));
+            /// ```
+            pub fn collides_any(&self, queries: &[([$a; K], $a)]) -> bool {
+                match vector_width() / size_of::<$a>() {
+                    16 => self.collides_any_lanes::<16>(queries),
+                    8 => self.collides_any_lanes::<8>(queries),
+                    4 => self.collides_any_lanes::<4>(queries),
+                    _ => self.collides_any_lanes::<2>(queries),
+                }
             }
-            for center in centers {
-                inbounds &= Mask::<isize, L>::from(
-                    Simd::gather_select_ptr(aabb_ptrs, inbounds, Simd::splat(A::NEG_INFINITY))
-                        .simd_ge(*center - radii),
-                );
-                aabb_ptrs = aabb_ptrs.wrapping_add(Simd::splat(1));
+
+            /// The body of [`Capt::collides_any`], which checks `M` queries at a time.
+            fn collides_any_lanes<const M: usize>(&self, queries: &[([$a; K], $a)]) -> bool
+            where
+                Simd<$a, M>: SimdPartialOrd + Sub<Output = Simd<$a, M>>,
+                Mask<isize, M>: From<<Simd<$a, M> as SimdPartialEq>::Mask>,
+                $a: AxisSimd<<Simd<$a, M> as SimdPartialEq>::Mask>,
+            {
+                multiversion(
+                    #[inline(always)]
+                    || {
+                        queries.chunks(M).any(|group| {
+                            let batch = SimdQueryBatch::<K, M, $a>::new(group);
+                            let zs = self.leaves_containing_simd(&batch.centers);
+                            unsafe {
+                                // SAFETY: The forward pass always finds a valid leaf, whose
+                                // affordance buffer range lies within `afforded`.
+                                collides_leaves_simd(
+                                    &self.aabbs,
+                                    &self.starts,
+                                    &self.afforded,
+                                    zs,
+                                    &batch.centers,
+                                    batch.radii,
+                                    batch.active,
+                                )
+                            }
+                        })
+                    },
+                )
             }
         }
-        if !inbounds.any() {
-            return false;
+    };
+}
+
+impl_collides_any!(f32);
+impl_collides_any!(f64);
+
+/// A progress callback for construction which never cancels.
+const fn no_progress(_: BuildPhase, _: f32) -> ControlFlow<()> {
+    ControlFlow::Continue(())
+}
+
+/// Report that construction has completed `fraction` of `phase` to `progress`, returning an
+/// error if construction should be cancelled.
+fn report(
+    progress: &mut dyn FnMut(BuildPhase, f32) -> ControlFlow<()>,
+    phase: BuildPhase,
+    fraction: f32,
+) -> Result<(), NewCaptError> {
+    match progress(phase, fraction) {
+        ControlFlow::Continue(()) => Ok(()),
+        ControlFlow::Break(()) => Err(NewCaptError::Cancelled),
+    }
+}
+
+/// Iterate over the points in an interleaved buffer `flat`, where the `i`-th point's coordinates
+/// are `flat[offset + i * stride..][..K]`.
+///
+/// # Panics
+///
+/// This function will panic if `stride` is less than `K`.
+fn strided<A: Copy, const K: usize>(
+    flat: &[A],
+    stride: usize,
+    offset: usize,
+) -> impl Iterator<Item = [A; K]> + '_ {
+    assert!(
+        stride >= K && stride > 0,
+        "stride must be at least the dimension of each point"
+    );
+    flat.get(offset..)
+        .unwrap_or_default()
+        .chunks(stride)
+        .filter(|c| c.len() >= K)
+        .map(|c| array::from_fn(|k| c[k]))
+}
+
+/// Pack the representative `rep` of a leaf cell and the points `afforded` to it into lanes of
+/// width `L`, passing each lane to `push` in order, and return the bounding box of the packed
+/// points.
+///
+/// `rep` goes in the first slot of the first lane, and the last lane is padded with infinities.
+fn pack_lanes<A: Axis, const K: usize, const L: usize, E>(
+    rep: [A; K],
+    afforded: impl IntoIterator<Item = [A; K]>,
+    mut push: impl FnMut([[A; L]; K]) -> Result<(), E>,
+) -> Result<Aabb<A, K>, E> {
+    let mut aabb = Aabb { lo: rep, hi: rep };
+
+    // lanes for afforded points
+    let mut news = [[A::INFINITY; L]; K];
+    for k in 0..K {
+        news[k][0] = rep[k];
+    }
+
+    // index into the current lane
+    let mut j = 1;
+
+    for p in afforded {
+        aabb.insert(&p);
+
+        // start a new lane if it's full
+        if j == L {
+            push(news)?;
+            news = [[A::INFINITY; L]; K];
+            j = 0;
         }
 
-        // retrieve start/end pointers for the affordance buffer
-        let start_ptrs = Simd::splat(self.starts.as_ptr()).wrapping_offset(zs);
-        let starts = unsafe { I::to_simd_usize_unchecked(Simd::gather_ptr(start_ptrs)) }.to_array();
-        let ends = unsafe {
-            I::to_simd_usize_unchecked(Simd::gather_ptr(start_ptrs.wrapping_add(Simd::splat(1))))
+        // add this point to the lane
+        for k in 0..K {
+            news[k][j] = p[k];
         }
-        .to_array();
 
-        starts
-            .into_iter()
-            .zip(ends)
-            .zip(inbounds.to_array())
-            .enumerate()
-            .filter_map(|(j, r)| r.1.then_some((j, r.0)))
-            .any(|(j, (start, end))| {
-                let mut n_center = [Simd::splat(A::ZERO); K];
-                for k in 0..K {
-                    n_center[k] = Simd::splat(centers[k][j]);
-                }
-                let rs = Simd::splat(radii[j]);
-                let rs_sq = rs * rs;
-                (start..end).any(|i| {
-                    let mut dists_sq = Simd::splat(A::ZERO);
-                    #[allow(clippy::needless_range_loop)]
-                    for k in 0..K {
-                        let vals: Simd<A, L> = unsafe {
-                            *ptr::from_ref(&self.afforded[k].get_unchecked(i).data).cast()
-                        };
-                        let diff = vals - n_center[k];
-                        dists_sq += diff * diff;
-                    }
-                    A::any(dists_sq.simd_le(rs_sq))
-                })
-            })
+        j += 1;
+    }
+
+    // fill out the last lane with infinities
+    push(news)?;
+    Ok(aabb)
+}
+
+/// Compute the bounding box of the points in `points`.
+fn bounds_of<A: Axis, const K: usize>(points: &[[A; K]]) -> Aabb<A, K> {
+    let mut bounds = Aabb {
+        lo: [A::INFINITY; K],
+        hi: [A::NEG_INFINITY; K],
+    };
+    for p in points {
+        bounds.insert(p);
     }
+    bounds
 }
 
 fn distsq<A: Axis, const K: usize>(a: [A; K], b: [A; K]) -> A {
@@ -889,6 +2597,11 @@ where
     }
 
     fn contained_by_ball(&self, center: &[A; K], rsq: A) -> bool {
+        self.farthest_distsq_to(center) <= rsq
+    }
+
+    /// Compute the squared distance from `center` to the farthest point in this volume.
+    fn farthest_distsq_to(&self, center: &[A; K]) -> A {
         let mut dist = A::ZERO;
 
         #[allow(clippy::needless_range_loop)]
@@ -899,7 +2612,7 @@ where
             dist = dist + if lo_diff < hi_diff { hi_diff } else { lo_diff };
         }
 
-        dist <= rsq
+        dist
     }
 
     #[doc(hidden)]
@@ -915,6 +2628,22 @@ where
         dist
     }
 
+    /// Compute the squared distance between the closest pair of points in `self` and `other`.
+    fn closest_distsq_to_aabb(&self, other: &Self) -> A {
+        let mut dist = A::ZERO;
+
+        for k in 0..K {
+            // compare before subtracting, so that two infinite bounds never produce `NaN`
+            if self.hi[k] < other.lo[k] {
+                dist = dist + (other.lo[k] - self.hi[k]).square();
+            } else if other.hi[k] < self.lo[k] {
+                dist = dist + (self.lo[k] - other.hi[k]).square();
+            }
+        }
+
+        dist
+    }
+
     fn insert(&mut self, point: &[A; K]) {
         self.lo
             .iter_mut()
@@ -931,25 +2660,197 @@ where
     }
 }
 
+/// Sort `points` into the leaves of a tree by repeatedly partitioning about the median, writing
+/// the splitting plane of each branch into `tests`.
+///
+/// `points` must have a power-of-two length and `tests` must have length `points.len() - 1`.
+/// The first split is along axis `k`, and the tests are written in level order.
+/// After partitioning, `points[z]` is the representative of the `z`-th leaf cell.
+///
+/// # Safety
+///
+/// This function will result in undefined behavior if `points` contains any `NaN` values.
+unsafe fn partition<A: Axis, const K: usize>(points: &mut [[A; K]], tests: &mut [A], mut k: usize) {
+    // every level splits each cell of the level above in half, so the cells of one level are
+    // exactly the equal-sized chunks of `points`
+    let mut chunk = points.len();
+    let mut first = 0;
+    while chunk > 1 {
+        for (j, cell) in points.chunks_exact_mut(chunk).enumerate() {
+            tests[first + j] = median_partition(cell, k);
+        }
+        chunk /= 2;
+        first = 2 * first + 1;
+        k = (k + 1) % K;
+    }
+}
+
+/// Compute the affordance set of every leaf cell of a tree which has already been built by
+/// [`partition`].
+///
+/// For each leaf cell `z`, in order, this calls `visit(z, in_range)`, where `in_range` contains the
+/// indices into `points` of every point other than `points[z]` which must be included in the
+/// affordance buffer for `z`.
+/// `in_range` may contain infinite (padding) points.
+fn afford<A: Axis, const K: usize, E>(
+    points: &[[A; K]],
+    tests: &[A],
+    split_dims: &[u8],
+    r_range: (A, A),
+    visit: &mut impl FnMut(usize, Vec<usize>) -> Result<(), E>,
+) -> Result<(), E> {
+    afford_help(
+        points,
+        tests,
+        split_dims,
+        r_range,
+        visit,
+        0,
+        0,
+        0..points.len(),
+        Vec::new(),
+        Aabb::ALL,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+/// Helper for [`afford`], which visits the leaves of the subtree rooted at test `i`.
+///
+/// `range` is the range of indices into `points` of the points contained in the subtree rooted at
+/// test `i`, `in_range` holds the points outside of it which its leaves may need to afford, and
+/// `cell` is the volume of that subtree.
+fn afford_help<A: Axis, const K: usize, E>(
+    points: &[[A; K]],
+    tests: &[A],
+    split_dims: &[u8],
+    r_range: (A, A),
+    visit: &mut impl FnMut(usize, Vec<usize>) -> Result<(), E>,
+    k: usize,
+    i: usize,
+    range: Range<usize>,
+    in_range: Vec<usize>,
+    cell: Aabb<A, K>,
+) -> Result<(), E> {
+    let rsq_min = r_range.0.square();
+    // subtrees still to be visited, with the leftmost on top so that leaves are visited in order
+    let mut stack = vec![(k, i, range, in_range, cell)];
+    while let Some((k, i, range, in_range, cell)) = stack.pop() {
+        if range.len() == 1 {
+            let z = range.start;
+            // the representative covers everything if the whole cell is within the minimum radius
+            if cell.contained_by_ball(&points[z], rsq_min) {
+                visit(z, Vec::new())?;
+            } else {
+                visit(z, in_range)?;
+            }
+            continue;
+        }
+
+        let test = tests[i];
+        let next_k = (k + 1) % K;
+        let k = split_dims.get(i).map_or(k, |&d| usize::from(d));
+        let mid = range.start + range.len() / 2;
+        let (lo_vol, hi_vol) = cell.split(test, k);
+
+        let lo_too_small = distsq(lo_vol.lo, lo_vol.hi) <= rsq_min;
+        let hi_too_small = distsq(hi_vol.lo, hi_vol.hi) <= rsq_min;
+
+        // retain only points which might be in the affordance buffer for the split-out cells
+        let lo_keep = |&j: &usize| points[j][k].is_finite() && points[j][k] <= test + r_range.1;
+        let hi_keep = |&j: &usize| points[j][k].is_finite() && test - r_range.1 <= points[j][k];
+        let (lo_afford, hi_afford) = match (lo_too_small, hi_too_small) {
+            (false, false) => {
+                let mut lo_afford = in_range;
+                let mut hi_afford = lo_afford.clone();
+                lo_afford.retain(lo_keep);
+                lo_afford.extend((mid..range.end).filter(lo_keep));
+                hi_afford.retain(hi_keep);
+                hi_afford.extend((range.start..mid).filter(hi_keep));
+
+                (lo_afford, hi_afford)
+            }
+            (false, true) => {
+                let mut lo_afford = in_range;
+                lo_afford.retain(lo_keep);
+                lo_afford.extend((mid..range.end).filter(lo_keep));
+
+                (lo_afford, Vec::new())
+            }
+            (true, false) => {
+                let mut hi_afford = in_range;
+                hi_afford.retain(|&j| test - r_range.1 <= points[j][k]);
+                hi_afford.extend((range.start..mid).filter(hi_keep));
+
+                (Vec::new(), hi_afford)
+            }
+            (true, true) => (Vec::new(), Vec::new()),
+        };
+
+        stack.push((next_k, 2 * i + 2, mid..range.end, hi_afford, hi_vol));
+        stack.push((next_k, 2 * i + 1, range.start..mid, lo_afford, lo_vol));
+    }
+
+    Ok(())
+}
+
 #[inline]
 /// Calculate the "true" median (halfway between two midpoints) and partition `points` about said
 /// median along axis `d`.
 ///
+/// Points whose coordinate equals the upper midpoint are gathered into one contiguous run around
+/// the middle of `points`.
+/// If that run crosses the middle, the median is the shared coordinate, so every tied point lies
+/// on the split plane: tied points in the lower half sit on the upper boundary of their cell, and
+/// a query exactly on the plane descends into the upper half, whose affordance buffers include
+/// every lower point within the query radius of the plane.
+///
 /// # Safety
 ///
 /// This function will result in undefined behavior if `points` contains any `NaN` values.
 unsafe fn median_partition<A: Axis, const K: usize>(points: &mut [[A; K]], k: usize) -> A {
-    let (lh, med_hi, _) = points.select_nth_unstable_by(points.len() / 2, |a, b| {
-        a[k].partial_cmp(&b[k]).unwrap_unchecked()
-    });
-    let med_lo = lh
-        .iter_mut()
-        .map(|p| p[k])
-        .max_by(|a, b| a.partial_cmp(b).unwrap_unchecked())
-        .unwrap();
+    let mid = points.len() / 2;
+    let (_, &mut med_hi, _) =
+        points.select_nth_unstable_by(mid, |a, b| a[k].partial_cmp(&b[k]).unwrap_unchecked());
+    let (ties_start, ties_end) = three_way_partition(points, k, med_hi[k]);
+    let med_lo = if ties_start < mid {
+        med_hi[k]
+    } else {
+        points[..mid]
+            .iter()
+            .map(|p| p[k])
+            .max_by(|a, b| a.partial_cmp(b).unwrap_unchecked())
+            .unwrap()
+    };
+    debug_assert!(ties_start <= mid && mid < ties_end);
     A::in_between(med_lo, med_hi[k])
 }
 
+/// Partition `points` into those less than, equal to, and greater than `pivot` along axis `k`,
+/// using a single Dutch national flag pass.
+///
+/// Returns `(start, end)`, where `points[..start]` are less than `pivot` and `points[start..end]`
+/// are equal to it.
+fn three_way_partition<A: Axis, const K: usize>(
+    points: &mut [[A; K]],
+    k: usize,
+    pivot: A,
+) -> (usize, usize) {
+    let (mut lt, mut i, mut gt) = (0, 0, points.len());
+    while i < gt {
+        if points[i][k] < pivot {
+            points.swap(lt, i);
+            lt += 1;
+            i += 1;
+        } else if pivot < points[i][k] {
+            gt -= 1;
+            points.swap(i, gt);
+        } else {
+            i += 1;
+        }
+    }
+    (lt, gt)
+}
+
 #[cfg(test)]
 mod tests {
     use rand::{thread_rng, Rng};
@@ -1045,6 +2946,347 @@ mod tests {
         assert!(t.collides(&[-0.001, -0.2], 1.0));
     }
 
+    #[test]
+    fn from_points_matches_new() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 2]> = (0..100).map(|_| [rng.gen(), rng.gen()]).collect();
+
+        let t0 = Capt::<2>::new(&points, (0.01, 0.1));
+        let t1 = Capt::<2>::from_points(points.iter().copied(), (0.01, 0.1));
+        assert_eq!(t0, t1);
+    }
+
+    #[test]
+    fn from_flat_strides() {
+        let flat = [9.0, 0.0, 1.0, -1.0, 2.0, 3.0, -1.0, 4.0];
+        let t = Capt::<2>::from_flat(&flat, 3, 1, (0.0, 0.1));
+        assert!(t.collides(&[0.0, 1.0], 0.05));
+        assert!(t.collides(&[2.0, 3.0], 0.05));
+        assert!(!t.collides(&[-1.0, 2.0], 0.05));
+        assert!(!t.collides(&[4.0, 0.0], 0.05));
+    }
+
+    #[test]
+    fn rebuild_matches_new() {
+        let mut rng = thread_rng();
+        let mut t = Capt::<2>::new(&[[0.0, 0.0]], (0.01, 0.1));
+        for n in [100, 120, 7, 0, 300] {
+            let points: Vec<[f32; 2]> = (0..n).map(|_| [rng.gen(), rng.gen()]).collect();
+            t.rebuild(&points, (0.01, 0.1)).unwrap();
+            assert_eq!(t, Capt::new(&points, (0.01, 0.1)));
+        }
+
+        assert!(t.rebuild(&[[f32::NAN, 0.0]], (0.01, 0.1)).is_err());
+        assert!(!t.collides(&[0.0, 0.0], 0.1));
+    }
+
+    #[test]
+    fn rebuild_reuses_buffers() {
+        use std::{alloc::Layout, cell::Cell, rc::Rc};
+
+        /// The global allocator, counting every allocation made through it.
+        #[derive(Clone, Default)]
+        struct Counting(Rc<Cell<usize>>);
+
+        unsafe impl Alloc for Counting {
+            fn allocate(&self, layout: Layout) -> *mut u8 {
+                self.0.set(self.0.get() + 1);
+                Global.allocate(layout)
+            }
+
+            unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+                Global.deallocate(ptr, layout);
+            }
+        }
+
+        let mut rng = thread_rng();
+        let frames: Vec<Vec<[f32; 2]>> = [1000, 900, 513, 3, 0]
+            .into_iter()
+            .map(|n| (0..n).map(|_| [rng.gen(), rng.gen()]).collect())
+            .collect();
+        let counting = Counting::default();
+        let mut t = Capt::<2, 8, f32, usize, Counting>::new_in(&[], (0.01, 0.1), counting.clone());
+
+        // the first pass grows the buffers to fit every frame, and the second reuses them
+        for frame in &frames {
+            t.rebuild(frame, (0.01, 0.1)).unwrap();
+        }
+        let allocations = counting.0.get();
+        for frame in &frames {
+            t.rebuild(frame, (0.01, 0.1)).unwrap();
+            assert!(t.points().eq(Capt::<2>::new(frame, (0.01, 0.1)).points()));
+        }
+        assert!(t.rebuild(&[[f32::NAN, 0.0]], (0.01, 0.1)).is_err());
+        assert_eq!(counting.0.get(), allocations);
+    }
+
+    #[test]
+    fn merge_matches_new() {
+        let mut rng = thread_rng();
+        for (n1, n2) in [(0, 0), (0, 10), (100, 28), (77, 300)] {
+            let points: Vec<[f32; 2]> = (0..n1 + n2).map(|_| [rng.gen(), rng.gen()]).collect();
+            let a = Capt::<2>::new(&points[..n1], (0.01, 0.1));
+            let b = Capt::new(&points[n1..], (0.01, 0.1));
+
+            let mut extracted: Vec<_> = a.points().chain(b.points()).collect();
+            let mut expected = points.clone();
+            extracted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            assert_eq!(extracted, expected);
+
+            let merged = a.merge(&b, (0.01, 0.1));
+            let fresh = Capt::<2>::new(&points, (0.01, 0.1));
+            for _ in 0..100 {
+                let center = [rng.gen(), rng.gen()];
+                let r = rng.gen_range(0.01..=0.1);
+                assert_eq!(merged.collides(&center, r), fresh.collides(&center, r));
+            }
+        }
+    }
+
+    #[test]
+    fn crop_matches_new() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 2]> = (0..500).map(|_| [rng.gen(), rng.gen()]).collect();
+        let t = Capt::<2>::new(&points, (0.01, 0.1));
+
+        let (lower, upper) = ([0.2, 0.3], [0.6, 0.5]);
+        let inside: Vec<_> = points
+            .iter()
+            .copied()
+            .filter(|p| (0..2).all(|k| lower[k] <= p[k] && p[k] <= upper[k]))
+            .collect();
+        let cropped = t.crop(&lower, &upper, (0.01, 0.1));
+        let fresh = Capt::<2>::new(&inside, (0.01, 0.1));
+        for _ in 0..1000 {
+            let center = [rng.gen(), rng.gen()];
+            let r = rng.gen_range(0.01..=0.1);
+            assert_eq!(cropped.collides(&center, r), fresh.collides(&center, r));
+        }
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn leaves_cover_points() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 2]> = (0..300).map(|_| [rng.gen(), rng.gen()]).collect();
+        let t = Capt::<2>::new(&points, (0.04, 0.1));
+
+        let leaves: Vec<_> = t.leaves().collect();
+        assert_eq!(leaves.len(), 512);
+        assert_eq!(
+            leaves.iter().filter(|l| l.representative.is_some()).count(),
+            300
+        );
+        for leaf in &leaves {
+            if let Some(rep) = leaf.representative {
+                assert_eq!(leaf.cell.closest_distsq_to(&rep), 0.0);
+                assert_eq!(leaf.bounds.closest_distsq_to(&rep), 0.0);
+                let in_range = points
+                    .iter()
+                    .filter(|&&p| leaf.cell.closest_distsq_to(&p) <= 0.01)
+                    .count();
+                if leaf.n_afforded == 1 && in_range > 1 {
+                    // only a cell within the minimum radius of its representative may drop the
+                    // other points in range
+                    assert!(leaf.cell.contained_by_ball(&rep, 0.04 * 0.04));
+                } else {
+                    assert!(leaf.n_afforded >= in_range);
+                }
+            } else {
+                assert_eq!(leaf.n_afforded, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn stats_match_leaves() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let t = Capt::<3>::new(&points, (0.01, 0.05));
+        let stats = t.stats();
+
+        assert_eq!(stats.depth, 10);
+        assert_eq!(stats.n_leaves, 1024);
+        assert_eq!(stats.n_ghost_leaves, 24);
+        let max = t.leaves().map(|l| l.n_afforded).max().unwrap();
+        assert_eq!(stats.afforded_max, max);
+        assert!(stats.afforded_min <= stats.afforded_median);
+        assert!(stats.afforded_median <= stats.afforded_p99);
+        assert!(stats.afforded_p99 <= stats.afforded_max);
+        assert_eq!(
+            size_of::<Capt<3>>()
+                + stats.tests_bytes
+                + stats.starts_bytes
+                + stats.aabbs_bytes
+                + stats.afforded_bytes,
+            t.memory_used()
+        );
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn metadata_accessors() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let r_range = (0.01, 0.05);
+
+        let t = Capt::<3>::new(&points, r_range);
+        assert_eq!(t.len(), 1000);
+        assert!(!t.is_empty());
+        assert_eq!(t.dimension(), 3);
+        assert_eq!(t.depth(), 10);
+        assert_eq!(t.r_range(), r_range);
+
+        let pkdt = PkdTree::new(&points);
+        assert_eq!(pkdt.len(), 1000);
+        assert_eq!(pkdt.depth(), 10);
+
+        let indexed = IndexedCapt::<3>::new(&points, r_range);
+        assert_eq!(indexed.len(), 1000);
+        assert_eq!(indexed.depth(), 10);
+        assert_eq!(indexed.r_range(), r_range);
+
+        let grid = GridCapt::<3>::new(&points, r_range, 0.1);
+        assert_eq!(grid.len(), 1000);
+        assert_eq!(grid.r_range(), r_range);
+
+        let octree = Octree::<3>::new(&points, r_range);
+        assert_eq!(octree.len(), 1000);
+        assert_eq!(octree.r_range(), r_range);
+
+        let empty = Capt::<3>::new(&[], r_range);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn defaults_are_empty() {
+        let t = Capt::<3>::default();
+        assert!(t.is_empty());
+        assert!(!t.collides(&[0.0; 3], 0.0));
+        assert!(PkdTree::<3>::default().is_empty());
+        assert!(CompactCapt::<3>::default().is_empty());
+        assert!(IndexedCapt::<3>::default().is_empty());
+        assert!(Octree::<3>::default().is_empty());
+
+        let e = Capt::<3>::try_new(&[[f32::NAN; 3]], (0.0, 1.0)).unwrap_err();
+        assert_eq!(e.to_string(), "point cloud contains a non-finite value");
+    }
+
+    #[test]
+    fn from_pkdt_matches_new() {
+        let mut rng = thread_rng();
+        for n in [0, 1, 100, 128, 300] {
+            let points: Vec<[f32; 3]> = (0..n).map(|_| [rng.gen(), rng.gen(), rng.gen()]).collect();
+            let pkdt = PkdTree::new(&points);
+            assert_eq!(
+                Capt::<3>::from_pkdt(&pkdt, (0.01, 0.1)),
+                Capt::new(&points, (0.01, 0.1))
+            );
+        }
+    }
+
+    #[test]
+    fn capped_reports_dense_cells() {
+        // a dense cluster near the origin, plus a few isolated points far away
+        let mut points: Vec<[f32; 2]> = (0..16u8).map(|i| [f32::from(i) * 0.001, 0.0]).collect();
+        points.extend([[10.0, 10.0], [20.0, 20.0], [30.0, 30.0], [40.0, 40.0]]);
+
+        let Err(NewCaptError::AffordanceOverflow(cells)) =
+            Capt::<2>::try_new_capped(&points, (0.0, 0.1), 4)
+        else {
+            panic!("dense cluster should overflow the cap");
+        };
+        assert!(!cells.is_empty());
+        assert!(cells.iter().all(|c| c.n_afforded > 4));
+        assert!(cells.windows(2).all(|w| w[0].cell < w[1].cell));
+
+        let capped = Capt::<2>::try_new_capped(&points, (0.0, 0.1), usize::MAX).unwrap();
+        assert_eq!(capped, Capt::<2>::new(&points, (0.0, 0.1)));
+    }
+
+    #[test]
+    fn lanes_are_padded() {
+        // use a power of two so that there are no infinite padding points in the cloud
+        let points = [
+            [0.0, 0.1],
+            [0.4, -0.2],
+            [-0.2, -0.1],
+            [0.3, 0.3],
+            [1.0, 1.0],
+            [0.5, 0.5],
+            [0.6, 0.2],
+            [0.1, 0.9],
+        ];
+        let t = Capt::<2, 4, f32, u32>::new(&points, (0.0, 0.5));
+
+        for z in 0..t.starts.len() - 1 {
+            let range = t.starts[z] as usize..t.starts[z + 1] as usize;
+            for afforded_k in &t.afforded {
+                let lanes = &afforded_k[range.clone()];
+                // padding may only appear in the last lane of each cell
+                for (i, lane) in lanes.iter().enumerate() {
+                    let n_finite = lane.data.iter().take_while(|x| x.is_finite()).count();
+                    assert!(lane.data[n_finite..].iter().all(|x| x.is_infinite()));
+                    assert!(n_finite == 4 || i + 1 == lanes.len());
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "aligned")]
+    fn lanes_are_aligned() {
+        let points: Vec<[f32; 3]> = (0..100u8)
+            .map(|i| [f32::from(i).sin(), f32::from(i).cos(), f32::from(i) / 100.0])
+            .collect();
+        let t = Capt::<3, 8>::new(&points, (0.0, 0.2));
+
+        // a lane of eight `f32`s fills one 32-byte vector register
+        for lane in t.afforded.iter().flat_map(|ak| ak.iter()) {
+            assert_eq!(std::ptr::from_ref(lane) as usize % 32, 0);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "simd")]
+    fn collides_any_matches_brute_force() {
+        let mut rng = thread_rng();
+        let bounds = Aabb {
+            lo: [0.0; 3],
+            hi: [1.0; 3],
+        };
+        let r_range = (0.01, 0.08);
+        let points = crate::testing::uniform_points(&mut rng, 500, &bounds);
+        let t = Capt::<3, 8>::new(&points, r_range);
+        let points64: Vec<[f64; 3]> = points.iter().map(|p| p.map(f64::from)).collect();
+        let t64 = Capt::<3, 4, f64>::new(&points64, (0.01, 0.08));
+
+        for n in [1, 3, 7, 16, 33] {
+            let queries = crate::testing::uniform_queries(&mut rng, n, &bounds, r_range);
+            let expected = queries
+                .iter()
+                .any(|(c, r)| crate::testing::brute_force_collides(&points, c, *r));
+
+            // every width must agree, whichever one this CPU would pick
+            assert_eq!(t.collides_any(&queries), expected);
+            assert_eq!(t.collides_any_lanes::<2>(&queries), expected);
+            assert_eq!(t.collides_any_lanes::<4>(&queries), expected);
+            assert_eq!(t.collides_any_lanes::<8>(&queries), expected);
+            assert_eq!(t.collides_any_lanes::<16>(&queries), expected);
+
+            let queries64: Vec<([f64; 3], f64)> = queries
+                .iter()
+                .map(|(c, r)| (c.map(f64::from), f64::from(*r)))
+                .collect();
+            assert_eq!(t64.collides_any(&queries64), expected);
+        }
+    }
+
     #[test]
     #[allow(clippy::float_cmp)]
     fn does_it_partition() {
@@ -1059,4 +3301,84 @@ mod tests {
             assert!(p0[0] >= median);
         }
     }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn partition_gathers_ties() {
+        let mut points = vec![[3.0], [1.0], [2.0], [2.0], [0.0], [2.0], [2.0], [4.0]];
+        let median = unsafe { median_partition(&mut points, 0) };
+        assert_eq!(median, 2.0);
+        assert_eq!(
+            points.iter().map(|p| p[0]).collect::<Vec<_>>()[2..6],
+            [2.0; 4]
+        );
+    }
+
+    #[test]
+    fn quantized_cloud() {
+        let mut rng = thread_rng();
+        // points snapped to a coarse grid, so that most coordinates are shared by many points
+        let points: Vec<[f32; 3]> = (0..2000)
+            .map(|_| [0; 3].map(|_: i32| f32::from(rng.gen_range(0u8..8)) * 0.1))
+            .collect();
+        let r_range = (0.0, 0.15);
+        let t = Capt::<3>::new(&points, r_range);
+        assert_eq!(t.validate(), Ok(()));
+
+        for _ in 0..1000 {
+            // queries often land exactly on split planes
+            let center = [0; 3].map(|_: i32| f32::from(rng.gen_range(0u8..16)) * 0.05);
+            let radius = rng.gen_range(r_range.0..=r_range.1);
+            let expected = points.iter().any(|&p| distsq(p, center) <= radius * radius);
+            assert_eq!(t.collides(&center, radius), expected);
+        }
+    }
+
+    #[test]
+    fn progress_phases() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..300)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+
+        let mut reports = Vec::new();
+        let t = Capt::<3>::try_new_with_progress(&points, (0.01, 0.1), |phase, fraction| {
+            reports.push((phase, fraction));
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        assert_eq!(t, Capt::new(&points, (0.01, 0.1)));
+        assert_eq!(reports[0], (BuildPhase::Partition, 0.0));
+        assert_eq!(reports.last(), Some(&(BuildPhase::Layout, 1.0)));
+        assert!(reports
+            .windows(2)
+            .all(|w| w[0].0 as u8 <= w[1].0 as u8 && (w[0].0 != w[1].0 || w[0].1 <= w[1].1)));
+
+        let mut n_calls = 0;
+        let cancelled = Capt::<3>::try_new_with_progress(&points, (0.01, 0.1), |_, _| {
+            n_calls += 1;
+            if n_calls == 10 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(cancelled, Err(NewCaptError::Cancelled));
+        assert_eq!(n_calls, 10);
+    }
+
+    #[test]
+    fn planar_matches_brute_force() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 2]> = (0..2000).map(|_| [rng.gen(), rng.gen()]).collect();
+        let (r_min, r_max) = (0.005, 0.05);
+        let t = Capt2d::<8>::new(&points, (r_min, r_max));
+
+        for _ in 0..10_000 {
+            let center = [rng.gen_range(-0.1..1.1), rng.gen_range(-0.1..1.1)];
+            let r = rng.gen_range(r_min..=r_max);
+            let collides = points.iter().any(|p| distsq(*p, center) <= r * r);
+            assert_eq!(collides, t.collides(&center, r));
+        }
+    }
 }
@@ -0,0 +1,236 @@
+//! Collision checking under a Mahalanobis distance.
+
+use elain::{Align, Alignment};
+
+use crate::{Capt, Collider, Index, NewCaptError};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A collision-affording point tree which measures distance by a fixed positive-definite metric
+/// matrix.
+///
+/// The squared distance between two points `a` and `b` is `(a - b)ᵀ M (a - b)`, where `M` is the
+/// metric provided at construction.
+/// Taking `M` to be the inverse of a covariance matrix makes each query ball an ellipsoid shaped
+/// like that covariance, which is useful for safety margins which follow positional uncertainty.
+/// When `M` is diagonal, this is the same metric as a [`ScaledCapt`](crate::ScaledCapt) whose
+/// scales are the square roots of the diagonal.
+///
+/// Internally, the metric is factored as `M = L Lᵀ` by a Cholesky decomposition, and every point
+/// and query center is multiplied by `Lᵀ`, under which the metric becomes Euclidean.
+///
+/// # Generic parameters
+///
+/// - `K`: The dimension of the space.
+/// - `L`: The lane size of the underlying tree.
+/// - `A`: The value of the axes of each point.
+/// - `I`: The index integer of the underlying tree.
+///
+/// # Examples
+///
+/// ```
+/// use captree::MahalanobisCapt;
+///
+/// // distances along the x-axis count four times as much as along the y-axis
+/// let metric = [[16.0, 0.0], [0.0, 1.0]];
+/// let t = MahalanobisCapt::<2>::new(&[[0.0, 0.0]], metric, (0.0, 1.0));
+///
+/// assert!(t.collides_mahalanobis(&[0.0, 0.5], 0.6));
+/// assert!(!t.collides_mahalanobis(&[0.5, 0.0], 0.6));
+/// ```
+pub struct MahalanobisCapt<const K: usize, const L: usize = 8, A = f32, I = usize>
+where
+    Align<L>: Alignment,
+{
+    /// The tree over the transformed points.
+    tree: Capt<K, L, A, I>,
+    /// The lower-triangular Cholesky factor of the metric.
+    /// Entries above the diagonal are zero.
+    factor: [[A; K]; K],
+}
+
+/// Implement `MahalanobisCapt` for axes of type `$a`.
+macro_rules! impl_mahalanobis {
+    ($a: ty) => {
+        impl<I, const K: usize, const L: usize> MahalanobisCapt<K, L, $a, I>
+        where
+            I: Index,
+            Align<L>: Alignment,
+        {
+            #[must_use]
+            /// Construct a new tree containing all the points in `points`, measuring distance by
+            /// the metric matrix `metric`.
+            ///
+            /// Only the lower triangle of `metric` (the entries `metric[i][j]` with `j <= i`) is
+            /// read; the matrix is assumed to be symmetric.
+            ///
+            /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on
+            /// the radius of the balls which will be queried against the tree, measured in the
+            /// Mahalanobis metric.
+            ///
+            /// # Panics
+            ///
+            /// This function will panic if `metric` is not positive-definite, or under the same
+            /// conditions as [`Capt::new`].
+            pub fn new(points: &[[$a; K]], metric: [[$a; K]; K], r_range: ($a, $a)) -> Self {
+                Self::try_new(points, metric, r_range).expect(
+                    "index type I must be able to support all points in CAPT during construction",
+                )
+            }
+
+            /// Construct a new tree containing all the points in `points`, checking for index
+            /// overflow.
+            ///
+            /// See [`MahalanobisCapt::new`] for further details.
+            ///
+            /// # Errors
+            ///
+            /// This function will return an error under the same conditions as
+            /// [`Capt::try_new`].
+            ///
+            /// # Panics
+            ///
+            /// This function will panic if `metric` is not positive-definite.
+            pub fn try_new(
+                points: &[[$a; K]],
+                metric: [[$a; K]; K],
+                r_range: ($a, $a),
+            ) -> Result<Self, NewCaptError> {
+                let factor = Self::cholesky(&metric);
+                Ok(Self {
+                    tree: Capt::try_from_points(
+                        points.iter().map(|p| Self::apply(&factor, p)),
+                        r_range,
+                    )?,
+                    factor,
+                })
+            }
+
+            /// Compute the lower-triangular factor `L` such that `metric = L Lᵀ`.
+            ///
+            /// # Panics
+            ///
+            /// This function will panic if `metric` is not positive-definite.
+            fn cholesky(metric: &[[$a; K]; K]) -> [[$a; K]; K] {
+                let mut factor: [[$a; K]; K] = [[0.0; K]; K];
+                for i in 0..K {
+                    for j in 0..=i {
+                        let s = (0..j).fold(metric[i][j], |s, k| {
+                            (-factor[i][k]).mul_add(factor[j][k], s)
+                        });
+                        if i == j {
+                            assert!(
+                                s > 0.0 && s.is_finite(),
+                                "metric matrix must be positive-definite"
+                            );
+                            factor[i][i] = s.sqrt();
+                        } else {
+                            factor[i][j] = s / factor[j][j];
+                        }
+                    }
+                }
+                factor
+            }
+
+            /// Multiply `point` by the transpose of `factor`.
+            fn apply(factor: &[[$a; K]; K], point: &[$a; K]) -> [$a; K] {
+                std::array::from_fn(|j| {
+                    (j..K).fold(0.0 as $a, |s, i| factor[i][j].mul_add(point[i], s))
+                })
+            }
+
+            #[must_use]
+            /// Determine whether a point in this tree is within a distance of `radius` to
+            /// `center`, measured in the Mahalanobis metric.
+            ///
+            /// As with [`Capt::collides`], the result is only guaranteed to be correct if `radius`
+            /// is inside the radius range used to construct this tree.
+            pub fn collides_mahalanobis(&self, center: &[$a; K], radius: $a) -> bool {
+                self.tree
+                    .collides(&Self::apply(&self.factor, center), radius)
+            }
+
+            #[must_use]
+            /// Get the lower-triangular Cholesky factor `L` of the metric, such that the metric
+            /// is `L Lᵀ`.
+            pub const fn factor(&self) -> &[[$a; K]; K] {
+                &self.factor
+            }
+
+            #[must_use]
+            /// Get the underlying tree, which contains the transformed points.
+            pub const fn tree(&self) -> &Capt<K, L, $a, I> {
+                &self.tree
+            }
+
+            #[must_use]
+            /// Get the number of points in this tree.
+            pub fn len(&self) -> usize {
+                self.tree.len()
+            }
+
+            #[must_use]
+            /// Determine whether this tree contains no points.
+            pub fn is_empty(&self) -> bool {
+                self.tree.is_empty()
+            }
+
+            #[must_use]
+            /// Get the dimension of the points in this tree, `K`.
+            pub const fn dimension(&self) -> usize {
+                K
+            }
+
+            #[must_use]
+            /// Get the `(minimum, maximum)` range of query radii which this tree was built to
+            /// support.
+            pub const fn r_range(&self) -> ($a, $a) {
+                self.tree.r_range()
+            }
+        }
+
+        impl<I, const K: usize, const L: usize> Collider<K, $a> for MahalanobisCapt<K, L, $a, I>
+        where
+            I: Index,
+            Align<L>: Alignment,
+        {
+            fn collides(&self, center: &[$a; K], radius: $a) -> bool {
+                self.collides_mahalanobis(center, radius)
+            }
+        }
+    };
+}
+
+impl_mahalanobis!(f32);
+impl_mahalanobis!(f64);
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = thread_rng();
+        let metric = [[4.0, 1.0, 0.5], [1.0, 2.0, -0.3], [0.5, -0.3, 1.0]];
+        let points: Vec<[f64; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let (r_min, r_max) = (0.01, 0.2);
+        let t = MahalanobisCapt::<3, 8, f64>::new(&points, metric, (r_min, r_max));
+
+        for _ in 0..1000 {
+            let center = [rng.gen(), rng.gen(), rng.gen()];
+            let radius = rng.gen_range(r_min..=r_max);
+            let expected = points.iter().any(|p| {
+                let d: [f64; 3] = std::array::from_fn(|k| p[k] - center[k]);
+                let dsq: f64 = (0..3)
+                    .flat_map(|i| (0..3).map(move |j| (i, j)))
+                    .map(|(i, j)| d[i] * metric[i][j] * d[j])
+                    .sum();
+                dsq <= radius * radius
+            });
+            assert_eq!(t.collides_mahalanobis(&center, radius), expected);
+        }
+    }
+}
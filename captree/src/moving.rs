@@ -0,0 +1,216 @@
+//! Collision checking against obstacles which may move during a plan.
+
+use elain::{Align, Alignment};
+
+use crate::{Capt, Index, NewCaptError};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A collision-affording point tree over obstacles with known velocities, which remains
+/// conservative for as long as a given time horizon.
+///
+/// Each obstacle at position `p` with velocity `v` may be anywhere within `|v| * horizon` of `p`
+/// by the end of the horizon.
+/// Rather than extrapolating positions, the tree inflates every query by the largest such
+/// distance, which is equivalent to widening the radius range used to prune its affordance sets.
+///
+/// # Generic parameters
+///
+/// - `K`: The dimension of the space.
+/// - `L`: The lane size of the underlying tree.
+/// - `A`: The value of the axes of each point.
+/// - `I`: The index integer of the underlying tree.
+///
+/// # Examples
+///
+/// ```
+/// use captree::MovingCapt;
+///
+/// // one obstacle at rest, and one moving at 2 units per second
+/// let obstacles = [([0.0, 0.0], [0.0, 0.0]), ([5.0, 0.0], [-2.0, 0.0])];
+/// let t = MovingCapt::<2>::new(&obstacles, 0.5, (0.0, 0.5));
+///
+/// assert_eq!(t.inflation(), 1.0);
+/// // the second obstacle could be at x = 4.0 within half a second
+/// assert!(t.collides(&[3.6, 0.0], 0.5));
+/// assert!(!t.collides(&[2.4, 0.0], 0.5));
+/// ```
+pub struct MovingCapt<const K: usize, const L: usize = 8, A = f32, I = usize>
+where
+    Align<L>: Alignment,
+{
+    /// The tree over the current positions of the obstacles.
+    tree: Capt<K, L, A, I>,
+    /// The farthest distance any obstacle may travel within the horizon.
+    inflation: A,
+}
+
+/// Implement `MovingCapt` for axes of type `$a`.
+macro_rules! impl_moving {
+    ($a: ty) => {
+        impl<I, const K: usize, const L: usize> MovingCapt<K, L, $a, I>
+        where
+            I: Index,
+            Align<L>: Alignment,
+        {
+            #[must_use]
+            /// Construct a new tree containing the obstacles in `points`, each given as a
+            /// `(position, velocity)` pair, which may move for up to `horizon` units of time.
+            ///
+            /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on
+            /// the radius of the balls which will be queried against the tree, before inflation.
+            ///
+            /// # Panics
+            ///
+            /// This function will panic if `horizon` is negative or not finite, or under the same
+            /// conditions as [`Capt::new`].
+            pub fn new(points: &[([$a; K], [$a; K])], horizon: $a, r_range: ($a, $a)) -> Self {
+                Self::try_new(points, horizon, r_range).expect(
+                    "index type I must be able to support all points in CAPT during construction",
+                )
+            }
+
+            /// Construct a new tree containing the obstacles in `points`, checking for index
+            /// overflow.
+            ///
+            /// See [`MovingCapt::new`] for further details.
+            ///
+            /// # Errors
+            ///
+            /// This function will return `Err(NewCaptError::NonFinite)` if any velocity is
+            /// non-finite, and will otherwise return an error under the same conditions as
+            /// [`Capt::try_new`].
+            ///
+            /// # Panics
+            ///
+            /// This function will panic if `horizon` is negative or not finite.
+            pub fn try_new(
+                points: &[([$a; K], [$a; K])],
+                horizon: $a,
+                r_range: ($a, $a),
+            ) -> Result<Self, NewCaptError> {
+                assert!(
+                    horizon >= 0.0 && horizon.is_finite(),
+                    "time horizon must be non-negative and finite"
+                );
+                let mut max_speed_sq: $a = 0.0;
+                for (_, v) in points {
+                    let speed_sq = v.iter().map(|x| x * x).sum::<$a>();
+                    if !speed_sq.is_finite() {
+                        return Err(NewCaptError::NonFinite);
+                    }
+                    max_speed_sq = max_speed_sq.max(speed_sq);
+                }
+                let inflation = max_speed_sq.sqrt() * horizon;
+
+                let positions: Vec<[$a; K]> = points.iter().map(|&(p, _)| p).collect();
+                Ok(Self {
+                    tree: Capt::try_new(
+                        &positions,
+                        (r_range.0 + inflation, r_range.1 + inflation),
+                    )?,
+                    inflation,
+                })
+            }
+
+            #[must_use]
+            /// Determine whether any obstacle in this tree may come within a distance of
+            /// `radius` to `center` before the end of the horizon.
+            ///
+            /// As with [`Capt::collides`], the result is only guaranteed to be conservative if
+            /// `radius` is inside the radius range used to construct this tree.
+            pub fn collides(&self, center: &[$a; K], radius: $a) -> bool {
+                self.tree.collides(center, radius + self.inflation)
+            }
+
+            #[must_use]
+            /// Get the distance by which every query is inflated: the largest speed of any
+            /// obstacle multiplied by the horizon.
+            pub const fn inflation(&self) -> $a {
+                self.inflation
+            }
+
+            #[must_use]
+            /// Get the number of points in this tree.
+            pub fn len(&self) -> usize {
+                self.tree.len()
+            }
+
+            #[must_use]
+            /// Determine whether this tree contains no points.
+            pub fn is_empty(&self) -> bool {
+                self.tree.is_empty()
+            }
+
+            #[must_use]
+            /// Get the dimension of the points in this tree, `K`.
+            pub const fn dimension(&self) -> usize {
+                K
+            }
+
+            #[must_use]
+            /// Get the `(minimum, maximum)` range of query radii which this tree was built to
+            /// support.
+            pub const fn r_range(&self) -> ($a, $a) {
+                let (r_min, r_max) = self.tree.r_range();
+                (r_min - self.inflation, r_max - self.inflation)
+            }
+        }
+    };
+}
+
+impl_moving!(f32);
+impl_moving!(f64);
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+    use crate::distsq;
+
+    #[test]
+    fn conservative() {
+        let mut rng = thread_rng();
+        let points: Vec<([f32; 3], [f32; 3])> = (0..500)
+            .map(|_| {
+                (
+                    [rng.gen(), rng.gen(), rng.gen()],
+                    [rng.gen_range(-0.1..0.1), rng.gen_range(-0.1..0.1), 0.0],
+                )
+            })
+            .collect();
+        let horizon = 0.5;
+        let (r_min, r_max) = (0.01, 0.05);
+        let t = MovingCapt::<3>::new(&points, horizon, (r_min, r_max));
+
+        for _ in 0..1000 {
+            let center = [rng.gen(), rng.gen(), rng.gen()];
+            let radius = rng.gen_range(r_min..=r_max);
+            let s = rng.gen_range(0.0..=horizon);
+
+            // somewhere along the horizon, an obstacle is within range
+            let hit = points.iter().any(|&(p, v)| {
+                let moved = std::array::from_fn(|k| v[k].mul_add(s, p[k]));
+                distsq(moved, center) <= radius * radius
+            });
+            if hit {
+                assert!(t.collides(&center, radius));
+            }
+
+            // no obstacle's current position is even within the inflated radius
+            let rsq = (radius + t.inflation()).powi(2);
+            if points.iter().all(|&(p, _)| distsq(p, center) > rsq) {
+                assert!(!t.collides(&center, radius));
+            }
+        }
+    }
+
+    #[test]
+    fn non_finite_velocity() {
+        let points = [([0.0; 2], [f64::NAN, 0.0])];
+        assert_eq!(
+            MovingCapt::<2, 8, f64>::try_new(&points, 1.0, (0.0, 1.0)),
+            Err(NewCaptError::NonFinite)
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! Hierarchies of progressively downsampled trees for answering queries at coarse resolution.
+
+use elain::{Align, Alignment};
+
+use crate::{
+    filter::{morton_filter, PERMUTATIONS_3D},
+    Capt, Collider, Index, NewCaptError,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+/// A stack of collision-affording point trees over one three-dimensional point cloud, each built
+/// from a more heavily downsampled copy of the cloud than the last.
+///
+/// Each coarse level is made by applying [`morton_filter`] to the points of the level below it,
+/// doubling the separation at every level.
+/// Since the filter keeps a subset of the points and every removed point stays near a kept one,
+/// a coarse level can often settle a query on its own: a collision with a coarse point is a
+/// collision with a real point, and a miss with a radius inflated by the level's slack is a miss
+/// for the whole cloud.
+/// Only queries which fall between those two cases descend to a finer level, so large and distant
+/// queries rarely touch the full-resolution tree.
+///
+/// # Generic parameters
+///
+/// - `L`: The lane size of each tree.
+/// - `I`: The index integer of each tree.
+///
+/// # Examples
+///
+/// ```
+/// use captree::MultiResTree;
+///
+/// let points: Vec<[f32; 3]> = (0..1000).map(|i| [i as f32 * 0.001, 0.0, 0.0]).collect();
+/// let t = MultiResTree::<8>::new(&points, (0.0, 1.0), 0.01, 3);
+///
+/// assert_eq!(t.n_levels(), 3);
+/// assert!(t.collides(&[0.5, 0.1, 0.0], 0.2));
+/// assert!(!t.collides(&[0.5, 0.8, 0.0], 0.2));
+/// ```
+pub struct MultiResTree<const L: usize = 8, I = usize>
+where
+    Align<L>: Alignment,
+{
+    /// The tree over every point in the cloud.
+    full: Capt<3, L, f32, I>,
+    /// The downsampled levels of this tree, from finest to coarsest, paired with the distance from
+    /// any point in the original cloud to the nearest point in the level.
+    coarse: Vec<(Capt<3, L, f32, I>, f32)>,
+}
+
+impl<I, const L: usize> MultiResTree<L, I>
+where
+    I: Index,
+    Align<L>: Alignment,
+{
+    #[must_use]
+    /// Construct a new multi-resolution tree with `n_levels` levels over the points in `points`.
+    ///
+    /// The finest level contains every point, and the `i`-th coarser level is filtered with a
+    /// separation of `min_separation * 2^(i - 1)`.
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `n_levels` is zero, if `min_separation` is not positive and
+    /// finite, or under the same conditions as [`Capt::new`].
+    pub fn new(
+        points: &[[f32; 3]],
+        r_range: (f32, f32),
+        min_separation: f32,
+        n_levels: usize,
+    ) -> Self {
+        Self::try_new(points, r_range, min_separation, n_levels)
+            .expect("index type I must be able to support all points in CAPT during construction")
+    }
+
+    /// Construct a new multi-resolution tree, checking for index overflow.
+    ///
+    /// See [`MultiResTree::new`] for further details.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error under the same conditions as [`Capt::try_new`].
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `n_levels` is zero, or if `min_separation` is not positive
+    /// and finite.
+    pub fn try_new(
+        points: &[[f32; 3]],
+        r_range: (f32, f32),
+        min_separation: f32,
+        n_levels: usize,
+    ) -> Result<Self, NewCaptError> {
+        assert!(
+            n_levels > 0,
+            "a multi-resolution tree needs at least one level"
+        );
+        assert!(
+            min_separation > 0.0 && min_separation.is_finite(),
+            "minimum separation must be positive and finite"
+        );
+
+        let full = Capt::try_new(points, r_range)?;
+        let mut levels = Vec::with_capacity(n_levels - 1);
+        let mut coarse = points.to_vec();
+        let mut separation = min_separation;
+        let mut slack = 0.0;
+        for _ in 1..n_levels {
+            morton_filter(&mut coarse, separation);
+            #[allow(clippy::cast_precision_loss)]
+            let max_shift = PERMUTATIONS_3D.len() as f32 * separation;
+            slack += max_shift;
+            levels.push((
+                Capt::try_new(&coarse, (r_range.0, r_range.1 + slack))?,
+                slack,
+            ));
+            separation *= 2.0;
+        }
+
+        Ok(Self {
+            full,
+            coarse: levels,
+        })
+    }
+
+    #[must_use]
+    /// Determine whether a point in this tree is within a distance of `radius` to `center`.
+    ///
+    /// Starting from the coarsest level, this returns as soon as some level gives a definite
+    /// answer, and otherwise falls back to the full-resolution tree.
+    /// The answer is always the same as that of a [`Capt`] over the full cloud.
+    pub fn collides(&self, center: &[f32; 3], radius: f32) -> bool {
+        for (tree, slack) in self.coarse.iter().rev() {
+            if !tree.collides(center, radius + slack) {
+                return false;
+            }
+            if tree.collides(center, radius) {
+                return true;
+            }
+        }
+        self.full.collides(center, radius)
+    }
+
+    #[must_use]
+    /// Get the number of levels in this tree, including the full-resolution level.
+    pub const fn n_levels(&self) -> usize {
+        self.coarse.len() + 1
+    }
+
+    #[must_use]
+    /// Get the number of points in this tree.
+    pub fn len(&self) -> usize {
+        self.full.len()
+    }
+
+    #[must_use]
+    /// Determine whether this tree contains no points.
+    pub fn is_empty(&self) -> bool {
+        self.full.is_empty()
+    }
+
+    #[must_use]
+    /// Get the dimension of the points in this tree, 3.
+    pub const fn dimension(&self) -> usize {
+        3
+    }
+
+    #[must_use]
+    /// Get the `(minimum, maximum)` range of query radii which this tree was built to support.
+    pub const fn r_range(&self) -> (f32, f32) {
+        self.full.r_range()
+    }
+
+    #[must_use]
+    /// Get the tree at level `i`, where level 0 is the full-resolution tree, along with the
+    /// farthest distance from any original point to the nearest point in that level.
+    ///
+    /// Returns `None` if `i` is not less than [`MultiResTree::n_levels`].
+    pub fn level(&self, i: usize) -> Option<(&Capt<3, L, f32, I>, f32)> {
+        match i {
+            0 => Some((&self.full, 0.0)),
+            i => self.coarse.get(i - 1).map(|(tree, slack)| (tree, *slack)),
+        }
+    }
+}
+
+impl<I, const L: usize> Collider<3, f32> for MultiResTree<L, I>
+where
+    I: Index,
+    Align<L>: Alignment,
+{
+    fn collides(&self, center: &[f32; 3], radius: f32) -> bool {
+        self.collides(center, radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+    use crate::distsq;
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..2000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let (r_min, r_max) = (0.01, 0.2);
+        let t = MultiResTree::<8>::new(&points, (r_min, r_max), 0.005, 4);
+        assert_eq!(t.n_levels(), 4);
+        assert!(t.level(3).unwrap().1 > 0.0);
+        assert!(t.level(4).is_none());
+
+        for _ in 0..1000 {
+            let center = [
+                rng.gen_range(-0.5..1.5),
+                rng.gen_range(-0.5..1.5),
+                rng.gen_range(-0.5..1.5),
+            ];
+            let radius = rng.gen_range(r_min..=r_max);
+            let expected = points.iter().any(|&p| distsq(p, center) <= radius * radius);
+            assert_eq!(t.collides(&center, radius), expected);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Construction and queries of [`Capt`]s with the types of the [`nalgebra`](::nalgebra) crate.
+
+use std::array;
+
+use ::nalgebra::{Const, Dyn, Isometry3, Matrix, Point, Storage};
+use elain::{Align, Alignment};
+
+use crate::{Capt, Index, NewCaptError};
+
+/// Implement `nalgebra` interop for `Capt`s over `$a`.
+macro_rules! impl_nalgebra {
+    ($a: ty) => {
+        impl<I, const K: usize, const L: usize> Capt<K, L, $a, I>
+        where
+            I: Index,
+            Align<L>: Alignment,
+        {
+            #[must_use]
+            /// Construct a new CAPT containing all the points in `points`.
+            ///
+            /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on
+            /// the radius of the balls which will be queried against the tree.
+            ///
+            /// # Panics
+            ///
+            /// This function will panic under the same conditions as [`Capt::new`].
+            ///
+            /// # Examples
+            ///
+            /// ```
+            /// use nalgebra::Point3;
+            ///
+            /// let points = [Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0)];
+            /// let capt = captree::Capt::<3>::from_nalgebra(&points, (0.0, 0.5));
+            ///
+            /// assert!(capt.collides_point(&Point3::new(1.0, 1.0, 1.1), 0.2));
+            /// ```
+            pub fn from_nalgebra(points: &[Point<$a, K>], r_range: ($a, $a)) -> Self {
+                Self::try_from_nalgebra(points, r_range).expect(
+                    "index type I must be able to support all points in CAPT during construction",
+                )
+            }
+
+            /// Construct a new CAPT containing all the points in `points`, checking for index
+            /// overflow.
+            ///
+            /// # Errors
+            ///
+            /// This function will return an error under the same conditions as
+            /// [`Capt::try_new`].
+            pub fn try_from_nalgebra(
+                points: &[Point<$a, K>],
+                r_range: ($a, $a),
+            ) -> Result<Self, NewCaptError> {
+                Self::try_from_points(points.iter().map(|p| p.coords.into()), r_range)
+            }
+
+            #[must_use]
+            /// Construct a new CAPT containing every row of `points` as a point, such as the rows
+            /// of a `MatrixXx3`.
+            ///
+            /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on
+            /// the radius of the balls which will be queried against the tree.
+            ///
+            /// # Panics
+            ///
+            /// This function will panic under the same conditions as [`Capt::new`].
+            pub fn from_matrix<S>(points: &Matrix<$a, Dyn, Const<K>, S>, r_range: ($a, $a)) -> Self
+            where
+                S: Storage<$a, Dyn, Const<K>>,
+            {
+                Self::try_from_matrix(points, r_range).expect(
+                    "index type I must be able to support all points in CAPT during construction",
+                )
+            }
+
+            /// Construct a new CAPT containing every row of `points` as a point, checking for
+            /// index overflow.
+            ///
+            /// # Errors
+            ///
+            /// This function will return an error under the same conditions as
+            /// [`Capt::try_new`].
+            pub fn try_from_matrix<S>(
+                points: &Matrix<$a, Dyn, Const<K>, S>,
+                r_range: ($a, $a),
+            ) -> Result<Self, NewCaptError>
+            where
+                S: Storage<$a, Dyn, Const<K>>,
+            {
+                Self::try_from_points(
+                    points.row_iter().map(|row| array::from_fn(|k| row[k])),
+                    r_range,
+                )
+            }
+
+            #[must_use]
+            /// Determine whether a point in this tree is within a distance of `radius` to
+            /// `center`.
+            ///
+            /// This is the same as [`Capt::collides`].
+            pub fn collides_point(&self, center: &Point<$a, K>, radius: $a) -> bool {
+                self.collides(&center.coords.into(), radius)
+            }
+
+            #[must_use]
+            /// Find the nearest point in this tree to `center`, so long as its distance is within
+            /// the radius range used to construct this tree.
+            ///
+            /// This is the same as [`Capt::nearest_within_range`].
+            pub fn nearest_point_within_range(
+                &self,
+                center: &Point<$a, K>,
+            ) -> Option<(Point<$a, K>, $a)> {
+                self.nearest_within_range(&center.coords.into())
+                    .map(|(p, d)| (Point::from(p), d))
+            }
+        }
+
+        impl<I, const L: usize> Capt<3, L, $a, I>
+        where
+            I: Index,
+            Align<L>: Alignment,
+        {
+            #[must_use]
+            /// Construct a new CAPT containing every point of this tree moved by `isometry`,
+            /// supporting the same radius range as this tree.
+            ///
+            /// This is useful for bringing a cloud captured in a sensor's frame into the world
+            /// frame.
+            /// Since a rotated cloud no longer fits the cells of this tree, the new tree is
+            /// built from scratch.
+            ///
+            /// # Panics
+            ///
+            /// This function will panic under the same conditions as [`Capt::new`].
+            ///
+            /// # Examples
+            ///
+            /// ```
+            /// use nalgebra::{Isometry3, Point3};
+            ///
+            /// let capt = captree::Capt::<3>::new(&[[1.0, 0.0, 0.0]], (0.0, 0.5));
+            /// let shifted = capt.transformed(&Isometry3::translation(0.0, 2.0, 0.0));
+            ///
+            /// assert!(shifted.collides_point(&Point3::new(1.0, 2.0, 0.0), 0.1));
+            /// ```
+            pub fn transformed(&self, isometry: &Isometry3<$a>) -> Self {
+                Self::try_from_points(
+                    self.points()
+                        .map(|p| (isometry * Point::from(p)).coords.into()),
+                    self.r_range,
+                )
+                .expect(
+                    "index type I must be able to support all points in CAPT during construction",
+                )
+            }
+        }
+    };
+}
+
+impl_nalgebra!(f32);
+impl_nalgebra!(f64);
+
+#[cfg(test)]
+mod tests {
+    use ::nalgebra::{MatrixXx3, Point3, UnitQuaternion, Vector3};
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+
+    #[test]
+    fn transformed_matches_brute_force() {
+        let mut rng = thread_rng();
+        let points: Vec<Point3<f64>> = (0..500)
+            .map(|_| Point3::new(rng.gen(), rng.gen(), rng.gen()))
+            .collect();
+        let matrix = MatrixXx3::from_fn(points.len(), |i, k| points[i][k]);
+        let r_range = (0.01, 0.2);
+        let capt = Capt::<3, 8, f64>::from_nalgebra(&points, r_range);
+        assert_eq!(capt, Capt::<3, 8, f64>::from_matrix(&matrix, r_range));
+
+        let isometry = Isometry3::from_parts(
+            Vector3::new(1.0, -2.0, 0.5).into(),
+            UnitQuaternion::from_euler_angles(0.3, -1.2, 2.0),
+        );
+        let moved = capt.transformed(&isometry);
+        let moved_points: Vec<Point3<f64>> = points.iter().map(|p| isometry * p).collect();
+        for _ in 0..1000 {
+            let center = isometry * Point3::new(rng.gen(), rng.gen(), rng.gen());
+            let r = rng.gen_range(r_range.0..=r_range.1);
+            let collides = moved_points
+                .iter()
+                .any(|p| (p - center).norm_squared() <= r * r);
+            assert_eq!(collides, moved.collides_point(&center, r));
+        }
+    }
+}
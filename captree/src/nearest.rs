@@ -0,0 +1,258 @@
+//! Nearest-neighbor queries answered from the affordance sets of a tree.
+
+use elain::{Align, Alignment};
+
+use crate::{distsq, Alloc, Axis, Capt, Index};
+
+impl<A, I, Al, const K: usize, const L: usize> Capt<K, L, A, I, Al>
+where
+    A: Axis,
+    I: Index,
+    Al: Alloc,
+    Align<L>: Alignment,
+{
+    /// Find the point afforded to the cell containing `center` which is nearest to `center`,
+    /// returning it and its squared distance to `center`.
+    ///
+    /// If the cell has no afforded points, the returned point and distance are infinite.
+    pub(crate) fn closest_afforded(&self, center: &[A; K]) -> ([A; K], A) {
+        let z = self.leaf_containing(center);
+        let range = unsafe {
+            // SAFETY: The conversion worked the first way.
+            self.starts[z].try_into().unwrap_unchecked()
+                ..self.starts[z + 1].try_into().unwrap_unchecked()
+        };
+
+        let mut best = [A::INFINITY; K];
+        let mut best_distsq = A::INFINITY;
+        for i in range {
+            for j in 0..L {
+                let aff_pt = std::array::from_fn(|k| self.afforded[k][i].data[j]);
+                let dsq = distsq(aff_pt, *center);
+                if dsq < best_distsq {
+                    best = aff_pt;
+                    best_distsq = dsq;
+                }
+            }
+        }
+        (best, best_distsq)
+    }
+}
+
+/// Implement `nearest_within_range`, `distance_and_gradient` and `distances_within_range` for
+/// `Capt`s with axes of type `$a`.
+macro_rules! impl_nearest {
+    ($a: ty) => {
+        impl<I, Al, const K: usize, const L: usize> Capt<K, L, $a, I, Al>
+        where
+            I: Index,
+            Al: Alloc,
+            Align<L>: Alignment,
+        {
+            #[must_use]
+            /// Find the point in this tree nearest to `center` and the distance to it, if that
+            /// distance is within the range of radii `(r_min, r_max)` which this tree was built
+            /// for.
+            ///
+            /// Every point within `r_max` of a cell is afforded to it, so this only needs to scan
+            /// the affordance buffer of the cell containing `center`.
+            /// This returns `None` if the nearest point is farther than `r_max` or closer than
+            /// `r_min`.
+            ///
+            /// # Examples
+            ///
+            /// ```
+            /// let points = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]];
+            /// let capt = captree::Capt::<2>::new(&points, (0.1, 0.5));
+            ///
+            /// assert_eq!(
+            ///     capt.nearest_within_range(&[0.75, 0.0]),
+            ///     Some(([1.0, 0.0], 0.25))
+            /// );
+            /// // too far
+            /// assert_eq!(capt.nearest_within_range(&[0.5, 0.5]), None);
+            /// // too close
+            /// assert_eq!(capt.nearest_within_range(&[0.0, 0.05]), None);
+            /// ```
+            pub fn nearest_within_range(&self, center: &[$a; K]) -> Option<([$a; K], $a)> {
+                let (best, best_distsq) = self.closest_afforded(center);
+                let (r_min, r_max) = self.r_range;
+                (r_min * r_min <= best_distsq && best_distsq <= r_max * r_max)
+                    .then(|| (best, best_distsq.sqrt()))
+            }
+
+            #[must_use]
+            /// Get the clearance of `center`, which is the distance to the nearest point in this
+            /// tree, along with its gradient with respect to `center`.
+            ///
+            /// The gradient is the unit vector pointing from the nearest point toward `center`,
+            /// which is the direction in which `center` must move to gain clearance fastest.
+            /// Like [`Capt::nearest_within_range`], this only scans the affordance buffer of one
+            /// cell, so it is much cheaper than estimating the gradient by finite differences of
+            /// several queries.
+            /// If `center` coincides with the nearest point, the gradient is zero.
+            ///
+            /// This returns `None` if the clearance is outside the range of radii which this tree
+            /// was built for, just as [`Capt::nearest_within_range`] does; use
+            /// [`Capt::collides`] to tell whether `center` is then too close or too far.
+            ///
+            /// # Examples
+            ///
+            /// ```
+            /// let points = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]];
+            /// let capt = captree::Capt::<2>::new(&points, (0.1, 0.5));
+            ///
+            /// let (clearance, gradient) = capt.distance_and_gradient(&[0.0, 0.7]).unwrap();
+            /// assert!((clearance - 0.3).abs() < 1e-6);
+            /// assert_eq!(gradient, [0.0, -1.0]);
+            /// ```
+            pub fn distance_and_gradient(&self, center: &[$a; K]) -> Option<($a, [$a; K])> {
+                let (nearest, distance) = self.nearest_within_range(center)?;
+                let gradient = if distance > 0.0 {
+                    std::array::from_fn(|k| (center[k] - nearest[k]) / distance)
+                } else {
+                    [0.0; K]
+                };
+                Some((distance, gradient))
+            }
+
+            /// Get the distance from each point in `queries` to the nearest point in this tree,
+            /// clamped to the range of radii `(r_min, r_max)` which this tree was built for, writing
+            /// the distance for `queries[i]` to `out[i]`.
+            ///
+            /// A distance of `r_max` means that the nearest point is at least `r_max` away, and a
+            /// distance of `r_min` means that it is at most `r_min` away; every distance strictly
+            /// between them is exact.
+            /// This never allocates, so it is safe to call from real-time loops which forbid heap
+            /// use after initialization.
+            ///
+            /// # Panics
+            ///
+            /// This function will panic if `queries` and `out` have different lengths.
+            ///
+            /// # Examples
+            ///
+            /// ```
+            /// let points = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]];
+            /// let capt = captree::Capt::<2>::new(&points, (0.1, 0.5));
+            ///
+            /// let mut out = [0.0; 3];
+            /// capt.distances_within_range(&[[0.75, 0.0], [0.5, 0.5], [0.0, 0.05]], &mut out);
+            /// assert_eq!(out, [0.25, 0.5, 0.1]);
+            /// ```
+            pub fn distances_within_range(&self, queries: &[[$a; K]], out: &mut [$a]) {
+                assert_eq!(
+                    queries.len(),
+                    out.len(),
+                    "must have one output distance for every query"
+                );
+                let (r_min, r_max) = self.r_range;
+                for (o, center) in out.iter_mut().zip(queries) {
+                    let (_, best_distsq) = self.closest_afforded(center);
+                    *o = best_distsq.sqrt().clamp(r_min, r_max);
+                }
+            }
+        }
+    };
+}
+
+impl_nearest!(f32);
+impl_nearest!(f64);
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn matches_brute_force() {
+        let mut rng = thread_rng();
+        let points: Vec<[f64; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let (r_min, r_max) = (0.02, 0.08);
+        let capt = Capt::<3, 8, f64>::new(&points, (r_min, r_max));
+
+        for _ in 0..1000 {
+            let center = [rng.gen(), rng.gen(), rng.gen()];
+            let (nearest, best) = points
+                .iter()
+                .map(|&p| (p, distsq(p, center)))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap();
+            let found = capt.nearest_within_range(&center);
+            if (r_min * r_min..=r_max * r_max).contains(&best) {
+                assert_eq!(found, Some((nearest, best.sqrt())));
+            } else if best > r_max * r_max {
+                assert_eq!(found, None);
+            }
+        }
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn batch_distances_are_clamped() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let (r_min, r_max) = (0.02, 0.08);
+        let capt = Capt::<3>::new(&points, (r_min, r_max));
+
+        let queries: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let mut out = vec![0.0; queries.len()];
+        capt.distances_within_range(&queries, &mut out);
+        for (center, &d) in queries.iter().zip(&out) {
+            let best = points
+                .iter()
+                .map(|&p| distsq(p, *center))
+                .min_by(f32::total_cmp)
+                .unwrap()
+                .sqrt();
+            assert!((r_min..=r_max).contains(&d));
+            match capt.nearest_within_range(center) {
+                Some((_, exact)) => assert_eq!(d, exact),
+                None if best > r_max => assert_eq!(d, r_max),
+                None => assert!(best <= r_min),
+            }
+        }
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn gradient_matches_finite_differences() {
+        let mut rng = thread_rng();
+        let points: Vec<[f64; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let capt = Capt::<3, 8, f64>::new(&points, (0.01, 0.1));
+        let h = 1e-7;
+
+        for _ in 0..1000 {
+            let center = [rng.gen(), rng.gen(), rng.gen()];
+            let Some((distance, gradient)) = capt.distance_and_gradient(&center) else {
+                continue;
+            };
+            assert_eq!(capt.nearest_within_range(&center).unwrap().1, distance);
+            assert!((gradient.iter().map(|g| g * g).sum::<f64>() - 1.0).abs() < 1e-9);
+
+            for k in 0..3 {
+                let mut stepped = center;
+                stepped[k] += h;
+                // the nearest point may change across the step, which moves the gradient
+                if let Some((d, _)) = capt.distance_and_gradient(&stepped) {
+                    let estimate = (d - distance) / h;
+                    assert!(
+                        (estimate - gradient[k]).abs() < 1e-3
+                            || capt.nearest_within_range(&stepped).unwrap().0
+                                != capt.nearest_within_range(&center).unwrap().0
+                    );
+                }
+            }
+        }
+    }
+}
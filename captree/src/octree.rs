@@ -0,0 +1,602 @@
+//! Collision checking with affordance buffers over the leaves of an octree.
+
+use std::{array, mem::size_of};
+
+#[cfg(feature = "simd")]
+use std::{
+    ops::{AddAssign, Mul, Sub},
+    simd::{
+        cmp::{SimdPartialEq, SimdPartialOrd},
+        Mask, Simd,
+    },
+};
+
+use elain::{Align, Alignment};
+
+use crate::{distsq, prefetch, Aabb, Axis, Buf, Collider, Index, MySimd, NewCaptError};
+
+#[cfg(feature = "simd")]
+use crate::{
+    collides_leaves_simd, multiversion, AxisSimd, BatchCollider, IndexSimd, SimdQueryBatch,
+};
+
+/// The maximum depth of a leaf, which stops subdivision of cells containing duplicate points.
+const MAX_DEPTH: usize = 24;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A collision-checking structure which partitions space by repeatedly bisecting a bounding box
+/// along every axis at once, rather than about the median along one axis as a [`Capt`] does.
+///
+/// Like a [`Capt`], each leaf cell stores an affordance buffer containing every point within the
+/// maximum query radius of the cell, so a query only needs to find its leaf and scan one buffer.
+/// The tree is implicit: no splitting planes are stored, since each is the midpoint of its cell.
+///
+/// Median splits give a balanced tree, while an octree adapts its depth to the local density of
+/// points, so very non-uniform clouds sometimes favor an octree.
+/// The descent through an octree visits one level per halving of the cell size rather than one
+/// per halving of the number of points.
+///
+/// # Generic parameters
+///
+/// - `K`: The dimension of the space. Each branch has `2^K` children.
+/// - `L`: The lane size of the affordance buffers.
+/// - `A`: The value of the axes of each point.
+/// - `I`: The index integer.
+///
+/// # Examples
+///
+/// ```
+/// let points = [[0.0, 0.1, 0.0], [0.4, -0.2, 0.1], [-0.2, -0.1, 0.0]];
+///
+/// let t = captree::Octree::<3>::new(&points, (0.0, 0.2));
+///
+/// assert!(!t.collides(&[0.0, 0.3, 0.0], 0.1));
+/// assert!(t.collides(&[0.0, 0.2, 0.0], 0.15));
+/// ```
+///
+/// [`Capt`]: crate::Capt
+pub struct Octree<const K: usize = 3, const L: usize = 8, A = f32, I = usize>
+where
+    Align<L>: Alignment,
+{
+    /// The bounding box of the points, which is the cell of the root node.
+    bounds: Aabb<A, K>,
+    /// For each node, the index of its first child, or zero if it is a leaf.
+    /// The children of a branch are contiguous, ordered by the bitmask of the axes along which
+    /// they are on the upper side of the midpoint.
+    children: Buf<I>,
+    /// For each leaf node, the index of that leaf in left-to-right order.
+    leaves: Buf<I>,
+    /// Axis-aligned bounding boxes containing the set of afforded points for each leaf.
+    aabbs: Buf<Aabb<A, K>>,
+    /// Indexes for the starts of the affordance buffer subsequence of `afforded` corresponding to
+    /// each leaf.
+    /// This buffer is padded with one extra element at the end with the length of `afforded`.
+    starts: Buf<I>,
+    /// The afforded points for each leaf, in lanes padded out with infinite points.
+    afforded: [Buf<MySimd<A, L>>; K],
+    /// The number of points in the tree.
+    n_points: usize,
+    /// The `(minimum, maximum)` range of query radii which this tree was built to support.
+    r_range: (A, A),
+}
+
+/// The buffers of an [`Octree`] under construction.
+struct Builder<'a, A, I, const K: usize, const L: usize>
+where
+    Align<L>: Alignment,
+{
+    points: &'a [[A; K]],
+    r_range: (A, A),
+    children: Vec<I>,
+    leaves: Vec<I>,
+    aabbs: Vec<Aabb<A, K>>,
+    starts: Vec<I>,
+    afforded: [Vec<MySimd<A, L>>; K],
+}
+
+impl<A, I, const K: usize, const L: usize> Octree<K, L, A, I>
+where
+    A: Axis,
+    I: Index,
+    Align<L>: Alignment,
+{
+    /// Construct a new octree containing all the points in `points`.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if there are too many nodes or afforded points in the tree to be
+    /// addressed by `I`, or if any points contain non-finite non-real value.
+    pub fn new(points: &[[A; K]], r_range: (A, A)) -> Self {
+        Self::try_new(points, r_range)
+            .expect("index type I must be able to support all points in CAPT during construction")
+    }
+
+    /// Construct a new octree containing all the points in `points`, checking for index overflow.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Err(NewCaptError::TooManyPoints)` if there are too many nodes or
+    /// afforded points to be indexed by `I`.
+    /// It will return `Err(NewCaptError::NonFinite)` if any element of `points` is non-finite.
+    pub fn try_new(points: &[[A; K]], r_range: (A, A)) -> Result<Self, NewCaptError> {
+        if points.iter().any(|p| p.iter().any(|x| !x.is_finite())) {
+            return Err(NewCaptError::NonFinite);
+        }
+
+        let bounds = match points.split_first() {
+            Some((first, rest)) => {
+                let mut bounds = Aabb {
+                    lo: *first,
+                    hi: *first,
+                };
+                for p in rest {
+                    bounds.insert(p);
+                }
+                bounds
+            }
+            None => Aabb {
+                lo: [A::ZERO; K],
+                hi: [A::ZERO; K],
+            },
+        };
+
+        let mut builder = Builder {
+            points,
+            r_range,
+            children: vec![I::ZERO],
+            leaves: vec![I::ZERO],
+            aabbs: Vec::new(),
+            starts: vec![I::ZERO],
+            afforded: array::from_fn(|_| Vec::new()),
+        };
+        let all: Vec<usize> = (0..points.len()).collect();
+        builder.build(0, bounds, Aabb::ALL, &all, all.clone(), 0)?;
+
+        Ok(Self {
+            bounds,
+            children: builder.children.into(),
+            leaves: builder.leaves.into(),
+            aabbs: builder.aabbs.into(),
+            starts: builder.starts.into(),
+            afforded: builder.afforded.map(Buf::from),
+            n_points: points.len(),
+            r_range,
+        })
+    }
+
+    /// Find the index of the leaf whose cell contains `center`.
+    fn leaf_containing(&self, center: &[A; K]) -> usize {
+        let mut node = 0;
+        let mut cell = self.bounds;
+        loop {
+            let first = unsafe {
+                // SAFETY: The conversion worked the first way.
+                (*self.children.get_unchecked(node))
+                    .try_into()
+                    .unwrap_unchecked()
+            };
+            if first == 0 {
+                return unsafe {
+                    // SAFETY: The conversion worked the first way.
+                    (*self.leaves.get_unchecked(node))
+                        .try_into()
+                        .unwrap_unchecked()
+                };
+            }
+            node = first + octant(&mut cell, center);
+        }
+    }
+
+    #[must_use]
+    /// Determine whether a point in this tree is within a distance of `radius` to `center`.
+    ///
+    /// As with [`Capt::collides`](crate::Capt::collides), the result is only guaranteed to be
+    /// correct if `radius` is inside the radius range used to construct this tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let points = [[0.0; 3], [1.0; 3], [0.1, 0.5, 1.0]];
+    /// let t = captree::Octree::<3>::new(&points, (0.0, 1.0));
+    ///
+    /// assert!(t.collides(&[1.1; 3], 0.2));
+    /// assert!(!t.collides(&[2.0; 3], 1.0));
+    /// ```
+    pub fn collides(&self, center: &[A; K], radius: A) -> bool {
+        let rsq = radius.square();
+        let z = self.leaf_containing(center);
+        let aabb = unsafe { self.aabbs.get_unchecked(z) };
+        if aabb.closest_distsq_to(center) > rsq {
+            return false;
+        }
+
+        let mut range = unsafe {
+            // SAFETY: The conversion worked the first way.
+            self.starts[z].try_into().unwrap_unchecked()
+                ..self.starts[z + 1].try_into().unwrap_unchecked()
+        };
+        for sk in &self.afforded {
+            prefetch(sk.as_ptr().wrapping_add(range.start));
+        }
+
+        range.any(|i| {
+            (0..L).any(|j| {
+                let aff_pt = array::from_fn(|k| self.afforded[k][i].data[j]);
+                distsq(aff_pt, *center) <= rsq
+            })
+        })
+    }
+
+    #[must_use]
+    /// Get the number of points in this tree.
+    pub const fn len(&self) -> usize {
+        self.n_points
+    }
+
+    #[must_use]
+    /// Determine whether this tree contains no points.
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[must_use]
+    /// Get the dimension of the points in this tree, `K`.
+    pub const fn dimension(&self) -> usize {
+        K
+    }
+
+    #[must_use]
+    /// Get the `(minimum, maximum)` range of query radii which this tree was built to support.
+    pub const fn r_range(&self) -> (A, A) {
+        self.r_range
+    }
+
+    #[must_use]
+    #[doc(hidden)]
+    /// Get the total memory used (stack + heap) by this structure, measured in bytes.
+    /// This function should not be considered stable; it is only used internally for benchmarks.
+    pub const fn memory_used(&self) -> usize {
+        size_of::<Self>()
+            + K * self.afforded[0].len() * size_of::<MySimd<A, L>>()
+            + (self.children.len() + self.leaves.len() + self.starts.len()) * size_of::<I>()
+            + self.aabbs.len() * size_of::<Aabb<A, K>>()
+    }
+}
+
+impl<A, I, const K: usize, const L: usize> Collider<K, A> for Octree<K, L, A, I>
+where
+    A: Axis,
+    I: Index,
+    Align<L>: Alignment,
+{
+    fn collides(&self, center: &[A; K], radius: A) -> bool {
+        self.collides(center, radius)
+    }
+}
+
+#[cfg(feature = "simd")]
+impl<A, I, const K: usize, const L: usize> BatchCollider<K, L, A> for Octree<K, L, A, I>
+where
+    I: Index + IndexSimd,
+    A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask> + Mul<Output = A>,
+    Align<L>: Alignment,
+    Simd<A, L>: SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+    Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+{
+    fn collides_batch(&self, batch: &SimdQueryBatch<K, L, A>) -> bool {
+        multiversion(
+            #[inline(always)]
+            || self.collides_simd_inner(&batch.centers, batch.radii, batch.active),
+        )
+    }
+}
+
+impl<A, I, const K: usize, const L: usize> Default for Octree<K, L, A, I>
+where
+    A: Axis,
+    I: Index,
+    Align<L>: Alignment,
+{
+    /// Construct an empty tree, which supports no query radius and never collides.
+    fn default() -> Self {
+        Self::new(&[], (A::ZERO, A::ZERO))
+    }
+}
+
+#[allow(clippy::mismatching_type_param_order)]
+#[cfg(feature = "simd")]
+impl<A, I, const K: usize, const L: usize> Octree<K, L, A, I>
+where
+    I: Index + IndexSimd,
+    A: Mul<Output = A>,
+    Align<L>: Alignment,
+{
+    #[must_use]
+    /// Determine whether any sphere in the list of provided spheres intersects a point in this
+    /// tree.
+    ///
+    /// Since the children of each branch are found from the midpoint of its cell rather than from
+    /// a stored test, the descent is done one sphere at a time; the bounding box pruning and the
+    /// scan of the affordance buffers are vectorized as in
+    /// [`Capt::collides_simd`](crate::Capt::collides_simd).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(portable_simd)]
+    /// use std::simd::Simd;
+    ///
+    /// let points = [[1.0, 2.0], [1.1, 1.1]];
+    ///
+    /// let centers = [
+    ///     Simd::from_array([1.0, 1.1, 1.2, 1.3]), // x-positions
+    ///     Simd::from_array([1.0, 1.1, 1.2, 1.3]), // y-positions
+    /// ];
+    /// let radii = Simd::splat(0.05);
+    ///
+    /// let tree = captree::Octree::<2, 4, f32, u32>::new(&points, (0.0, 0.1));
+    ///
+    /// assert!(tree.collides_simd(&centers, radii));
+    /// ```
+    pub fn collides_simd(&self, centers: &[Simd<A, L>; K], radii: Simd<A, L>) -> bool
+    where
+        Simd<A, L>:
+            SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+        Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+        A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask>,
+    {
+        multiversion(
+            #[inline(always)]
+            || self.collides_simd_inner(centers, radii, Mask::splat(true)),
+        )
+    }
+
+    #[allow(clippy::inline_always, clippy::cast_possible_wrap)]
+    #[inline(always)]
+    /// The body of [`Octree::collides_simd`], which is compiled once per supported instruction
+    /// set.
+    fn collides_simd_inner(
+        &self,
+        centers: &[Simd<A, L>; K],
+        radii: Simd<A, L>,
+        active: Mask<isize, L>,
+    ) -> bool
+    where
+        Simd<A, L>:
+            SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+        Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+        A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask>,
+    {
+        let zs = Simd::from_array(array::from_fn(|j| {
+            self.leaf_containing(&array::from_fn(|k| centers[k][j])) as isize
+        }));
+
+        unsafe {
+            // SAFETY: Every center is in some leaf, whose affordance buffer range lies within
+            // `afforded`.
+            collides_leaves_simd(
+                &self.aabbs,
+                &self.starts,
+                &self.afforded,
+                zs,
+                centers,
+                radii,
+                active,
+            )
+        }
+    }
+}
+
+impl<A, I, const K: usize, const L: usize> Builder<'_, A, I, K, L>
+where
+    A: Axis,
+    I: Index,
+    Align<L>: Alignment,
+{
+    #[allow(clippy::too_many_arguments)]
+    /// Build the subtree rooted at `node`.
+    ///
+    /// `cell` is the box which is bisected to find the children of `node`, and `region` is the
+    /// volume of space covered by `node`, which extends to infinity on the outer faces of the
+    /// root.
+    /// `contained` holds the indices of the points inside `cell`, and `in_range` holds the
+    /// indices of every point within the maximum radius of `region`.
+    fn build(
+        &mut self,
+        node: usize,
+        cell: Aabb<A, K>,
+        region: Aabb<A, K>,
+        contained: &[usize],
+        in_range: Vec<usize>,
+        depth: usize,
+    ) -> Result<(), NewCaptError> {
+        if contained.len() <= 1 || depth == MAX_DEPTH {
+            return self.afford_leaf(node, &region, contained, in_range);
+        }
+
+        let first = self.children.len();
+        let n_children = 1 << K;
+        self.children[node] = first.try_into().map_err(|_| NewCaptError::TooManyPoints)?;
+        self.children.resize(first + n_children, I::ZERO);
+        self.leaves.resize(first + n_children, I::ZERO);
+
+        let rsq_max = self.r_range.1.square();
+        for o in 0..n_children {
+            let mut child_cell = cell;
+            let mut child_region = region;
+            for k in 0..K {
+                let mid = A::in_between(cell.lo[k], cell.hi[k]);
+                if o & (1 << k) == 0 {
+                    child_cell.hi[k] = mid;
+                    child_region.hi[k] = mid;
+                } else {
+                    child_cell.lo[k] = mid;
+                    child_region.lo[k] = mid;
+                }
+            }
+            let child_contained: Vec<usize> = contained
+                .iter()
+                .copied()
+                .filter(|&i| octant(&mut { cell }, &self.points[i]) == o)
+                .collect();
+            let child_in_range = in_range
+                .iter()
+                .copied()
+                .filter(|&i| child_region.closest_distsq_to(&self.points[i]) <= rsq_max)
+                .collect();
+            self.build(
+                first + o,
+                child_cell,
+                child_region,
+                &child_contained,
+                child_in_range,
+                depth + 1,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Make `node` a leaf, filling out its affordance buffer from the points in `in_range`.
+    fn afford_leaf(
+        &mut self,
+        node: usize,
+        region: &Aabb<A, K>,
+        contained: &[usize],
+        mut in_range: Vec<usize>,
+    ) -> Result<(), NewCaptError> {
+        let z = self.aabbs.len();
+        self.leaves[node] = z.try_into().map_err(|_| NewCaptError::TooManyPoints)?;
+
+        // a lone point covers everything if the whole cell is within the minimum radius
+        if let [i] = contained {
+            if region.contained_by_ball(&self.points[*i], self.r_range.0.square()) {
+                in_range = vec![*i];
+            }
+        }
+
+        let mut aabb = match in_range.first() {
+            Some(&i) => Aabb {
+                lo: self.points[i],
+                hi: self.points[i],
+            },
+            None => Aabb {
+                lo: [A::INFINITY; K],
+                hi: [A::INFINITY; K],
+            },
+        };
+        for chunk in in_range.chunks(L) {
+            let mut news = [[A::INFINITY; L]; K];
+            for (j, &i) in chunk.iter().enumerate() {
+                aabb.insert(&self.points[i]);
+                for (nk, &x) in news.iter_mut().zip(&self.points[i]) {
+                    nk[j] = x;
+                }
+            }
+            for (ak, data) in self.afforded.iter_mut().zip(news) {
+                ak.push(MySimd {
+                    data,
+                    _align: Align::NEW,
+                });
+            }
+        }
+
+        self.aabbs.push(aabb);
+        self.starts.push(
+            self.afforded[0]
+                .len()
+                .try_into()
+                .map_err(|_| NewCaptError::TooManyPoints)?,
+        );
+        Ok(())
+    }
+}
+
+/// Find which child of `cell` contains `point`, and shrink `cell` to that child.
+///
+/// Bit `k` of the result is set if `point` is on the upper side of the midpoint of `cell` along
+/// axis `k`.
+fn octant<A: Axis, const K: usize>(cell: &mut Aabb<A, K>, point: &[A; K]) -> usize {
+    let mut o = 0;
+    #[allow(clippy::needless_range_loop)]
+    for k in 0..K {
+        let mid = A::in_between(cell.lo[k], cell.hi[k]);
+        if mid <= point[k] {
+            o |= 1 << k;
+            cell.lo[k] = mid;
+        } else {
+            cell.hi[k] = mid;
+        }
+    }
+    o
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+    use crate::Capt;
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = thread_rng();
+        // a dense cluster inside a sparse cloud
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|i| {
+                let scale = if i % 4 == 0 { 1.0 } else { 0.05 };
+                [rng.gen(), rng.gen(), rng.gen()].map(|x: f32| x * scale)
+            })
+            .collect();
+        let (r_min, r_max) = (0.005, 0.08);
+        let t = Octree::<3>::new(&points, (r_min, r_max));
+        let capt = Capt::<3>::new(&points, (r_min, r_max));
+
+        for _ in 0..10_000 {
+            let center = [
+                rng.gen_range(-0.1..1.1),
+                rng.gen_range(-0.1..1.1),
+                rng.gen_range(-0.1..1.1),
+            ];
+            let r = rng.gen_range(r_min..=r_max);
+            let collides = points.iter().any(|p| distsq(*p, center) <= r * r);
+            assert_eq!(collides, t.collides(&center, r));
+            assert_eq!(collides, capt.collides(&center, r));
+        }
+    }
+
+    #[test]
+    fn duplicates_and_empty() {
+        let t = Octree::<2, 4, f64, u16>::new(&[[1.0, 1.0]; 10], (0.0, 0.5));
+        assert!(t.collides(&[1.2, 1.2], 0.3));
+        assert!(!t.collides(&[1.5, 1.5], 0.5));
+
+        let t = Octree::<2>::new(&[], (0.0, 0.5));
+        assert!(!t.collides(&[0.0, 0.0], 0.5));
+    }
+
+    #[test]
+    #[cfg(feature = "simd")]
+    fn simd_matches_collides() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..500)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let t = Octree::<3, 8, f32, u32>::new(&points, (0.01, 0.05));
+
+        for _ in 0..200 {
+            let centers: [Simd<f32, 8>; 3] =
+                array::from_fn(|_| Simd::from_array(array::from_fn(|_| rng.gen())));
+            let radii = Simd::from_array(array::from_fn(|_| rng.gen_range(0.01..=0.05)));
+            let expected = (0..8).any(|j| t.collides(&array::from_fn(|k| centers[k][j]), radii[j]));
+            assert_eq!(t.collides_simd(&centers, radii), expected);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Collision checking between [`Capt`]s and the shapes of the [`parry3d`](::parry3d) crate.
+
+use ::parry3d::{
+    math::{Isometry, Point, Real},
+    shape::Shape,
+};
+use elain::{Align, Alignment};
+
+use crate::{Capt, Index};
+
+impl<I, const L: usize> Capt<3, L, Real, I>
+where
+    I: Index,
+    Align<L>: Alignment,
+{
+    #[must_use]
+    /// Determine whether a point in this tree collides with `shape`, placed at `position`.
+    ///
+    /// Balls, capsules and compounds of those shapes are supported, and are decomposed into
+    /// sphere queries against this tree.
+    /// Balls are checked exactly, and capsules as by [`Capt::collides_capsule`].
+    /// A compound collides if any of its parts collides.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if `shape` is of an unsupported type, or is a compound which contains a
+    /// part of an unsupported type and none of its supported parts collide.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parry3d::{
+    ///     math::Isometry,
+    ///     shape::{Ball, Cuboid},
+    /// };
+    ///
+    /// let capt = captree::Capt::<3>::new(&[[1.0, 0.0, 0.0]], (0.0, 0.5));
+    ///
+    /// assert_eq!(
+    ///     capt.collides_shape(&Isometry::translation(1.0, 0.1, 0.0), &Ball::new(0.2)),
+    ///     Some(true)
+    /// );
+    /// assert_eq!(
+    ///     capt.collides_shape(&Isometry::identity(), &Cuboid::new([0.1; 3].into())),
+    ///     None
+    /// );
+    /// ```
+    pub fn collides_shape(&self, position: &Isometry<Real>, shape: &dyn Shape) -> Option<bool> {
+        if let Some(ball) = shape.as_ball() {
+            return Some(self.collides(&position.translation.vector.into(), ball.radius));
+        }
+        if let Some(capsule) = shape.as_capsule() {
+            return Some(self.collides_capsule(
+                &(position * capsule.segment.a),
+                &(position * capsule.segment.b),
+                capsule.radius,
+            ));
+        }
+        if let Some(compound) = shape.as_compound() {
+            let mut supported = true;
+            for (part_position, part) in compound.shapes() {
+                match self.collides_shape(&(position * part_position), part.as_ref()) {
+                    Some(true) => return Some(true),
+                    Some(false) => (),
+                    None => supported = false,
+                }
+            }
+            return supported.then_some(false);
+        }
+        None
+    }
+
+    #[must_use]
+    /// Determine whether a point in this tree may be within a distance of `radius` to the segment
+    /// from `a` to `b`.
+    ///
+    /// This is [`Capt::collides_swept`] over `parry3d` points, and is equally conservative.
+    pub fn collides_capsule(&self, a: &Point<Real>, b: &Point<Real>, radius: Real) -> bool {
+        self.collides_swept(&a.coords.into(), &b.coords.into(), radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::parry3d::{
+        math::Vector,
+        query::PointQuery,
+        shape::{Ball, Capsule, Compound, SharedShape},
+    };
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+
+    #[test]
+    fn capsules_are_conservative() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let t = Capt::<3>::new(&points, (0.01, 0.2));
+
+        for _ in 0..1000 {
+            let a = Point::new(rng.gen(), rng.gen(), rng.gen());
+            let b = a + Vector::new(rng.gen(), rng.gen(), rng.gen()) * 0.3;
+            let radius = rng.gen_range(0.01..0.15);
+            let capsule = Capsule::new(a, b, radius);
+            let distances: Vec<f32> = points
+                .iter()
+                .map(|&p| capsule.segment.distance_to_local_point(&p.into(), true))
+                .collect();
+
+            let collides = t.collides_capsule(&a, &b, radius);
+            if distances.iter().any(|&d| d <= radius) {
+                assert!(collides);
+            }
+            if collides {
+                assert!(distances.iter().any(|&d| d <= radius * 1.12));
+            }
+            assert_eq!(
+                t.collides_shape(&Isometry::identity(), &capsule),
+                Some(collides)
+            );
+        }
+    }
+
+    #[test]
+    fn compound_of_balls() {
+        let t = Capt::<3>::new(&[[0.0; 3], [1.0, 0.0, 0.0]], (0.0, 0.5));
+        let compound = Compound::new(vec![
+            (Isometry::translation(0.0, 0.3, 0.0), SharedShape::ball(0.2)),
+            (Isometry::translation(1.0, 0.1, 0.0), SharedShape::ball(0.2)),
+        ]);
+
+        assert_eq!(
+            t.collides_shape(&Isometry::identity(), &compound),
+            Some(true)
+        );
+        assert_eq!(
+            t.collides_shape(&Isometry::translation(0.0, 0.2, 0.0), &compound),
+            Some(false)
+        );
+        assert_eq!(
+            t.collides_shape(&Isometry::translation(0.0, 0.2, 0.0), &Ball::new(0.25)),
+            Some(true)
+        );
+    }
+}
@@ -0,0 +1,1892 @@
+//! Power-of-two k-d trees, which share their spatial partition with [`Capt`](crate::Capt).
+
+use std::{mem::size_of, ops::Mul};
+
+#[cfg(feature = "simd")]
+use std::{
+    ops::{AddAssign, Sub},
+    simd::{
+        cmp::{SimdPartialEq, SimdPartialOrd},
+        num::SimdInt,
+        ptr::SimdConstPtr,
+        Mask, Simd, SimdElement,
+    },
+};
+
+use crate::{bounds_of, distsq, forward_pass, median_partition, strided, Aabb, Axis, Radius};
+#[cfg(feature = "simd")]
+use crate::{forward_pass_simd, AxisSimd, SimdQueryBatch};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A power-of-two KD-tree.
+///
+/// A `PkdTree` partitions space exactly as a [`Capt`](crate::Capt) built from the same points
+/// does, so a [`Capt`](crate::Capt) can be derived from it with
+/// [`Capt::from_pkdt`](crate::Capt::from_pkdt) without sorting the points again.
+///
+/// # Generic parameters
+///
+/// - `K`: The dimension of the space.
+/// - `A`: The value of the axes of each point.
+///
+/// # Examples
+///
+/// ```
+/// use captree::PkdTree;
+///
+/// let points = [[0.0, 0.1], [0.4, -0.2], [-0.2, -0.1]];
+/// let t = PkdTree::new(&points);
+///
+/// assert_eq!(t.get_point(t.query1_exact([0.3, -0.3])), [0.4, -0.2]);
+/// assert_eq!(t.points().len(), 3);
+/// ```
+pub struct PkdTree<const K: usize, A = f32> {
+    /// The test values for determining which part of the tree to enter.
+    ///
+    /// The first element of `tests` should be the first value to test against.
+    /// If we are less than `tests[0]`, we move on to `tests[1]`; if not, we move on to `tests[2]`.
+    /// At the `i`-th test performed in sequence of the traversal, if we are less than
+    /// `tests[idx]`, we advance to `2 * idx + 1`; otherwise, we go to `2 * idx + 2`.
+    ///
+    /// The length of `tests` must be `N`, rounded up to the next power of 2, minus one.
+    pub(crate) tests: Box<[A]>,
+    /// The relevant points at the center of each volume divided by `tests`.
+    ///
+    /// Padding points, whose coordinates are all infinite, are always stored after every real
+    /// point.
+    /// The leaf cells holding them are ghosts: no query ever reports them.
+    pub(crate) points: Box<[[A; K]]>,
+    /// The number of real points in `points`, before the padding.
+    pub(crate) n_points: usize,
+    /// The smallest box containing every real point, which bounds the outermost cells.
+    pub(crate) bounds: Aabb<A, K>,
+    /// The sum of every real point, from which the centroid is found.
+    pub(crate) sum: [A; K],
+}
+
+impl<A: Axis, const K: usize> PkdTree<K, A> {
+    #[must_use]
+    /// Construct a new `PkdTree` containing all the points in `points`.
+    /// For performance, this function changes the ordering of `points`, but does not affect the
+    /// set of points inside it.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `K` is greater than or equal to 255 or if any point in `points`
+    /// is not finite.
+    pub fn new(points: &[[A; K]]) -> Self {
+        let mut new_points = Vec::with_capacity(points.len().next_power_of_two());
+        new_points.extend_from_slice(points);
+        Self::build(new_points)
+    }
+
+    #[must_use]
+    /// Construct a new `PkdTree` containing all the points yielded by `points`, sorting them
+    /// directly in the allocation which the tree will keep.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `K` is greater than or equal to 255 or if any point in `points`
+    /// is not finite.
+    pub fn from_points(points: impl IntoIterator<Item = [A; K]>) -> Self {
+        Self::build(points.into_iter().collect())
+    }
+
+    #[must_use]
+    /// Construct a new `PkdTree` from points stored in an interleaved buffer, where the `i`-th
+    /// point's coordinates are `flat[offset + i * stride..][..K]`.
+    /// Any trailing elements which are too few to make up a whole point are ignored.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `stride` is less than `K`, if `K` is greater than or equal to
+    /// 255, or if any point is not finite.
+    pub fn from_flat(flat: &[A], stride: usize, offset: usize) -> Self {
+        Self::from_points(strided(flat, stride, offset))
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    /// Construct a new `PkdTree`, sorting `new_points` in place.
+    pub(crate) fn build(mut new_points: Vec<[A; K]>) -> Self {
+        span!(DEBUG, "build", n_points = new_points.len());
+        assert!(K < u8::MAX as usize);
+        assert!(
+            new_points.iter().all(|p| p.iter().all(|x| x.is_finite())),
+            "all points in a PkdTree must be finite"
+        );
+
+        let bounds = bounds_of(&new_points);
+
+        // hack: just pad with infinity to make it a power of 2
+        let n_points = new_points.len();
+        let n2 = new_points.len().next_power_of_two();
+        new_points.resize(n2, [A::INFINITY; K]);
+
+        let mut tests = vec![A::INFINITY; n2 - 1].into_boxed_slice();
+
+        // sort the points for the KD tree and generate the tests in O(n log n), using an explicit
+        // worklist of `(start, end, k, i)` instead of recursing
+        let mut worklist = vec![(0, n2, 0, 0)];
+        while let Some((start, end, k, i)) = worklist.pop() {
+            if end - start > 1 {
+                tests[i] = unsafe {
+                    // SAFETY: We checked that every point is finite, so none are `NaN`.
+                    median_partition(&mut new_points[start..end], k as usize)
+                };
+                let next_k = (k + 1) % K as u8;
+                let mid = start + (end - start) / 2;
+                worklist.push((mid, end, next_k, 2 * i + 2));
+                worklist.push((start, mid, next_k, 2 * i + 1));
+            }
+        }
+
+        // sum in leaf order, so that rebuilding a tree from its own points gives an equal tree
+        let sum = new_points[..n_points]
+            .iter()
+            .fold([A::ZERO; K], |mut sum, p| {
+                sum.iter_mut().zip(p).for_each(|(s, &x)| *s = *s + x);
+                sum
+            });
+
+        Self {
+            tests,
+            points: new_points.into_boxed_slice(),
+            n_points,
+            bounds,
+            sum,
+        }
+    }
+
+    #[must_use]
+    /// Get the set of points in this tree, in the order of the leaf cells which contain them.
+    pub fn points(&self) -> &[[A; K]] {
+        &self.points[..self.n_points]
+    }
+
+    #[must_use]
+    /// Get the number of points in this tree, not counting the padding which fills out the tree
+    /// to a power of two.
+    pub const fn len(&self) -> usize {
+        self.n_points
+    }
+
+    #[must_use]
+    /// Determine whether this tree contains no points.
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[must_use]
+    /// Get the dimension of the points in this tree, `K`.
+    pub const fn dimension(&self) -> usize {
+        K
+    }
+
+    #[must_use]
+    /// Get the smallest axis-aligned box containing every point in this tree, or `None` if this
+    /// tree is empty.
+    ///
+    /// This is computed at construction, so it takes constant time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let t = captree::PkdTree::new(&[[0.0, 2.0], [1.0, -1.0], [0.5, 0.0]]);
+    ///
+    /// let bounds = t.bounding_box().unwrap();
+    /// assert_eq!(bounds.lo, [0.0, -1.0]);
+    /// assert_eq!(bounds.hi, [1.0, 2.0]);
+    /// ```
+    pub fn bounding_box(&self) -> Option<Aabb<A, K>> {
+        (!self.is_empty()).then_some(self.bounds)
+    }
+
+    #[must_use]
+    /// Get the number of tests on the path from the root of this tree to any leaf.
+    pub const fn depth(&self) -> u32 {
+        self.tests.len().trailing_ones()
+    }
+
+    #[must_use]
+    /// Consume this tree, returning the set of points in it in the order of the leaf cells which
+    /// contain them.
+    pub fn into_points(self) -> Vec<[A; K]> {
+        let mut points = self.points.into_vec();
+        points.truncate(self.n_points);
+        points
+    }
+
+    #[must_use]
+    /// Get the point stored in the leaf cell which `needle` falls into.
+    ///
+    /// This takes a single pass down the tree with no backtracking, so it is much faster than
+    /// [`PkdTree::query1_exact`], but the returned point is only an approximation of the nearest
+    /// point: a nearer point may lie in a neighboring cell.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let t = captree::PkdTree::new(&[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+    ///
+    /// assert_eq!(t.approx_nearest([0.9, 0.8]), [1.0, 1.0]);
+    /// ```
+    pub fn approx_nearest(&self, needle: [A; K]) -> [A; K] {
+        self.approx_nearest_with_id(needle).1
+    }
+
+    #[must_use]
+    /// Get the index of the leaf cell which `needle` falls into, along with the point stored in
+    /// it.
+    ///
+    /// This is the same as [`PkdTree::approx_nearest`], but also returns the index of the cell, as
+    /// accepted by [`PkdTree::get_point`].
+    /// The descent never ends in a ghost cell unless this tree is empty, in which case the
+    /// returned point is infinite and the index is not that of any point.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let t = captree::PkdTree::new(&[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+    ///
+    /// let (id, point) = t.approx_nearest_with_id([0.9, 0.8]);
+    /// assert_eq!(point, [1.0, 1.0]);
+    /// assert_eq!(t.get_point(id), point);
+    /// ```
+    pub fn approx_nearest_with_id(&self, needle: [A; K]) -> (usize, [A; K]) {
+        let id = forward_pass(&self.tests, &needle);
+        (id, self.points[id])
+    }
+
+    #[must_use]
+    /// Determine whether a ball centered at `needle` with radius `r_squared` could collide with a
+    /// point in this tree.
+    pub fn might_collide(&self, needle: [A; K], r_squared: A) -> bool {
+        distsq(self.approx_nearest(needle), needle) <= r_squared
+    }
+
+    #[must_use]
+    /// Determine whether a ball centered at `needle` with radius `radius` could collide with a
+    /// point in this tree.
+    ///
+    /// This is the same as [`PkdTree::might_collide`], but takes a radius instead of a squared
+    /// radius.
+    pub fn might_collide_r(&self, needle: [A; K], radius: Radius<A>) -> bool {
+        self.might_collide(needle, radius.squared().0)
+    }
+
+    #[must_use]
+    /// Get an iterator over progressively nearer points in this tree to `needle`.
+    ///
+    /// The first item is the point in the leaf cell containing `needle`, as given by
+    /// [`PkdTree::approx_nearest`], and each later item is strictly nearer than the one before it.
+    /// The last item is the exact nearest point, as given by [`PkdTree::query1_exact`].
+    /// Each item is the index of a leaf cell and the squared distance from its point to `needle`.
+    ///
+    /// Searching for the next item may take as long as a whole exact query, but the search can be
+    /// abandoned at any point while keeping the best point found so far.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let t = captree::PkdTree::new(&[[0.0], [1.0], [3.0], [7.0]]);
+    ///
+    /// let candidates: Vec<(usize, f32)> = t.anytime_nearest([2.9]).collect();
+    /// let (nearest, distsq) = *candidates.last().unwrap();
+    /// assert_eq!(t.get_point(nearest), [3.0]);
+    /// assert!(distsq < 0.011);
+    /// ```
+    pub const fn anytime_nearest(&self, needle: [A; K]) -> AnytimeNearest<'_, K, A> {
+        AnytimeNearest {
+            tree: self,
+            needle,
+            stack: Vec::new(),
+            best_distsq: None,
+        }
+    }
+
+    #[must_use]
+    /// Query for one point in this tree, returning an exact answer.
+    ///
+    /// If this tree contains no points, the returned index is not the index of a leaf cell; use
+    /// [`PkdTree::get_point_checked`] to look it up safely.
+    pub fn query1_exact(&self, needle: [A; K]) -> usize {
+        self.nearest_from::<true>(needle, usize::MAX, A::INFINITY, usize::MAX)
+            .0
+    }
+
+    #[must_use]
+    /// Query for one point in this tree, visiting at most `max_leaves` leaf cells.
+    ///
+    /// The search visits cells in the same order as [`PkdTree::query1_exact`], starting with the
+    /// cell containing `needle`, and gives up once it would visit more than `max_leaves` of them.
+    /// This bounds the latency of a query while still returning the best point found so far.
+    ///
+    /// Returns the index of the nearest point found and whether the search completed.
+    /// If the search completed, the index is the same as that returned by
+    /// [`PkdTree::query1_exact`].
+    /// If `max_leaves` is 0 or this tree is empty, the returned index is not the index of a leaf
+    /// cell; use [`PkdTree::get_point_checked`] to look it up safely.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let t = captree::PkdTree::new(&[[0.0], [1.0], [3.0], [7.0]]);
+    ///
+    /// // one leaf is enough for a needle well inside its cell
+    /// let (id, complete) = t.query1_within_budget([7.5], 1);
+    /// assert_eq!(t.get_point(id), [7.0]);
+    /// assert!(complete);
+    ///
+    /// // a needle near a split may need to check its neighbors
+    /// let (id, complete) = t.query1_within_budget([2.1], 1);
+    /// assert_eq!(t.get_point(id), [3.0]);
+    /// assert!(!complete);
+    /// let (id, complete) = t.query1_within_budget([2.1], 4);
+    /// assert_eq!(t.get_point(id), [3.0]);
+    /// assert!(complete);
+    /// ```
+    pub fn query1_within_budget(&self, needle: [A; K], max_leaves: usize) -> (usize, bool) {
+        let (id, _, complete) =
+            self.nearest_from::<true>(needle, usize::MAX, A::INFINITY, max_leaves);
+        (id, complete)
+    }
+
+    #[must_use]
+    /// Query for one point in this tree, returning an exact answer without checking any bounds.
+    ///
+    /// This is the same as [`PkdTree::query1_exact`], but skips the bounds checks made while
+    /// descending the tree, for hot loops which have already established that the tree is
+    /// nonempty.
+    ///
+    /// # Safety
+    ///
+    /// This tree must contain at least one point.
+    /// The returned index is then always the index of a leaf cell, so it may be passed to
+    /// [`PkdTree::get_point_unchecked`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let t = captree::PkdTree::new(&[[0.0, 0.0], [1.0, 0.0]]);
+    ///
+    /// // SAFETY: `t` is nonempty
+    /// let p = unsafe { t.get_point_unchecked(t.query1_unchecked([0.9, 0.1])) };
+    /// assert_eq!(p, [1.0, 0.0]);
+    /// ```
+    pub unsafe fn query1_unchecked(&self, needle: [A; K]) -> usize {
+        self.nearest_from::<false>(needle, usize::MAX, A::INFINITY, usize::MAX)
+            .0
+    }
+
+    #[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+    /// Find the nearest point to `needle` which is strictly closer than `best_distsq`, returning
+    /// its index and squared distance, or `(best_id, best_distsq)` if there is no such point.
+    ///
+    /// The search stops early rather than visit more than `max_leaves` leaf cells; the returned
+    /// flag is true only if the search completed.
+    ///
+    /// If `CHECKED` is false, the tests and points of the tree are indexed without bounds checks,
+    /// which is sound because every index visited is bounded by the shape of the tree.
+    fn nearest_from<const CHECKED: bool>(
+        &self,
+        needle: [A; K],
+        mut best_id: usize,
+        mut best_distsq: A,
+        max_leaves: usize,
+    ) -> (usize, A, bool) {
+        let mut n_leaves = 0;
+        // explicit stack of `(test_idx, k, bounding_box)`, holding at most one entry per level of
+        // the tree plus one
+        let mut stack = Vec::with_capacity(self.tests.len().trailing_ones() as usize + 1);
+        stack.push((0, 0, Aabb::ALL));
+
+        while let Some((test_idx, k, bounding_box)) = stack.pop() {
+            if bounding_box.closest_distsq_to(&needle) > best_distsq {
+                continue;
+            }
+
+            if self.tests.len() <= test_idx {
+                let id = test_idx - self.tests.len();
+                if id >= self.n_points {
+                    continue;
+                }
+                if n_leaves == max_leaves {
+                    return (best_id, best_distsq, false);
+                }
+                n_leaves += 1;
+                let point = if CHECKED {
+                    self.get_point(id)
+                } else {
+                    // SAFETY: `id` was just checked to be the index of a real point
+                    unsafe { self.get_point_unchecked(id) }
+                };
+                let new_distsq = distsq(needle, point);
+                if new_distsq < best_distsq {
+                    best_id = id;
+                    best_distsq = new_distsq;
+                }
+
+                continue;
+            }
+
+            let test = if CHECKED {
+                self.tests[test_idx]
+            } else {
+                // SAFETY: `test_idx` was just checked to be less than the number of tests
+                unsafe { *self.tests.get_unchecked(test_idx) }
+            };
+
+            let mut bb_below = bounding_box;
+            bb_below.hi[k] = test;
+            let mut bb_above = bounding_box;
+            bb_above.lo[k] = test;
+
+            let next_k = (k + 1) % K;
+            // push the far side first so that the near side is searched first
+            if needle[k] < test {
+                stack.push((2 * test_idx + 2, next_k, bb_above));
+                stack.push((2 * test_idx + 1, next_k, bb_below));
+            } else {
+                stack.push((2 * test_idx + 1, next_k, bb_below));
+                stack.push((2 * test_idx + 2, next_k, bb_above));
+            }
+        }
+
+        (best_id, best_distsq, true)
+    }
+
+    #[must_use]
+    #[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+    /// Query for the `k` points in this tree nearest to `needle`, returning an exact answer.
+    ///
+    /// The returned leaf indices are sorted from nearest to farthest.
+    /// If this tree has fewer than `k` points, every point is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let t = captree::PkdTree::new(&[[0.0], [1.0], [3.0], [7.0]]);
+    ///
+    /// let nearest: Vec<[f32; 1]> = t
+    ///     .query_k_exact([2.5], 2)
+    ///     .into_iter()
+    ///     .map(|id| t.get_point(id))
+    ///     .collect();
+    /// assert_eq!(nearest, [[3.0], [1.0]]);
+    /// ```
+    pub fn query_k_exact(&self, needle: [A; K], k: usize) -> Vec<usize> {
+        // the best `(distsq, id)` pairs found so far, sorted by distance
+        let mut best: Vec<(A, usize)> = Vec::with_capacity(k + 1);
+        let bound = |best: &[(A, usize)]| {
+            if best.len() < k {
+                A::INFINITY
+            } else {
+                best[k - 1].0
+            }
+        };
+
+        let mut stack = Vec::with_capacity(self.tests.len().trailing_ones() as usize + 1);
+        stack.push((0, 0, Aabb::ALL));
+
+        while let Some((test_idx, dim, bounding_box)) = stack.pop() {
+            if k == 0 || bounding_box.closest_distsq_to(&needle) > bound(&best) {
+                continue;
+            }
+
+            if self.tests.len() <= test_idx {
+                let id = test_idx - self.tests.len();
+                if id >= self.n_points {
+                    continue;
+                }
+                let new_distsq = distsq(needle, self.get_point(id));
+                if new_distsq < bound(&best) {
+                    let pos = best.partition_point(|&(d, _)| d <= new_distsq);
+                    best.insert(pos, (new_distsq, id));
+                    best.truncate(k);
+                }
+
+                continue;
+            }
+
+            let test = self.tests[test_idx];
+
+            let mut bb_below = bounding_box;
+            bb_below.hi[dim] = test;
+            let mut bb_above = bounding_box;
+            bb_above.lo[dim] = test;
+
+            let next_dim = (dim + 1) % K;
+            if needle[dim] < test {
+                stack.push((2 * test_idx + 2, next_dim, bb_above));
+                stack.push((2 * test_idx + 1, next_dim, bb_below));
+            } else {
+                stack.push((2 * test_idx + 1, next_dim, bb_below));
+                stack.push((2 * test_idx + 2, next_dim, bb_above));
+            }
+        }
+
+        best.into_iter().map(|(_, id)| id).collect()
+    }
+
+    #[must_use]
+    /// Count the points in this tree which are within a distance of `r` of `needle`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let t = captree::PkdTree::new(&[[0.0], [1.0], [1.5], [7.0]]);
+    ///
+    /// assert_eq!(t.count_within([1.2], 0.5), 2);
+    /// assert_eq!(t.count_within([4.0], 1.0), 0);
+    /// ```
+    pub fn count_within(&self, needle: [A; K], r: A) -> usize {
+        let rsq = r.square();
+        let mut count = 0;
+
+        let mut stack = vec![(0, 0, Aabb::ALL)];
+        while let Some((test_idx, k, bounding_box)) = stack.pop() {
+            if bounding_box.closest_distsq_to(&needle) > rsq {
+                continue;
+            }
+
+            if self.tests.len() <= test_idx {
+                let id = test_idx - self.tests.len();
+                if id < self.n_points && distsq(needle, self.points[id]) <= rsq {
+                    count += 1;
+                }
+                continue;
+            }
+
+            let (bb_below, bb_above) = bounding_box.split(self.tests[test_idx], k);
+            let next_k = (k + 1) % K;
+            stack.push((2 * test_idx + 1, next_k, bb_below));
+            stack.push((2 * test_idx + 2, next_k, bb_above));
+        }
+
+        count
+    }
+
+    #[must_use]
+    /// Determine whether any point in this tree is at a distance between `r_inner` and `r_outer`
+    /// (inclusive) from `center`, which is to say that it lies in the spherical shell between the
+    /// two radii.
+    ///
+    /// Subtrees whose bounding volumes lie entirely inside the inner sphere or entirely outside
+    /// the outer sphere are pruned.
+    /// [`Capt`](crate::Capt)s cannot answer this query, since their affordance buffers omit points
+    /// which are well within the minimum radius of a cell's representative.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let t = captree::PkdTree::new(&[[0.0, 0.0], [1.0, 0.0], [3.0, 0.0]]);
+    ///
+    /// assert!(t.collides_shell([0.0, 0.0], 0.5, 1.5));
+    /// assert!(!t.collides_shell([0.0, 0.0], 1.5, 2.5));
+    /// assert!(t.collides_shell([0.0, 0.0], 1.5, 3.0));
+    /// ```
+    pub fn collides_shell(&self, center: [A; K], r_inner: A, r_outer: A) -> bool {
+        let (rsq_inner, rsq_outer) = (r_inner.square(), r_outer.square());
+
+        let mut stack = Vec::with_capacity(self.tests.len().trailing_ones() as usize + 1);
+        stack.push((0, 0, Aabb::ALL));
+        while let Some((test_idx, k, bounding_box)) = stack.pop() {
+            if bounding_box.closest_distsq_to(&center) > rsq_outer
+                || bounding_box.farthest_distsq_to(&center) < rsq_inner
+            {
+                continue;
+            }
+
+            if self.tests.len() <= test_idx {
+                let id = test_idx - self.tests.len();
+                if id < self.n_points {
+                    let d = distsq(center, self.points[id]);
+                    if rsq_inner <= d && d <= rsq_outer {
+                        return true;
+                    }
+                }
+                continue;
+            }
+
+            let (bb_below, bb_above) = bounding_box.split(self.tests[test_idx], k);
+            let next_k = (k + 1) % K;
+            stack.push((2 * test_idx + 1, next_k, bb_below));
+            stack.push((2 * test_idx + 2, next_k, bb_above));
+        }
+
+        false
+    }
+
+    #[must_use]
+    /// Query for the point in this tree farthest from `center`, returning its index.
+    ///
+    /// Subtrees are pruned once the farthest corner of their bounding volume is no farther than
+    /// the best point found so far.
+    /// The farthest point in a direction is a support point of the cloud, as used by GJK-style
+    /// algorithms, and the farthest point from the centroid bounds the extent of the cloud.
+    ///
+    /// If this tree contains no points, the returned index is not the index of a leaf cell; use
+    /// [`PkdTree::get_point_checked`] to look it up safely.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let t = captree::PkdTree::new(&[[0.0], [1.0], [3.0], [7.0]]);
+    ///
+    /// assert_eq!(t.get_point(t.farthest_point([2.0])), [7.0]);
+    /// assert_eq!(t.get_point(t.farthest_point([5.0])), [0.0]);
+    /// ```
+    pub fn farthest_point(&self, center: [A; K]) -> usize {
+        let mut best_id = usize::MAX;
+        let mut best_distsq = A::NEG_INFINITY;
+
+        let mut stack = Vec::with_capacity(self.tests.len().trailing_ones() as usize + 1);
+        stack.push((0, 0, self.bounds));
+        while let Some((test_idx, k, bounding_box)) = stack.pop() {
+            // a box which is empty along any axis holds only padding
+            if bounding_box
+                .lo
+                .iter()
+                .zip(&bounding_box.hi)
+                .any(|(l, h)| l > h)
+                || bounding_box.farthest_distsq_to(&center) <= best_distsq
+            {
+                continue;
+            }
+
+            if self.tests.len() <= test_idx {
+                let id = test_idx - self.tests.len();
+                if id < self.n_points {
+                    let d = distsq(center, self.points[id]);
+                    if d > best_distsq {
+                        best_id = id;
+                        best_distsq = d;
+                    }
+                }
+                continue;
+            }
+
+            // clip the split to the bounds, so that infinite tests never loosen them
+            let test = self.tests[test_idx];
+            let mut bb_below = bounding_box;
+            if test < bb_below.hi[k] {
+                bb_below.hi[k] = test;
+            }
+            let mut bb_above = bounding_box;
+            if bb_above.lo[k] < test {
+                bb_above.lo[k] = test;
+            }
+
+            let next_k = (k + 1) % K;
+            // push the near side first so that the far side is searched first
+            if center[k] < test {
+                stack.push((2 * test_idx + 1, next_k, bb_below));
+                stack.push((2 * test_idx + 2, next_k, bb_above));
+            } else {
+                stack.push((2 * test_idx + 2, next_k, bb_above));
+                stack.push((2 * test_idx + 1, next_k, bb_below));
+            }
+        }
+
+        best_id
+    }
+
+    /// Call `callback(i, j)` once for every pair of distinct points in this tree which are
+    /// within a distance of `r` of each other, where `i < j` are the indices of the points in
+    /// [`PkdTree::points`].
+    ///
+    /// This is a dual-tree traversal of the tree against itself: any pair of cells whose bounding
+    /// volumes are farther apart than `r` is pruned without visiting the points inside.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let t = captree::PkdTree::new(&[[0.0], [0.1], [1.0], [1.05]]);
+    ///
+    /// let mut pairs = Vec::new();
+    /// t.for_each_pair_within(0.2, |i, j| pairs.push([t.points()[i], t.points()[j]]));
+    /// pairs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    ///
+    /// assert_eq!(pairs, [[[0.0], [0.1]], [[1.0], [1.05]]]);
+    /// ```
+    pub fn for_each_pair_within(&self, r: A, mut callback: impl FnMut(usize, usize)) {
+        let rsq = r.square();
+        let n_tests = self.tests.len();
+
+        // explicit stack of `(a, b, k, aabb_a, aabb_b)`, where `a` and `b` are node indices at the
+        // same depth of the tree with `a <= b`
+        let mut stack = vec![(0, 0, 0, Aabb::<A, K>::ALL, Aabb::ALL)];
+        while let Some((a, b, k, aabb_a, aabb_b)) = stack.pop() {
+            if aabb_a.closest_distsq_to_aabb(&aabb_b) > rsq {
+                continue;
+            }
+
+            if n_tests <= a {
+                let (i, j) = (a - n_tests, b - n_tests);
+                if i != j && j < self.n_points && distsq(self.points[i], self.points[j]) <= rsq {
+                    callback(i, j);
+                }
+                continue;
+            }
+
+            let next_k = (k + 1) % K;
+            let (lo_a, hi_a) = aabb_a.split(self.tests[a], k);
+            if a == b {
+                stack.push((2 * a + 1, 2 * a + 1, next_k, lo_a, lo_a));
+                stack.push((2 * a + 1, 2 * a + 2, next_k, lo_a, hi_a));
+                stack.push((2 * a + 2, 2 * a + 2, next_k, hi_a, hi_a));
+            } else {
+                let (lo_b, hi_b) = aabb_b.split(self.tests[b], k);
+                for (c, aabb_c) in [(2 * a + 1, lo_a), (2 * a + 2, hi_a)] {
+                    for (d, aabb_d) in [(2 * b + 1, lo_b), (2 * b + 2, hi_b)] {
+                        stack.push((c, d, next_k, aabb_c, aabb_d));
+                    }
+                }
+            }
+        }
+    }
+
+    #[must_use]
+    /// Get the representative point of the `id`-th leaf cell.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `id` is not the index of a leaf cell holding a point, including
+    /// the ghost cells which pad the tree out to a power of two.
+    pub fn get_point(&self, id: usize) -> [A; K] {
+        self.points()[id]
+    }
+
+    #[must_use]
+    /// Get the representative point of the `id`-th leaf cell, or `None` if `id` is not the index
+    /// of a leaf cell holding a point.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let t = captree::PkdTree::new(&[[0.0, 0.0], [1.0, 0.0]]);
+    ///
+    /// assert_eq!(
+    ///     t.get_point_checked(t.query1_exact([0.9, 0.1])),
+    ///     Some([1.0, 0.0])
+    /// );
+    /// assert_eq!(t.get_point_checked(usize::MAX), None);
+    /// ```
+    pub fn get_point_checked(&self, id: usize) -> Option<[A; K]> {
+        self.points().get(id).copied()
+    }
+
+    #[must_use]
+    /// Get the representative point of the `id`-th leaf cell without checking that `id` is in
+    /// bounds.
+    ///
+    /// # Safety
+    ///
+    /// `id` must be less than [`PkdTree::len`], as is any index returned by an exact query on this
+    /// tree.
+    pub unsafe fn get_point_unchecked(&self, id: usize) -> [A; K] {
+        *self.points.get_unchecked(id)
+    }
+
+    /// Get the representative point of each leaf cell in `ids`, writing the point for `ids[i]` to
+    /// `out[i]`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `ids` and `out` have different lengths, or if any element of
+    /// `ids` is not the index of a leaf cell holding a point.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let t = captree::PkdTree::new(&[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+    ///
+    /// let ids = [t.query1_exact([0.9, 0.9]), t.query1_exact([0.1, 0.0])];
+    /// let mut out = [[0.0; 2]; 2];
+    /// t.get_points(&ids, &mut out);
+    /// assert_eq!(out, [[1.0, 1.0], [0.0, 0.0]]);
+    /// ```
+    pub fn get_points(&self, ids: &[usize], out: &mut [[A; K]]) {
+        assert_eq!(
+            ids.len(),
+            out.len(),
+            "must have one output point for every id"
+        );
+        for (o, &id) in out.iter_mut().zip(ids) {
+            *o = self.get_point(id);
+        }
+    }
+
+    #[must_use]
+    /// Return the total memory used (stack + heap) by this structure.
+    pub const fn memory_used(&self) -> usize {
+        size_of::<Self>() + (self.points.len() * K + self.tests.len()) * size_of::<A>()
+    }
+}
+
+impl<A, const K: usize> PkdTree<K, A>
+where
+    A: Axis + Mul<Output = A>,
+{
+    #[must_use]
+    /// Determine whether any point in this tree lies in the convex polytope formed by the
+    /// intersection of `halfspaces`.
+    ///
+    /// Each half-space `(normal, offset)` contains the points `x` for which the dot product of
+    /// `normal` and `x` is at most `offset`.
+    /// Subtrees whose bounding volumes lie entirely outside any one half-space are pruned, so this
+    /// is suited to checking camera frusta or the convex regions of a safe-corridor planner.
+    /// With no half-spaces, the polytope is all of space.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let t = captree::PkdTree::new(&[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+    ///
+    /// // the triangle below the line `x + y = 1.5` with `x >= 0.5` and `y >= -0.5`
+    /// let triangle = [([1.0, 1.0], 1.5), ([-1.0, 0.0], -0.5), ([0.0, -1.0], 0.5)];
+    /// assert!(t.collides_convex(&triangle));
+    ///
+    /// // the same triangle, cut off above `y = -0.1`
+    /// let sliver = [([1.0, 1.0], 1.5), ([-1.0, 0.0], -0.5), ([0.0, 1.0], -0.1)];
+    /// assert!(!t.collides_convex(&sliver));
+    /// ```
+    pub fn collides_convex(&self, halfspaces: &[([A; K], A)]) -> bool {
+        let contains = |p: &[A; K]| {
+            halfspaces
+                .iter()
+                .all(|(normal, offset)| dot(normal, p) <= *offset)
+        };
+
+        let mut stack = Vec::with_capacity(self.tests.len().trailing_ones() as usize + 1);
+        stack.push((0, 0, Aabb::ALL));
+        while let Some((test_idx, k, bounding_box)) = stack.pop() {
+            if halfspaces
+                .iter()
+                .any(|(normal, offset)| outside_halfspace(&bounding_box, normal, *offset))
+            {
+                continue;
+            }
+
+            if self.tests.len() <= test_idx {
+                let id = test_idx - self.tests.len();
+                if id < self.n_points && contains(&self.points[id]) {
+                    return true;
+                }
+                continue;
+            }
+
+            let (bb_below, bb_above) = bounding_box.split(self.tests[test_idx], k);
+            let next_k = (k + 1) % K;
+            stack.push((2 * test_idx + 1, next_k, bb_below));
+            stack.push((2 * test_idx + 2, next_k, bb_above));
+        }
+
+        false
+    }
+
+    #[must_use]
+    /// Determine whether any point in this tree lies in an oriented bounding box.
+    ///
+    /// The box is centered at `center` and extends `half_extents[j]` to either side along its
+    /// `j`-th axis, which is the `j`-th column of `rotation`: a point `x` in the frame of the box
+    /// is at `center + rotation * x` in the frame of the tree.
+    /// `rotation` should be orthogonal.
+    ///
+    /// This is answered as [`PkdTree::collides_convex`] with one pair of half-spaces per axis, so
+    /// footprints with a heading can be checked exactly instead of by a bounding sphere.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let t = captree::PkdTree::new(&[[1.0, 1.0], [1.0, -1.0]]);
+    ///
+    /// // a long, thin box along the x-axis misses both points
+    /// assert!(!t.collides_obb([0.0, 0.0], [2.0, 0.1], [[1.0, 0.0], [0.0, 1.0]]));
+    ///
+    /// // turned by 45 degrees, it reaches the first one
+    /// let h = std::f32::consts::FRAC_1_SQRT_2;
+    /// assert!(t.collides_obb([0.0, 0.0], [2.0, 0.1], [[h, -h], [h, h]]));
+    /// ```
+    pub fn collides_obb(
+        &self,
+        center: [A; K],
+        half_extents: [A; K],
+        rotation: [[A; K]; K],
+    ) -> bool {
+        let halfspaces: Vec<([A; K], A)> = (0..K)
+            .flat_map(|j| {
+                let axis: [A; K] = std::array::from_fn(|k| rotation[k][j]);
+                let mid = dot(&axis, &center);
+                [
+                    (axis, mid + half_extents[j]),
+                    (axis.map(|x| A::ZERO - x), A::ZERO - (mid - half_extents[j])),
+                ]
+            })
+            .collect();
+        self.collides_convex(&halfspaces)
+    }
+}
+
+/// Compute the dot product of `a` and `b`.
+fn dot<A: Axis + Mul<Output = A>, const K: usize>(a: &[A; K], b: &[A; K]) -> A {
+    a.iter()
+        .zip(b)
+        .fold(A::ZERO, |total, (&x, &y)| total + x * y)
+}
+
+/// Determine whether every point in `aabb` lies outside the half-space of points whose dot
+/// product with `normal` is at most `offset`.
+fn outside_halfspace<A: Axis + Mul<Output = A>, const K: usize>(
+    aabb: &Aabb<A, K>,
+    normal: &[A; K],
+    offset: A,
+) -> bool {
+    // the least dot product of `normal` with any point in `aabb`, taken at one of its corners
+    let mut lowest = A::ZERO;
+    #[allow(clippy::needless_range_loop)]
+    for k in 0..K {
+        // zero components are skipped so that an infinite bound never produces `NaN`
+        if normal[k] > A::ZERO {
+            lowest = lowest + normal[k] * aabb.lo[k];
+        } else if normal[k] < A::ZERO {
+            lowest = lowest + normal[k] * aabb.hi[k];
+        }
+    }
+
+    // if the cell only holds padding, `lowest` may still be `NaN`, which never prunes
+    lowest > offset
+}
+
+impl<A: Axis, const K: usize> Default for PkdTree<K, A> {
+    /// Construct an empty tree.
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+#[derive(Clone, Debug)]
+/// An iterator over progressively nearer points in a [`PkdTree`] to a needle, created by
+/// [`PkdTree::anytime_nearest`].
+pub struct AnytimeNearest<'a, const K: usize, A = f32> {
+    /// The tree being searched.
+    tree: &'a PkdTree<K, A>,
+    /// The point to search near.
+    needle: [A; K],
+    /// The subtrees which have yet to be searched, as `(test_idx, k, bounding_box)`.
+    stack: Vec<(usize, usize, Aabb<A, K>)>,
+    /// The squared distance to the best point yielded so far, or `None` if nothing has been
+    /// yielded yet.
+    best_distsq: Option<A>,
+}
+
+impl<A: Axis, const K: usize> Iterator for AnytimeNearest<'_, K, A> {
+    type Item = (usize, A);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tests = &self.tree.tests;
+        let Some(best_distsq) = self.best_distsq else {
+            if self.tree.points().is_empty() {
+                return None;
+            }
+            // start with the leaf containing the needle, then backtrack from the root
+            let id = forward_pass(tests, &self.needle);
+            let first_distsq = distsq(self.needle, self.tree.get_point(id));
+            self.best_distsq = Some(first_distsq);
+            self.stack.reserve(tests.len().trailing_ones() as usize + 1);
+            self.stack.push((0, 0, Aabb::ALL));
+            return Some((id, first_distsq));
+        };
+
+        while let Some((test_idx, k, bounding_box)) = self.stack.pop() {
+            if bounding_box.closest_distsq_to(&self.needle) >= best_distsq {
+                continue;
+            }
+
+            if tests.len() <= test_idx {
+                let id = test_idx - tests.len();
+                if id >= self.tree.n_points {
+                    continue;
+                }
+                let new_distsq = distsq(self.needle, self.tree.get_point(id));
+                if new_distsq < best_distsq {
+                    self.best_distsq = Some(new_distsq);
+                    return Some((id, new_distsq));
+                }
+
+                continue;
+            }
+
+            let test = tests[test_idx];
+
+            let mut bb_below = bounding_box;
+            bb_below.hi[k] = test;
+            let mut bb_above = bounding_box;
+            bb_above.lo[k] = test;
+
+            let next_k = (k + 1) % K;
+            if self.needle[k] < test {
+                self.stack.push((2 * test_idx + 2, next_k, bb_above));
+                self.stack.push((2 * test_idx + 1, next_k, bb_below));
+            } else {
+                self.stack.push((2 * test_idx + 1, next_k, bb_below));
+                self.stack.push((2 * test_idx + 2, next_k, bb_above));
+            }
+        }
+
+        None
+    }
+}
+
+/// Implement `centroid` and `diameter_upper_bound` for `PkdTree`s with axes of type `$a`.
+macro_rules! impl_extent {
+    ($a: ty) => {
+        impl<const K: usize> PkdTree<K, $a> {
+            #[must_use]
+            #[allow(clippy::cast_precision_loss)]
+            /// Get the mean of the points in this tree, or `None` if this tree is empty.
+            ///
+            /// This is computed from a sum taken at construction, so it takes constant time.
+            ///
+            /// # Examples
+            ///
+            /// ```
+            /// let t = captree::PkdTree::<2>::new(&[[0.0, 2.0], [1.0, -1.0], [0.5, 0.5]]);
+            ///
+            /// assert_eq!(t.centroid(), Some([0.5, 0.5]));
+            /// ```
+            pub fn centroid(&self) -> Option<[$a; K]> {
+                let n = self.len() as $a;
+                (!self.is_empty()).then(|| self.sum.map(|x| x / n))
+            }
+
+            #[must_use]
+            /// Get an upper bound on the greatest distance between any two points in this tree,
+            /// or zero if this tree is empty.
+            ///
+            /// This is the length of the diagonal of [`PkdTree::bounding_box`], so it is at most
+            /// `sqrt(K)` times the true diameter.
+            ///
+            /// # Examples
+            ///
+            /// ```
+            /// let t = captree::PkdTree::<2>::new(&[[0.0, 0.0], [3.0, 4.0], [1.0, 1.0]]);
+            ///
+            /// assert_eq!(t.diameter_upper_bound(), 5.0);
+            /// ```
+            pub fn diameter_upper_bound(&self) -> $a {
+                self.bounding_box()
+                    .map_or(0.0, |b| distsq(b.lo, b.hi).sqrt())
+            }
+        }
+    };
+}
+
+impl_extent!(f32);
+impl_extent!(f64);
+
+impl<const K: usize> PkdTree<K, f32> {
+    #[must_use]
+    /// Find the nearest point in this tree to each point in `source`, for matching a scan against
+    /// this tree as in iterative closest point (ICP) registration.
+    ///
+    /// For each source point, this returns the index of its nearest point in this tree (as in
+    /// [`PkdTree::query1_exact`]) and the distance to it, or `None` if no point in this tree is
+    /// closer than `max_dist`.
+    ///
+    /// With the `simd` feature enabled, the leaf cells containing the source points are found
+    /// several at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let t = captree::PkdTree::new(&[[0.0, 0.0], [1.0, 0.0]]);
+    ///
+    /// let matches = t.correspondences(&[[0.9, 0.0], [5.0, 5.0]], 0.5);
+    /// assert_eq!(t.get_point(matches[0].unwrap().0), [1.0, 0.0]);
+    /// assert_eq!(matches[1], None);
+    /// ```
+    pub fn correspondences(&self, source: &[[f32; K]], max_dist: f32) -> Vec<Option<(usize, f32)>> {
+        let max_distsq = max_dist * max_dist;
+        source
+            .iter()
+            .zip(self.leaves_containing(source))
+            .map(|(&needle, leaf)| {
+                // start the exact search from the leaf's point, which is usually the answer
+                // the leaf is only a ghost if this tree is empty, in which case its point is
+                // infinitely far away
+                let leaf_distsq = distsq(needle, self.points[leaf]);
+                let (id, d, _) = if leaf_distsq < max_distsq {
+                    self.nearest_from::<true>(needle, leaf, leaf_distsq, usize::MAX)
+                } else {
+                    self.nearest_from::<true>(needle, usize::MAX, max_distsq, usize::MAX)
+                };
+                (id != usize::MAX).then(|| (id, d.sqrt()))
+            })
+            .collect()
+    }
+
+    /// Find the index of the leaf cell containing each point in `needles`.
+    #[cfg(not(feature = "simd"))]
+    fn leaves_containing(&self, needles: &[[f32; K]]) -> Vec<usize> {
+        needles
+            .iter()
+            .map(|needle| forward_pass(&self.tests, needle))
+            .collect()
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    /// Find the index of the leaf cell containing each point in `needles`, descending the tree for
+    /// eight needles at a time.
+    #[cfg(feature = "simd")]
+    fn leaves_containing(&self, needles: &[[f32; K]]) -> Vec<usize> {
+        const L: usize = 8;
+        let mut leaves = Vec::with_capacity(needles.len());
+        let chunks = needles.chunks_exact(L);
+        let remainder = chunks.remainder();
+        for chunk in chunks {
+            let centers =
+                std::array::from_fn(|k| Simd::from_array(std::array::from_fn(|l| chunk[l][k])));
+            leaves.extend(
+                forward_pass_simd::<f32, K, L>(&self.tests, &centers)
+                    .to_array()
+                    .map(|z| z as usize),
+            );
+        }
+        leaves.extend(
+            remainder
+                .iter()
+                .map(|needle| forward_pass(&self.tests, needle)),
+        );
+        leaves
+    }
+}
+
+#[cfg(feature = "simd")]
+impl<A, const K: usize> PkdTree<K, A> {
+    #[must_use]
+    #[allow(clippy::cast_possible_wrap)]
+    /// Get the point stored in the leaf cell which each lane of `needles` falls into, as in
+    /// [`PkdTree::approx_nearest`], for `L` needles at a time.
+    ///
+    /// The points are returned directly, transposed in the same layout as `needles`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(portable_simd)]
+    /// use std::simd::Simd;
+    ///
+    /// let t = captree::PkdTree::new(&[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+    ///
+    /// let needles = [Simd::from_array([0.9, 0.1]), Simd::from_array([0.8, 0.2])];
+    /// let [xs, ys] = t.approx_nearest_simd::<2>(&needles);
+    /// assert_eq!(xs.to_array(), [1.0, 0.0]);
+    /// assert_eq!(ys.to_array(), [1.0, 0.0]);
+    /// ```
+    pub fn approx_nearest_simd<const L: usize>(&self, needles: &[Simd<A, L>; K]) -> [Simd<A, L>; K]
+    where
+        Simd<A, L>: SimdPartialOrd,
+        Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+        A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask>,
+    {
+        let indices = forward_pass_simd(&self.tests, needles);
+        // SAFETY: the forward pass always ends at a leaf cell
+        unsafe { self.gather_points(indices.cast()) }
+    }
+
+    #[must_use]
+    /// Get the representative points of the leaf cells in each lane of `ids`, as in
+    /// [`PkdTree::get_point`], for `L` cells at a time.
+    ///
+    /// The points are returned transposed, with the `k`-th element holding the `k`-th coordinate
+    /// of every point.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if any lane of `ids` is not the index of a leaf cell holding a
+    /// point.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(portable_simd)]
+    /// use std::simd::Simd;
+    ///
+    /// let t = captree::PkdTree::new(&[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+    ///
+    /// let ids = [t.query1_exact([0.9, 0.9]), t.query1_exact([0.1, 0.0])];
+    /// let [xs, ys] = t.get_points_simd(Simd::from_array(ids));
+    /// assert_eq!(xs.to_array(), [1.0, 0.0]);
+    /// assert_eq!(ys.to_array(), [1.0, 0.0]);
+    /// ```
+    pub fn get_points_simd<const L: usize>(&self, ids: Simd<usize, L>) -> [Simd<A, L>; K]
+    where
+        A: SimdElement + Default,
+    {
+        assert!(
+            ids.simd_lt(Simd::splat(self.n_points)).all(),
+            "every id must be the index of a leaf cell"
+        );
+        // SAFETY: every id was just checked to be in bounds
+        unsafe { self.gather_points(ids) }
+    }
+
+    /// Gather the points at each lane of `ids` into transposed form.
+    ///
+    /// # Safety
+    ///
+    /// Every lane of `ids` must be less than the length of `self.points`.
+    unsafe fn gather_points<const L: usize>(&self, ids: Simd<usize, L>) -> [Simd<A, L>; K]
+    where
+        A: SimdElement + Default,
+    {
+        let ptrs = Simd::splat(self.points.as_ptr().cast::<A>()).wrapping_add(ids * Simd::splat(K));
+        std::array::from_fn(|k| unsafe { Simd::gather_ptr(ptrs.wrapping_add(Simd::splat(k))) })
+    }
+
+    #[must_use]
+    #[allow(clippy::cast_possible_wrap)]
+    /// Determine whether any ball in the list of provided balls could collide with a point in this
+    /// tree.
+    pub fn might_collide_simd<const L: usize>(
+        &self,
+        needles: &[Simd<A, L>; K],
+        radii_squared: Simd<A, L>,
+    ) -> bool
+    where
+        Simd<A, L>:
+            SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+        Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+        A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask>,
+    {
+        self.might_collide_simd_masked(needles, radii_squared, Mask::splat(true))
+    }
+
+    #[must_use]
+    #[allow(clippy::cast_possible_wrap)]
+    /// Determine whether any ball in the list of provided balls whose lane is set in `active`
+    /// could collide with a point in this tree.
+    ///
+    /// This is the same as [`PkdTree::might_collide_simd`], except that inactive lanes are
+    /// ignored.
+    pub fn might_collide_simd_masked<const L: usize>(
+        &self,
+        needles: &[Simd<A, L>; K],
+        radii_squared: Simd<A, L>,
+        active: Mask<isize, L>,
+    ) -> bool
+    where
+        Simd<A, L>:
+            SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+        Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+        A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask>,
+    {
+        let nearest = self.approx_nearest_simd(needles);
+        let mut dists_squared = Simd::splat(A::ZERO);
+        for (&point_values, &needle_values) in nearest.iter().zip(needles) {
+            let deltas = point_values - needle_values;
+            dists_squared += deltas * deltas;
+        }
+        (Mask::from(dists_squared.simd_le(radii_squared)) & active).any()
+    }
+
+    #[must_use]
+    /// Determine whether any ball in `batch` could collide with a point in this tree.
+    ///
+    /// Unlike [`PkdTree::might_collide_simd`], this takes radii rather than squared radii, so the
+    /// same batch can be passed to [`Capt::collides_batch`](crate::Capt::collides_batch).
+    pub fn might_collide_batch<const L: usize>(&self, batch: &SimdQueryBatch<K, L, A>) -> bool
+    where
+        Simd<A, L>:
+            SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+        Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+        A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask>,
+    {
+        span!(TRACE, "might_collide_batch");
+        self.might_collide_simd_masked(&batch.centers, batch.radii * batch.radii, batch.active)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+
+    #[test]
+    fn single_query() {
+        let points = vec![
+            [0.1, 0.1],
+            [0.1, 0.2],
+            [0.5, 0.0],
+            [0.3, 0.9],
+            [1.0, 1.0],
+            [0.35, 0.75],
+            [0.6, 0.2],
+            [0.7, 0.8],
+        ];
+        let kdt = PkdTree::new(&points);
+
+        let neg1 = [-1.0, -1.0];
+        let neg1_idx = forward_pass(&kdt.tests, &neg1);
+        assert_eq!(neg1_idx, 0);
+
+        let pos1 = [1.0, 1.0];
+        let pos1_idx = forward_pass(&kdt.tests, &pos1);
+        assert_eq!(pos1_idx, points.len() - 1);
+    }
+
+    #[test]
+    #[cfg(feature = "simd")]
+    fn multi_query() {
+        let points = vec![
+            [0.1, 0.1],
+            [0.1, 0.2],
+            [0.5, 0.0],
+            [0.3, 0.9],
+            [1.0, 1.0],
+            [0.35, 0.75],
+            [0.6, 0.2],
+            [0.7, 0.8],
+        ];
+        let kdt = PkdTree::new(&points);
+
+        let needles = [Simd::from_array([-1.0, 2.0]), Simd::from_array([-1.0, 2.0])];
+        assert_eq!(
+            forward_pass_simd(&kdt.tests, &needles),
+            Simd::from_array([0, 7])
+        );
+        assert!(kdt.might_collide_simd(&needles, Simd::splat(5.0)));
+        assert!(!kdt.might_collide_simd(&needles, Simd::splat(0.5)));
+    }
+
+    #[test]
+    #[cfg(feature = "simd")]
+    #[allow(clippy::float_cmp)]
+    fn approx_nearest_simd_matches() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let kdt = PkdTree::new(&points);
+
+        for _ in 0..100 {
+            let needles: [[f32; 3]; 8] = std::array::from_fn(|_| [rng.gen(), rng.gen(), rng.gen()]);
+            let simd_needles =
+                std::array::from_fn(|k| Simd::from_array(needles.map(|needle| needle[k])));
+            let nearest = kdt.approx_nearest_simd::<8>(&simd_needles);
+            for (l, &needle) in needles.iter().enumerate() {
+                assert_eq!(kdt.approx_nearest(needle), nearest.map(|lane| lane[l]));
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "simd")]
+    fn batch_agrees_on_boundary() {
+        use crate::testing;
+
+        let t = PkdTree::new(&[[0.0, 0.0], [1.0, 1.0]]);
+        // exactly representable distances, so that the query touches its point
+        let touching = SimdQueryBatch::<2, 4>::new(&[([0.0, 0.5], 0.5)]);
+        assert!(t.might_collide([0.0, 0.5], 0.25));
+        assert!(t.might_collide_batch(&touching));
+
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let t = PkdTree::new(&points);
+        for (center, r) in testing::boundary_queries(&mut rng, &points, 1000, (0.01, 0.08)) {
+            assert_eq!(
+                t.might_collide_batch(&SimdQueryBatch::<3, 8>::new(&[(center, r)])),
+                t.might_collide(center, r * r)
+            );
+        }
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn batch_get_points() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let kdt = PkdTree::new(&points);
+        let ids: Vec<usize> = (0..64).map(|_| rng.gen_range(0..points.len())).collect();
+
+        let mut out = vec![[0.0; 3]; ids.len()];
+        kdt.get_points(&ids, &mut out);
+        for (&id, &p) in ids.iter().zip(&out) {
+            assert_eq!(kdt.get_point(id), p);
+        }
+
+        #[cfg(feature = "simd")]
+        for chunk in ids.chunks_exact(8) {
+            let lanes = kdt.get_points_simd(Simd::<usize, 8>::from_slice(chunk));
+            for (l, &id) in chunk.iter().enumerate() {
+                assert_eq!(kdt.get_point(id), lanes.map(|lane| lane[l]));
+            }
+        }
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn checked_and_unchecked() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let kdt = PkdTree::new(&points);
+
+        for _ in 0..200 {
+            let needle = [rng.gen(), rng.gen(), rng.gen()];
+            let id = kdt.query1_exact(needle);
+            assert_eq!(unsafe { kdt.query1_unchecked(needle) }, id);
+            assert_eq!(kdt.get_point_checked(id), Some(kdt.get_point(id)));
+            assert_eq!(unsafe { kdt.get_point_unchecked(id) }, kdt.get_point(id));
+        }
+
+        let empty = PkdTree::<3>::new(&[]);
+        assert_eq!(empty.get_point_checked(empty.query1_exact([0.0; 3])), None);
+    }
+
+    #[test]
+    fn budget_degrades_gracefully() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let kdt = PkdTree::new(&points);
+
+        for _ in 0..200 {
+            let needle = [rng.gen(), rng.gen(), rng.gen()];
+            let exact = kdt.query1_exact(needle);
+            assert_eq!(kdt.query1_within_budget(needle, usize::MAX), (exact, true));
+            assert_eq!(
+                kdt.get_point_checked(kdt.query1_within_budget(needle, 0).0),
+                None
+            );
+
+            let (first, _) = kdt.query1_within_budget(needle, 1);
+            assert_eq!(first, kdt.approx_nearest_with_id(needle).0);
+
+            // a larger budget never finds a worse point
+            let mut prev_distsq = f32::INFINITY;
+            for budget in 1..=kdt.len() {
+                let (id, complete) = kdt.query1_within_budget(needle, budget);
+                let d = distsq(kdt.get_point(id), needle);
+                assert!(d <= prev_distsq);
+                prev_distsq = d;
+                if complete {
+                    assert_eq!(id, exact);
+                    break;
+                }
+            }
+        }
+
+        let empty = PkdTree::<3>::new(&[]);
+        assert!(empty.query1_within_budget([0.0; 3], 0).1);
+    }
+
+    #[test]
+    fn convex_matches_brute_force() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let kdt = PkdTree::new(&points);
+
+        for _ in 0..500 {
+            // a random polytope around a random center, which may or may not hold any points
+            let center: [f32; 3] = [rng.gen(), rng.gen(), rng.gen()];
+            let halfspaces: Vec<([f32; 3], f32)> = (0..rng.gen_range(1..6))
+                .map(|_| {
+                    let normal = [
+                        rng.gen_range(-1.0..1.0),
+                        rng.gen_range(-1.0..1.0),
+                        rng.gen_range(-1.0..1.0),
+                    ];
+                    (normal, dot(&normal, &center) + rng.gen_range(-0.01..0.05))
+                })
+                .collect();
+            let expected = points
+                .iter()
+                .any(|p| halfspaces.iter().all(|(n, o)| dot(n, p) <= *o));
+            assert_eq!(kdt.collides_convex(&halfspaces), expected);
+        }
+
+        assert!(kdt.collides_convex(&[]));
+        assert!(!PkdTree::<3>::new(&[]).collides_convex(&[]));
+    }
+
+    #[test]
+    fn obb_matches_brute_force() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 2]> = (0..1000).map(|_| [rng.gen(), rng.gen()]).collect();
+        let kdt = PkdTree::new(&points);
+
+        for _ in 0..500 {
+            let center: [f32; 2] = [rng.gen(), rng.gen()];
+            let half_extents = [rng.gen_range(0.0..0.1), rng.gen_range(0.0..0.02)];
+            let (sin, cos) = rng.gen_range(0.0..std::f32::consts::TAU).sin_cos();
+            let rotation = [[cos, -sin], [sin, cos]];
+
+            let expected = points.iter().any(|p| {
+                let d = [p[0] - center[0], p[1] - center[1]];
+                // the coordinates of `p` in the frame of the box
+                let local = [
+                    dot(&[rotation[0][0], rotation[1][0]], &d),
+                    dot(&[rotation[0][1], rotation[1][1]], &d),
+                ];
+                local[0].abs() <= half_extents[0] && local[1].abs() <= half_extents[1]
+            });
+            assert_eq!(kdt.collides_obb(center, half_extents, rotation), expected);
+        }
+    }
+
+    #[test]
+    fn shell_matches_brute_force() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let kdt = PkdTree::new(&points);
+
+        for _ in 0..500 {
+            let center = [rng.gen(), rng.gen(), rng.gen()];
+            let r_inner = rng.gen_range(0.0..0.3);
+            let r_outer = r_inner + rng.gen_range(0.0..0.02);
+            let expected = points.iter().any(|&p| {
+                let d = distsq(center, p);
+                r_inner * r_inner <= d && d <= r_outer * r_outer
+            });
+            assert_eq!(kdt.collides_shell(center, r_inner, r_outer), expected);
+        }
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn farthest_matches_brute_force() {
+        let mut rng = thread_rng();
+        for n in [1, 5, 100, 1000] {
+            let points: Vec<[f32; 3]> = (0..n).map(|_| [rng.gen(), rng.gen(), rng.gen()]).collect();
+            let kdt = PkdTree::new(&points);
+
+            for _ in 0..200 {
+                let center = [
+                    rng.gen_range(-1.0..2.0),
+                    rng.gen_range(-1.0..2.0),
+                    rng.gen_range(-1.0..2.0),
+                ];
+                let expected = points
+                    .iter()
+                    .map(|&p| distsq(center, p))
+                    .max_by(f32::total_cmp)
+                    .unwrap();
+                assert_eq!(
+                    distsq(center, kdt.get_point(kdt.farthest_point(center))),
+                    expected
+                );
+            }
+        }
+
+        let empty = PkdTree::<3>::new(&[]);
+        assert_eq!(
+            empty.get_point_checked(empty.farthest_point([0.0; 3])),
+            None
+        );
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn extents_bound_points() {
+        let mut rng = thread_rng();
+        let points: Vec<[f64; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen_range(-5.0..5.0), rng.gen()])
+            .collect();
+        let kdt = PkdTree::new(&points);
+
+        let bounds = kdt.bounding_box().unwrap();
+        assert!(points.iter().all(|p| bounds.closest_distsq_to(p) == 0.0));
+        for k in 0..3 {
+            assert!(points.iter().any(|p| p[k] == bounds.lo[k]));
+            assert!(points.iter().any(|p| p[k] == bounds.hi[k]));
+        }
+
+        let centroid = kdt.centroid().unwrap();
+        for k in 0..3 {
+            let mean = points.iter().map(|p| p[k]).sum::<f64>() / 1000.0;
+            assert!((centroid[k] - mean).abs() < 1e-9);
+        }
+
+        let diameter = kdt.diameter_upper_bound();
+        for _ in 0..100 {
+            let (a, b) = (
+                points[rng.gen_range(0..1000)],
+                points[rng.gen_range(0..1000)],
+            );
+            assert!(distsq(a, b).sqrt() <= diameter);
+        }
+
+        let empty = PkdTree::<3>::new(&[]);
+        assert_eq!(empty.bounding_box(), None);
+        assert_eq!(empty.centroid(), None);
+        assert!(empty.diameter_upper_bound().abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn ghosts_are_invisible() {
+        let mut rng = thread_rng();
+        for n in [3, 5, 17, 1025] {
+            let points: Vec<[f32; 2]> = (0..n).map(|_| [rng.gen(), rng.gen()]).collect();
+            let kdt = PkdTree::new(&points);
+            assert_eq!(kdt.len(), n);
+            assert_eq!(kdt.points().len(), n);
+            assert_eq!(kdt.get_point_checked(n), None);
+            assert_eq!(kdt.count_within([0.5, 0.5], 10.0), n);
+            assert_eq!(kdt.query_k_exact([0.5, 0.5], n + 3).len(), n);
+
+            let mut n_pairs = 0;
+            kdt.for_each_pair_within(10.0, |_, j| {
+                assert!(j < n);
+                n_pairs += 1;
+            });
+            assert_eq!(n_pairs, n * (n - 1) / 2);
+
+            for _ in 0..100 {
+                let needle = [rng.gen_range(-2.0..3.0), rng.gen_range(-2.0..3.0)];
+                assert!(kdt.query1_exact(needle) < n);
+                assert!(kdt.approx_nearest_with_id(needle).0 < n);
+                assert!(kdt.anytime_nearest(needle).all(|(id, _)| id < n));
+            }
+        }
+    }
+
+    #[test]
+    fn not_a_power_of_two() {
+        let points = vec![[0.0], [2.0], [4.0]];
+        let kdt = PkdTree::new(&points);
+
+        assert_eq!(forward_pass(&kdt.tests, &[-0.1]), 0);
+        assert_eq!(forward_pass(&kdt.tests, &[0.5]), 0);
+        assert_eq!(forward_pass(&kdt.tests, &[1.5]), 1);
+        assert_eq!(forward_pass(&kdt.tests, &[2.5]), 1);
+        assert_eq!(forward_pass(&kdt.tests, &[3.5]), 2);
+        assert_eq!(forward_pass(&kdt.tests, &[4.5]), 2);
+    }
+
+    #[test]
+    fn a_power_of_two() {
+        let points = vec![[0.0], [2.0], [4.0], [6.0]];
+        let kdt = PkdTree::new(&points);
+
+        assert_eq!(forward_pass(&kdt.tests, &[-0.1]), 0);
+        assert_eq!(forward_pass(&kdt.tests, &[0.5]), 0);
+        assert_eq!(forward_pass(&kdt.tests, &[1.5]), 1);
+        assert_eq!(forward_pass(&kdt.tests, &[2.5]), 1);
+        assert_eq!(forward_pass(&kdt.tests, &[3.5]), 2);
+        assert_eq!(forward_pass(&kdt.tests, &[4.5]), 2);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn anytime_converges() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let kdt = PkdTree::new(&points);
+
+        for _ in 0..200 {
+            let needle = [rng.gen(), rng.gen(), rng.gen()];
+            let candidates: Vec<(usize, f32)> = kdt.anytime_nearest(needle).collect();
+            assert_eq!(kdt.get_point(candidates[0].0), kdt.approx_nearest(needle));
+            assert!(candidates.windows(2).all(|w| w[1].1 < w[0].1));
+            assert_eq!(
+                candidates.last().unwrap().1,
+                distsq(kdt.get_point(kdt.query1_exact(needle)), needle)
+            );
+        }
+
+        assert_eq!(
+            PkdTree::<2>::new(&[]).anytime_nearest([0.0; 2]).next(),
+            None
+        );
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn exact_matches_brute_force() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let kdt = PkdTree::new(&points);
+
+        for _ in 0..1000 {
+            let needle = [rng.gen(), rng.gen(), rng.gen()];
+            let best = points
+                .iter()
+                .map(|&p| distsq(p, needle))
+                .min_by(f32::total_cmp)
+                .unwrap();
+            assert_eq!(
+                distsq(kdt.get_point(kdt.query1_exact(needle)), needle),
+                best
+            );
+        }
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn k_nearest_matches_brute_force() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let kdt = PkdTree::new(&points);
+
+        for k in [0, 1, 5, 20] {
+            let needle = [rng.gen(), rng.gen(), rng.gen()];
+            let mut dists: Vec<f32> = points.iter().map(|&p| distsq(p, needle)).collect();
+            dists.sort_by(f32::total_cmp);
+
+            let found: Vec<f32> = kdt
+                .query_k_exact(needle, k)
+                .into_iter()
+                .map(|id| distsq(kdt.get_point(id), needle))
+                .collect();
+            assert_eq!(found, dists[..k]);
+        }
+
+        assert_eq!(kdt.query_k_exact([0.0; 3], 2000).len(), 1000);
+    }
+
+    #[test]
+    fn correspondences_match_brute_force() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let kdt = PkdTree::new(&points);
+        let source: Vec<[f32; 3]> = (0..203)
+            .map(|_| [rng.gen_range(-0.5..1.5), rng.gen(), rng.gen()])
+            .collect();
+        let max_dist = 0.1;
+
+        for (needle, found) in source.iter().zip(kdt.correspondences(&source, max_dist)) {
+            let best = points
+                .iter()
+                .map(|&p| distsq(p, *needle))
+                .min_by(f32::total_cmp)
+                .unwrap();
+            match found {
+                Some((id, d)) => {
+                    assert!((d - best.sqrt()).abs() < 1e-6);
+                    assert!((distsq(kdt.get_point(id), *needle) - best).abs() < 1e-6);
+                }
+                None => assert!(best >= max_dist * max_dist),
+            }
+        }
+    }
+
+    #[test]
+    fn count_matches_brute_force() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let kdt = PkdTree::new(&points);
+
+        for _ in 0..100 {
+            let needle = [rng.gen(), rng.gen(), rng.gen()];
+            let r = rng.gen_range(0.0..0.3);
+            assert_eq!(
+                kdt.count_within(needle, r),
+                points
+                    .iter()
+                    .filter(|&&p| distsq(p, needle) <= r * r)
+                    .count()
+            );
+        }
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn deep_tree_small_stack() {
+        const N: usize = 1 << 16;
+
+        // neither construction nor exact search may recurse, so a tiny stack suffices
+        std::thread::Builder::new()
+            .stack_size(32 * 1024)
+            .spawn(|| {
+                #[allow(clippy::cast_precision_loss)]
+                let points: Vec<[f32; 1]> = (0..N).rev().map(|i| [i as f32]).collect();
+                let kdt = PkdTree::new(&points);
+                assert_eq!(kdt.get_point(kdt.query1_exact([1234.4])), [1234.0]);
+                assert_eq!(kdt.get_point(kdt.query1_exact([-5.0])), [0.0]);
+
+                let capt = crate::Capt::<1>::new(&points, (0.0, 2.0));
+                assert!(capt.collides(&[1234.4], 0.5));
+                assert!(!capt.collides(&[-5.0], 2.0));
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn from_flat_matches_new() {
+        let flat = [0.0, 0.5, 9.0, 2.0, 1.5, 9.0, 4.0, 2.5, 9.0];
+        assert_eq!(
+            PkdTree::<2>::from_flat(&flat, 3, 0),
+            PkdTree::new(&[[0.0, 0.5], [2.0, 1.5], [4.0, 2.5]])
+        );
+    }
+
+    #[test]
+    fn pairs_match_brute_force() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..300)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let kdt = PkdTree::new(&points);
+        let pts = kdt.points();
+
+        for r in [0.0, 0.05, 0.2, 2.0] {
+            let mut pairs = Vec::new();
+            kdt.for_each_pair_within(r, |i, j| pairs.push((i, j)));
+            pairs.sort_unstable();
+
+            let mut expected = Vec::new();
+            for i in 0..pts.len() {
+                for j in i + 1..pts.len() {
+                    if distsq(pts[i], pts[j]) <= r * r {
+                        expected.push((i, j));
+                    }
+                }
+            }
+            assert_eq!(pairs, expected);
+        }
+    }
+
+    #[test]
+    fn points_round_trip() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..100)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let kdt = PkdTree::new(&points);
+
+        let mut extracted = kdt.clone().into_points();
+        assert_eq!(extracted, kdt.points());
+        assert_eq!(PkdTree::new(&extracted), kdt);
+
+        let mut expected = points;
+        extracted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(extracted, expected);
+    }
+}
@@ -0,0 +1,457 @@
+//! A pool of worker threads which answer batches of collision queries.
+
+use std::{
+    fmt,
+    future::Future,
+    ops::Range,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{mpsc, Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll, Waker},
+    thread::{self, JoinHandle},
+};
+
+#[cfg(feature = "simd")]
+use std::simd::SimdElement;
+
+use crate::Collider;
+
+#[cfg(feature = "simd")]
+use crate::BatchCollider;
+
+/// The results of a batch of queries: whether each query collides, in order.
+type Results = Result<Vec<bool>, QueryPanicked>;
+
+/// A function called with the results of a batch once every query in it has been answered.
+type Callback = Box<dyn FnOnce(Results) + Send>;
+
+/// A function which answers a chunk of queries against a collider, writing whether each one
+/// collides to the corresponding slot.
+type Check<C, A, const K: usize> = fn(&C, &[([A; K], A)], &mut [bool]);
+
+/// The progress of the workers answering a batch.
+struct Progress {
+    /// The results answered so far.
+    results: Vec<bool>,
+    /// The number of chunks still unanswered.
+    remaining: usize,
+    /// Whether a query in any answered chunk panicked.
+    failed: bool,
+    /// The function to call once every chunk is answered.
+    callback: Option<Callback>,
+}
+
+/// A batch of queries submitted to a [`QueryPool`], shared by the workers answering it.
+struct Batch<A, const K: usize> {
+    /// The `(center, radius)` pairs to check.
+    queries: Vec<([A; K], A)>,
+    /// The progress of the workers answering this batch.
+    progress: Mutex<Progress>,
+}
+
+/// A chunk of a batch for a single worker to answer.
+type Job<A, const K: usize> = (Arc<Batch<A, K>>, Range<usize>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// The error returned for a batch submitted to a [`QueryPool`] in which some query panicked.
+///
+/// The panic is caught by the worker answering the query, so the pool keeps answering other
+/// batches.
+pub struct QueryPanicked;
+
+impl fmt::Display for QueryPanicked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a query in the batch panicked")
+    }
+}
+
+impl std::error::Error for QueryPanicked {}
+
+/// A pool of worker threads which check batches of sphere queries against a shared collider.
+///
+/// Each batch is split into one chunk per worker, so a single large batch keeps every worker
+/// busy, and many small batches are answered concurrently.
+/// Results come back either through a [`QueryTicket`], which can be waited on or awaited as a
+/// future, or through a callback run on the worker which finishes the batch.
+/// This is for applications which do not already have a thread pool such as rayon's; the pool
+/// needs no dependencies beyond the standard library.
+///
+/// Dropping the pool waits for every submitted batch to be answered, then joins the workers.
+/// If a query panics, the batch containing it is answered with [`QueryPanicked`] instead of its
+/// results, and the worker goes on to the next chunk.
+///
+/// # Examples
+///
+/// ```
+/// use captree::{Capt, QueryPool};
+///
+/// let capt = Capt::<3>::new(&[[0.0; 3], [1.0; 3]], (0.0, 0.5));
+/// let pool = QueryPool::new(capt, 2);
+///
+/// let ticket = pool.submit(vec![([0.1; 3], 0.2), ([0.5; 3], 0.2)]);
+/// assert_eq!(ticket.wait(), Ok(vec![true, false]));
+/// ```
+pub struct QueryPool<A, const K: usize> {
+    /// The sending end of the queue of jobs, which is dropped to tell the workers to stop.
+    sender: Option<mpsc::Sender<Job<A, K>>>,
+    /// The worker threads, which are joined when the pool is dropped.
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<A, const K: usize> QueryPool<A, K>
+where
+    A: Copy + Send + Sync + 'static,
+{
+    #[must_use]
+    /// Construct a new pool of `n_threads` workers, each checking queries against `collider` one
+    /// at a time.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `n_threads` is 0 or if a worker thread cannot be spawned.
+    pub fn new<C>(collider: C, n_threads: usize) -> Self
+    where
+        C: Collider<K, A> + Send + Sync + 'static,
+    {
+        Self::spawn(collider, n_threads, |collider, queries, results| {
+            for ((center, radius), result) in queries.iter().zip(results) {
+                *result = collider.collides(center, *radius);
+            }
+        })
+    }
+
+    #[must_use]
+    #[cfg(feature = "simd")]
+    /// Construct a new pool of `n_threads` workers, each checking queries against `collider` in
+    /// groups of `L` with [`BatchCollider::collides_each`].
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `n_threads` is 0 or if a worker thread cannot be spawned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(portable_simd)]
+    /// use captree::{Capt, QueryPool};
+    ///
+    /// let capt = Capt::<3, 8>::new(&[[0.0; 3], [1.0; 3]], (0.0, 0.5));
+    /// let pool = QueryPool::new_batched(capt, 2);
+    ///
+    /// let ticket = pool.submit(vec![([0.1; 3], 0.2), ([0.5; 3], 0.2)]);
+    /// assert_eq!(ticket.wait(), Ok(vec![true, false]));
+    /// ```
+    pub fn new_batched<C, const L: usize>(collider: C, n_threads: usize) -> Self
+    where
+        C: BatchCollider<K, L, A> + Send + Sync + 'static,
+        A: SimdElement,
+    {
+        Self::spawn(collider, n_threads, C::collides_each)
+    }
+
+    /// Spawn `n_threads` workers, each answering a chunk of queries by calling `check` with
+    /// `collider`, the queries, and the slots for their results.
+    fn spawn<C>(collider: C, n_threads: usize, check: Check<C, A, K>) -> Self
+    where
+        C: Send + Sync + 'static,
+    {
+        assert!(n_threads > 0, "a query pool needs at least one worker");
+        let collider = Arc::new(collider);
+        let (sender, receiver) = mpsc::channel::<Job<A, K>>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let workers = (0..n_threads)
+            .map(|i| {
+                let collider = Arc::clone(&collider);
+                let receiver = Arc::clone(&receiver);
+                thread::Builder::new()
+                    .name(format!("captree-query-{i}"))
+                    .spawn(move || loop {
+                        // release the queue as soon as a job is received, so that other workers
+                        // can take jobs while this one answers
+                        let job = lock(&receiver).recv();
+                        let Ok((batch, range)) = job else {
+                            break;
+                        };
+                        // a panicking callback has nothing left to report to, but must not
+                        // stop the worker
+                        let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+                            answer(collider.as_ref(), check, &batch, range);
+                        }));
+                    })
+                    .expect("failed to spawn query worker")
+            })
+            .collect();
+
+        Self {
+            sender: Some(sender),
+            workers,
+        }
+    }
+
+    #[must_use]
+    /// Get the number of worker threads in this pool.
+    pub const fn n_threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    #[must_use]
+    /// Submit a batch of `(center, radius)` queries, returning a ticket which yields whether each
+    /// query collides, in order.
+    pub fn submit(&self, queries: Vec<([A; K], A)>) -> QueryTicket {
+        let shared = Arc::new(TicketState::default());
+        let filled = Arc::clone(&shared);
+        self.submit_with(queries, move |results| {
+            let waker = {
+                let mut slot = lock(&filled.slot);
+                slot.0 = Some(results);
+                slot.1.take()
+            };
+            filled.ready.notify_all();
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        });
+        QueryTicket { shared }
+    }
+
+    /// Submit a batch of `(center, radius)` queries, calling `callback` with whether each query
+    /// collides, in order, once they have all been answered.
+    ///
+    /// `callback` runs on whichever worker answers the last chunk of the batch, or immediately if
+    /// `queries` is empty, so it should be quick.
+    /// It is passed [`QueryPanicked`] instead if any query in the batch panicked.
+    pub fn submit_with(
+        &self,
+        queries: Vec<([A; K], A)>,
+        callback: impl FnOnce(Results) + Send + 'static,
+    ) {
+        let n = queries.len();
+        if n == 0 {
+            callback(Ok(Vec::new()));
+            return;
+        }
+
+        let chunk = n.div_ceil(self.workers.len());
+        let n_chunks = n.div_ceil(chunk);
+        let batch = Arc::new(Batch {
+            queries,
+            progress: Mutex::new(Progress {
+                results: vec![false; n],
+                remaining: n_chunks,
+                failed: false,
+                callback: Some(Box::new(callback)),
+            }),
+        });
+        let sender = self
+            .sender
+            .as_ref()
+            .unwrap_or_else(|| unreachable!("the queue is only closed once the pool is dropped"));
+        for start in (0..n).step_by(chunk) {
+            sender
+                .send((Arc::clone(&batch), start..n.min(start + chunk)))
+                .unwrap_or_else(|_| unreachable!("workers only stop once the pool is dropped"));
+        }
+    }
+}
+
+impl<A, const K: usize> Drop for QueryPool<A, K> {
+    fn drop(&mut self) {
+        // workers finish the jobs already queued, then stop once the queue is closed and empty
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Answer the queries in `range` of `batch` with `check`, completing the batch if they are the
+/// last ones.
+fn answer<A, C, const K: usize>(
+    collider: &C,
+    check: Check<C, A, K>,
+    batch: &Batch<A, K>,
+    range: Range<usize>,
+) {
+    let mut results = vec![false; range.len()];
+    let answered = panic::catch_unwind(AssertUnwindSafe(|| {
+        check(collider, &batch.queries[range.clone()], &mut results);
+    }))
+    .is_ok();
+
+    let finished = {
+        let mut progress = lock(&batch.progress);
+        progress.results[range].copy_from_slice(&results);
+        progress.failed |= !answered;
+        progress.remaining -= 1;
+        (progress.remaining == 0).then(|| {
+            let results = std::mem::take(&mut progress.results);
+            (
+                if progress.failed {
+                    Err(QueryPanicked)
+                } else {
+                    Ok(results)
+                },
+                progress.callback.take(),
+            )
+        })
+    };
+    if let Some((results, Some(callback))) = finished {
+        callback(results);
+    }
+}
+
+/// Lock `mutex`, ignoring poisoning.
+///
+/// Every critical section in this module leaves its state consistent before doing anything which
+/// could panic, so a poisoned lock is still safe to use.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[derive(Default)]
+/// The state shared between a [`QueryTicket`] and the workers answering its batch.
+struct TicketState {
+    /// The results, once answered, and the waker of the task awaiting them, if any.
+    slot: Mutex<(Option<Results>, Option<Waker>)>,
+    /// Notified once the results are answered.
+    ready: Condvar,
+}
+
+/// A handle to the results of a batch submitted with [`QueryPool::submit`].
+///
+/// The results can be retrieved by blocking with [`QueryTicket::wait`], or by awaiting the
+/// ticket as a future on any executor.
+pub struct QueryTicket {
+    shared: Arc<TicketState>,
+}
+
+impl QueryTicket {
+    /// Block until every query in the batch has been answered, then return whether each one
+    /// collides, in order.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`QueryPanicked`] if any query in the batch panicked.
+    pub fn wait(self) -> Result<Vec<bool>, QueryPanicked> {
+        let mut slot = lock(&self.shared.slot);
+        loop {
+            if let Some(results) = slot.0.take() {
+                return results;
+            }
+            slot = self
+                .shared
+                .ready
+                .wait(slot)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    #[must_use]
+    /// Determine whether every query in the batch has been answered, so that
+    /// [`QueryTicket::wait`] would not block.
+    pub fn is_ready(&self) -> bool {
+        lock(&self.shared.slot).0.is_some()
+    }
+}
+
+impl Future for QueryTicket {
+    type Output = Result<Vec<bool>, QueryPanicked>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = lock(&self.shared.slot);
+        if let Some(results) = slot.0.take() {
+            return Poll::Ready(results);
+        }
+        slot.1 = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, task::Wake};
+
+    use rand::thread_rng;
+
+    use super::*;
+    use crate::{testing, Aabb, Capt};
+
+    #[test]
+    fn matches_sequential() {
+        let mut rng = thread_rng();
+        let bounds = Aabb {
+            lo: [0.0; 3],
+            hi: [1.0; 3],
+        };
+        let r_range = (0.01, 0.08);
+        let points = testing::uniform_points(&mut rng, 1000, &bounds);
+        let capt = Capt::<3>::new(&points, r_range);
+        #[cfg(feature = "simd")]
+        let batched = QueryPool::new_batched(capt.clone(), 3);
+        let pool = QueryPool::new(capt, 3);
+        assert_eq!(pool.n_threads(), 3);
+
+        for n in [0, 1, 2, 10, 1000] {
+            let queries = testing::uniform_queries(&mut rng, n, &bounds, r_range);
+            let expected: Vec<bool> = queries
+                .iter()
+                .map(|(c, r)| testing::brute_force_collides(&points, c, *r))
+                .collect();
+
+            assert_eq!(pool.submit(queries.clone()).wait(), Ok(expected.clone()));
+            #[cfg(feature = "simd")]
+            assert_eq!(batched.submit(queries.clone()).wait(), Ok(expected.clone()));
+
+            let (tx, rx) = mpsc::channel();
+            pool.submit_with(queries, move |results| tx.send(results).unwrap());
+            assert_eq!(rx.recv().unwrap(), Ok(expected));
+        }
+    }
+
+    #[test]
+    fn panicking_query_fails_batch() {
+        /// A collider which panics on queries with a negative radius.
+        struct Fussy;
+        impl Collider<1, f32> for Fussy {
+            fn collides(&self, _: &[f32; 1], radius: f32) -> bool {
+                assert!(radius >= 0.0, "negative radius");
+                true
+            }
+        }
+
+        let pool = QueryPool::new(Fussy, 2);
+        let ticket = pool.submit(vec![([0.0], 1.0), ([0.0], -1.0), ([0.0], 1.0)]);
+        assert_eq!(ticket.wait(), Err(QueryPanicked));
+
+        // every worker survives to answer later batches
+        for _ in 0..4 {
+            assert_eq!(pool.submit(vec![([0.0], 1.0); 4]).wait(), Ok(vec![true; 4]));
+        }
+    }
+
+    #[test]
+    fn ticket_is_a_future() {
+        /// A waker which unparks the thread polling the future.
+        struct Unpark(thread::Thread);
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let capt = Capt::<2>::new(&[[0.0, 0.0]], (0.0, 0.5));
+        let pool = QueryPool::new(capt, 1);
+        let mut ticket = pool.submit(vec![([0.1, 0.0], 0.2), ([1.0, 0.0], 0.2)]);
+
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let results = loop {
+            match Pin::new(&mut ticket).poll(&mut cx) {
+                Poll::Ready(results) => break results,
+                Poll::Pending => thread::park(),
+            }
+        };
+        assert_eq!(results, Ok(vec![true, false]));
+    }
+}
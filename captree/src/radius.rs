@@ -0,0 +1,100 @@
+//! Helpers for choosing the range of query radii which a tree supports.
+
+use crate::Axis;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+/// A distance, such as the radius of a query ball.
+///
+/// Some queries are phrased in terms of radii and others in terms of squared radii.
+/// Wrapping the value in `Radius` or [`RadiusSq`] lets the compiler check which one is meant.
+///
+/// # Examples
+///
+/// ```
+/// use captree::{Radius, RadiusSq};
+///
+/// assert_eq!(Radius(3.0).squared(), RadiusSq(9.0));
+/// ```
+pub struct Radius<A>(pub A);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+/// The square of a distance, such as the squared radius of a query ball.
+///
+/// Since [`Axis`] has no square root, a `RadiusSq` cannot be converted back into a [`Radius`].
+pub struct RadiusSq<A>(pub A);
+
+impl<A: Axis> Radius<A> {
+    #[must_use]
+    /// Square this radius.
+    pub fn squared(self) -> RadiusSq<A> {
+        RadiusSq(self.0.square())
+    }
+}
+
+impl<A: Axis> From<Radius<A>> for RadiusSq<A> {
+    fn from(r: Radius<A>) -> Self {
+        r.squared()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// A range of query radii, from `min` to `max`.
+///
+/// This converts into the `(minimum, maximum)` pair taken as `r_range` by the constructors of
+/// [`Capt`](crate::Capt).
+/// Both bounds are radii, not squared radii.
+pub struct RadiusRange<A> {
+    /// The smallest radius which will be queried.
+    pub min: A,
+    /// The largest radius which will be queried.
+    pub max: A,
+}
+
+impl<A: Axis> RadiusRange<A> {
+    #[must_use]
+    /// Find the range of radii covering every query in a representative trace of `(center, radius)`
+    /// pairs, widened by `margin` on both ends.
+    ///
+    /// The lower bound is never less than zero.
+    /// If `trace` is empty, this returns the range from zero to infinity, which is always safe.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use captree::RadiusRange;
+    ///
+    /// let trace = [([0.0, 0.0], 0.25), ([1.0, 0.0], 0.5), ([0.5, 0.5], 0.125)];
+    ///
+    /// assert_eq!(
+    ///     RadiusRange::fit(&trace, 0.25),
+    ///     RadiusRange {
+    ///         min: 0.0,
+    ///         max: 0.75
+    ///     }
+    /// );
+    /// ```
+    pub fn fit<const K: usize>(trace: &[([A; K], A)], margin: A) -> Self {
+        let Some(&(_, first)) = trace.first() else {
+            return Self {
+                min: A::ZERO,
+                max: A::INFINITY,
+            };
+        };
+
+        let (min, max) = trace.iter().fold((first, first), |(min, max), &(_, r)| {
+            (if r < min { r } else { min }, if max < r { r } else { max })
+        });
+
+        let min = min - margin;
+        Self {
+            min: if min < A::ZERO { A::ZERO } else { min },
+            max: max + margin,
+        }
+    }
+}
+
+impl<A> From<RadiusRange<A>> for (A, A) {
+    fn from(range: RadiusRange<A>) -> Self {
+        (range.min, range.max)
+    }
+}
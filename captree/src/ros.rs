@@ -0,0 +1,399 @@
+//! Ingestion of point clouds laid out as ROS `sensor_msgs/PointCloud2` messages.
+//!
+//! This module mirrors the fields of a `PointCloud2` message without depending on any ROS client
+//! library, so that a node can borrow the fields of a received message and build a tree directly
+//! from its byte buffer.
+//!
+//! # Examples
+//!
+//! ```
+//! use captree::{
+//!     ros::{datatype, PointCloud2, PointField},
+//!     Capt,
+//! };
+//!
+//! let fields = [
+//!     PointField {
+//!         name: "x",
+//!         offset: 0,
+//!         datatype: datatype::FLOAT32,
+//!         count: 1,
+//!     },
+//!     PointField {
+//!         name: "y",
+//!         offset: 4,
+//!         datatype: datatype::FLOAT32,
+//!         count: 1,
+//!     },
+//!     PointField {
+//!         name: "z",
+//!         offset: 8,
+//!         datatype: datatype::FLOAT32,
+//!         count: 1,
+//!     },
+//! ];
+//! let data: Vec<u8> = [[0.0f32, 0.0, 1.0], [f32::NAN; 3]]
+//!     .iter()
+//!     .flatten()
+//!     .flat_map(|x| x.to_le_bytes())
+//!     .collect();
+//! let cloud = PointCloud2 {
+//!     height: 1,
+//!     width: 2,
+//!     fields: &fields,
+//!     is_bigendian: false,
+//!     point_step: 12,
+//!     row_step: 24,
+//!     data: &data,
+//! };
+//!
+//! // the NaN point, as produced by a sensor for a missing return, is dropped
+//! assert_eq!(cloud.xyz().unwrap(), [[0.0, 0.0, 1.0]]);
+//!
+//! let t = Capt::<3>::try_from_point_cloud2(&cloud, (0.0, 0.5)).unwrap();
+//! assert!(t.collides(&[0.0, 0.0, 1.1], 0.2));
+//! ```
+
+use std::fmt;
+
+use elain::{Align, Alignment};
+
+use crate::{io::Scalar, Capt, Index, NewCaptError};
+
+/// The codes for the type of each field, as in `sensor_msgs/PointField`.
+pub mod datatype {
+    /// A signed 8-bit integer.
+    pub const INT8: u8 = 1;
+    /// An unsigned 8-bit integer.
+    pub const UINT8: u8 = 2;
+    /// A signed 16-bit integer.
+    pub const INT16: u8 = 3;
+    /// An unsigned 16-bit integer.
+    pub const UINT16: u8 = 4;
+    /// A signed 32-bit integer.
+    pub const INT32: u8 = 5;
+    /// An unsigned 32-bit integer.
+    pub const UINT32: u8 = 6;
+    /// A 32-bit float.
+    pub const FLOAT32: u8 = 7;
+    /// A 64-bit float.
+    pub const FLOAT64: u8 = 8;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The description of one field of each point, as in `sensor_msgs/PointField`.
+pub struct PointField<'a> {
+    /// The name of the field, such as `"x"` or `"intensity"`.
+    pub name: &'a str,
+    /// The offset of the field from the start of each point, in bytes.
+    pub offset: u32,
+    /// The type of the field, as one of the codes in [`datatype`].
+    pub datatype: u8,
+    /// The number of elements in the field.
+    pub count: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// A borrowed point cloud with the layout of a `sensor_msgs/PointCloud2` message.
+pub struct PointCloud2<'a> {
+    /// The number of rows of points, or 1 for an unorganized cloud.
+    pub height: u32,
+    /// The number of points in each row.
+    pub width: u32,
+    /// The fields of each point.
+    pub fields: &'a [PointField<'a>],
+    /// Whether multi-byte fields are stored in big-endian order.
+    pub is_bigendian: bool,
+    /// The length of each point, in bytes.
+    pub point_step: u32,
+    /// The length of each row, in bytes.
+    pub row_step: u32,
+    /// The points, stored row by row.
+    pub data: &'a [u8],
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+/// The errors which can occur when reading a [`PointCloud2`].
+pub enum PointCloud2Error {
+    /// The cloud has no field with this name.
+    MissingField(&'static str),
+    /// A field has a type code which is not in [`datatype`].
+    UnsupportedDatatype {
+        /// The name of the field.
+        field: &'static str,
+        /// The field's type code.
+        datatype: u8,
+    },
+    /// The data buffer is too short for the cloud's dimensions and steps, or the step between
+    /// points is too short to hold the coordinates.
+    Truncated,
+    /// Building a tree from the points failed.
+    Build(NewCaptError),
+}
+
+impl From<NewCaptError> for PointCloud2Error {
+    fn from(e: NewCaptError) -> Self {
+        Self::Build(e)
+    }
+}
+
+impl fmt::Display for PointCloud2Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingField(name) => write!(f, "point cloud has no field `{name}`"),
+            Self::UnsupportedDatatype { field, datatype } => {
+                write!(f, "field `{field}` has unsupported datatype {datatype}")
+            }
+            Self::Truncated => write!(f, "point cloud data is truncated"),
+            Self::Build(e) => write!(f, "failed to build tree: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for PointCloud2Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Build(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// The location and type of a coordinate within each point.
+#[derive(Clone, Copy)]
+struct Coordinate {
+    /// The offset of the coordinate from the start of each point, in bytes.
+    offset: usize,
+    /// The type of the coordinate.
+    scalar: Scalar,
+}
+
+impl Coordinate {
+    /// Find the field named `name` in `fields`.
+    fn find(fields: &[PointField], name: &'static str) -> Result<Self, PointCloud2Error> {
+        let field = fields
+            .iter()
+            .find(|f| f.name == name)
+            .ok_or(PointCloud2Error::MissingField(name))?;
+        let (kind, size) = match field.datatype {
+            datatype::INT8 => (b'I', 1),
+            datatype::UINT8 => (b'U', 1),
+            datatype::INT16 => (b'I', 2),
+            datatype::UINT16 => (b'U', 2),
+            datatype::INT32 => (b'I', 4),
+            datatype::UINT32 => (b'U', 4),
+            datatype::FLOAT32 => (b'F', 4),
+            datatype::FLOAT64 => (b'F', 8),
+            datatype => {
+                return Err(PointCloud2Error::UnsupportedDatatype {
+                    field: name,
+                    datatype,
+                })
+            }
+        };
+        Ok(Self {
+            offset: field.offset as usize,
+            scalar: Scalar { kind, size },
+        })
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    /// Read this coordinate from `point`, which must contain it.
+    fn read(self, point: &[u8], big_endian: bool) -> f32 {
+        self.scalar
+            .decode(&point[self.offset..], big_endian)
+            .unwrap_or_else(|| unreachable!("coordinate must be a known type within the point"))
+            as f32
+    }
+}
+
+impl PointCloud2<'_> {
+    /// Read the `x`, `y` and `z` fields of every point in this cloud, dropping any point with a
+    /// non-finite coordinate.
+    ///
+    /// Other fields are ignored, and coordinates of any numeric type are converted to `f32`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if this cloud lacks an `x`, `y` or `z` field, if one of
+    /// them has an unknown type, or if `data` is too short to hold every point.
+    pub fn xyz(&self) -> Result<Vec<[f32; 3]>, PointCloud2Error> {
+        let coords = [
+            Coordinate::find(self.fields, "x")?,
+            Coordinate::find(self.fields, "y")?,
+            Coordinate::find(self.fields, "z")?,
+        ];
+        let extent = coords
+            .iter()
+            .map(|c| c.offset + c.scalar.size)
+            .max()
+            .unwrap_or(0);
+
+        // check the dimensions against the buffer before trusting them to size the output
+        let (height, width) = (self.height as usize, self.width as usize);
+        let (point_step, row_step) = (self.point_step as usize, self.row_step as usize);
+        let n_points = height
+            .checked_mul(width)
+            .ok_or(PointCloud2Error::Truncated)?;
+        let last_end = match (height.checked_sub(1), width.checked_sub(1)) {
+            (Some(last_row), Some(last_col)) => last_row
+                .checked_mul(row_step)
+                .zip(last_col.checked_mul(point_step))
+                .and_then(|(r, c)| r.checked_add(c)?.checked_add(extent)),
+            _ => Some(0),
+        };
+        if n_points > 0 && point_step < extent
+            || n_points
+                .checked_mul(point_step)
+                .is_none_or(|n| n > self.data.len())
+            || last_end.is_none_or(|end| end > self.data.len())
+        {
+            return Err(PointCloud2Error::Truncated);
+        }
+
+        let mut points = Vec::with_capacity(n_points);
+        for row in 0..height {
+            for col in 0..width {
+                let start = row * row_step + col * point_step;
+                let point = &self.data[start..start + extent];
+                let p = coords.map(|c| c.read(point, self.is_bigendian));
+                if p.iter().all(|x| x.is_finite()) {
+                    points.push(p);
+                }
+            }
+        }
+        Ok(points)
+    }
+}
+
+impl<I, const L: usize> Capt<3, L, f32, I>
+where
+    I: Index,
+    Align<L>: Alignment,
+{
+    /// Construct a new CAPT containing every finite point of `cloud`.
+    ///
+    /// See [`PointCloud2::xyz`] for how points are read.
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// radius of the balls which will be queried against the tree.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error under the same conditions as [`PointCloud2::xyz`] or
+    /// [`Capt::try_new`].
+    pub fn try_from_point_cloud2(
+        cloud: &PointCloud2,
+        r_range: (f32, f32),
+    ) -> Result<Self, PointCloud2Error> {
+        Ok(Self::try_new(&cloud.xyz()?, r_range)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layouts() {
+        let points = [[1.0, -2.0, 3.5], [f64::NAN, 0.0, 0.0], [0.25, 0.5, -8.0]];
+        let fields = [
+            PointField {
+                name: "intensity",
+                offset: 0,
+                datatype: datatype::UINT8,
+                count: 1,
+            },
+            PointField {
+                name: "z",
+                offset: 4,
+                datatype: datatype::FLOAT64,
+                count: 1,
+            },
+            PointField {
+                name: "x",
+                offset: 12,
+                datatype: datatype::FLOAT64,
+                count: 1,
+            },
+            PointField {
+                name: "y",
+                offset: 20,
+                datatype: datatype::FLOAT64,
+                count: 1,
+            },
+        ];
+        for is_bigendian in [false, true] {
+            let mut data = Vec::new();
+            for [x, y, z] in points {
+                data.extend([0xff; 4]);
+                for v in [z, x, y] {
+                    data.extend(if is_bigendian {
+                        v.to_be_bytes()
+                    } else {
+                        v.to_le_bytes()
+                    });
+                }
+                data.extend([0; 4]);
+            }
+            let cloud = PointCloud2 {
+                height: 1,
+                width: 3,
+                fields: &fields,
+                is_bigendian,
+                point_step: 32,
+                row_step: 96,
+                data: &data,
+            };
+            assert_eq!(cloud.xyz(), Ok(vec![[1.0, -2.0, 3.5], [0.25, 0.5, -8.0]]));
+
+            let short = PointCloud2 {
+                data: &data[..90],
+                ..cloud
+            };
+            assert_eq!(short.xyz(), Err(PointCloud2Error::Truncated));
+        }
+
+        let missing = PointCloud2 {
+            height: 1,
+            width: 0,
+            fields: &fields[..3],
+            is_bigendian: false,
+            point_step: 32,
+            row_step: 0,
+            data: &[],
+        };
+        assert_eq!(missing.xyz(), Err(PointCloud2Error::MissingField("y")));
+    }
+
+    #[test]
+    fn rejects_huge_dimensions() {
+        let fields = ["x", "y", "z"].map(|name| PointField {
+            name,
+            offset: 0,
+            datatype: datatype::INT16,
+            count: 1,
+        });
+        let data = [0xfe, 0xff];
+        let cloud = PointCloud2 {
+            height: 1,
+            width: 1,
+            fields: &fields,
+            is_bigendian: false,
+            point_step: 2,
+            row_step: 2,
+            data: &data,
+        };
+        assert_eq!(cloud.xyz(), Ok(vec![[-2.0; 3]]));
+
+        for (height, width, point_step) in [(u32::MAX, u32::MAX, 2), (1, 2, 0), (1, u32::MAX, 0)] {
+            let huge = PointCloud2 {
+                height,
+                width,
+                point_step,
+                ..cloud
+            };
+            assert_eq!(huge.xyz(), Err(PointCloud2Error::Truncated));
+        }
+    }
+}
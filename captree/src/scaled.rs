@@ -0,0 +1,203 @@
+//! Collision checking under a metric which weights each axis differently.
+
+use elain::{Align, Alignment};
+
+use crate::{Capt, Collider, Index, NewCaptError};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A collision-affording point tree which measures distance after multiplying each axis by a fixed
+/// scale.
+///
+/// The distance between two points `a` and `b` is the Euclidean length of the vector whose `k`-th
+/// component is `scale[k] * (a[k] - b[k])`.
+/// This suits configuration spaces with mixed units, such as a planar position in meters and a
+/// heading in radians, where a unit of one axis is worth more or less than a unit of another.
+///
+/// Points are scaled once, at construction, and each query center is scaled as it is checked, so
+/// callers work entirely in the original units.
+///
+/// # Generic parameters
+///
+/// - `K`: The dimension of the space.
+/// - `L`: The lane size of the underlying tree.
+/// - `A`: The value of the axes of each point.
+/// - `I`: The index integer of the underlying tree.
+///
+/// # Examples
+///
+/// ```
+/// use captree::ScaledCapt;
+///
+/// // one radian of heading is worth half a meter
+/// let configurations = [[0.0, 0.0, 0.0], [2.0, 0.0, 1.5]];
+/// let t = ScaledCapt::<3>::new(&configurations, [1.0, 1.0, 0.5], (0.0, 0.5));
+///
+/// // the same position, but half a radian away, is only a quarter of a meter away
+/// assert!(t.collides(&[0.0, 0.0, 0.5], 0.3));
+/// assert!(!t.collides(&[0.0, 0.0, 1.0], 0.3));
+/// ```
+pub struct ScaledCapt<const K: usize, const L: usize = 8, A = f32, I = usize>
+where
+    Align<L>: Alignment,
+{
+    /// The tree over the scaled points.
+    tree: Capt<K, L, A, I>,
+    /// The factor by which each axis is multiplied.
+    scale: [A; K],
+}
+
+/// Implement `ScaledCapt` for axes of type `$a`.
+macro_rules! impl_scaled {
+    ($a: ty) => {
+        impl<I, const K: usize, const L: usize> ScaledCapt<K, L, $a, I>
+        where
+            I: Index,
+            Align<L>: Alignment,
+        {
+            #[must_use]
+            /// Construct a new tree containing all the points in `points`, measuring distance
+            /// with each axis `k` multiplied by `scale[k]`.
+            ///
+            /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on
+            /// the radius of the balls which will be queried against the tree, measured in the
+            /// scaled metric.
+            ///
+            /// # Panics
+            ///
+            /// This function will panic if any element of `scale` is not positive and finite, or
+            /// under the same conditions as [`Capt::new`].
+            pub fn new(points: &[[$a; K]], scale: [$a; K], r_range: ($a, $a)) -> Self {
+                Self::try_new(points, scale, r_range).expect(
+                    "index type I must be able to support all points in CAPT during construction",
+                )
+            }
+
+            /// Construct a new tree containing all the points in `points`, checking for index
+            /// overflow.
+            ///
+            /// See [`ScaledCapt::new`] for further details.
+            ///
+            /// # Errors
+            ///
+            /// This function will return an error under the same conditions as
+            /// [`Capt::try_new`].
+            ///
+            /// # Panics
+            ///
+            /// This function will panic if any element of `scale` is not positive and finite.
+            pub fn try_new(
+                points: &[[$a; K]],
+                scale: [$a; K],
+                r_range: ($a, $a),
+            ) -> Result<Self, NewCaptError> {
+                assert!(
+                    scale.iter().all(|&s| s > 0.0 && s.is_finite()),
+                    "axis scales must be positive and finite"
+                );
+                Ok(Self {
+                    tree: Capt::try_from_points(
+                        points.iter().map(|p| Self::apply(&scale, p)),
+                        r_range,
+                    )?,
+                    scale,
+                })
+            }
+
+            /// Multiply each axis of `point` by its scale.
+            fn apply(scale: &[$a; K], point: &[$a; K]) -> [$a; K] {
+                std::array::from_fn(|k| point[k] * scale[k])
+            }
+
+            #[must_use]
+            /// Determine whether a point in this tree is within a distance of `radius` to
+            /// `center`, measured in the scaled metric.
+            ///
+            /// As with [`Capt::collides`], the result is only guaranteed to be correct if `radius`
+            /// is inside the radius range used to construct this tree.
+            pub fn collides(&self, center: &[$a; K], radius: $a) -> bool {
+                self.tree
+                    .collides(&Self::apply(&self.scale, center), radius)
+            }
+
+            #[must_use]
+            /// Get the factor by which each axis is multiplied.
+            pub const fn scale(&self) -> [$a; K] {
+                self.scale
+            }
+
+            #[must_use]
+            /// Get the underlying tree, which contains the scaled points.
+            pub const fn tree(&self) -> &Capt<K, L, $a, I> {
+                &self.tree
+            }
+
+            #[must_use]
+            /// Get the number of points in this tree.
+            pub fn len(&self) -> usize {
+                self.tree.len()
+            }
+
+            #[must_use]
+            /// Determine whether this tree contains no points.
+            pub fn is_empty(&self) -> bool {
+                self.tree.is_empty()
+            }
+
+            #[must_use]
+            /// Get the dimension of the points in this tree, `K`.
+            pub const fn dimension(&self) -> usize {
+                K
+            }
+
+            #[must_use]
+            /// Get the `(minimum, maximum)` range of query radii which this tree was built to
+            /// support.
+            pub const fn r_range(&self) -> ($a, $a) {
+                self.tree.r_range()
+            }
+        }
+
+        impl<I, const K: usize, const L: usize> Collider<K, $a> for ScaledCapt<K, L, $a, I>
+        where
+            I: Index,
+            Align<L>: Alignment,
+        {
+            fn collides(&self, center: &[$a; K], radius: $a) -> bool {
+                self.collides(center, radius)
+            }
+        }
+    };
+}
+
+impl_scaled!(f32);
+impl_scaled!(f64);
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = thread_rng();
+        let scale = [1.0, 4.0, 0.25];
+        let points: Vec<[f64; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen_range(-3.0..3.0)])
+            .collect();
+        let (r_min, r_max) = (0.01, 0.2);
+        let t = ScaledCapt::<3, 8, f64>::new(&points, scale, (r_min, r_max));
+
+        for _ in 0..1000 {
+            let center = [rng.gen(), rng.gen(), rng.gen_range(-3.0..3.0)];
+            let radius = rng.gen_range(r_min..=r_max);
+            let expected = points.iter().any(|p| {
+                (0..3)
+                    .map(|k| ((p[k] - center[k]) * scale[k]).powi(2))
+                    .sum::<f64>()
+                    <= radius * radius
+            });
+            assert_eq!(t.collides(&center, radius), expected);
+        }
+    }
+}
@@ -0,0 +1,318 @@
+//! Rasterizing trees onto regular grids, such as distance fields and occupancy grids, for
+//! planners which consume them.
+
+use std::{num::NonZeroUsize, thread};
+
+use elain::{Align, Alignment};
+
+use crate::{Aabb, Alloc, Capt, Index};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A regular three-dimensional grid of samples of type `T`, positioned with coordinates of type
+/// `A`.
+///
+/// The sample at grid index `[i, j, k]` lies at `origin + resolution * [i, j, k]`.
+pub struct Grid3<T, A = T> {
+    /// The position of the sample at index `[0, 0, 0]`.
+    pub origin: [A; 3],
+    /// The spacing between adjacent samples along each axis.
+    pub resolution: A,
+    /// The number of samples along each axis.
+    pub dims: [usize; 3],
+    /// The samples, with the first axis varying fastest.
+    pub values: Vec<T>,
+}
+
+impl<T: Copy, A> Grid3<T, A> {
+    #[must_use]
+    /// Get the sample at grid index `[i, j, k]`, or `None` if it is out of bounds.
+    pub fn get(&self, [i, j, k]: [usize; 3]) -> Option<T> {
+        let [ni, nj, nk] = self.dims;
+        (i < ni && j < nj && k < nk).then(|| self.values[i + ni * (j + nj * k)])
+    }
+}
+
+#[must_use]
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+/// Sample the distance from each point of a regular grid to the nearest point in `tree`, clamped
+/// to the largest radius the tree was built for.
+///
+/// The grid starts at `bounds.lo` and has `resolution` spacing between samples, extending up to
+/// `bounds.hi`.
+/// Since a point cloud has no inside, the distances are never negative.
+/// Samples whose nearest point is within the smallest radius the tree was built for may
+/// overestimate the distance, but never beyond that smallest radius.
+///
+/// The grid is split into slabs along its last axis, which are sampled in parallel.
+/// Where threads are unavailable, such as on `wasm32-unknown-unknown`, the whole grid is sampled
+/// on the calling thread instead.
+///
+/// # Panics
+///
+/// This function will panic if `resolution` is not positive and finite, or if `bounds` is empty
+/// or not finite.
+///
+/// # Examples
+///
+/// ```
+/// use captree::{sdf, Aabb, Capt};
+///
+/// let capt = Capt::<3>::new(&[[0.0; 3]], (0.0, 1.0));
+/// let grid = sdf::bake(
+///     &capt,
+///     Aabb {
+///         lo: [0.0; 3],
+///         hi: [2.0; 3],
+///     },
+///     0.5,
+/// );
+///
+/// assert_eq!(grid.dims, [5, 5, 5]);
+/// assert_eq!(grid.get([1, 0, 0]), Some(0.5));
+/// // clamped to the maximum radius
+/// assert_eq!(grid.get([4, 4, 4]), Some(1.0));
+/// ```
+pub fn bake<I, Al, const L: usize>(
+    tree: &Capt<3, L, f32, I, Al>,
+    bounds: Aabb<f32, 3>,
+    resolution: f32,
+) -> Grid3<f32>
+where
+    I: Index + Sync,
+    Al: Alloc + Sync,
+    Align<L>: Alignment,
+{
+    assert!(
+        resolution > 0.0 && resolution.is_finite(),
+        "grid resolution must be positive and finite"
+    );
+    assert!(
+        (0..3).all(|k| bounds.lo[k] <= bounds.hi[k] && (bounds.hi[k] - bounds.lo[k]).is_finite()),
+        "grid bounds must be finite and non-empty"
+    );
+
+    let origin = bounds.lo;
+    let dims = std::array::from_fn(|k| ((bounds.hi[k] - origin[k]) / resolution) as usize + 1);
+    let [ni, nj, nk] = dims;
+    let r_max = tree.r_range.1;
+
+    let mut values = vec![0.0; ni * nj * nk];
+    // fill the samples of `chunk`, whose first sample has index `start`
+    let fill = |start: usize, chunk: &mut [f32]| {
+        for (n, value) in chunk.iter_mut().enumerate() {
+            let idx = start + n;
+            let ijk = [idx % ni, idx / ni % nj, idx / (ni * nj)];
+            let center = std::array::from_fn(|k| resolution.mul_add(ijk[k] as f32, origin[k]));
+            let d = tree.closest_afforded(&center).1.sqrt();
+            *value = if d < r_max { d } else { r_max };
+        }
+    };
+
+    // spawning a thread panics on targets without threads, rather than failing gracefully
+    let n_threads = if cfg!(all(target_arch = "wasm32", target_os = "unknown")) {
+        1
+    } else {
+        thread::available_parallelism().map_or(1, NonZeroUsize::get)
+    };
+    if n_threads == 1 {
+        fill(0, &mut values);
+    } else {
+        let chunk_len = ni * nj * nk.div_ceil(n_threads);
+        let fill = &fill;
+        thread::scope(|s| {
+            for (t, chunk) in values.chunks_mut(chunk_len).enumerate() {
+                s.spawn(move || fill(t * chunk_len, chunk));
+            }
+        });
+    }
+
+    Grid3 {
+        origin,
+        resolution,
+        dims,
+        values,
+    }
+}
+
+impl<I, Al, const L: usize> Capt<3, L, f32, I, Al>
+where
+    I: Index,
+    Al: Alloc,
+    Align<L>: Alignment,
+{
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    /// Mark every voxel of a grid which is within a distance of `inflation` of a point in this
+    /// tree.
+    ///
+    /// Voxel `[i, j, k]` is the cube of side length `cell_size` whose lowest corner is at
+    /// `origin + cell_size * [i, j, k]`, and the grid has `dims` voxels along each axis.
+    /// A voxel is occupied if any part of it is within `inflation` of a point.
+    ///
+    /// Rather than checking each voxel against the tree, this visits each leaf cell whose volume
+    /// comes within `inflation` of the grid and marks only the voxels near its point.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `cell_size` is not positive and finite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use captree::Capt;
+    ///
+    /// let capt = Capt::<3>::new(&[[0.5, 0.5, 0.5], [9.0; 3]], (0.0, 0.1));
+    /// let grid = capt.to_occupancy_grid([0.0; 3], 0.25, [4, 4, 4], 0.1);
+    ///
+    /// assert_eq!(grid.values.iter().filter(|&&o| o).count(), 8);
+    /// assert_eq!(grid.get([1, 1, 1]), Some(true));
+    /// assert_eq!(grid.get([0, 0, 0]), Some(false));
+    /// ```
+    pub fn to_occupancy_grid(
+        &self,
+        origin: [f32; 3],
+        cell_size: f32,
+        dims: [usize; 3],
+        inflation: f32,
+    ) -> Grid3<bool, f32> {
+        assert!(
+            cell_size > 0.0 && cell_size.is_finite(),
+            "voxel size must be positive and finite"
+        );
+
+        let [ni, nj, _] = dims;
+        let mut values = vec![false; dims.iter().product()];
+        #[allow(clippy::cast_precision_loss)]
+        let grid_box = Aabb {
+            lo: origin,
+            hi: std::array::from_fn(|k| cell_size.mul_add(dims[k] as f32, origin[k])),
+        };
+        let infl_sq = inflation * inflation;
+
+        for leaf in self.leaves() {
+            let Some(p) = leaf.representative else {
+                continue;
+            };
+            if leaf.cell.closest_distsq_to_aabb(&grid_box) > infl_sq {
+                continue;
+            }
+
+            // the range of indices along axis `d` of voxels which could be within `inflation` of
+            // `p`
+            let voxel_range = |d: usize| {
+                let lo = ((p[d] - inflation - origin[d]) / cell_size).floor();
+                let hi = ((p[d] + inflation - origin[d]) / cell_size).floor() + 1.0;
+                lo.max(0.0) as usize..(hi.max(0.0) as usize).min(dims[d])
+            };
+            let [range_i, range_j, range_k] = [0, 1, 2].map(voxel_range);
+
+            for k in range_k {
+                for j in range_j.clone() {
+                    for i in range_i.clone() {
+                        let idx = i + ni * (j + nj * k);
+                        if values[idx] {
+                            continue;
+                        }
+                        #[allow(clippy::cast_precision_loss)]
+                        let voxel_lo: [f32; 3] = std::array::from_fn(|d| {
+                            cell_size.mul_add([i, j, k][d] as f32, origin[d])
+                        });
+                        let voxel = Aabb {
+                            lo: voxel_lo,
+                            hi: voxel_lo.map(|x| x + cell_size),
+                        };
+                        values[idx] = voxel.closest_distsq_to(&p) <= infl_sq;
+                    }
+                }
+            }
+        }
+
+        Grid3 {
+            origin,
+            resolution: cell_size,
+            dims,
+            values,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+    use crate::distsq;
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..500)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let (r_min, r_max) = (0.01, 0.1);
+        let capt = Capt::<3>::new(&points, (r_min, r_max));
+
+        let grid = bake(
+            &capt,
+            Aabb {
+                lo: [-0.2; 3],
+                hi: [1.19, 1.19, 1.0],
+            },
+            0.07,
+        );
+        assert_eq!(grid.dims, [20, 20, 18]);
+        for i in 0..grid.dims[0] {
+            for j in 0..grid.dims[1] {
+                for k in 0..grid.dims[2] {
+                    #[allow(clippy::cast_precision_loss)]
+                    let center = [i, j, k].map(|x| 0.07f32.mul_add(x as f32, -0.2));
+                    let best = points
+                        .iter()
+                        .map(|&p| distsq(p, center).sqrt())
+                        .min_by(f32::total_cmp)
+                        .unwrap();
+                    let d = grid.get([i, j, k]).unwrap();
+                    if best <= r_min {
+                        assert!(best <= d && d <= r_min);
+                    } else {
+                        assert!((d - best.min(r_max)).abs() < 1e-6);
+                    }
+                }
+            }
+        }
+        assert_eq!(grid.get([20, 0, 0]), None);
+    }
+
+    #[test]
+    fn occupancy_matches_brute_force() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..200)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let capt = Capt::<3>::new(&points, (0.0, 0.1));
+        let (origin, cell_size, dims, inflation) = ([0.1, -0.2, 0.0], 0.05, [20, 30, 10], 0.04);
+
+        let grid = capt.to_occupancy_grid(origin, cell_size, dims, inflation);
+        for k in 0..dims[2] {
+            for j in 0..dims[1] {
+                for i in 0..dims[0] {
+                    #[allow(clippy::cast_precision_loss)]
+                    let lo: [f32; 3] =
+                        std::array::from_fn(|d| cell_size.mul_add([i, j, k][d] as f32, origin[d]));
+                    let voxel = Aabb {
+                        lo,
+                        hi: lo.map(|x| x + cell_size),
+                    };
+                    let expected = points
+                        .iter()
+                        .any(|p| voxel.closest_distsq_to(p) <= inflation * inflation);
+                    assert_eq!(grid.get([i, j, k]), Some(expected));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! Collision checking against obstacles which move over time.
+
+use std::f32::consts::SQRT_2;
+
+use elain::{Align, Alignment};
+
+use crate::{Capt, Index, NewCaptError};
+
+#[derive(Clone, Debug, PartialEq)]
+/// A collision-affording point tree over points in three-dimensional space which are each tagged
+/// with a time, such as samples along the predicted trajectories of moving obstacles.
+///
+/// Internally, this is a four-dimensional [`Capt`] whose last axis is time multiplied by a
+/// `time_scale`, which sets how many units of distance one unit of time is worth when the tree
+/// partitions space.
+///
+/// # Generic parameters
+///
+/// - `L`: The lane size of the underlying tree.
+/// - `I`: The index integer of the underlying tree.
+///
+/// # Examples
+///
+/// ```
+/// use captree::SpaceTimeCapt;
+///
+/// // an obstacle moving along the x-axis at one unit per second
+/// let trajectory: Vec<([f32; 3], f32)> = (0..=10)
+///     .map(|i| {
+///         let t = i as f32 * 0.1;
+///         ([t, 0.0, 0.0], t)
+///     })
+///     .collect();
+/// let t = SpaceTimeCapt::<8>::new(&trajectory, 1.0, (0.0, 0.2));
+///
+/// // the obstacle is at the origin early on, but long gone later
+/// assert!(t.collides_at(&[0.0; 3], 0.05, (0.0, 0.1)));
+/// assert!(!t.collides_at(&[0.0; 3], 0.05, (0.8, 1.0)));
+/// ```
+pub struct SpaceTimeCapt<const L: usize = 8, I = usize>
+where
+    Align<L>: Alignment,
+{
+    /// The tree over points whose last coordinate is scaled time.
+    tree: Capt<4, L, f32, I>,
+    /// The number of units of distance per unit of time.
+    time_scale: f32,
+    /// The range of spatial query radii which this tree supports.
+    r_range: (f32, f32),
+}
+
+impl<I, const L: usize> SpaceTimeCapt<L, I>
+where
+    I: Index,
+    Align<L>: Alignment,
+{
+    #[must_use]
+    /// Construct a new space-time tree containing the points in `points`, each given as a
+    /// `(position, time)` pair.
+    ///
+    /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on the
+    /// spatial radius of the balls which will be queried against the tree.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `time_scale` is not positive and finite, if any point or time is
+    /// not finite, or if there are too many points to be indexed by `I`.
+    pub fn new(points: &[([f32; 3], f32)], time_scale: f32, r_range: (f32, f32)) -> Self {
+        Self::try_new(points, time_scale, r_range)
+            .expect("index type I must be able to support all points in CAPT during construction")
+    }
+
+    /// Construct a new space-time tree containing the points in `points`, checking for index
+    /// overflow.
+    ///
+    /// See [`SpaceTimeCapt::new`] for further details.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error under the same conditions as [`Capt::try_new`].
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `time_scale` is not positive and finite.
+    pub fn try_new(
+        points: &[([f32; 3], f32)],
+        time_scale: f32,
+        r_range: (f32, f32),
+    ) -> Result<Self, NewCaptError> {
+        assert!(
+            time_scale > 0.0 && time_scale.is_finite(),
+            "time scale must be positive and finite"
+        );
+        let points4: Vec<[f32; 4]> = points
+            .iter()
+            .map(|&([x, y, z], t)| [x, y, z, t * time_scale])
+            .collect();
+
+        // each query is covered by balls up to sqrt(2) times larger than its spatial radius
+        Ok(Self {
+            tree: Capt::try_new(&points4, (r_range.0, r_range.1 * SQRT_2))?,
+            time_scale,
+            r_range,
+        })
+    }
+
+    #[must_use]
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    /// Determine whether any point in this tree whose time is in `t_range` is within a distance of
+    /// `radius` to `center`.
+    ///
+    /// `t_range` is a `(start, end)` pair of times, and is empty if `end < start`.
+    ///
+    /// The time interval is covered by a sequence of four-dimensional balls, each at most
+    /// `sqrt(2)` times larger than `radius`, so this never misses a collision but may report a
+    /// collision with a point slightly farther than `radius` away or slightly outside `t_range`.
+    /// The number of balls checked grows with the length of `t_range` times the time scale,
+    /// divided by `radius`.
+    ///
+    /// As with [`Capt::collides`], the result is only guaranteed to be conservative if `radius` is
+    /// inside the radius range used to construct this tree.
+    pub fn collides_at(&self, center: &[f32; 3], radius: f32, t_range: (f32, f32)) -> bool {
+        let (t_start, t_end) = t_range;
+        if t_end < t_start {
+            return false;
+        }
+
+        // cover the cylinder swept by the query ball with `n` balls spaced `step` apart
+        let height = (t_end - t_start) * self.time_scale;
+        let (r_min, r_max) = self.r_range;
+        let r_cover = match radius.max(r_min) {
+            0.0 => r_max,
+            r => r,
+        };
+        let n = (height / (2.0 * r_cover)).ceil().max(1.0) as usize;
+        let step = height / n as f32;
+        let ball_radius = radius.hypot(step / 2.0);
+
+        (0..n).any(|i| {
+            let tau = (i as f32 + 0.5).mul_add(step, t_start * self.time_scale);
+            self.tree
+                .collides(&[center[0], center[1], center[2], tau], ball_radius)
+        })
+    }
+
+    #[must_use]
+    /// Get the number of units of distance per unit of time used to build this tree.
+    pub const fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    #[must_use]
+    /// Get the number of points in this tree.
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    #[must_use]
+    /// Determine whether this tree contains no points.
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    #[must_use]
+    /// Get the dimension of the points in this tree, 3.
+    pub const fn dimension(&self) -> usize {
+        3
+    }
+
+    #[must_use]
+    /// Get the `(minimum, maximum)` range of query radii which this tree was built to support.
+    pub const fn r_range(&self) -> (f32, f32) {
+        self.r_range
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+    use crate::distsq;
+
+    #[test]
+    fn conservative() {
+        let mut rng = thread_rng();
+        let points: Vec<([f32; 3], f32)> = (0..500)
+            .map(|_| ([rng.gen(), rng.gen(), rng.gen()], rng.gen_range(0.0..10.0)))
+            .collect();
+        let time_scale = 0.1;
+        let (r_min, r_max) = (0.02, 0.1);
+        let t = SpaceTimeCapt::<8>::new(&points, time_scale, (r_min, r_max));
+
+        for _ in 0..1000 {
+            let center: [f32; 3] = [rng.gen(), rng.gen(), rng.gen()];
+            let radius = rng.gen_range(r_min..=r_max);
+            let t_start = rng.gen_range(0.0..10.0);
+            let t_range = (t_start, t_start + rng.gen_range(0.0..2.0));
+            let spatial = |p: &[f32; 3]| distsq(*p, center).sqrt();
+
+            let exact = points
+                .iter()
+                .any(|(p, time)| spatial(p) <= radius && (t_range.0..=t_range.1).contains(time));
+            let found = t.collides_at(&center, radius, t_range);
+            if exact {
+                assert!(found);
+            }
+            if found {
+                let slack = radius * SQRT_2;
+                assert!(points.iter().any(|(p, time)| spatial(p) <= slack
+                    && (t_range.0 - slack / time_scale..=t_range.1 + slack / time_scale)
+                        .contains(time)));
+            }
+        }
+
+        assert!(!t.collides_at(&[0.5; 3], 0.1, (5.0, 4.0)));
+    }
+}
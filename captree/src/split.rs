@@ -0,0 +1,398 @@
+//! Alternative rules for choosing the split planes of a tree during construction.
+
+use std::{array, cmp::Ordering};
+
+use elain::{Align, Alignment};
+
+use crate::{bounds_of, no_progress, Aabb, Axis, Buf, Capt, Index, NewCaptError, Scratch};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+/// The rule used to choose the axis and position of each split plane when constructing a
+/// [`Capt`].
+///
+/// Every policy other than [`SplitPolicy::Median`] records the axis of each split in the tree, and
+/// all but [`SplitPolicy::WidestDimension`] may split unevenly, so the tree is padded to twice as
+/// many leaves to leave room for lopsided subtrees.
+/// Padding leaves are never reached by a query and have empty affordance buffers.
+pub enum SplitPolicy {
+    #[default]
+    /// Split at the median point, cycling through the axes with depth.
+    ///
+    /// This is the policy used by [`Capt::new`].
+    Median,
+    /// Split at the median point along the axis over which the points in a cell are most spread
+    /// out.
+    ///
+    /// This keeps cells from becoming long and thin on anisotropic point clouds, such as scans of
+    /// walls and floors.
+    WidestDimension,
+    /// Split at the median point along the axis over which the cell being split is widest, where
+    /// the root cell is the bounding box of the whole point cloud.
+    ///
+    /// Unlike [`SplitPolicy::WidestDimension`], this only looks at the shape of the cell, so it is
+    /// cheaper to build, and on flat scans it never spends a split on the thin axis.
+    WidestCell,
+    /// Split halfway across the widest spread of the points in a cell, sliding the plane toward
+    /// the points if it would leave one side empty.
+    ///
+    /// Cells follow the shape of the space rather than the density of the points, so clusters
+    /// are isolated from sparse regions in few splits.
+    SlidingMidpoint,
+    /// Split where the number of points on each side times the surface area of their bounding box
+    /// is smallest, considering every axis.
+    ///
+    /// This is the slowest policy to build, but tends to produce the tightest cells.
+    SurfaceArea,
+}
+
+/// Implement construction with a [`SplitPolicy`] for axes of type `$a`.
+macro_rules! impl_split {
+    ($a: ty) => {
+        impl<I, const K: usize, const L: usize> Capt<K, L, $a, I>
+        where
+            I: Index,
+            Align<L>: Alignment,
+        {
+            #[must_use]
+            /// Construct a new CAPT containing all the points in `points`, choosing its split
+            /// planes according to `policy`.
+            ///
+            /// `r_range` is a `(minimum, maximum)` pair containing the lower and upper bound on
+            /// the radius of the balls which will be queried against the tree.
+            ///
+            /// # Panics
+            ///
+            /// This function will panic under the same conditions as [`Capt::new`], or if `K` is
+            /// greater than 256 and `policy` is not [`SplitPolicy::Median`].
+            ///
+            /// # Examples
+            ///
+            /// ```
+            /// use captree::{Capt, SplitPolicy};
+            ///
+            /// let points: Vec<[f32; 2]> = (0..100).map(|i| [i as f32, 0.0]).collect();
+            /// let capt = Capt::<2>::new_with_policy(&points, (0.0, 0.5), SplitPolicy::SurfaceArea);
+            ///
+            /// assert!(capt.collides(&[50.2, 0.1], 0.3));
+            /// assert!(!capt.collides(&[50.5, 0.4], 0.3));
+            /// ```
+            pub fn new_with_policy(
+                points: &[[$a; K]],
+                r_range: ($a, $a),
+                policy: SplitPolicy,
+            ) -> Self {
+                Self::try_new_with_policy(points, r_range, policy).expect(
+                    "index type I must be able to support all points in CAPT during construction",
+                )
+            }
+
+            /// Construct a new CAPT containing all the points in `points`, choosing its split
+            /// planes according to `policy` and checking for index overflow.
+            ///
+            /// See [`Capt::new_with_policy`] for further details.
+            ///
+            /// # Errors
+            ///
+            /// This function will return an error under the same conditions as
+            /// [`Capt::try_new`].
+            ///
+            /// # Panics
+            ///
+            /// This function will panic if `K` is greater than 256 and `policy` is not
+            /// [`SplitPolicy::Median`].
+            pub fn try_new_with_policy(
+                points: &[[$a; K]],
+                r_range: ($a, $a),
+                policy: SplitPolicy,
+            ) -> Result<Self, NewCaptError> {
+                if policy == SplitPolicy::Median {
+                    return Self::try_new(points, r_range);
+                }
+                assert!(K <= 256, "split axes must fit in a u8");
+                if points.iter().any(|p| p.iter().any(|x| !x.is_finite())) {
+                    return Err(NewCaptError::NonFinite);
+                }
+
+                let n = points.len();
+                let n2 = match policy {
+                    SplitPolicy::Median
+                    | SplitPolicy::WidestDimension
+                    | SplitPolicy::WidestCell => n.next_power_of_two(),
+                    SplitPolicy::SlidingMidpoint | SplitPolicy::SurfaceArea => {
+                        (2 * n).next_power_of_two()
+                    }
+                };
+                let mut points2 = Vec::with_capacity(n2);
+                points2.extend_from_slice(points);
+                points2.resize(n2, [<$a>::INFINITY; K]);
+                let mut tests = vec![<$a>::INFINITY; n2 - 1];
+                let mut split_dims = vec![0; n2 - 1];
+                let root = bounds_of(points);
+                Self::split_node(
+                    &mut points2,
+                    n,
+                    &mut tests,
+                    &mut split_dims,
+                    0,
+                    policy,
+                    root,
+                );
+
+                let mut t = Self {
+                    tests: tests.into(),
+                    split_dims: split_dims.into(),
+                    aabbs: Buf::default(),
+                    starts: Buf::default(),
+                    afforded: array::from_fn(|_| Buf::default()),
+                    r_range,
+                    scratch: Scratch::default(),
+                };
+                t.afford_partitioned(&points2, r_range, usize::MAX, &mut no_progress)?;
+                Ok(t)
+            }
+
+            /// Choose the split of test `i`, whose subtree contains the points in `points`, of
+            /// which the first `n` are real and the rest are padding, and then split its
+            /// children.
+            /// `cell` is the volume of the subtree, clipped to the bounding box of the point cloud.
+            ///
+            /// Afterwards, the real points of each half of `points` are at the start of that
+            /// half.
+            #[allow(clippy::cast_possible_truncation)]
+            fn split_node(
+                points: &mut [[$a; K]],
+                n: usize,
+                tests: &mut [$a],
+                split_dims: &mut [u8],
+                i: usize,
+                policy: SplitPolicy,
+                cell: Aabb<$a, K>,
+            ) {
+                let half = points.len() / 2;
+                if half == 0 || n == 0 {
+                    // padding-only subtrees keep their infinite tests, so queries never reach
+                    // their right halves
+                    return;
+                }
+
+                let reals = &mut points[..n];
+                let (k, m) = match policy {
+                    SplitPolicy::Median | SplitPolicy::WidestDimension => {
+                        (widest_axis(&bounds_of(reals)), n / 2)
+                    }
+                    SplitPolicy::WidestCell => (widest_axis(&cell), n / 2),
+                    SplitPolicy::SlidingMidpoint => {
+                        let bounds = bounds_of(reals);
+                        let k = widest_axis(&bounds);
+                        let (lo, hi) = (bounds.lo[k], bounds.hi[k]);
+                        let mid = lo.in_between(hi);
+                        let below = reals.iter().filter(|p| p[k] < mid).count();
+                        (k, below.clamp(1, n.saturating_sub(1).max(1)))
+                    }
+                    SplitPolicy::SurfaceArea => Self::cheapest_split(reals),
+                };
+                // both halves must have room for their real points
+                let m = m.clamp(n.saturating_sub(half), n.min(half));
+
+                let test = match m {
+                    0 => <$a>::NEG_INFINITY,
+                    m if m == n => <$a>::INFINITY,
+                    m => {
+                        let (lhs, med_hi, _) =
+                            reals.select_nth_unstable_by(m, |a, b| a[k].total_cmp(&b[k]));
+                        let med_lo = lhs.iter().map(|p| p[k]).fold(<$a>::NEG_INFINITY, <$a>::max);
+                        med_lo.in_between(med_hi[k])
+                    }
+                };
+                tests[i] = test;
+                split_dims[i] = k as u8;
+
+                points[m..].rotate_right(half - m);
+                let (lo_cell, hi_cell) = cell.split(test.clamp(cell.lo[k], cell.hi[k]), k);
+                let (lhs, rhs) = points.split_at_mut(half);
+                Self::split_node(lhs, m, tests, split_dims, 2 * i + 1, policy, lo_cell);
+                Self::split_node(rhs, n - m, tests, split_dims, 2 * i + 2, policy, hi_cell);
+            }
+
+            #[allow(clippy::cast_precision_loss)]
+            /// Find the axis and number of points on the low side of the split of `points` with
+            /// the lowest surface area cost, sorting `points` along the way.
+            ///
+            /// Ties are broken in favor of the most even split.
+            fn cheapest_split(points: &mut [[$a; K]]) -> (usize, usize) {
+                let n = points.len();
+                let mut best = (<$a>::INFINITY, usize::MAX, 0, n.div_ceil(2));
+                let mut suffix_areas = vec![0.0; n];
+                for k in 0..K {
+                    points.sort_unstable_by(|a, b| a[k].total_cmp(&b[k]));
+
+                    let mut bounds = Aabb {
+                        lo: points[n - 1],
+                        hi: points[n - 1],
+                    };
+                    for m in (1..n).rev() {
+                        bounds.insert(&points[m]);
+                        suffix_areas[m] = surface_area(&bounds);
+                    }
+
+                    let mut bounds = Aabb {
+                        lo: points[0],
+                        hi: points[0],
+                    };
+                    for m in 1..n {
+                        bounds.insert(&points[m - 1]);
+                        let cost =
+                            surface_area(&bounds).mul_add(m as $a, suffix_areas[m] * (n - m) as $a);
+                        let imbalance = (2 * m).abs_diff(n);
+                        if (cost, imbalance) < (best.0, best.1) {
+                            best = (cost, imbalance, k, m);
+                        }
+                    }
+                }
+
+                (best.2, best.3)
+            }
+        }
+    };
+}
+
+impl_split!(f32);
+impl_split!(f64);
+
+/// Find the axis along which `aabb` is widest.
+fn widest_axis<A: Axis, const K: usize>(aabb: &Aabb<A, K>) -> usize {
+    (0..K)
+        .max_by(|&k1, &k2| {
+            (aabb.hi[k1] - aabb.lo[k1])
+                .partial_cmp(&(aabb.hi[k2] - aabb.lo[k2]))
+                .unwrap_or(Ordering::Equal)
+        })
+        .unwrap_or(0)
+}
+
+/// Compute the surface area of `aabb`, up to a constant factor: the sum over every axis of the
+/// product of the extents along all the other axes.
+fn surface_area<A, const K: usize>(aabb: &Aabb<A, K>) -> A
+where
+    A: Copy + std::iter::Product + std::iter::Sum + std::ops::Sub<Output = A>,
+{
+    (0..K)
+        .map(|k| {
+            (0..K)
+                .filter(|&j| j != k)
+                .map(|j| aabb.hi[j] - aabb.lo[j])
+                .product()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "simd")]
+    use std::simd::Simd;
+
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+    use crate::distsq;
+
+    const POLICIES: [SplitPolicy; 5] = [
+        SplitPolicy::Median,
+        SplitPolicy::WidestDimension,
+        SplitPolicy::WidestCell,
+        SplitPolicy::SlidingMidpoint,
+        SplitPolicy::SurfaceArea,
+    ];
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = thread_rng();
+        // a thin, clustered slab, which different policies split very differently
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|i| {
+                let x: f32 = rng.gen();
+                let cluster = if i % 4 == 0 { 5.0 } else { 0.0 };
+                [
+                    x * 10.0,
+                    rng.gen::<f32>() + cluster,
+                    rng.gen::<f32>() * 0.05,
+                ]
+            })
+            .collect();
+        let (r_min, r_max) = (0.01, 0.3);
+
+        for policy in POLICIES {
+            let t = Capt::<3>::new_with_policy(&points, (r_min, r_max), policy);
+            assert_eq!(t.validate(), Ok(()), "{policy:?}");
+            assert_eq!(t.points().count(), points.len());
+
+            for _ in 0..1000 {
+                let center = [
+                    rng.gen_range(-1.0..11.0),
+                    rng.gen_range(-1.0..7.0),
+                    rng.gen_range(-0.5..0.5),
+                ];
+                let radius = rng.gen_range(r_min..=r_max);
+                let expected = points.iter().any(|&p| distsq(p, center) <= radius * radius);
+                assert_eq!(t.collides(&center, radius), expected, "{policy:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn widest_cell_flat_scan() {
+        // a dense, perfectly flat floor
+        let points: Vec<[f32; 3]> = (0..4096u16)
+            .map(|i| [f32::from(i % 64) * 0.02, f32::from(i / 64) * 0.02, 0.0])
+            .collect();
+        let r_range = (0.0, 0.05);
+        let median = Capt::<3>::new_with_policy(&points, r_range, SplitPolicy::Median);
+        let widest = Capt::<3>::new_with_policy(&points, r_range, SplitPolicy::WidestCell);
+
+        assert!(widest.affordance_size() < median.affordance_size());
+        assert!(widest.split_dims.iter().all(|&k| k != 2));
+    }
+
+    #[test]
+    fn small_and_duplicate() {
+        for policy in POLICIES {
+            for points in [vec![], vec![[1.0, 2.0]], vec![[1.0, 2.0]; 9]] {
+                let t = Capt::<2, 8, f64>::new_with_policy(&points, (0.0, 0.5), policy);
+                assert_eq!(t.validate(), Ok(()));
+                assert_eq!(t.collides(&[1.2, 2.0], 0.3), !points.is_empty());
+                assert!(!t.collides(&[0.0, 0.0], 0.3));
+            }
+        }
+
+        assert_eq!(
+            Capt::<2>::try_new_with_policy(
+                &[[f32::NAN, 0.0]],
+                (0.0, 1.0),
+                SplitPolicy::SurfaceArea
+            ),
+            Err(NewCaptError::NonFinite)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "simd")]
+    fn simd_matches_collides() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..500)
+            .map(|_| [rng.gen::<f32>() * 4.0, rng.gen(), rng.gen::<f32>() * 0.1])
+            .collect();
+
+        for policy in POLICIES {
+            let t = Capt::<3, 8, f32, u32>::new_with_policy(&points, (0.01, 0.05), policy);
+            for _ in 0..200 {
+                let centers: [Simd<f32, 8>; 3] = array::from_fn(|k| {
+                    Simd::from_array(array::from_fn(|_| rng.gen::<f32>() * [4.0, 1.0, 0.1][k]))
+                });
+                let radii = Simd::from_array(array::from_fn(|_| rng.gen_range(0.01..=0.05)));
+                let expected =
+                    (0..8).any(|j| t.collides(&array::from_fn(|k| centers[k][j]), radii[j]));
+                assert_eq!(t.collides_simd(&centers, radii), expected, "{policy:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,319 @@
+//! Vectorized queries on stable Rust, using the [`wide`](::wide) crate instead of `portable_simd`.
+
+use std::{
+    array,
+    ops::{Add, AddAssign, Mul, Sub},
+};
+
+use ::wide::{
+    f32x16, f32x4, f32x8, f64x2, f64x4, f64x8, u32x16, u32x4, u32x8, u64x2, u64x4, u64x8,
+};
+
+use crate::{
+    forward_pass, forward_pass_dims, load_lane, prefetch, Alloc, Axis, Capt, Index, PkdTree,
+};
+
+/// A vector type from the [`wide`](::wide) crate, used for vectorized queries on stable Rust.
+///
+/// This is implemented for every floating-point vector type of `wide`.
+/// The interface for this trait should be considered unstable, since it mirrors the `wide` API.
+pub trait AxisWide:
+    Copy + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + AddAssign
+{
+    /// The axis type of each lane.
+    type Axis;
+    /// The indices of the leaf cells reached by each lane.
+    type Ids: Copy;
+
+    /// A vector with every lane set to zero.
+    const ZERO: Self;
+
+    #[must_use]
+    /// Find the index of the leaf cell containing each lane of `centers` in a tree with tests
+    /// `tests`, where the `i`-th test splits along axis `split_dims[i]`, or along the depth of the
+    /// test modulo `K` if `split_dims` is empty.
+    fn forward_pass<const K: usize>(
+        tests: &[Self::Axis],
+        split_dims: &[u8],
+        centers: &[Self; K],
+    ) -> Self::Ids;
+
+    #[must_use]
+    /// Load `f` of the item at each lane of `ids` into one vector.
+    ///
+    /// # Safety
+    ///
+    /// Every lane of `ids` must be a valid index into `items`.
+    unsafe fn gather<T>(items: &[T], ids: Self::Ids, f: impl Fn(&T) -> Self::Axis) -> Self;
+
+    #[must_use]
+    /// Determine whether any lane of `self` is less than or equal to the same lane of `rhs`.
+    fn any_le(self, rhs: Self) -> bool;
+}
+
+/// Implement stable vectorized queries for trees of `$a` with `$l` lanes, using the `wide` vector
+/// type `$v`, whose comparison masks are the bits of the unsigned vector type `$u` of `$ui`.
+macro_rules! impl_wide {
+    ($a: ty, $v: ident, $u: ident, $ui: ty, $l: literal) => {
+        impl AxisWide for $v {
+            type Axis = $a;
+            type Ids = [usize; $l];
+
+            const ZERO: Self = $v::ZERO;
+
+            #[allow(clippy::cast_possible_truncation)]
+            fn forward_pass<const K: usize>(
+                tests: &[$a],
+                split_dims: &[u8],
+                centers: &[Self; K],
+            ) -> [usize; $l] {
+                // the deepest index reached is that of a leaf, one level below the last test
+                if <$ui>::try_from(2 * tests.len() + 1).is_err() {
+                    let centers = centers.map($v::to_array);
+                    return array::from_fn(|j| {
+                        let center: [$a; K] = array::from_fn(|k| centers[k][j]);
+                        if split_dims.is_empty() {
+                            forward_pass(tests, &center)
+                        } else {
+                            forward_pass_dims(tests, split_dims, &center)
+                        }
+                    });
+                }
+
+                let one = $u::splat(1);
+                let mut test_idxs = $u::splat(0);
+                for depth in 0..tests.len().trailing_ones() as usize {
+                    let idxs = test_idxs.to_array().map(|i| i as usize);
+                    // SAFETY: the descent stops above the leaves, so every index is that of a test
+                    let relevant_tests = unsafe { Self::gather(tests, idxs, |&t| t) };
+                    let lane_centers = if split_dims.is_empty() {
+                        centers[depth % K]
+                    } else {
+                        // blend in the coordinate of each lane along the axis of its test
+                        let dims = unsafe { Self::gather(split_dims, idxs, |&d| <$a>::from(d)) };
+                        let mut lane_centers = centers[0];
+                        for (d, &center) in (1u8..).zip(&centers[1..]) {
+                            lane_centers = dims
+                                .simd_eq(Self::splat(<$a>::from(d)))
+                                .select(center, lane_centers);
+                        }
+                        lane_centers
+                    };
+                    let cmp_results = lane_centers.simd_ge(relevant_tests).to_bits();
+                    test_idxs = (test_idxs << 1) + one + (cmp_results & one);
+                }
+
+                test_idxs.to_array().map(|i| i as usize - tests.len())
+            }
+
+            unsafe fn gather<T>(items: &[T], ids: [usize; $l], f: impl Fn(&T) -> $a) -> Self {
+                Self::new(ids.map(|i| f(unsafe { items.get_unchecked(i) })))
+            }
+
+            fn any_le(self, rhs: Self) -> bool {
+                self.simd_le(rhs).any()
+            }
+        }
+
+        impl<I: Index, Al: Alloc, const K: usize> Capt<K, $l, $a, I, Al> {
+            #[must_use]
+            #[doc = concat!("Determine whether any sphere in the list of provided spheres intersects a point in this tree, using [`", stringify!($v), "`](::wide::", stringify!($v), ").")]
+            ///
+            /// This is the stable-Rust counterpart of `collides_simd`.
+            /// All spheres descend the tree and are checked against the bounding boxes of their
+            /// leaves at once, and each affordance buffer is then searched one full lane at a
+            /// time.
+            ///
+            /// # Examples
+            ///
+            /// ```
+            #[doc = concat!("use wide::", stringify!($v), ";")]
+            ///
+            #[doc = concat!("let points = [[1.0, 2.0], [1.1, 1.1]];")]
+            #[doc = concat!("let tree = captree::Capt::<2, ", stringify!($l), ", ", stringify!($a), ">::new(&points, (0.0, 0.1));")]
+            ///
+            #[doc = concat!("let centers = [", stringify!($v), "::splat(1.1), ", stringify!($v), "::splat(1.1)];")]
+            #[doc = concat!("assert!(tree.collides_wide(&centers, ", stringify!($v), "::splat(0.05)));")]
+            /// ```
+            pub fn collides_wide(&self, centers: &[$v; K], radii: $v) -> bool {
+                let zs = $v::forward_pass(&self.tests, &self.split_dims, centers);
+
+                let mut inbounds = $v::ZERO.simd_eq($v::ZERO);
+                for (k, &center) in centers.iter().enumerate() {
+                    // SAFETY: the forward pass always ends at a leaf cell, which has a bounding box
+                    let (lo, hi) = unsafe {
+                        (
+                            $v::gather(&self.aabbs, zs, |aabb| aabb.lo[k]),
+                            $v::gather(&self.aabbs, zs, |aabb| aabb.hi[k]),
+                        )
+                    };
+                    inbounds &= (lo - radii).simd_le(center) & hi.simd_ge(center - radii);
+                }
+                let inbounds = inbounds.to_bitmask();
+
+                let centers = centers.map($v::to_array);
+                let radii = radii.to_array();
+                (0..$l).filter(|&j| inbounds & (1 << j) != 0).any(|j| {
+                    let z = zs[j];
+                    let range = unsafe {
+                        // SAFETY: The conversion worked the first way.
+                        self.starts[z].try_into().unwrap_unchecked()
+                            ..self.starts[z + 1].try_into().unwrap_unchecked()
+                    };
+                    for sk in &self.afforded {
+                        prefetch(sk.as_ptr().wrapping_add(range.start));
+                    }
+
+                    let n_center: [$v; K] = array::from_fn(|k| $v::splat(centers[k][j]));
+                    let rs_sq = $v::splat(radii[j].square());
+                    range.into_iter().any(|i: usize| {
+                        let mut dists_sq = $v::ZERO;
+                        for (sk, ck) in self.afforded.iter().zip(n_center) {
+                            let diff = unsafe {
+                                // SAFETY: `i` is in the affordance buffer of leaf `z`, and wide
+                                // vectors are laid out as arrays.
+                                load_lane(sk, i, $v::new)
+                            } - ck;
+                            dists_sq += diff * diff;
+                        }
+                        dists_sq.simd_le(rs_sq).any()
+                    })
+                })
+            }
+        }
+    };
+}
+
+impl_wide!(f32, f32x4, u32x4, u32, 4);
+impl_wide!(f32, f32x8, u32x8, u32, 8);
+impl_wide!(f32, f32x16, u32x16, u32, 16);
+impl_wide!(f64, f64x2, u64x2, u64, 2);
+impl_wide!(f64, f64x4, u64x4, u64, 4);
+impl_wide!(f64, f64x8, u64x8, u64, 8);
+
+impl<A: Axis, const K: usize> PkdTree<K, A> {
+    #[must_use]
+    /// Get the point stored in the leaf cell which each lane of `needles` falls into, as in
+    /// [`PkdTree::approx_nearest`], using vectors from the [`wide`](::wide) crate.
+    ///
+    /// This is the stable-Rust counterpart of `approx_nearest_simd`.
+    /// The points are returned transposed, with the `k`-th element holding the `k`-th coordinate
+    /// of every point.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wide::f32x8;
+    ///
+    /// let t = captree::PkdTree::new(&[[0.0, 0.0], [1.0, 1.0]]);
+    ///
+    /// let [xs, ys] = t.approx_nearest_wide(&[f32x8::splat(0.9), f32x8::splat(0.8)]);
+    /// assert_eq!(xs, f32x8::splat(1.0));
+    /// assert_eq!(ys, f32x8::splat(1.0));
+    /// ```
+    pub fn approx_nearest_wide<V: AxisWide<Axis = A>>(&self, needles: &[V; K]) -> [V; K] {
+        let ids = V::forward_pass(&self.tests, &[], needles);
+        // SAFETY: the forward pass always ends at a leaf cell
+        array::from_fn(|k| unsafe { V::gather(&self.points, ids, |p| p[k]) })
+    }
+
+    #[must_use]
+    /// Determine whether any ball in the list of provided balls could collide with a point in this
+    /// tree, using vectors from the [`wide`](::wide) crate.
+    ///
+    /// This is the stable-Rust counterpart of `might_collide_simd`.
+    pub fn might_collide_wide<V: AxisWide<Axis = A>>(
+        &self,
+        needles: &[V; K],
+        radii_squared: V,
+    ) -> bool {
+        let mut dists_squared = V::ZERO;
+        for (point_values, &needle_values) in
+            self.approx_nearest_wide(needles).into_iter().zip(needles)
+        {
+            let deltas = point_values - needle_values;
+            dists_squared += deltas * deltas;
+        }
+        dists_squared.any_le(radii_squared)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+    use crate::SplitPolicy;
+
+    #[test]
+    fn matches_scalar() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..500)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let t = Capt::<3>::new(&points, (0.01, 0.05));
+
+        for _ in 0..1000 {
+            let centers: [[f32; 8]; 3] = array::from_fn(|_| array::from_fn(|_| rng.gen()));
+            let radii: [f32; 8] = array::from_fn(|_| rng.gen_range(0.01..=0.05));
+            let expected = (0..8).any(|j| t.collides(&array::from_fn(|k| centers[k][j]), radii[j]));
+            assert_eq!(
+                t.collides_wide(&centers.map(f32x8::new), f32x8::new(radii)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn matches_scalar_f64x2_split_dims() {
+        let mut rng = thread_rng();
+        // a long, thin cloud, so that the widest-axis splits do not cycle through the axes
+        let points: Vec<[f64; 3]> = (0..500)
+            .map(|_| [rng.gen::<f64>() * 10.0, rng.gen(), rng.gen::<f64>() * 0.1])
+            .collect();
+        let t =
+            Capt::<3, 2, f64>::new_with_policy(&points, (0.01, 0.05), SplitPolicy::WidestDimension);
+
+        for _ in 0..1000 {
+            let centers: [[f64; 2]; 3] = [
+                array::from_fn(|_| rng.gen::<f64>() * 10.0),
+                array::from_fn(|_| rng.gen()),
+                array::from_fn(|_| rng.gen::<f64>() * 0.1),
+            ];
+            let radii: [f64; 2] = array::from_fn(|_| rng.gen_range(0.01..=0.05));
+            let expected = (0..2).any(|j| t.collides(&array::from_fn(|k| centers[k][j]), radii[j]));
+            assert_eq!(
+                t.collides_wide(&centers.map(f64x2::new), f64x2::new(radii)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn pkdt_matches_scalar() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let t = PkdTree::new(&points);
+
+        for _ in 0..100 {
+            let needles: [[f32; 16]; 3] = array::from_fn(|_| array::from_fn(|_| rng.gen()));
+            let radii_squared: [f32; 16] = array::from_fn(|_| rng.gen_range(0.0001..=0.0025));
+            let nearest = t
+                .approx_nearest_wide(&needles.map(f32x16::new))
+                .map(f32x16::to_array);
+            for j in 0..16 {
+                let needle = array::from_fn(|k| needles[k][j]);
+                assert_eq!(t.approx_nearest(needle), array::from_fn(|k| nearest[k][j]));
+            }
+            let expected = (0..16)
+                .any(|j| t.might_collide(array::from_fn(|k| needles[k][j]), radii_squared[j]));
+            assert_eq!(
+                t.might_collide_wide(&needles.map(f32x16::new), f32x16::new(radii_squared)),
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Counters for measuring where query time goes.
+
+use std::ops::AddAssign;
+
+#[cfg(feature = "simd")]
+use std::{
+    ops::{Mul, Sub},
+    simd::{
+        cmp::{SimdPartialEq, SimdPartialOrd},
+        Mask, Simd,
+    },
+};
+
+use elain::{Align, Alignment};
+
+#[cfg(feature = "simd")]
+use crate::{aabb_prune_simd, buffer_ranges_simd, AxisSimd, IndexSimd};
+use crate::{distsq, Alloc, Axis, Capt, Index};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+/// An accumulator of counts of the work done by instrumented queries, such as
+/// [`Capt::collides_with_stats`].
+///
+/// # Examples
+///
+/// ```
+/// use captree::{Capt, QueryStats};
+///
+/// let capt = Capt::<2>::new(&[[0.0, 0.0], [1.0, 1.0]], (0.0, 0.5));
+/// let mut stats = QueryStats::default();
+///
+/// assert!(capt.collides_with_stats(&[0.1, 0.0], 0.2, &mut stats));
+/// assert!(!capt.collides_with_stats(&[5.0, 5.0], 0.2, &mut stats));
+///
+/// assert_eq!(stats.queries, 2);
+/// assert_eq!(stats.aabb_rejections, 1);
+/// ```
+pub struct QueryStats {
+    /// The number of ball queries made.
+    /// A SIMD query counts as one query per lane.
+    pub queries: u64,
+    /// The number of test values compared against while descending the tree.
+    pub descents: u64,
+    /// The number of queries which were rejected by the bounding box of their affordance buffer,
+    /// without scanning any afforded points.
+    pub aabb_rejections: u64,
+    /// The number of afforded points compared against, including padding in partial lanes.
+    pub points_scanned: u64,
+    /// The number of lanes of afforded points compared against.
+    /// Each lane is one vector operation in a SIMD query.
+    pub simd_iterations: u64,
+}
+
+impl AddAssign for QueryStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.queries += rhs.queries;
+        self.descents += rhs.descents;
+        self.aabb_rejections += rhs.aabb_rejections;
+        self.points_scanned += rhs.points_scanned;
+        self.simd_iterations += rhs.simd_iterations;
+    }
+}
+
+impl<A, I, Al, const K: usize, const L: usize> Capt<K, L, A, I, Al>
+where
+    A: Axis,
+    I: Index,
+    Al: Alloc,
+    Align<L>: Alignment,
+{
+    #[must_use]
+    /// Determine whether a point in this tree is within a distance of `radius` to `center`,
+    /// recording the work done in `stats`.
+    ///
+    /// This gives the same answer as [`Capt::collides`], but is slower.
+    pub fn collides_with_stats(&self, center: &[A; K], radius: A, stats: &mut QueryStats) -> bool {
+        stats.queries += 1;
+        stats.descents += u64::from(self.tests.len().trailing_ones());
+
+        let rsq = radius.square();
+        let z = self.leaf_containing(center);
+        if self.aabbs[z].closest_distsq_to(center) > rsq {
+            stats.aabb_rejections += 1;
+            return false;
+        }
+
+        let range = unsafe {
+            // SAFETY: The conversion worked the first way.
+            self.starts[z].try_into().unwrap_unchecked()
+                ..self.starts[z + 1].try_into().unwrap_unchecked()
+        };
+        range.into_iter().any(|i: usize| {
+            stats.simd_iterations += 1;
+            stats.points_scanned += L as u64;
+            (0..L).any(|j| {
+                let aff_pt = std::array::from_fn(|k| self.afforded[k][i].data[j]);
+                distsq(aff_pt, *center) <= rsq
+            })
+        })
+    }
+}
+
+#[allow(clippy::mismatching_type_param_order)]
+#[cfg(feature = "simd")]
+impl<A, I, Al, const K: usize, const L: usize> Capt<K, L, A, I, Al>
+where
+    I: IndexSimd,
+    A: Mul<Output = A>,
+    Al: Alloc,
+    Align<L>: Alignment,
+{
+    #[must_use]
+    /// Determine whether any sphere in the list of provided spheres intersects a point in this
+    /// tree, recording the work done in `stats`.
+    ///
+    /// This gives the same answer as [`Capt::collides_simd`], but is slower.
+    pub fn collides_simd_with_stats(
+        &self,
+        centers: &[Simd<A, L>; K],
+        radii: Simd<A, L>,
+        stats: &mut QueryStats,
+    ) -> bool
+    where
+        Simd<A, L>:
+            SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+        Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+        A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask>,
+    {
+        stats.queries += L as u64;
+        stats.descents += L as u64 * u64::from(self.tests.len().trailing_ones());
+
+        let zs = self.leaves_containing_simd(centers);
+        let inbounds = unsafe { aabb_prune_simd(&self.aabbs, zs, centers, radii) }.to_array();
+        stats.aabb_rejections += inbounds.iter().filter(|&&b| !b).count() as u64;
+
+        let (buf_starts, buf_ends) = unsafe { buffer_ranges_simd(&self.starts, zs) };
+        (0..L).filter(|&j| inbounds[j]).any(|j| {
+            let n_center: [Simd<A, L>; K] = std::array::from_fn(|k| Simd::splat(centers[k][j]));
+            let rs_sq = Simd::splat(radii[j]) * Simd::splat(radii[j]);
+            (buf_starts[j]..buf_ends[j]).any(|i| {
+                stats.simd_iterations += 1;
+                stats.points_scanned += L as u64;
+                let mut dists_sq = Simd::splat(A::ZERO);
+                for (ak, ck) in self.afforded.iter().zip(n_center) {
+                    let diff = Simd::from_array(ak[i].data) - ck;
+                    dists_sq += diff * diff;
+                }
+                A::any(dists_sq.simd_le(rs_sq))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+
+    #[test]
+    fn matches_collides() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..500)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let t = Capt::<3>::new(&points, (0.01, 0.05));
+
+        let mut stats = QueryStats::default();
+        for _ in 0..1000 {
+            let center = [rng.gen(), rng.gen(), rng.gen()];
+            let r = rng.gen_range(0.01..=0.05);
+            assert_eq!(
+                t.collides_with_stats(&center, r, &mut stats),
+                t.collides(&center, r)
+            );
+        }
+
+        assert_eq!(stats.queries, 1000);
+        assert_eq!(stats.descents, 9000);
+        assert_eq!(stats.points_scanned, 8 * stats.simd_iterations);
+        assert!(stats.aabb_rejections < 1000);
+    }
+
+    #[test]
+    #[cfg(feature = "simd")]
+    fn simd_matches_collides() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..500)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let t = Capt::<3, 8, f32, u32>::new(&points, (0.01, 0.05));
+
+        let mut stats = QueryStats::default();
+        for _ in 0..200 {
+            let centers: [Simd<f32, 8>; 3] =
+                std::array::from_fn(|_| Simd::from_array(std::array::from_fn(|_| rng.gen())));
+            let radii = Simd::from_array(std::array::from_fn(|_| rng.gen_range(0.01..=0.05)));
+            assert_eq!(
+                t.collides_simd_with_stats(&centers, radii, &mut stats),
+                t.collides_simd(&centers, radii)
+            );
+        }
+
+        assert_eq!(stats.queries, 1600);
+    }
+}
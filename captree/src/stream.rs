@@ -0,0 +1,330 @@
+//! Answering asynchronous streams of collision queries.
+
+use std::{
+    collections::VecDeque,
+    iter,
+    ops::{AddAssign, Mul, Sub},
+    pin::Pin,
+    simd::{
+        cmp::{SimdPartialEq, SimdPartialOrd},
+        Mask, Simd,
+    },
+    task::{Context, Poll},
+};
+
+use elain::{Align, Alignment};
+use futures_core::Stream;
+
+use crate::{Alloc, Axis, AxisSimd, Capt, Index, IndexSimd, SimdQueryBatch};
+
+/// A stream of collision results for a stream of `(center, radius)` queries, made by
+/// [`Capt::collides_stream`].
+///
+/// Queries are gathered into groups of `L` and checked together with
+/// [`Capt::collides_batch`]; only groups containing a collision are checked again one query at a
+/// time, so streams which rarely collide are answered at SIMD speed.
+/// If the query stream has no query ready, the stream yields to the executor once so that the
+/// producer may run; only if the query stream is still not ready after that wakeup are the queries
+/// gathered so far answered as a partial group.
+/// A producer which sends one query per wakeup thus still fills whole groups, while results are
+/// never held back by a stalled producer for longer than one wakeup.
+pub struct CollisionStream<'a, S, const K: usize, const L: usize, A, I, Al>
+where
+    Al: Alloc,
+    Align<L>: Alignment,
+{
+    /// The tree to check queries against.
+    capt: &'a Capt<K, L, A, I, Al>,
+    /// The source of queries.
+    queries: S,
+    /// The queries gathered into the current group, which has fewer than `L` queries.
+    group: Vec<([A; K], A)>,
+    /// The results of answered queries which have not yet been yielded, in order.
+    answered: VecDeque<bool>,
+    /// Whether `queries` has ended.
+    finished: bool,
+    /// Whether `queries` was not ready when last polled with a partial group, so that the group
+    /// should be answered if it is still not ready.
+    stalled: bool,
+}
+
+#[allow(clippy::mismatching_type_param_order)]
+impl<A, I, Al, const K: usize, const L: usize> Capt<K, L, A, I, Al>
+where
+    I: IndexSimd,
+    A: Mul<Output = A>,
+    Al: Alloc,
+    Align<L>: Alignment,
+{
+    /// Construct a stream which yields whether each `(center, radius)` query from `queries`
+    /// collides with a point in this tree, in order.
+    ///
+    /// Queries are checked in groups of `L`; see [`CollisionStream`] for how they are batched.
+    /// A query stream which is not [`Unpin`] can be pinned with [`Box::pin`] first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(portable_simd)]
+    /// use std::{
+    ///     pin::pin,
+    ///     task::{Context, Poll, Waker},
+    /// };
+    ///
+    /// use captree::Capt;
+    /// use futures_core::Stream;
+    ///
+    /// /// A stream over a fixed list of queries.
+    /// struct Queries(Vec<([f32; 2], f32)>);
+    ///
+    /// impl Stream for Queries {
+    ///     type Item = ([f32; 2], f32);
+    ///
+    ///     fn poll_next(self: std::pin::Pin<&mut Self>, _: &mut Context) -> Poll<Option<Self::Item>> {
+    ///         Poll::Ready((!self.0.is_empty()).then(|| self.get_mut().0.remove(0)))
+    ///     }
+    /// }
+    ///
+    /// let capt = Capt::<2, 4>::new(&[[0.0, 0.0]], (0.0, 0.5));
+    /// let mut results =
+    ///     pin!(capt.collides_stream(Queries(vec![([0.1, 0.0], 0.2), ([1.0, 0.0], 0.2),])));
+    ///
+    /// let mut cx = Context::from_waker(Waker::noop());
+    /// assert_eq!(results.as_mut().poll_next(&mut cx), Poll::Ready(Some(true)));
+    /// assert_eq!(
+    ///     results.as_mut().poll_next(&mut cx),
+    ///     Poll::Ready(Some(false))
+    /// );
+    /// assert_eq!(results.as_mut().poll_next(&mut cx), Poll::Ready(None));
+    /// ```
+    pub fn collides_stream<S>(&self, queries: S) -> CollisionStream<'_, S, K, L, A, I, Al>
+    where
+        S: Stream<Item = ([A; K], A)> + Unpin,
+    {
+        CollisionStream {
+            capt: self,
+            queries,
+            group: Vec::with_capacity(L),
+            answered: VecDeque::with_capacity(L),
+            finished: false,
+            stalled: false,
+        }
+    }
+}
+
+impl<S, A, I, Al, const K: usize, const L: usize> CollisionStream<'_, S, K, L, A, I, Al>
+where
+    I: Index + IndexSimd,
+    A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask> + Mul<Output = A>,
+    Al: Alloc,
+    Align<L>: Alignment,
+    Simd<A, L>: SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+    Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+{
+    /// Answer every query in the current group, then empty it.
+    fn answer_group(&mut self) {
+        if self.group.is_empty() {
+            return;
+        }
+
+        if self.capt.collides_batch(&SimdQueryBatch::new(&self.group)) {
+            self.answered.extend(
+                self.group
+                    .iter()
+                    .map(|(center, radius)| self.capt.collides(center, *radius)),
+            );
+        } else {
+            self.answered
+                .extend(iter::repeat_n(false, self.group.len()));
+        }
+        self.group.clear();
+    }
+}
+
+// no field is ever pinned, so the stream may move whether or not its axes may
+impl<S, A, I, Al, const K: usize, const L: usize> Unpin for CollisionStream<'_, S, K, L, A, I, Al>
+where
+    S: Unpin,
+    Al: Alloc,
+    Align<L>: Alignment,
+{
+}
+
+impl<S, A, I, Al, const K: usize, const L: usize> Stream for CollisionStream<'_, S, K, L, A, I, Al>
+where
+    S: Stream<Item = ([A; K], A)> + Unpin,
+    I: Index + IndexSimd,
+    A: Axis + AxisSimd<<Simd<A, L> as SimdPartialEq>::Mask> + Mul<Output = A>,
+    Al: Alloc,
+    Align<L>: Alignment,
+    Simd<A, L>: SimdPartialOrd + Sub<Output = Simd<A, L>> + Mul<Output = Simd<A, L>> + AddAssign,
+    Mask<isize, L>: From<<Simd<A, L> as SimdPartialEq>::Mask>,
+{
+    type Item = bool;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<bool>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(hit) = this.answered.pop_front() {
+                return Poll::Ready(Some(hit));
+            }
+            if this.finished {
+                return Poll::Ready(None);
+            }
+
+            match Pin::new(&mut this.queries).poll_next(cx) {
+                Poll::Ready(Some(query)) => {
+                    this.stalled = false;
+                    this.group.push(query);
+                    if this.group.len() == L {
+                        this.answer_group();
+                    }
+                }
+                Poll::Ready(None) => {
+                    this.finished = true;
+                    this.answer_group();
+                }
+                // the producer had a chance to run and sent nothing, so answer what has arrived
+                Poll::Pending if this.stalled => {
+                    this.stalled = false;
+                    this.answer_group();
+                }
+                // yield once so that the producer may send more queries to fill the group
+                Poll::Pending if !this.group.is_empty() => {
+                    this.stalled = true;
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let held = self.answered.len() + self.group.len();
+        if self.finished {
+            return (held, Some(held));
+        }
+        let (lo, hi) = self.queries.size_hint();
+        (
+            lo.saturating_add(held),
+            hi.and_then(|hi| hi.checked_add(held)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::Waker;
+
+    use rand::thread_rng;
+
+    use super::*;
+    use crate::{testing, Aabb};
+
+    /// A stream over a list of queries which is only ready every other time it is polled.
+    struct Trickle {
+        queries: VecDeque<([f32; 3], f32)>,
+        ready: bool,
+    }
+
+    impl Stream for Trickle {
+        type Item = ([f32; 3], f32);
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            let this = self.get_mut();
+            this.ready = !this.ready;
+            if this.ready {
+                Poll::Ready(this.queries.pop_front())
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Collect every item of `stream` by polling it until it ends.
+    fn collect(mut stream: impl Stream<Item = bool> + Unpin) -> Vec<bool> {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut results = Vec::new();
+        loop {
+            match Pin::new(&mut stream).poll_next(&mut cx) {
+                Poll::Ready(Some(hit)) => results.push(hit),
+                Poll::Ready(None) => return results,
+                Poll::Pending => (),
+            }
+        }
+    }
+
+    #[test]
+    fn matches_sequential() {
+        let mut rng = thread_rng();
+        let bounds = Aabb {
+            lo: [0.0; 3],
+            hi: [1.0; 3],
+        };
+        let r_range = (0.01, 0.08);
+        let points = testing::uniform_points(&mut rng, 1000, &bounds);
+        let capt = Capt::<3, 8>::new(&points, r_range);
+
+        for n in [0, 1, 7, 8, 9, 1000] {
+            let queries: VecDeque<([f32; 3], f32)> =
+                testing::uniform_queries(&mut rng, n, &bounds, r_range).into();
+            let expected: Vec<bool> = queries
+                .iter()
+                .map(|(c, r)| testing::brute_force_collides(&points, c, *r))
+                .collect();
+
+            for ready in [false, true] {
+                let results = capt.collides_stream(Trickle {
+                    queries: queries.clone(),
+                    ready,
+                });
+                assert_eq!(collect(results), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn answers_partial_group_when_pending() {
+        /// A stream which yields one query and then never becomes ready again.
+        struct Stall(Option<([f32; 2], f32)>);
+
+        impl Stream for Stall {
+            type Item = ([f32; 2], f32);
+
+            fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+                self.get_mut()
+                    .0
+                    .take()
+                    .map_or(Poll::Pending, |q| Poll::Ready(Some(q)))
+            }
+        }
+
+        let capt = Capt::<2, 8>::new(&[[0.0, 0.0]], (0.0, 0.5));
+        let mut results = capt.collides_stream(Stall(Some(([0.1, 0.0], 0.2))));
+        let mut cx = Context::from_waker(Waker::noop());
+
+        // the first poll yields once in case the producer sends more queries
+        assert_eq!(Pin::new(&mut results).poll_next(&mut cx), Poll::Pending);
+        assert_eq!(
+            Pin::new(&mut results).poll_next(&mut cx),
+            Poll::Ready(Some(true))
+        );
+        assert_eq!(Pin::new(&mut results).poll_next(&mut cx), Poll::Pending);
+    }
+
+    #[test]
+    fn groups_trickling_queries() {
+        let capt = Capt::<3, 8>::new(&[[0.0; 3]], (0.0, 0.5));
+        let mut results = capt.collides_stream(Trickle {
+            queries: (0..20).map(|_| ([1.0; 3], 0.1)).collect(),
+            ready: true,
+        });
+        let mut cx = Context::from_waker(Waker::noop());
+
+        while Pin::new(&mut results).poll_next(&mut cx).is_pending() {}
+        // a query arriving on every wakeup is gathered into a full group before any is answered
+        assert_eq!(results.queries.queries.len(), 12);
+    }
+}
@@ -0,0 +1,113 @@
+//! Collision checking of spheres swept along line segments.
+
+use elain::{Align, Alignment};
+
+use crate::{Alloc, Capt, Index};
+
+/// Implement `collides_swept` for `Capt`s with axes of type `$a`.
+macro_rules! impl_swept {
+    ($a: ty) => {
+        impl<I, Al, const K: usize, const L: usize> Capt<K, L, $a, I, Al>
+        where
+            I: Index,
+            Al: Alloc,
+            Align<L>: Alignment,
+        {
+            #[must_use]
+            #[allow(
+                clippy::cast_possible_truncation,
+                clippy::cast_sign_loss,
+                clippy::cast_precision_loss
+            )]
+            /// Determine whether a point in this tree may be within a distance of `radius` to the
+            /// segment from `a` to `b`, as when a sphere of radius `radius` is swept from `a` to
+            /// `b`.
+            ///
+            /// The swept volume is covered by a row of spheres along the segment, spaced at most
+            /// `radius` apart, whose radii are inflated by up to 12% so that their union contains
+            /// it.
+            /// This function therefore never misses a collision, but may report a point slightly
+            /// outside the swept volume as colliding.
+            /// Results are only guaranteed if the inflated radius, `1.12 * radius`, is inside the
+            /// radius range used to construct this tree.
+            ///
+            /// # Examples
+            ///
+            /// ```
+            /// let capt = captree::Capt::<2>::new(&[[1.0, 0.1]], (0.0, 0.5));
+            ///
+            /// assert!(capt.collides_swept(&[0.0, 0.0], &[2.0, 0.0], 0.2));
+            /// assert!(!capt.collides_swept(&[0.0, 0.0], &[0.0, 2.0], 0.2));
+            /// ```
+            pub fn collides_swept(&self, a: &[$a; K], b: &[$a; K], radius: $a) -> bool {
+                let axis: [$a; K] = std::array::from_fn(|k| b[k] - a[k]);
+                let length = axis.iter().map(|x| x * x).sum::<$a>().sqrt();
+                // a segment has no thickness to space spheres by, so space them to fit the radius
+                // range
+                let spacing = if radius > 0.0 { radius } else { self.r_range.1 };
+                let n = if spacing > 0.0 {
+                    (length / spacing).ceil().max(1.0) as usize
+                } else {
+                    1
+                };
+                let step = length / n as $a;
+                let inflated = radius.hypot(step / 2.0);
+                (0..=n).any(|i| {
+                    let t = i as $a / n as $a;
+                    let center = std::array::from_fn(|k| axis[k].mul_add(t, a[k]));
+                    self.collides(&center, inflated)
+                })
+            }
+        }
+    };
+}
+
+impl_swept!(f32);
+impl_swept!(f64);
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use crate::Capt;
+
+    /// Compute the distance from `p` to the segment from `a` to `b`.
+    fn segment_dist(p: [f32; 3], a: [f32; 3], b: [f32; 3]) -> f32 {
+        let ab: [f32; 3] = std::array::from_fn(|k| b[k] - a[k]);
+        let ap: [f32; 3] = std::array::from_fn(|k| p[k] - a[k]);
+        let lensq: f32 = ab.iter().map(|x| x * x).sum();
+        let t = if lensq > 0.0 {
+            (ab.iter().zip(ap).map(|(x, y)| x * y).sum::<f32>() / lensq).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        (0..3)
+            .map(|k| (ap[k] - t * ab[k]).powi(2))
+            .sum::<f32>()
+            .sqrt()
+    }
+
+    #[test]
+    fn conservative() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..1000)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let t = Capt::<3>::new(&points, (0.0, 0.2));
+
+        for _ in 0..1000 {
+            let a: [f32; 3] = [rng.gen(), rng.gen(), rng.gen()];
+            let b = a.map(|x| x + rng.gen_range(-0.3..0.3));
+            let radius = rng.gen_range(0.0..0.15);
+            let distances: Vec<f32> = points.iter().map(|&p| segment_dist(p, a, b)).collect();
+
+            let collides = t.collides_swept(&a, &b, radius);
+            if distances.iter().any(|&d| d <= radius) {
+                assert!(collides);
+            }
+            if collides {
+                assert!(distances.iter().any(|&d| d <= radius.mul_add(1.12, 1e-5)));
+            }
+        }
+    }
+}
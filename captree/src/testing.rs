@@ -0,0 +1,258 @@
+//! Generators of point clouds and queries, and brute-force oracles to check trees against them.
+//!
+//! These are the same checks used to test this crate, packaged so that they can be run against
+//! your own point clouds and radius ranges, or against structures of your own which implement
+//! [`Collider`].
+//! The oracles take time proportional to the number of points for every query, so they are only
+//! suitable for tests.
+//!
+//! # Examples
+//!
+//! ```
+//! use captree::{testing, Aabb, Capt};
+//! use rand::thread_rng;
+//!
+//! let mut rng = thread_rng();
+//! let bounds = Aabb {
+//!     lo: [0.0; 3],
+//!     hi: [1.0; 3],
+//! };
+//! let r_range = (0.01, 0.08);
+//!
+//! let points = testing::clustered_points(&mut rng, 1000, 8, 0.02, &bounds);
+//! let mut queries = testing::uniform_queries(&mut rng, 1000, &bounds, r_range);
+//! queries.extend(testing::boundary_queries(&mut rng, &points, 1000, r_range));
+//!
+//! let capt = Capt::<3>::new(&points, r_range);
+//! assert_eq!(capt.validate(), Ok(()));
+//! assert_eq!(testing::check_collider(&capt, &points, &queries), Ok(()));
+//! ```
+
+use rand::{distributions::uniform::SampleUniform, Rng};
+
+use crate::{distsq, Aabb, Axis, Collider, InvariantError};
+
+/// Sample `n` points uniformly from within `bounds`.
+///
+/// # Panics
+///
+/// This function will panic if `bounds` is empty along any axis.
+pub fn uniform_points<A, R, const K: usize>(
+    rng: &mut R,
+    n: usize,
+    bounds: &Aabb<A, K>,
+) -> Vec<[A; K]>
+where
+    A: Axis + SampleUniform,
+    R: Rng + ?Sized,
+{
+    (0..n).map(|_| sample_in(rng, bounds)).collect()
+}
+
+/// Sample `n` points in `n_clusters` dense clusters, each a box extending `spread` from a center
+/// sampled uniformly from within `bounds`.
+///
+/// Dense clusters make for long affordance buffers and many near-ties between points, so they
+/// exercise the edge cases of construction more than uniform clouds do.
+///
+/// # Panics
+///
+/// This function will panic if `n_clusters` is zero while `n` is not, if `spread` is negative, or
+/// if `bounds` is empty along any axis.
+pub fn clustered_points<A, R, const K: usize>(
+    rng: &mut R,
+    n: usize,
+    n_clusters: usize,
+    spread: A,
+    bounds: &Aabb<A, K>,
+) -> Vec<[A; K]>
+where
+    A: Axis + SampleUniform,
+    R: Rng + ?Sized,
+{
+    let centers = uniform_points(rng, n_clusters, bounds);
+    (0..n)
+        .map(|_| {
+            let center = centers[rng.gen_range(0..n_clusters)];
+            sample_in(
+                rng,
+                &Aabb {
+                    lo: center.map(|x| x - spread),
+                    hi: center.map(|x| x + spread),
+                },
+            )
+        })
+        .collect()
+}
+
+/// Sample `n` queries with centers uniformly within `bounds` and radii uniformly within
+/// `r_range`.
+///
+/// # Panics
+///
+/// This function will panic if `bounds` is empty along any axis or if `r_range` is empty.
+pub fn uniform_queries<A, R, const K: usize>(
+    rng: &mut R,
+    n: usize,
+    bounds: &Aabb<A, K>,
+    r_range: (A, A),
+) -> Vec<([A; K], A)>
+where
+    A: Axis + SampleUniform,
+    R: Rng + ?Sized,
+{
+    (0..n)
+        .map(|_| (sample_in(rng, bounds), rng.gen_range(r_range.0..=r_range.1)))
+        .collect()
+}
+
+/// Sample `n` queries whose spheres pass through or very near a point of `points`, with radii
+/// uniformly within `r_range`.
+///
+/// Each query is centered a distance of its radius away from a random point along one axis, so
+/// that the answer depends on the rounding of the distance computation.
+/// These are the queries most likely to catch an off-by-one comparison in a tree.
+///
+/// # Panics
+///
+/// This function will panic if `points` is empty while `n` is not, or if `r_range` is empty.
+pub fn boundary_queries<A, R, const K: usize>(
+    rng: &mut R,
+    points: &[[A; K]],
+    n: usize,
+    r_range: (A, A),
+) -> Vec<([A; K], A)>
+where
+    A: Axis + SampleUniform,
+    R: Rng + ?Sized,
+{
+    (0..n)
+        .map(|_| {
+            let mut center = points[rng.gen_range(0..points.len())];
+            let radius = rng.gen_range(r_range.0..=r_range.1);
+            let k = rng.gen_range(0..K);
+            center[k] = if rng.gen() {
+                center[k] + radius
+            } else {
+                center[k] - radius
+            };
+            (center, radius)
+        })
+        .collect()
+}
+
+#[must_use]
+/// Determine whether any point in `points` is within a distance of `radius` of `center` by
+/// checking every point.
+pub fn brute_force_collides<A: Axis, const K: usize>(
+    points: &[[A; K]],
+    center: &[A; K],
+    radius: A,
+) -> bool {
+    let rsq = radius.square();
+    points.iter().any(|&p| distsq(p, *center) <= rsq)
+}
+
+#[must_use]
+/// Find the nearest point in `points` to `needle` by checking every point, returning its index
+/// and squared distance, or `None` if `points` is empty.
+///
+/// Ties are broken in favor of the earliest point.
+pub fn brute_force_nearest<A: Axis, const K: usize>(
+    points: &[[A; K]],
+    needle: &[A; K],
+) -> Option<(usize, A)> {
+    let mut best = None;
+    for (i, &p) in points.iter().enumerate() {
+        let d = distsq(p, *needle);
+        if best.is_none_or(|(_, best_d)| d < best_d) {
+            best = Some((i, d));
+        }
+    }
+    best
+}
+
+/// Check that `collider` gives the same answer as a brute-force search of `points` for every
+/// `(center, radius)` query in `queries`.
+///
+/// `collider` should contain exactly the points in `points`, and every radius should be within
+/// the range of radii it was built for.
+///
+/// # Errors
+///
+/// This function will return [`InvariantError::MissedCollision`] or
+/// [`InvariantError::FalseCollision`] for the first query whose answer differs from the
+/// brute-force answer.
+pub fn check_collider<A, C, const K: usize>(
+    collider: &C,
+    points: &[[A; K]],
+    queries: &[([A; K], A)],
+) -> Result<(), InvariantError>
+where
+    A: Axis,
+    C: Collider<K, A> + ?Sized,
+{
+    for (query, (center, radius)) in queries.iter().enumerate() {
+        match (
+            collider.collides(center, *radius),
+            brute_force_collides(points, center, *radius),
+        ) {
+            (false, true) => return Err(InvariantError::MissedCollision { query }),
+            (true, false) => return Err(InvariantError::FalseCollision { query }),
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+/// Sample a point uniformly from within `bounds`.
+fn sample_in<A, R, const K: usize>(rng: &mut R, bounds: &Aabb<A, K>) -> [A; K]
+where
+    A: Axis + SampleUniform,
+    R: Rng + ?Sized,
+{
+    std::array::from_fn(|k| rng.gen_range(bounds.lo[k]..=bounds.hi[k]))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+
+    use super::*;
+    use crate::{Capt, GridCapt, IndexedCapt, Octree};
+
+    #[test]
+    fn every_structure_passes() {
+        let mut rng = thread_rng();
+        let bounds = Aabb {
+            lo: [0.0; 3],
+            hi: [1.0; 3],
+        };
+        let r_range = (0.01, 0.08);
+        for points in [
+            uniform_points(&mut rng, 1000, &bounds),
+            clustered_points(&mut rng, 1000, 5, 0.01, &bounds),
+        ] {
+            let mut queries = uniform_queries(&mut rng, 500, &bounds, r_range);
+            queries.extend(boundary_queries(&mut rng, &points, 500, r_range));
+
+            let colliders: [Box<dyn Collider<3, f32>>; 4] = [
+                Box::new(Capt::<3>::new(&points, r_range)),
+                Box::new(IndexedCapt::<3>::new(&points, r_range)),
+                Box::new(GridCapt::<3>::new(&points, r_range, 0.1)),
+                Box::new(Octree::<3>::new(&points, r_range)),
+            ];
+            for c in &colliders {
+                assert_eq!(check_collider(c.as_ref(), &points, &queries), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_breaks_ties_early() {
+        let points = [[2.0, 0.0], [1.0, 0.0], [0.0, 1.0]];
+        assert_eq!(brute_force_nearest(&points, &[0.0, 0.0]), Some((1, 1.0)));
+        assert_eq!(brute_force_nearest::<f32, 2>(&[], &[0.0, 0.0]), None);
+    }
+}
@@ -0,0 +1,240 @@
+//! A compact binary format for recording the queries made against a tree, so that real workloads
+//! can be replayed in benchmarks.
+//!
+//! A trace consists of a header followed by any number of records, and ends where the file ends,
+//! so that a recorder can append to it for as long as a planner runs.
+//! All values are stored in little-endian order.
+//!
+//! | Field          | Encoding                                                         |
+//! | -------------- | ---------------------------------------------------------------- |
+//! | magic number   | the bytes `CQTR`                                                 |
+//! | format version | `u16`                                                            |
+//! | dimension `K`  | `u32`                                                            |
+//! | axis type      | `u8` tag (see [`FileElement::TAG`])                              |
+//! | records        | `K` axis values of the center, the radius, then a `u64` timestamp |
+//!
+//! Timestamps are in nanoseconds since the recorder was created.
+//! Traces are conventionally saved with the extension `.captrace`, which the benchmarks and the
+//! command-line interface read in place of CSV traces.
+
+use std::{
+    io::{self, Read, Write},
+    marker::PhantomData,
+    time::Duration,
+};
+
+use std::time::Instant;
+
+use crate::{
+    file::{FileElement, LoadError, Reader},
+    Axis,
+};
+
+/// The magic number at the start of every trace.
+const MAGIC: [u8; 4] = *b"CQTR";
+
+/// The current version of the trace format.
+pub const TRACE_VERSION: u16 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// A single query in a trace.
+pub struct TraceRecord<const K: usize, A> {
+    /// The center of the queried sphere.
+    pub center: [A; K],
+    /// The radius of the queried sphere.
+    pub radius: A,
+    /// The time of the query, since the start of the trace.
+    pub timestamp: Duration,
+}
+
+/// A recorder which writes queries to a trace as they are made.
+///
+/// Records are written to `W` as soon as they are made, so `W` should usually be buffered.
+///
+/// # Examples
+///
+/// ```
+/// use captree::trace::{read_trace, TraceRecorder};
+///
+/// let mut recorder = TraceRecorder::<_, 3, f32>::new(Vec::new()).unwrap();
+/// recorder.record(&[0.0, 0.1, 0.2], 0.05).unwrap();
+/// recorder.record(&[0.3, 0.4, 0.5], 0.01).unwrap();
+/// let bytes = recorder.into_inner();
+///
+/// let trace = read_trace::<3, f32>(bytes.as_slice()).unwrap();
+/// assert_eq!(trace.len(), 2);
+/// assert_eq!(trace[1].center, [0.3, 0.4, 0.5]);
+/// ```
+pub struct TraceRecorder<W: Write, const K: usize, A> {
+    w: W,
+    start: Instant,
+    buf: Vec<u8>,
+    _phantom: PhantomData<A>,
+}
+
+impl<W, A, const K: usize> TraceRecorder<W, K, A>
+where
+    W: Write,
+    A: Axis + FileElement,
+{
+    /// Create a new recorder, writing the header of the trace to `w`.
+    ///
+    /// Timestamps of records made with [`TraceRecorder::record`] are measured from the creation of
+    /// the recorder.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing to `w` fails.
+    pub fn new(mut w: W) -> io::Result<Self> {
+        let mut header = Vec::new();
+        header.extend_from_slice(&MAGIC);
+        TRACE_VERSION.write_le(&mut header);
+        #[allow(clippy::cast_possible_truncation)]
+        (K as u32).write_le(&mut header);
+        A::TAG.write_le(&mut header);
+        w.write_all(&header)?;
+        Ok(Self {
+            w,
+            start: Instant::now(),
+            buf: Vec::with_capacity((K + 1) * A::SIZE + u64::SIZE),
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Record a query of a sphere at `center` with radius `radius`, made now.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing the record fails.
+    pub fn record(&mut self, center: &[A; K], radius: A) -> io::Result<()> {
+        let timestamp = self.start.elapsed();
+        self.record_at(center, radius, timestamp)
+    }
+
+    /// Record a query of a sphere at `center` with radius `radius`, made at `timestamp` since the
+    /// start of the trace.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing the record fails.
+    pub fn record_at(&mut self, center: &[A; K], radius: A, timestamp: Duration) -> io::Result<()> {
+        self.buf.clear();
+        for &x in center {
+            x.write_le(&mut self.buf);
+        }
+        radius.write_le(&mut self.buf);
+        u64::try_from(timestamp.as_nanos())
+            .unwrap_or(u64::MAX)
+            .write_le(&mut self.buf);
+        self.w.write_all(&self.buf)
+    }
+
+    /// Flush the underlying writer.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if flushing fails.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+
+    #[must_use]
+    /// Get back the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+/// Read every record of a trace written by a [`TraceRecorder`].
+///
+/// # Errors
+///
+/// This function will return an error if reading fails, if the trace was recorded with a
+/// different dimension or axis type, or if it ends partway through a record.
+pub fn read_trace<const K: usize, A>(mut r: impl Read) -> Result<Vec<TraceRecord<K, A>>, LoadError>
+where
+    A: Axis + FileElement,
+{
+    let mut bytes = Vec::new();
+    r.read_to_end(&mut bytes)?;
+
+    if bytes.get(..MAGIC.len()) != Some(&MAGIC) {
+        return Err(LoadError::BadMagic);
+    }
+    let mut reader = Reader(&bytes[MAGIC.len()..]);
+    let version: u16 = reader.read()?;
+    if version != TRACE_VERSION {
+        return Err(LoadError::UnsupportedVersion(version));
+    }
+    let k = reader.read::<u32>()? as usize;
+    if k != K {
+        return Err(LoadError::DimensionMismatch {
+            expected: K,
+            found: k,
+        });
+    }
+    if reader.read::<u8>()? != A::TAG {
+        return Err(LoadError::TypeMismatch);
+    }
+
+    let record_size = (K + 1) * A::SIZE + u64::SIZE;
+    if reader.0.len() % record_size != 0 {
+        return Err(LoadError::Corrupt);
+    }
+    let mut records = Vec::with_capacity(reader.0.len() / record_size);
+    while !reader.0.is_empty() {
+        let mut center = [A::ZERO; K];
+        for x in &mut center {
+            *x = reader.read()?;
+        }
+        records.push(TraceRecord {
+            center,
+            radius: reader.read()?,
+            timestamp: Duration::from_nanos(reader.read()?),
+        });
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut rng = thread_rng();
+        let mut recorder = TraceRecorder::<_, 2, f64>::new(Vec::new()).unwrap();
+        let records: Vec<TraceRecord<2, f64>> = (0..100)
+            .map(|i| TraceRecord {
+                center: [rng.gen(), rng.gen()],
+                radius: rng.gen_range(0.0..0.1),
+                timestamp: Duration::from_micros(i * 10),
+            })
+            .collect();
+        for r in &records {
+            recorder
+                .record_at(&r.center, r.radius, r.timestamp)
+                .unwrap();
+        }
+        let bytes = recorder.into_inner();
+
+        assert_eq!(read_trace::<2, f64>(bytes.as_slice()).unwrap(), records);
+        assert!(matches!(
+            read_trace::<3, f64>(bytes.as_slice()),
+            Err(LoadError::DimensionMismatch {
+                expected: 3,
+                found: 2
+            })
+        ));
+        assert!(matches!(
+            read_trace::<2, f32>(bytes.as_slice()),
+            Err(LoadError::TypeMismatch)
+        ));
+        assert!(matches!(
+            read_trace::<2, f64>(&bytes[..bytes.len() - 1]),
+            Err(LoadError::Corrupt)
+        ));
+    }
+}
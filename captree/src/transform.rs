@@ -0,0 +1,345 @@
+//! Moving a tree into another coordinate frame without rebuilding it.
+
+use elain::{Align, Alignment};
+
+use crate::{Aabb, Alloc, Axis, Buf, Capt, Index};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// A rotation which maps coordinate axes onto coordinate axes, followed by a translation.
+///
+/// A point `p` is mapped to the point whose `k`-th coordinate is `p[permutation[k]]`, negated if
+/// `negated[k]` is set, plus `translation[k]`.
+/// These are exactly the rotations by multiples of 90 degrees about the coordinate axes and their
+/// compositions, such as the change of frame (x, y) → (−y, x).
+/// Since every axis lands on another axis, a tree can be moved by such a transform without
+/// sorting its points again; see [`Capt::transform`].
+/// General rotations would turn axis-aligned cells into oriented ones, so they require a rebuild.
+///
+/// A transform whose permutation and negations combine to a reflection, such as swapping two axes
+/// without negating either, is not rigid and is rejected by [`Capt::transform`].
+///
+/// # Examples
+///
+/// ```
+/// use captree::AxisIsometry;
+///
+/// // turn a quarter turn counterclockwise, then shift up by 1
+/// let iso = AxisIsometry {
+///     permutation: [1, 0],
+///     negated: [true, false],
+///     translation: [0.0, 1.0],
+/// };
+///
+/// assert_eq!(iso.apply([2.0, 3.0]), [-3.0, 3.0]);
+/// ```
+pub struct AxisIsometry<A, const K: usize> {
+    /// The axis of the original point which each axis of the transformed point is taken from.
+    ///
+    /// This must contain each of `0..K` exactly once.
+    pub permutation: [usize; K],
+    /// Whether each axis of the transformed point is negated after permuting.
+    pub negated: [bool; K],
+    /// The offset added to each axis after permuting and negating.
+    pub translation: [A; K],
+}
+
+impl<A: Axis, const K: usize> AxisIsometry<A, K> {
+    #[must_use]
+    /// Construct a transform which only translates points by `translation`.
+    pub fn translation(translation: [A; K]) -> Self {
+        Self {
+            permutation: std::array::from_fn(|k| k),
+            negated: [false; K],
+            translation,
+        }
+    }
+
+    #[must_use]
+    /// Apply this transform to `point`.
+    pub fn apply(&self, point: [A; K]) -> [A; K] {
+        std::array::from_fn(|k| self.apply_axis(k, point[self.permutation[k]]))
+    }
+
+    /// Map the value `x` of an original axis onto the `k`-th axis of the transformed point.
+    fn apply_axis(&self, k: usize, x: A) -> A {
+        if self.negated[k] {
+            self.translation[k] - x
+        } else {
+            x + self.translation[k]
+        }
+    }
+
+    /// Compute the inverse of `permutation`, mapping each original axis to the transformed axis
+    /// it lands on.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `permutation` is not a permutation of `0..K`, or if this
+    /// transform is a reflection.
+    fn inverse_permutation(&self) -> [usize; K] {
+        let mut inverse = [usize::MAX; K];
+        for (k, &from) in self.permutation.iter().enumerate() {
+            assert!(
+                from < K && inverse[from] == usize::MAX,
+                "permutation must contain each axis exactly once"
+            );
+            inverse[from] = k;
+        }
+
+        // a rotation has determinant 1: the parity of the permutation, counted by its even-length
+        // cycles, must match the parity of the number of negations
+        let mut odd = self.negated.iter().filter(|&&n| n).count() % 2 == 1;
+        let mut seen = [false; K];
+        for k in 0..K {
+            let mut len = 0;
+            let mut j = k;
+            while !seen[j] {
+                seen[j] = true;
+                j = self.permutation[j];
+                len += 1;
+            }
+            odd ^= len != 0 && len % 2 == 0;
+        }
+        assert!(!odd, "transform must be a rotation, not a reflection");
+
+        inverse
+    }
+}
+
+impl<A, I, Al, const K: usize, const L: usize> Capt<K, L, A, I, Al>
+where
+    A: Axis,
+    I: Index,
+    Al: Alloc,
+    Align<L>: Alignment,
+{
+    #[must_use]
+    /// Construct a tree over the points of this tree moved by `iso`.
+    ///
+    /// The tests, cell bounding boxes and afforded points are moved in place, so this takes time
+    /// linear in the size of the tree and never fails, unlike building a new tree from the moved
+    /// points.
+    /// Splits along negated axes have their children swapped, so that smaller coordinates still
+    /// go to the left.
+    /// Up to rounding in the translation, the moved tree answers every query as a tree built from
+    /// the moved points would, but it is not necessarily equal to one, since it keeps the shape of
+    /// the original tree.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `iso.permutation` is not a permutation of `0..K`, or if `iso`
+    /// is a reflection rather than a rotation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use captree::{AxisIsometry, Capt};
+    ///
+    /// let t = Capt::<2>::new(&[[0.0, 0.0], [1.0, 0.0]], (0.0, 0.2));
+    ///
+    /// // a quarter turn counterclockwise about the origin, then a shift
+    /// let moved = t.transform(&AxisIsometry {
+    ///     permutation: [1, 0],
+    ///     negated: [true, false],
+    ///     translation: [5.0, 5.0],
+    /// });
+    ///
+    /// assert!(moved.collides(&[5.0, 6.1], 0.15));
+    /// assert!(!moved.collides(&[6.1, 5.0], 0.15));
+    /// ```
+    pub fn transform(&self, iso: &AxisIsometry<A, K>) -> Self {
+        let inverse = iso.inverse_permutation();
+        let mut moved = self.clone();
+        let n2 = self.aabbs.len();
+
+        // the axis each test of this tree splits along
+        let axis_of = |i: usize| {
+            let depth = (i + 1).ilog2() as usize;
+            self.split_dims
+                .get(i)
+                .map_or(depth % K, |&d| usize::from(d))
+        };
+
+        // the position of every node of this tree in the moved tree, where the children of each
+        // split along a negated axis trade places
+        let mut position = vec![0; 2 * n2 - 1];
+        for i in 0..n2 - 1 {
+            let swapped = usize::from(iso.negated[inverse[axis_of(i)]]);
+            position[2 * i + 1] = 2 * position[i] + 1 + swapped;
+            position[2 * i + 2] = 2 * position[i] + 2 - swapped;
+        }
+
+        // the axis of each test must be explicit unless it stays at its depth modulo `K`
+        if inverse.iter().enumerate().any(|(k, &to)| k != to) {
+            let mut dims = vec![0; n2 - 1];
+            for i in 0..n2 - 1 {
+                // `K` is less than 255, so every axis fits
+                dims[position[i]] = u8::try_from(inverse[axis_of(i)]).unwrap();
+            }
+            moved.split_dims = Buf::from_vec_in(dims, self.split_dims.allocator().clone());
+        }
+
+        for (i, &test) in self.tests.iter().enumerate() {
+            let k = inverse[axis_of(i)];
+            moved.tests[position[i]] = iso.apply_axis(k, test);
+        }
+
+        // padding stays at infinity, wherever it is moved
+        let move_axis = |k: usize, x: A| {
+            if x.is_finite() {
+                iso.apply_axis(k, x)
+            } else {
+                x
+            }
+        };
+        let leaf_of = |z: usize| position[n2 - 1 + z] - (n2 - 1);
+
+        for (z, aabb) in self.aabbs.iter().enumerate() {
+            let (lo, hi) = (iso.apply(aabb.lo), iso.apply(aabb.hi));
+            let (lo, hi): ([A; K], [A; K]) = (
+                std::array::from_fn(|k| {
+                    let x = aabb.lo[iso.permutation[k]];
+                    if x.is_finite() {
+                        lo[k]
+                    } else {
+                        x
+                    }
+                }),
+                std::array::from_fn(|k| {
+                    let x = aabb.hi[iso.permutation[k]];
+                    if x.is_finite() {
+                        hi[k]
+                    } else {
+                        x
+                    }
+                }),
+            );
+            // a negated axis turns the lower bound into the upper one
+            moved.aabbs[leaf_of(z)] = Aabb {
+                lo: std::array::from_fn(|k| if iso.negated[k] { hi[k] } else { lo[k] }),
+                hi: std::array::from_fn(|k| if iso.negated[k] { lo[k] } else { hi[k] }),
+            };
+        }
+
+        // lay out the affordance buffers in the moved order of their leaves
+        let to_usize = |i: I| {
+            i.try_into()
+                .unwrap_or_else(|_| unreachable!("every start of a tree fits in a usize"))
+        };
+        let mut by_leaf = vec![0; n2];
+        for z in 0..n2 {
+            by_leaf[leaf_of(z)] = z;
+        }
+        for (k, afforded) in moved.afforded.iter_mut().enumerate() {
+            let from = &self.afforded[iso.permutation[k]];
+            let mut n_lanes = 0;
+            for (new_z, &z) in by_leaf.iter().enumerate() {
+                let (start, end) = (to_usize(self.starts[z]), to_usize(self.starts[z + 1]));
+                for lane in &from[start..end] {
+                    let mut lane = *lane;
+                    for x in &mut lane.data {
+                        *x = move_axis(k, *x);
+                    }
+                    afforded[n_lanes] = lane;
+                    n_lanes += 1;
+                }
+                if k == 0 {
+                    moved.starts[new_z + 1] = I::try_from(n_lanes).unwrap_or_else(|_| {
+                        unreachable!("the moved tree has as many lanes as this one")
+                    });
+                }
+            }
+        }
+
+        moved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{seq::SliceRandom, thread_rng, Rng};
+
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn moved_tree_matches_rebuilt() {
+        let mut rng = thread_rng();
+        let bounds = Aabb {
+            lo: [0.0; 3],
+            hi: [1.0; 3],
+        };
+        let r_range = (0.01, 0.08);
+        let points = testing::uniform_points(&mut rng, 1000, &bounds);
+        let t = Capt::<3>::new(&points, r_range);
+
+        for _ in 0..10 {
+            let mut permutation = [0, 1, 2];
+            permutation.shuffle(&mut rng);
+            let mut negated = [rng.gen(), rng.gen(), rng.gen()];
+            // an odd permutation is only a rotation with an odd number of negations
+            let inversions = (0..3)
+                .flat_map(|i| (i + 1..3).map(move |j| (i, j)))
+                .filter(|&(i, j)| permutation[i] > permutation[j])
+                .count();
+            if (inversions + negated.iter().filter(|&&n| n).count()) % 2 == 1 {
+                negated[0] = !negated[0];
+            }
+            let iso = AxisIsometry {
+                permutation,
+                negated,
+                translation: [
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                ],
+            };
+            let moved = t.transform(&iso);
+            let moved_points: Vec<[f32; 3]> = points.iter().map(|&p| iso.apply(p)).collect();
+            assert_eq!(moved.validate(), Ok(()));
+
+            let queries: Vec<([f32; 3], f32)> =
+                testing::uniform_queries(&mut rng, 1000, &bounds, r_range)
+                    .into_iter()
+                    .map(|(center, r)| (iso.apply(center), r))
+                    .collect();
+            assert_eq!(
+                testing::check_collider(&moved, &moved_points, &queries),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "permutation must contain each axis exactly once")]
+    fn rejects_bad_permutation() {
+        let t = Capt::<2>::new(&[[0.0, 0.0]], (0.0, 0.1));
+        let _ = t.transform(&AxisIsometry {
+            permutation: [0, 0],
+            negated: [false, false],
+            translation: [0.0, 0.0],
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "transform must be a rotation, not a reflection")]
+    fn rejects_swap() {
+        let t = Capt::<2>::new(&[[0.0, 0.0]], (0.0, 0.1));
+        let _ = t.transform(&AxisIsometry {
+            permutation: [1, 0],
+            negated: [false, false],
+            translation: [0.0, 0.0],
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "transform must be a rotation, not a reflection")]
+    fn rejects_mirror() {
+        let t = Capt::<3>::new(&[[0.0, 0.0, 0.0]], (0.0, 0.1));
+        let _ = t.transform(&AxisIsometry {
+            permutation: [0, 1, 2],
+            negated: [false, true, false],
+            translation: [0.0, 0.0, 0.0],
+        });
+    }
+}
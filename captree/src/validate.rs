@@ -0,0 +1,262 @@
+//! Checking the internal invariants of a tree.
+
+use std::fmt;
+
+use elain::{Align, Alignment};
+
+use crate::{distsq, Aabb, Alloc, Axis, Capt, Index};
+
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// The invariants of a [`Capt`] which can be violated, as reported by [`Capt::validate`] and
+/// [`Capt::validate_queries`].
+pub enum InvariantError {
+    /// The buffers of the tree have inconsistent lengths, or the number of leaves is not a power
+    /// of two.
+    BufferLength,
+    /// The start of the affordance buffer for a cell could not be converted to a `usize`, was out
+    /// of bounds, or was before the start of the previous cell's buffer.
+    BadStart {
+        /// The index of the leaf cell.
+        cell: usize,
+    },
+    /// The split axis of a test is not less than the dimension of the tree.
+    BadSplitAxis {
+        /// The index of the test.
+        test: usize,
+    },
+    /// The representative point of a cell is not finite or lies outside the split planes which
+    /// bound the cell.
+    RepresentativeOutsideCell {
+        /// The index of the leaf cell.
+        cell: usize,
+    },
+    /// A point in the affordance buffer of a cell is outside the cell's stored bounding box.
+    AffordedOutsideBounds {
+        /// The index of the leaf cell.
+        cell: usize,
+    },
+    /// A query reported no collision although a point in the tree is within its radius.
+    MissedCollision {
+        /// The index of the query.
+        query: usize,
+    },
+    /// A query reported a collision although no point in the tree is within its radius.
+    FalseCollision {
+        /// The index of the query.
+        query: usize,
+    },
+}
+
+impl fmt::Display for InvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BufferLength => write!(f, "tree buffers have inconsistent lengths"),
+            Self::BadStart { cell } => write!(f, "bad affordance buffer start for cell {cell}"),
+            Self::BadSplitAxis { test } => write!(f, "test {test} splits along an invalid axis"),
+            Self::RepresentativeOutsideCell { cell } => {
+                write!(f, "representative of cell {cell} lies outside the cell")
+            }
+            Self::AffordedOutsideBounds { cell } => {
+                write!(
+                    f,
+                    "afforded point of cell {cell} lies outside its bounding box"
+                )
+            }
+            Self::MissedCollision { query } => write!(f, "query {query} missed a collision"),
+            Self::FalseCollision { query } => write!(f, "query {query} reported a false collision"),
+        }
+    }
+}
+
+impl std::error::Error for InvariantError {}
+
+/// Determine whether `point` is inside `aabb`, including its boundary.
+fn contains<A: Axis, const K: usize>(aabb: &Aabb<A, K>, point: &[A; K]) -> bool {
+    (0..K).all(|k| aabb.lo[k] <= point[k] && point[k] <= aabb.hi[k])
+}
+
+impl<A, I, Al, const K: usize, const L: usize> Capt<K, L, A, I, Al>
+where
+    A: Axis,
+    I: Index,
+    Al: Alloc,
+    Align<L>: Alignment,
+{
+    /// Check the structural invariants of this tree.
+    ///
+    /// This checks that the buffers have consistent lengths, that the starts of the affordance
+    /// buffers are in bounds and non-decreasing, that every representative point satisfies the
+    /// split planes above its cell, and that every afforded point is inside its cell's bounding
+    /// box.
+    /// It is mostly useful when experimenting with custom construction or serialization.
+    ///
+    /// # Errors
+    ///
+    /// This function will return the first violated invariant which it finds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let capt = captree::Capt::<2>::new(&[[0.0, 0.1], [0.4, -0.2]], (0.0, 0.2));
+    ///
+    /// assert_eq!(capt.validate(), Ok(()));
+    /// ```
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        let n2 = self.aabbs.len();
+        let n_lanes = self.afforded[0].len();
+        if !n2.is_power_of_two()
+            || self.tests.len() != n2 - 1
+            || !(self.split_dims.is_empty() || self.split_dims.len() == n2 - 1)
+            || self.starts.len() != n2 + 1
+            || self.afforded.iter().any(|ak| ak.len() != n_lanes)
+        {
+            return Err(InvariantError::BufferLength);
+        }
+
+        if let Some(test) = self.split_dims.iter().position(|&d| usize::from(d) >= K) {
+            return Err(InvariantError::BadSplitAxis { test });
+        }
+
+        let mut prev = 0;
+        for (i, &start) in self.starts.iter().enumerate() {
+            let start: usize = start
+                .try_into()
+                .map_err(|_| InvariantError::BadStart { cell: i })?;
+            if start < prev || n_lanes < start || (i == 0 && start != 0) {
+                return Err(InvariantError::BadStart { cell: i });
+            }
+            prev = start;
+        }
+        if prev != n_lanes {
+            return Err(InvariantError::BadStart { cell: n2 });
+        }
+
+        for leaf in self.leaves() {
+            let z = leaf.index;
+            if let Some(rep) = leaf.representative {
+                if rep.iter().any(|x| !x.is_finite()) || !contains(&leaf.cell, &rep) {
+                    return Err(InvariantError::RepresentativeOutsideCell { cell: z });
+                }
+            }
+
+            let (start, end) = (start_of(&self.starts, z), start_of(&self.starts, z + 1));
+            for i in start..end {
+                for j in 0..L {
+                    let p = std::array::from_fn(|k| self.afforded[k][i].data[j]);
+                    if p[0].is_finite() && !contains(&leaf.bounds, &p) {
+                        return Err(InvariantError::AffordedOutsideBounds { cell: z });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that this tree gives the same answers as a brute-force search for a sample of
+    /// `(center, radius)` queries.
+    ///
+    /// Every radius should be within the range of radii this tree was built for.
+    /// This runs in time proportional to the number of queries times the number of points, so it
+    /// is only suitable for testing.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error for the first query whose answer differs from the
+    /// brute-force answer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let capt = captree::Capt::<2>::new(&[[0.0, 0.1], [0.4, -0.2]], (0.0, 0.2));
+    ///
+    /// assert_eq!(
+    ///     capt.validate_queries(&[([0.0, 0.0], 0.15), ([1.0, 1.0], 0.2)]),
+    ///     Ok(())
+    /// );
+    /// ```
+    pub fn validate_queries(&self, queries: &[([A; K], A)]) -> Result<(), InvariantError> {
+        for (query, (center, radius)) in queries.iter().enumerate() {
+            let rsq = radius.square();
+            let expected = self.points().any(|p| distsq(p, *center) <= rsq);
+            match (self.collides(center, *radius), expected) {
+                (false, true) => return Err(InvariantError::MissedCollision { query }),
+                (true, false) => return Err(InvariantError::FalseCollision { query }),
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Get the start of the `z`-th affordance buffer as a `usize`, given that all starts have already
+/// been checked to convert.
+fn start_of<I: Index>(starts: &[I], z: usize) -> usize {
+    unsafe {
+        // SAFETY: `Capt::validate` checked that every start converts.
+        starts[z].try_into().unwrap_unchecked()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+
+    #[test]
+    fn valid_tree() {
+        let mut rng = thread_rng();
+        let points: Vec<[f32; 3]> = (0..500)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let t = Capt::<3, 8, f32, u32>::new(&points, (0.01, 0.05));
+        assert_eq!(t.validate(), Ok(()));
+
+        let queries: Vec<([f32; 3], f32)> = (0..200)
+            .map(|_| {
+                (
+                    [rng.gen(), rng.gen(), rng.gen()],
+                    rng.gen_range(0.01..=0.05),
+                )
+            })
+            .collect();
+        assert_eq!(t.validate_queries(&queries), Ok(()));
+    }
+
+    #[test]
+    fn detects_corruption() {
+        let points = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]];
+        let t = Capt::<2>::new(&points, (0.0, 0.5));
+
+        let mut bad = t.clone();
+        bad.starts.swap(1, 2);
+        assert!(matches!(
+            bad.validate(),
+            Err(InvariantError::BadStart { .. })
+        ));
+
+        let mut bad = t.clone();
+        bad.tests[0] = -1.0;
+        assert!(matches!(
+            bad.validate(),
+            Err(InvariantError::RepresentativeOutsideCell { .. })
+        ));
+
+        let mut bad = t;
+        bad.aabbs.fill(Aabb {
+            lo: [5.0; 2],
+            hi: [5.0; 2],
+        });
+        assert!(matches!(
+            bad.validate(),
+            Err(InvariantError::AffordedOutsideBounds { .. })
+        ));
+        assert_eq!(
+            bad.validate_queries(&[([0.0, 0.0], 0.1)]),
+            Err(InvariantError::MissedCollision { query: 0 })
+        );
+    }
+}